@@ -0,0 +1,11 @@
+CREATE TABLE webpush_subscriptions (
+    id           TEXT PRIMARY KEY,
+    recipient_id TEXT NOT NULL,
+    endpoint     TEXT NOT NULL UNIQUE,
+    p256dh       TEXT NOT NULL,
+    auth         TEXT NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX webpush_subscriptions_recipient_id_created_at
+    ON webpush_subscriptions (recipient_id, created_at);
@@ -0,0 +1 @@
+ALTER TABLE notifications ADD COLUMN replays INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,52 @@
+-- Timestamps are milliseconds since the Unix epoch so they compare as numbers.
+CREATE TABLE notifications (
+    id                        TEXT PRIMARY KEY,
+    content                   TEXT NOT NULL,
+    channel                   TEXT NOT NULL,
+    recipient_id              TEXT NOT NULL,
+    recipient_timezone_offset TEXT NOT NULL,
+    scheduled_time            INTEGER NOT NULL,
+    priority                  TEXT NOT NULL,
+    status                    TEXT NOT NULL,
+    attempts                  INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at           INTEGER,
+    last_error                TEXT,
+    failed_at                 INTEGER,
+    failure_reason            TEXT,
+    owner                     TEXT,
+    lease_expires_at          INTEGER
+);
+
+CREATE INDEX notifications_priority_status_scheduled_time
+    ON notifications (priority, status, scheduled_time);
+CREATE INDEX notifications_status_lease_expires_at
+    ON notifications (status, lease_expires_at);
+
+CREATE TABLE outbox (
+    id               TEXT PRIMARY KEY,
+    notification_id  TEXT NOT NULL REFERENCES notifications (id) ON DELETE CASCADE,
+    channel          TEXT NOT NULL,
+    priority         TEXT NOT NULL,
+    recipient        TEXT NOT NULL,
+    payload          TEXT NOT NULL,
+    dedup_key        TEXT NOT NULL,
+    attempts         INTEGER NOT NULL,
+    created_at       INTEGER NOT NULL,
+    owner            TEXT,
+    lease_expires_at INTEGER
+);
+
+CREATE INDEX outbox_lease_expires_at_created_at ON outbox (lease_expires_at, created_at);
+
+CREATE TABLE replays (
+    id                 TEXT PRIMARY KEY,
+    notification_id    TEXT NOT NULL,
+    requested_by       TEXT NOT NULL,
+    replayed_at        INTEGER NOT NULL,
+    previous_attempts  INTEGER NOT NULL,
+    previous_error     TEXT,
+    previous_failed_at INTEGER,
+    scheduled_time     INTEGER
+);
+
+CREATE INDEX replays_notification_id_replayed_at ON replays (notification_id, replayed_at DESC);
@@ -0,0 +1,5 @@
+ALTER TABLE notifications ADD COLUMN read_at INTEGER;
+ALTER TABLE notifications ADD COLUMN archived_at INTEGER;
+
+CREATE INDEX notifications_recipient_id_channel_status_scheduled_time
+    ON notifications (recipient_id, channel, status, scheduled_time DESC);
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use common::axum::{
+	Json,
+	Router,
+	debug_handler,
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+	routing::{delete, get, post},
+};
+use serde::Deserialize;
+
+use crate::{
+	api::common::AppResponse,
+	app_state::AppState,
+	data::notifications::{Channel, DeadLetterFilter, Priority},
+	services::dead_letters::{DeadLetterQuery, ReplayOptions},
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+	let routes = Router::new()
+		.route("/", get(list))
+		.route("/", delete(purge))
+		.route("/replay", post(replay_matching))
+		.route("/replays", get(replays))
+		.route("/{id}", delete(purge_one))
+		.route("/{id}/replay", post(replay))
+		.with_state(state);
+
+	Router::new().nest("/dead-letters", routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+	#[serde(rename = "channel")]
+	channel:  Option<Channel>,
+	#[serde(rename = "priority")]
+	priority: Option<Priority>,
+	#[serde(rename = "limit")]
+	limit:    Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PurgeQuery {
+	#[serde(rename = "channel")]
+	channel:       Option<Channel>,
+	#[serde(rename = "priority")]
+	priority:      Option<Priority>,
+	#[serde(rename = "failedBefore")]
+	failed_before: Option<DateTime<Utc>>,
+	#[serde(rename = "all")]
+	all:           Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplaysQuery {
+	#[serde(rename = "notificationId")]
+	notification_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplayRequest {
+	#[serde(rename = "requestedBy")]
+	requested_by:   String,
+	#[serde(rename = "scheduledTime")]
+	scheduled_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplayMatchingRequest {
+	#[serde(flatten)]
+	replay:   ReplayRequest,
+	#[serde(rename = "channel")]
+	channel:  Option<Channel>,
+	#[serde(rename = "priority")]
+	priority: Option<Priority>,
+	#[serde(rename = "limit")]
+	limit:    Option<i64>,
+}
+
+#[debug_handler]
+async fn list(state: State<Arc<AppState>>, Query(query): Query<ListQuery>) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service
+		.list(DeadLetterQuery {
+			channel:  query.channel,
+			priority: query.priority,
+			limit:    query.limit,
+		})
+		.await
+	{
+		Ok(dead_letters) => {
+			AppResponse::new_with_data(StatusCode::OK, dead_letters).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn replay(
+	state: State<Arc<AppState>>,
+	Path(id): Path<String>,
+	Json(req): Json<ReplayRequest>,
+) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service
+		.replay(
+			id,
+			ReplayOptions {
+				requested_by:   req.requested_by,
+				scheduled_time: req.scheduled_time,
+			},
+		)
+		.await
+	{
+		Ok(record) => AppResponse::new_with_data(StatusCode::OK, record).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn replay_matching(
+	state: State<Arc<AppState>>,
+	Json(req): Json<ReplayMatchingRequest>,
+) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service
+		.replay_matching(
+			DeadLetterQuery {
+				channel:  req.channel,
+				priority: req.priority,
+				limit:    req.limit,
+			},
+			ReplayOptions {
+				requested_by:   req.replay.requested_by,
+				scheduled_time: req.replay.scheduled_time,
+			},
+		)
+		.await
+	{
+		Ok(records) => AppResponse::new_with_data(StatusCode::OK, records).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn purge(state: State<Arc<AppState>>, Query(query): Query<PurgeQuery>) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service
+		.purge(DeadLetterFilter {
+			id:            None,
+			channel:       query.channel,
+			priority:      query.priority,
+			failed_before: query.failed_before,
+			all:           query.all.unwrap_or(false),
+		})
+		.await
+	{
+		Ok(purged) => AppResponse::new_with_data(StatusCode::OK, purged).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn purge_one(state: State<Arc<AppState>>, Path(id): Path<String>) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service
+		.purge(DeadLetterFilter {
+			id: Some(id),
+			..Default::default()
+		})
+		.await
+	{
+		Ok(0) => AppResponse::new(StatusCode::NOT_FOUND).into_response(),
+		Ok(_) => AppResponse::new(StatusCode::OK).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn replays(
+	state: State<Arc<AppState>>,
+	Query(query): Query<ReplaysQuery>,
+) -> impl IntoResponse {
+	let service = state.dead_letter_service.clone();
+	match service.replays(query.notification_id).await {
+		Ok(records) => AppResponse::new_with_data(StatusCode::OK, records).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use common::axum::http::{Method, StatusCode};
+	use serde_json::json;
+
+	use crate::{
+		data::notifications::{Priority, Status},
+		testing::{TestApp, notification, settings},
+	};
+
+	/// Creates a notification and lets its only delivery attempt fail.
+	async fn dead_letter(app: &TestApp) -> String {
+		let now = Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(notification(Priority::Normal, now), None)
+			.await
+			.unwrap();
+		app.fail_broker();
+		app.deliver(now).await;
+		app.faults.set_config(Default::default()).unwrap();
+		id
+	}
+
+	fn app() -> TestApp {
+		let mut settings = settings();
+		settings.retry_policy.default_max_attempts = 1;
+		TestApp::with_settings(settings)
+	}
+
+	#[tokio::test]
+	async fn replayed_dead_letters_are_delivered_and_audited() {
+		let app = app();
+		let id = dead_letter(&app).await;
+
+		let (status, list) = app.request(Method::GET, "/api/v1/dead-letters", None).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(list[0]["id"], id.as_str());
+		assert_eq!(list[0]["attempts"], 1);
+
+		let (status, record) = app
+			.request(
+				Method::POST,
+				&format!("/api/v1/dead-letters/{}/replay", id),
+				Some(json!({ "requestedBy": "ops" })),
+			)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(record["requestedBy"], "ops");
+		assert_eq!(record["previousAttempts"], 1);
+		assert_eq!(app.notification(&id).status, Status::Pending);
+
+		app.deliver(Utc::now()).await;
+		assert_eq!(app.notification(&id).status, Status::Sent);
+		assert_eq!(app.broker.published()[0].dedup_key, format!("{}:1:0", id));
+
+		let (_, replays) = app
+			.request(
+				Method::GET,
+				&format!("/api/v1/dead-letters/replays?notificationId={}", id),
+				None,
+			)
+			.await;
+		assert_eq!(replays.as_array().unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn replay_validates_and_reports_missing_notifications() {
+		let app = app();
+		let id = dead_letter(&app).await;
+
+		let (status, _) = app
+			.request(
+				Method::POST,
+				&format!("/api/v1/dead-letters/{}/replay", id),
+				Some(json!({ "requestedBy": "" })),
+			)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		let (status, _) = app
+			.request(
+				Method::POST,
+				"/api/v1/dead-letters/unknown/replay",
+				Some(json!({ "requestedBy": "ops" })),
+			)
+			.await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn limits_are_bounded() {
+		let app = app();
+		dead_letter(&app).await;
+
+		let (status, _) = app
+			.request(Method::GET, "/api/v1/dead-letters?limit=0", None)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		let (status, _) = app
+			.request(
+				Method::POST,
+				"/api/v1/dead-letters/replay",
+				Some(json!({ "requestedBy": "ops", "limit": 1001 })),
+			)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+		assert_eq!(app.store.notifications()[0].status, Status::Failed);
+	}
+
+	#[tokio::test]
+	async fn purge_removes_dead_letters() {
+		let app = app();
+		let id = dead_letter(&app).await;
+
+		let (status, _) = app
+			.request(
+				Method::DELETE,
+				&format!("/api/v1/dead-letters/{}", id),
+				None,
+			)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		assert!(app.store.notifications().is_empty());
+
+		let (status, _) = app
+			.request(
+				Method::DELETE,
+				&format!("/api/v1/dead-letters/{}", id),
+				None,
+			)
+			.await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn purging_every_dead_letter_must_be_explicit() {
+		let app = app();
+		dead_letter(&app).await;
+
+		let (status, _) = app
+			.request(Method::DELETE, "/api/v1/dead-letters", None)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+		assert_eq!(app.store.notifications().len(), 1);
+
+		let (status, purged) = app
+			.request(Method::DELETE, "/api/v1/dead-letters?all=true", None)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(purged, 1);
+		assert!(app.store.notifications().is_empty());
+	}
+}
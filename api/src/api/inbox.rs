@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use common::axum::{
+	Router,
+	debug_handler,
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+	routing::{delete, get, post},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::common::AppResponse, app_state::AppState, services::inbox::ListInboxOptions};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+	let routes = Router::new()
+		.route("/", get(list))
+		.route("/unread-count", get(unread_count))
+		.route("/{id}/read", post(mark_read))
+		.route("/{id}/archive", post(archive))
+		.route("/{id}", delete(remove))
+		.with_state(state);
+
+	Router::new().nest("/recipients/{recipient_id}/inbox", routes)
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+	#[serde(rename = "limit")]
+	limit:       Option<i64>,
+	#[serde(rename = "offset")]
+	offset:      Option<u64>,
+	#[serde(rename = "unreadOnly", default)]
+	unread_only: bool,
+	#[serde(rename = "archived", default)]
+	archived:    bool,
+}
+
+#[derive(Debug, Serialize)]
+struct UnreadCount {
+	#[serde(rename = "unread")]
+	unread: u64,
+}
+
+#[debug_handler]
+async fn list(
+	state: State<Arc<AppState>>,
+	Path(recipient_id): Path<String>,
+	Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+	let service = state.inbox_service.clone();
+	match service
+		.list(
+			recipient_id,
+			ListInboxOptions {
+				limit:       query.limit,
+				offset:      query.offset,
+				unread_only: query.unread_only,
+				archived:    query.archived,
+			},
+		)
+		.await
+	{
+		Ok(page) => AppResponse::new_with_data(StatusCode::OK, page).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn unread_count(
+	state: State<Arc<AppState>>,
+	Path(recipient_id): Path<String>,
+) -> impl IntoResponse {
+	let service = state.inbox_service.clone();
+	match service.unread_count(recipient_id).await {
+		Ok(unread) => {
+			AppResponse::new_with_data(StatusCode::OK, UnreadCount { unread }).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn mark_read(
+	state: State<Arc<AppState>>,
+	Path((recipient_id, id)): Path<(String, String)>,
+) -> impl IntoResponse {
+	let service = state.inbox_service.clone();
+	match service.mark_read(recipient_id, id).await {
+		Ok(item) => AppResponse::new_with_data(StatusCode::OK, item).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn archive(
+	state: State<Arc<AppState>>,
+	Path((recipient_id, id)): Path<(String, String)>,
+) -> impl IntoResponse {
+	let service = state.inbox_service.clone();
+	match service.archive(recipient_id, id).await {
+		Ok(item) => AppResponse::new_with_data(StatusCode::OK, item).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn remove(
+	state: State<Arc<AppState>>,
+	Path((recipient_id, id)): Path<(String, String)>,
+) -> impl IntoResponse {
+	let service = state.inbox_service.clone();
+	match service.delete(recipient_id, id).await {
+		Ok(_) => AppResponse::new(StatusCode::OK).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, Utc};
+	use common::axum::http::{Method, StatusCode};
+
+	use crate::{
+		data::notifications::{Channel, Notification, Priority},
+		testing::{TestApp, notification},
+	};
+
+	const PATH: &str = "/api/v1/recipients/user-1/inbox";
+
+	async fn create(app: &TestApp, content: &str, minutes_ago: i64) -> String {
+		app.state
+			.notification_service
+			.create_notification(
+				Notification {
+					content: content.to_string(),
+					channel: Channel::InApp,
+					..notification(
+						Priority::Normal,
+						Utc::now() - Duration::minutes(minutes_ago),
+					)
+				},
+				None,
+			)
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn due_in_app_notifications_land_in_the_inbox() {
+		let app = TestApp::new();
+		create(&app, "older", 10).await;
+		create(&app, "newer", 5).await;
+		create(&app, "later", -60).await;
+
+		let (_, page) = app.request(Method::GET, PATH, None).await;
+		assert_eq!(page["total"], 0, "nothing is delivered before a tick");
+
+		app.deliver(Utc::now()).await;
+		assert!(app.broker.published().is_empty());
+
+		let (status, page) = app.request(Method::GET, PATH, None).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(page["total"], 2);
+		assert_eq!(page["items"][0]["content"], "newer");
+		assert_eq!(page["items"][1]["content"], "older");
+
+		let (_, page) = app
+			.request(Method::GET, &format!("{}?limit=1&offset=1", PATH), None)
+			.await;
+		assert_eq!(page["total"], 2);
+		assert_eq!(page["items"].as_array().unwrap().len(), 1);
+		assert_eq!(page["items"][0]["content"], "older");
+
+		let (status, _) = app
+			.request(Method::GET, &format!("{}?limit=0", PATH), None)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+		let (_, page) = app
+			.request(Method::GET, "/api/v1/recipients/user-2/inbox", None)
+			.await;
+		assert_eq!(page["total"], 0);
+	}
+
+	#[tokio::test]
+	async fn items_are_read_archived_and_deleted() {
+		let app = TestApp::new();
+		let first = create(&app, "first", 2).await;
+		let second = create(&app, "second", 1).await;
+		app.deliver(Utc::now()).await;
+
+		let count = format!("{}/unread-count", PATH);
+		let (status, body) = app.request(Method::GET, &count, None).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(body["unread"], 2);
+
+		let read = format!("{}/{}/read", PATH, first);
+		let (status, item) = app.request(Method::POST, &read, None).await;
+		assert_eq!(status, StatusCode::OK);
+		let read_at = item["readAt"].clone();
+		assert!(read_at.is_string());
+		// Reading again keeps the first time.
+		let (_, item) = app.request(Method::POST, &read, None).await;
+		assert_eq!(item["readAt"], read_at);
+		let (_, body) = app.request(Method::GET, &count, None).await;
+		assert_eq!(body["unread"], 1);
+		let (_, page) = app
+			.request(Method::GET, &format!("{}?unreadOnly=true", PATH), None)
+			.await;
+		assert_eq!(page["items"][0]["_id"], second.as_str());
+
+		let (status, _) = app
+			.request(Method::POST, &format!("{}/{}/archive", PATH, second), None)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		let (_, body) = app.request(Method::GET, &count, None).await;
+		assert_eq!(body["unread"], 0);
+		let (_, page) = app.request(Method::GET, PATH, None).await;
+		assert_eq!(page["total"], 1);
+		let (_, page) = app
+			.request(Method::GET, &format!("{}?archived=true", PATH), None)
+			.await;
+		assert_eq!(page["items"][0]["_id"], second.as_str());
+
+		let item = format!("{}/{}", PATH, first);
+		let (status, _) = app.request(Method::DELETE, &item, None).await;
+		assert_eq!(status, StatusCode::OK);
+		let (status, _) = app.request(Method::DELETE, &item, None).await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+		let (status, _) = app
+			.request(
+				Method::POST,
+				&format!("/api/v1/recipients/user-2/inbox/{}/read", second),
+				None,
+			)
+			.await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+}
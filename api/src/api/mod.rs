@@ -1,2 +1,5 @@
 mod common;
+pub mod dead_letters;
+pub mod inbox;
 pub mod notifications;
+pub mod subscriptions;
@@ -67,3 +67,131 @@ async fn get_all(state: State<Arc<AppState>>) -> impl IntoResponse {
 		Err(e) => e.into_response(),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, Utc};
+	use common::axum::http::{Method, StatusCode};
+	use serde_json::json;
+
+	use crate::{data::notifications::Status, testing::TestApp};
+
+	fn body(scheduled_time: chrono::DateTime<Utc>) -> serde_json::Value {
+		json!({
+			"content": "hello",
+			"channel": "push",
+			"recipient": { "id": "device-1", "timezone_offset": "+02:00" },
+			"scheduledTime": scheduled_time,
+			"priority": "high",
+			"status": "pending",
+		})
+	}
+
+	#[tokio::test]
+	async fn create_list_and_cancel() {
+		let app = TestApp::new();
+
+		let (status, id) = app
+			.request(
+				Method::POST,
+				"/api/v1/notifications",
+				Some(body(Utc::now() + Duration::hours(1))),
+			)
+			.await;
+		assert_eq!(status, StatusCode::CREATED);
+		let id = id.as_str().unwrap().to_string();
+
+		let (status, list) = app
+			.request(Method::GET, "/api/v1/notifications", None)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(list[0]["_id"], id.as_str());
+		assert_eq!(list[0]["recipient"]["timezone_offset"], "+02:00");
+
+		let (status, _) = app
+			.request(
+				Method::DELETE,
+				&format!("/api/v1/notifications/{}", id),
+				None,
+			)
+			.await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(app.notification(&id).status, Status::Cancelled);
+	}
+
+	#[tokio::test]
+	async fn forced_create_is_queued_immediately() {
+		let app = TestApp::new();
+		let mut request = body(Utc::now() + Duration::hours(1));
+		request["force"] = json!(true);
+
+		let (status, id) = app
+			.request(Method::POST, "/api/v1/notifications", Some(request))
+			.await;
+		assert_eq!(status, StatusCode::CREATED);
+		assert_eq!(
+			app.notification(id.as_str().unwrap()).status,
+			Status::Queued
+		);
+		assert_eq!(app.store.outbox().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn cancelling_a_queued_notification_drops_its_message() {
+		let app = TestApp::new();
+		let mut request = body(Utc::now() + Duration::hours(1));
+		request["force"] = json!(true);
+		let (_, id) = app
+			.request(Method::POST, "/api/v1/notifications", Some(request))
+			.await;
+		let uri = format!("/api/v1/notifications/{}", id.as_str().unwrap());
+
+		let (status, _) = app.request(Method::DELETE, &uri, None).await;
+		assert_eq!(status, StatusCode::OK);
+		assert!(app.store.outbox().is_empty());
+		app.deliver(Utc::now()).await;
+		assert!(app.broker.published().is_empty());
+		assert_eq!(
+			app.notification(id.as_str().unwrap()).status,
+			Status::Cancelled
+		);
+
+		let (status, _) = app.request(Method::DELETE, &uri, None).await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn server_owned_fields_are_ignored_on_create() {
+		let app = TestApp::new();
+		let mut request = body(Utc::now() + Duration::hours(1));
+		request["status"] = json!("sent");
+		request["owner"] = json!("someone-else");
+		request["leaseExpiresAt"] = json!(Utc::now() + Duration::days(1));
+		request["attempts"] = json!(7);
+		request["replays"] = json!(3);
+
+		let (status, id) = app
+			.request(Method::POST, "/api/v1/notifications", Some(request))
+			.await;
+		assert_eq!(status, StatusCode::CREATED);
+
+		let stored = app.notification(id.as_str().unwrap());
+		assert_eq!(stored.status, Status::Pending);
+		assert_eq!(stored.owner, None);
+		assert_eq!(stored.lease_expires_at, None);
+		assert_eq!((stored.attempts, stored.replays), (0, 0));
+	}
+
+	#[tokio::test]
+	async fn invalid_body_is_rejected() {
+		let app = TestApp::new();
+		let (status, _) = app
+			.request(
+				Method::POST,
+				"/api/v1/notifications",
+				Some(json!({ "content": "missing everything else" })),
+			)
+			.await;
+		assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+	}
+}
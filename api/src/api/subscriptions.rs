@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use common::axum::{
+	Json,
+	Router,
+	debug_handler,
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+	routing::{delete, get, post},
+};
+use serde::Deserialize;
+
+use crate::{
+	api::common::AppResponse,
+	app_state::AppState,
+	services::subscriptions::SubscribeOptions,
+};
+
+pub fn routes(state: Arc<AppState>) -> Router {
+	let routes = Router::new()
+		.route("/", post(subscribe))
+		.route("/", get(list))
+		.route("/", delete(unsubscribe))
+		.with_state(state);
+
+	Router::new().nest("/recipients/{recipient_id}/webpush-subscriptions", routes)
+}
+
+/// The JSON of a browser `PushSubscription`, posted as is.
+#[derive(Debug, Deserialize)]
+struct SubscribeRequest {
+	#[serde(rename = "endpoint")]
+	endpoint: String,
+	#[serde(rename = "keys")]
+	keys:     SubscriptionKeys,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionKeys {
+	#[serde(rename = "p256dh")]
+	p256dh: String,
+	#[serde(rename = "auth")]
+	auth:   String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnsubscribeQuery {
+	#[serde(rename = "endpoint")]
+	endpoint: String,
+}
+
+#[debug_handler]
+async fn subscribe(
+	state: State<Arc<AppState>>,
+	Path(recipient_id): Path<String>,
+	Json(req): Json<SubscribeRequest>,
+) -> impl IntoResponse {
+	let service = state.subscription_service.clone();
+	match service
+		.subscribe(
+			recipient_id,
+			SubscribeOptions {
+				endpoint: req.endpoint,
+				p256dh:   req.keys.p256dh,
+				auth:     req.keys.auth,
+			},
+		)
+		.await
+	{
+		Ok(subscription) => {
+			AppResponse::new_with_data(StatusCode::CREATED, subscription).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn list(state: State<Arc<AppState>>, Path(recipient_id): Path<String>) -> impl IntoResponse {
+	let service = state.subscription_service.clone();
+	match service.list(recipient_id).await {
+		Ok(subscriptions) => {
+			AppResponse::new_with_data(StatusCode::OK, subscriptions).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+#[debug_handler]
+async fn unsubscribe(
+	state: State<Arc<AppState>>,
+	Path(recipient_id): Path<String>,
+	Query(query): Query<UnsubscribeQuery>,
+) -> impl IntoResponse {
+	let service = state.subscription_service.clone();
+	match service.unsubscribe(recipient_id, query.endpoint).await {
+		Ok(_) => AppResponse::new(StatusCode::OK).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::axum::http::{Method, StatusCode};
+	use serde_json::json;
+
+	use crate::testing::TestApp;
+
+	const PATH: &str = "/api/v1/recipients/user-1/webpush-subscriptions";
+
+	fn subscription(endpoint: &str) -> serde_json::Value {
+		json!({
+			"endpoint": endpoint,
+			"expirationTime": null,
+			"keys": {
+				"p256dh": "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+				"auth": "BTBZMqHH6r4Tts7J_aSIgg",
+			},
+		})
+	}
+
+	#[tokio::test]
+	async fn subscribe_list_and_unsubscribe() {
+		let app = TestApp::new();
+		let endpoint = "https://push.example.com/send/abc";
+
+		let (status, created) = app
+			.request(Method::POST, PATH, Some(subscription(endpoint)))
+			.await;
+		assert_eq!(status, StatusCode::CREATED);
+		assert_eq!(created["recipientId"], "user-1");
+		// Subscribing again with the same endpoint replaces the subscription.
+		app.request(Method::POST, PATH, Some(subscription(endpoint)))
+			.await;
+
+		let (status, list) = app.request(Method::GET, PATH, None).await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(list.as_array().unwrap().len(), 1);
+		assert_eq!(list[0]["endpoint"], endpoint);
+		assert_eq!(list[0]["_id"], created["_id"]);
+
+		let uri = format!(
+			"{}?endpoint={}",
+			PATH, "https%3A%2F%2Fpush.example.com%2Fsend%2Fabc"
+		);
+		let (status, _) = app.request(Method::DELETE, &uri, None).await;
+		assert_eq!(status, StatusCode::OK);
+		let (status, _) = app.request(Method::DELETE, &uri, None).await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+		let (_, list) = app.request(Method::GET, PATH, None).await;
+		assert!(list.as_array().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn invalid_subscriptions_are_rejected() {
+		let app = TestApp::new();
+
+		let (status, _) = app
+			.request(
+				Method::POST,
+				PATH,
+				Some(subscription("http://push.example.com/send/abc")),
+			)
+			.await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+
+		let mut body = subscription("https://push.example.com/send/abc");
+		body["keys"]["auth"] = json!("c2hvcnQ");
+		let (status, _) = app.request(Method::POST, PATH, Some(body)).await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+	}
+}
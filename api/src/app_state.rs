@@ -1,46 +1,274 @@
 use std::sync::Arc;
 
+use common::{faults::FaultInjector, monitoring::health::HealthCheck};
+use consumer::registry::ChannelHandler;
+
 use crate::{
+	config::{BrokerBackend, StorageBackend},
 	data,
-	data::notifications::NotificationRepository,
+	data::{
+		inbox::InboxRepository,
+		memory::MemoryStore,
+		notifications::{Daytime, NotificationRepository},
+		outbox::OutboxRepository,
+		postgres::{PgContext, PgOptions},
+		replays::ReplayRepository,
+		sqlite::SqliteContext,
+		subscriptions::SubscriptionRepository,
+	},
+	faults,
 	messaging,
-	messaging::broker::NatsOptions,
+	messaging::{
+		broker::{Broker, NatsOptions, StreamSettings},
+		local::LocalBroker,
+	},
 	services,
-	services::notifications::NotificationService,
+	services::{
+		dead_letters::DeadLetterService,
+		inbox::InboxService,
+		notifications::{ClaimSettings, NotificationService},
+		outbox::{OutboxRelayService, RelaySettings},
+		retry::RetryPolicy,
+		scheduling::SchedulingPolicy,
+		subscriptions::SubscriptionService,
+	},
 };
 
 pub struct AppStateOptions {
+	pub storage:   StorageBackend,
 	pub mongo_url: String,
+	pub postgres:  PgOptions,
+	pub sqlite:    String,
+	pub broker:    BrokerBackend,
 	pub nats_url:  String,
+	/// What the `local` broker delivers with, built from `[local]`.
+	pub local:     Vec<ChannelHandler>,
+	pub streams:   StreamSettings,
+	pub daytime:   Daytime,
+	pub faults:    Arc<FaultInjector>,
+	pub services:  ServiceSettings,
+}
+
+/// Tunables of the services, independent of the storage and broker used.
+pub struct ServiceSettings {
+	pub retry_policy: RetryPolicy,
+	pub claim:        ClaimSettings,
+	pub relay:        RelaySettings,
+	pub scheduling:   SchedulingPolicy,
+}
+
+/// Storage and messaging the services are built on.
+pub struct AppParts {
+	pub notifications: Arc<dyn NotificationRepository>,
+	pub outbox:        Arc<dyn OutboxRepository>,
+	pub replays:       Arc<dyn ReplayRepository>,
+	pub subscriptions: Arc<dyn SubscriptionRepository>,
+	pub inbox:         Arc<dyn InboxRepository>,
+	pub broker:        Arc<dyn Broker>,
+	pub health_checks: Vec<Arc<dyn HealthCheck>>,
 }
 
 pub struct AppState {
 	pub notification_service: Arc<dyn NotificationService>,
+	pub dead_letter_service:  Arc<dyn DeadLetterService>,
+	pub outbox_relay_service: Arc<dyn OutboxRelayService>,
+	pub subscription_service: Arc<dyn SubscriptionService>,
+	pub inbox_service:        Arc<dyn InboxService>,
+	pub health_checks:        Vec<Arc<dyn HealthCheck>>,
 }
 
+type Repositories = (
+	Arc<dyn NotificationRepository>,
+	Arc<dyn OutboxRepository>,
+	Arc<dyn ReplayRepository>,
+	Arc<dyn SubscriptionRepository>,
+	Arc<dyn InboxRepository>,
+);
+
 impl AppState {
 	pub async fn new(opts: AppStateOptions) -> Arc<AppState> {
-		let db = data::db::DbContext::new(opts.mongo_url.as_ref())
-			.await
-			.expect("Failed to set up database");
+		let mut health_checks: Vec<Arc<dyn HealthCheck>> = Vec::new();
 
-		let notification_repository: Arc<dyn NotificationRepository> = Arc::new(
-			data::notifications::NotificationRepositoryImpl::new(db.notifications_collection),
-		);
-		let nats_broker: Arc<dyn messaging::broker::Broker> = Arc::new(
-			messaging::broker::NatsImpl::new(NatsOptions {
-				nats_url: opts.nats_url.clone(),
-			})
-			.await,
-		);
+		let (notifications, outbox, replays, subscriptions, inbox): Repositories =
+			match opts.storage {
+				StorageBackend::Mongo => {
+					let db = data::db::DbContext::new(opts.mongo_url.as_ref())
+						.await
+						.expect("Failed to set up database");
+					health_checks.push(Arc::new(db.clone()));
+					(
+						Arc::new(data::notifications::NotificationRepositoryImpl::new(
+							db.client.clone(),
+							db.notifications_collection.clone(),
+							db.replays_collection.clone(),
+							opts.daytime,
+						)),
+						Arc::new(data::outbox::OutboxRepositoryImpl::new(
+							db.client.clone(),
+							db.notifications_collection.clone(),
+							db.outbox_collection.clone(),
+						)),
+						Arc::new(data::replays::ReplayRepositoryImpl::new(
+							db.replays_collection.clone(),
+						)),
+						Arc::new(data::subscriptions::SubscriptionRepositoryImpl::new(
+							db.subscriptions_collection.clone(),
+						)),
+						Arc::new(data::inbox::InboxRepositoryImpl::new(
+							db.notifications_collection.clone(),
+						)),
+					)
+				}
+				StorageBackend::Postgres => {
+					let pg = PgContext::new(opts.postgres)
+						.await
+						.expect("Failed to set up database");
+					health_checks.push(Arc::new(pg.clone()));
+					(
+						Arc::new(
+							data::postgres::notifications::PgNotificationRepository::new(
+								pg.pool.clone(),
+								opts.daytime,
+							),
+						),
+						Arc::new(data::postgres::outbox::PgOutboxRepository::new(
+							pg.pool.clone(),
+						)),
+						Arc::new(data::postgres::replays::PgReplayRepository::new(
+							pg.pool.clone(),
+						)),
+						Arc::new(
+							data::postgres::subscriptions::PgSubscriptionRepository::new(
+								pg.pool.clone(),
+							),
+						),
+						Arc::new(data::postgres::inbox::PgInboxRepository::new(pg.pool)),
+					)
+				}
+				StorageBackend::Sqlite => {
+					let sqlite = SqliteContext::new(&opts.sqlite)
+						.await
+						.expect("Failed to set up database");
+					health_checks.push(Arc::new(sqlite.clone()));
+					(
+						Arc::new(
+							data::sqlite::notifications::SqliteNotificationRepository::new(
+								sqlite.pool.clone(),
+								opts.daytime,
+							),
+						),
+						Arc::new(data::sqlite::outbox::SqliteOutboxRepository::new(
+							sqlite.pool.clone(),
+						)),
+						Arc::new(data::sqlite::replays::SqliteReplayRepository::new(
+							sqlite.pool.clone(),
+						)),
+						Arc::new(
+							data::sqlite::subscriptions::SqliteSubscriptionRepository::new(
+								sqlite.pool.clone(),
+							),
+						),
+						Arc::new(data::sqlite::inbox::SqliteInboxRepository::new(sqlite.pool)),
+					)
+				}
+				StorageBackend::Memory => {
+					let store = MemoryStore::new();
+					(
+						Arc::new(
+							data::memory::notifications::MemoryNotificationRepository::new(
+								store.clone(),
+								opts.daytime,
+							),
+						),
+						Arc::new(data::memory::outbox::MemoryOutboxRepository::new(
+							store.clone(),
+						)),
+						Arc::new(data::memory::replays::MemoryReplayRepository::new(
+							store.clone(),
+						)),
+						Arc::new(
+							data::memory::subscriptions::MemorySubscriptionRepository::new(
+								store.clone(),
+							),
+						),
+						Arc::new(data::memory::inbox::MemoryInboxRepository::new(store)),
+					)
+				}
+			};
+
+		let broker: Arc<dyn Broker> = match opts.broker {
+			BrokerBackend::Nats => {
+				let nats = messaging::broker::NatsImpl::new(NatsOptions {
+					nats_url: opts.nats_url.clone(),
+					streams:  opts.streams,
+				})
+				.await;
+				health_checks.push(Arc::new(nats.health_check()));
+				Arc::new(nats)
+			}
+			BrokerBackend::Memory => Arc::new(messaging::memory::InMemoryBroker::new(
+				opts.streams.duplicate_window,
+			)),
+			BrokerBackend::Local => {
+				Arc::new(LocalBroker::new(opts.local, opts.streams.duplicate_window))
+			}
+		};
+
+		Self::from_parts(
+			AppParts {
+				notifications: Arc::new(faults::repository::FaultyNotificationRepository::new(
+					notifications,
+					opts.faults.clone(),
+				)),
+				outbox,
+				replays,
+				subscriptions,
+				inbox,
+				broker: Arc::new(faults::broker::FaultyBroker::new(
+					broker,
+					opts.faults.clone(),
+				)),
+				health_checks,
+			},
+			opts.services,
+		)
+	}
+
+	/// Wires the services on top of ready made parts, which lets tests run
+	/// the whole application against in-memory storage.
+	pub fn from_parts(parts: AppParts, settings: ServiceSettings) -> Arc<AppState> {
 		let notification_service: Arc<dyn NotificationService> =
 			Arc::new(services::notifications::NotificationServiceImpl::new(
-				notification_repository,
-				nats_broker,
+				parts.notifications.clone(),
+				parts.outbox.clone(),
+				settings.claim,
+				settings.scheduling,
+			));
+		let outbox_relay_service: Arc<dyn OutboxRelayService> =
+			Arc::new(services::outbox::OutboxRelayServiceImpl::new(
+				parts.outbox,
+				parts.broker,
+				settings.retry_policy,
+				settings.relay,
 			));
+		let dead_letter_service: Arc<dyn DeadLetterService> = Arc::new(
+			services::dead_letters::DeadLetterServiceImpl::new(parts.notifications, parts.replays),
+		);
+
+		let subscription_service: Arc<dyn SubscriptionService> = Arc::new(
+			services::subscriptions::SubscriptionServiceImpl::new(parts.subscriptions),
+		);
+
+		let inbox_service: Arc<dyn InboxService> =
+			Arc::new(services::inbox::InboxServiceImpl::new(parts.inbox));
 
 		Arc::new(AppState {
 			notification_service,
+			dead_letter_service,
+			outbox_relay_service,
+			subscription_service,
+			inbox_service,
+			health_checks: parts.health_checks,
 		})
 	}
 }
@@ -0,0 +1,445 @@
+use std::{collections::HashMap, time::Duration};
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use consumer_host::config::{Channels, Handler};
+use serde::Deserialize;
+
+use crate::{
+	crone::scheduler::{MissedTickPolicy, Schedule},
+	data::{
+		notifications::{Channel, Daytime, Priority},
+		postgres::PgOptions,
+	},
+	messaging::broker::StreamSettings,
+	services::{retry::RetryPolicy, scheduling::SchedulingPolicy},
+};
+
+/// Everything the api can be tuned with. Loaded from the TOML file named by
+/// `CONFIG_FILE`, overridden by `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub server:       ServerConfig,
+	pub metrics:      MetricsConfig,
+	pub log:          LogConfig,
+	pub storage:      StorageConfig,
+	pub mongo:        MongoConfig,
+	pub postgres:     PostgresConfig,
+	pub sqlite:       SqliteConfig,
+	pub broker:       BrokerConfig,
+	pub local:        LocalConfig,
+	pub channels:     Channels,
+	pub nats:         NatsConfig,
+	pub streams:      StreamsConfig,
+	pub claim:        ClaimConfig,
+	pub scheduler:    SchedulerConfig,
+	pub outbox:       OutboxConfig,
+	pub retry:        RetryConfig,
+	pub dead_letters: DeadLettersConfig,
+	pub shutdown:     ShutdownConfig,
+	pub faults:       FaultConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+	pub host: String,
+	pub port: u16,
+}
+
+impl Default for ServerConfig {
+	fn default() -> Self {
+		ServerConfig {
+			host: "0.0.0.0".to_string(),
+			port: 8080,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum StorageBackend {
+	#[default]
+	#[serde(rename = "mongo")]
+	Mongo,
+	#[serde(rename = "postgres")]
+	Postgres,
+	/// Embedded database file, for single node deployments.
+	#[serde(rename = "sqlite")]
+	Sqlite,
+	/// Process-local, lost on restart. Meant for tests and local runs.
+	#[serde(rename = "memory")]
+	Memory,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+	pub backend: StorageBackend,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum BrokerBackend {
+	#[default]
+	#[serde(rename = "nats")]
+	Nats,
+	/// Keeps published messages in memory, nothing is delivered.
+	#[serde(rename = "memory")]
+	Memory,
+	/// Delivers in process with the handlers of the channel crates, see
+	/// [`LocalConfig`].
+	#[serde(rename = "local")]
+	Local,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrokerConfig {
+	pub backend: BrokerBackend,
+}
+
+/// The handlers the `local` broker delivers with, named as in
+/// `consumer_host` and built from their sections under `[channels]`. A
+/// message of a channel without one fails and is retried.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalConfig {
+	pub handler: Vec<Handler>,
+}
+
+/// Logs the messages of every channel that has a stdout handler.
+impl Default for LocalConfig {
+	fn default() -> Self {
+		LocalConfig {
+			handler: Handler::stdout(),
+		}
+	}
+}
+
+impl LocalConfig {
+	/// The `[consumer]` section a host would run these handlers with. The
+	/// claim lease takes the place of the ack wait, an outbox entry still
+	/// being delivered when it runs out is claimed again.
+	pub fn consumer(&self, lease_secs: u64) -> ConsumerConfig<Handler> {
+		ConsumerConfig {
+			handler: self.handler.clone(),
+			ack_wait_secs: lease_secs,
+			..ConsumerConfig::new("api")
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoConfig {
+	pub uri: String,
+}
+
+impl Default for MongoConfig {
+	fn default() -> Self {
+		MongoConfig {
+			uri: "mongodb://localhost:27017".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostgresConfig {
+	pub url:             String,
+	pub max_connections: u32,
+}
+
+impl Default for PostgresConfig {
+	fn default() -> Self {
+		PostgresConfig {
+			url:             "postgres://localhost:5432/notifications".to_string(),
+			max_connections: 10,
+		}
+	}
+}
+
+impl PostgresConfig {
+	pub fn options(&self) -> PgOptions {
+		PgOptions {
+			url:             self.url.clone(),
+			max_connections: self.max_connections,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqliteConfig {
+	/// Database file, created if missing. `:memory:` keeps nothing on disk.
+	pub path: String,
+}
+
+impl Default for SqliteConfig {
+	fn default() -> Self {
+		SqliteConfig {
+			path: "notifications.db".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamsConfig {
+	pub duplicate_window_secs: u64,
+	pub max_age_secs:          u64,
+}
+
+impl Default for StreamsConfig {
+	fn default() -> Self {
+		StreamsConfig {
+			duplicate_window_secs: 60,
+			max_age_secs:          60 * 60 * 24,
+		}
+	}
+}
+
+impl StreamsConfig {
+	pub fn settings(&self) -> StreamSettings {
+		StreamSettings {
+			duplicate_window: Duration::from_secs(self.duplicate_window_secs),
+			max_age:          Duration::from_secs(self.max_age_secs),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClaimConfig {
+	/// Falls back to `HOSTNAME`, then to the process id.
+	pub replica_id: Option<String>,
+	pub lease_secs: u64,
+}
+
+impl Default for ClaimConfig {
+	fn default() -> Self {
+		ClaimConfig {
+			replica_id: None,
+			lease_secs: 60,
+		}
+	}
+}
+
+impl ClaimConfig {
+	pub fn replica_id(&self) -> String {
+		self.replica_id
+			.clone()
+			.or_else(|| std::env::var("HOSTNAME").ok())
+			.unwrap_or_else(|| format!("api-{}", std::process::id()))
+	}
+
+	pub fn lease(&self) -> Duration {
+		Duration::from_secs(self.lease_secs)
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+	pub interval_ms:        u64,
+	pub missed_tick_policy: MissedTickPolicy,
+	pub batch_size:         i64,
+	pub weights:            HashMap<Priority, u32>,
+	/// 0 turns aging off.
+	pub aging_secs:         u64,
+	pub daytime_start_hour: i32,
+	pub daytime_end_hour:   i32,
+}
+
+impl Default for SchedulerConfig {
+	fn default() -> Self {
+		let scheduling = SchedulingPolicy::default();
+		let daytime = Daytime::default();
+		SchedulerConfig {
+			interval_ms:        1000,
+			missed_tick_policy: MissedTickPolicy::default(),
+			batch_size:         scheduling.batch_size,
+			weights:            scheduling.weights,
+			aging_secs:         scheduling.aging_step.map_or(0, |step| step.as_secs()),
+			daytime_start_hour: daytime.start_hour,
+			daytime_end_hour:   daytime.end_hour,
+		}
+	}
+}
+
+impl SchedulerConfig {
+	pub fn interval(&self) -> Duration {
+		Duration::from_millis(self.interval_ms)
+	}
+
+	pub fn scheduling_policy(&self) -> SchedulingPolicy {
+		SchedulingPolicy {
+			weights:    self.weights.clone(),
+			batch_size: self.batch_size,
+			aging_step: Some(Duration::from_secs(self.aging_secs)).filter(|step| !step.is_zero()),
+		}
+	}
+
+	pub fn daytime(&self) -> Daytime {
+		Daytime {
+			start_hour: self.daytime_start_hour,
+			end_hour:   self.daytime_end_hour,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+	pub interval_ms: u64,
+	pub batch_size:  i64,
+}
+
+impl Default for OutboxConfig {
+	fn default() -> Self {
+		OutboxConfig {
+			interval_ms: 500,
+			batch_size:  50,
+		}
+	}
+}
+
+impl OutboxConfig {
+	pub fn interval(&self) -> Duration {
+		Duration::from_millis(self.interval_ms)
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+	pub base_delay_ms:            u64,
+	pub max_delay_ms:             u64,
+	pub jitter:                   f64,
+	pub max_attempts:             u32,
+	pub max_attempts_by_priority: HashMap<Priority, u32>,
+	pub max_attempts_by_channel:  HashMap<Channel, u32>,
+}
+
+impl Default for RetryConfig {
+	fn default() -> Self {
+		let retry = RetryPolicy::default();
+		RetryConfig {
+			base_delay_ms:            retry.base_delay.as_millis() as u64,
+			max_delay_ms:             retry.max_delay.as_millis() as u64,
+			jitter:                   retry.jitter,
+			max_attempts:             retry.default_max_attempts,
+			max_attempts_by_priority: retry.max_attempts_by_priority,
+			max_attempts_by_channel:  retry.max_attempts_by_channel,
+		}
+	}
+}
+
+impl RetryConfig {
+	pub fn retry_policy(&self) -> RetryPolicy {
+		RetryPolicy {
+			base_delay:               Duration::from_millis(self.base_delay_ms),
+			max_delay:                Duration::from_millis(self.max_delay_ms),
+			jitter:                   self.jitter,
+			default_max_attempts:     self.max_attempts,
+			max_attempts_by_priority: self.max_attempts_by_priority.clone(),
+			max_attempts_by_channel:  self.max_attempts_by_channel.clone(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadLettersConfig {
+	/// Purge job schedule, e.g. `0 0 3 * * *`. Unset disables the job.
+	pub purge_cron:        Option<String>,
+	pub retention_days:    u64,
+	pub purge_jitter_secs: u64,
+}
+
+impl Default for DeadLettersConfig {
+	fn default() -> Self {
+		DeadLettersConfig {
+			purge_cron:        None,
+			retention_days:    30,
+			purge_jitter_secs: 60,
+		}
+	}
+}
+
+impl DeadLettersConfig {
+	pub fn purge_schedule(&self) -> Option<Schedule> {
+		self.purge_cron
+			.as_deref()
+			.map(|expression| Schedule::cron(expression).expect("validated at startup"))
+	}
+
+	pub fn retention(&self) -> Duration {
+		Duration::from_secs(60 * 60 * 24 * self.retention_days)
+	}
+
+	pub fn purge_jitter(&self) -> Duration {
+		Duration::from_secs(self.purge_jitter_secs)
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		let invalid = |section: &str, e: String| ConfigError(format!("{}: {}", section, e));
+
+		require_positive("server.port", self.server.port)?;
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive(
+			"streams.duplicate_window_secs",
+			self.streams.duplicate_window_secs,
+		)?;
+		require_positive("postgres.max_connections", self.postgres.max_connections)?;
+		require_positive("claim.lease_secs", self.claim.lease_secs)?;
+		require_positive("scheduler.interval_ms", self.scheduler.interval_ms)?;
+		require_positive("outbox.interval_ms", self.outbox.interval_ms)?;
+		require_positive("outbox.batch_size", self.outbox.batch_size)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+
+		let daytime = self.scheduler.daytime();
+		if !(0..=24).contains(&daytime.start_hour)
+			|| !(0..=24).contains(&daytime.end_hour)
+			|| daytime.start_hour >= daytime.end_hour
+		{
+			return Err(ConfigError(format!(
+				"scheduler: daytime must satisfy 0 <= start < end <= 24, got {}..{}",
+				daytime.start_hour, daytime.end_hour
+			)));
+		}
+		self.scheduler
+			.scheduling_policy()
+			.validate()
+			.map_err(|e| invalid("scheduler", e.to_string()))?;
+		self.retry
+			.retry_policy()
+			.validate()
+			.map_err(|e| invalid("retry", e.to_string()))?;
+		if let Some(expression) = &self.dead_letters.purge_cron {
+			Schedule::cron(expression).map_err(|e| invalid("dead_letters.purge_cron", e))?;
+		}
+		if self.broker.backend == BrokerBackend::Local {
+			if self.local.handler.is_empty() {
+				return Err(ConfigError("local.handler must not be empty".to_string()));
+			}
+			self.channels
+				.validate(&self.local.consumer(self.claim.lease_secs))?;
+		}
+		self.faults.validate().map_err(|e| invalid("faults", e))?;
+		Ok(())
+	}
+}
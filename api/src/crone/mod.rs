@@ -1,32 +1,81 @@
 use std::{sync::Arc, time::Duration};
 
-use log::debug;
+use common::shutdown::Shutdown;
+use log::{debug, error, info};
 
 use crate::{
 	app_state,
-	crone::scheduler::{CronScheduler, CronSchedulerImpl},
-	data::notifications::Priority,
+	crone::scheduler::{CronScheduler, CronSchedulerImpl, MissedTickPolicy, Schedule},
+	data::notifications::DeadLetterFilter,
 };
 
 pub(crate) mod scheduler;
 
 pub fn build_notification_scheduler_future(
 	app_state: Arc<app_state::AppState>,
-	priority: Priority,
 	duration: Duration,
+	missed_tick_policy: MissedTickPolicy,
+	shutdown: Shutdown,
 ) -> impl Future<Output = ()> + Send {
-	let scheduler = CronSchedulerImpl::new(duration, move || {
+	let scheduler = CronSchedulerImpl::new(Schedule::Every(duration), move || {
 		let notification_service = app_state.notification_service.clone();
-		let priority = priority.clone();
 		async move {
 			let utc_now = chrono::Utc::now();
-			debug!("{:?} priority scheduler running at: {}", priority, utc_now);
-			notification_service
-				.send_messages(priority, utc_now)
-				.await
-				.unwrap();
+			debug!("Notification scheduler running at: {}", utc_now);
+			if let Err(e) = notification_service.send_messages(utc_now).await {
+				error!("Notification scheduler failed: {:?}", e);
+			}
 		}
-	});
+	})
+	.with_missed_tick_policy(missed_tick_policy)
+	.with_shutdown(shutdown);
+
+	async move { scheduler.start().await }
+}
+
+pub fn build_outbox_relay_future(
+	app_state: Arc<app_state::AppState>,
+	duration: Duration,
+	shutdown: Shutdown,
+) -> impl Future<Output = ()> + Send {
+	let scheduler = CronSchedulerImpl::new(Schedule::Every(duration), move || {
+		let outbox_relay_service = app_state.outbox_relay_service.clone();
+		async move {
+			if let Err(e) = outbox_relay_service.relay(chrono::Utc::now()).await {
+				error!("Outbox relay failed: {:?}", e);
+			}
+		}
+	})
+	.with_shutdown(shutdown);
+
+	async move { scheduler.start().await }
+}
+
+/// Maintenance job removing dead letters that failed more than `retention`
+/// ago.
+pub fn build_dead_letter_purge_future(
+	app_state: Arc<app_state::AppState>,
+	schedule: Schedule,
+	jitter: Duration,
+	retention: Duration,
+	shutdown: Shutdown,
+) -> impl Future<Output = ()> + Send {
+	let scheduler = CronSchedulerImpl::new(schedule, move || {
+		let dead_letter_service = app_state.dead_letter_service.clone();
+		async move {
+			let failed_before = chrono::Utc::now() - retention;
+			info!("Purging dead letters failed before: {}", failed_before);
+			let filter = DeadLetterFilter {
+				failed_before: Some(failed_before),
+				..Default::default()
+			};
+			if let Err(e) = dead_letter_service.purge(filter).await {
+				error!("Dead letter purge failed: {:?}", e);
+			}
+		}
+	})
+	.with_jitter(jitter)
+	.with_shutdown(shutdown);
 
 	async move { scheduler.start().await }
 }
@@ -1,21 +1,110 @@
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
 
 use async_trait::async_trait;
-use common::tokio::time;
+use chrono::{DateTime, Utc};
+use common::{
+	shutdown::Shutdown,
+	tokio::{self, time},
+};
+use log::{info, warn};
+use serde::Deserialize;
 
 #[async_trait]
 pub trait CronScheduler {
 	async fn start(&self);
 }
 
-// Our scheduler struct holds the interval and a task function.
+/// When a task runs.
+pub enum Schedule {
+	/// Fixed period, the first run starts immediately.
+	Every(Duration),
+	/// Cron expression with a seconds field, e.g. `0 */5 * * * *`, in UTC.
+	Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+	pub fn cron(expression: &str) -> Result<Self, String> {
+		cron::Schedule::from_str(expression)
+			.map(|schedule| Schedule::Cron(Box::new(schedule)))
+			.map_err(|e| format!("invalid cron expression {:?}: {}", expression, e))
+	}
+
+	fn first(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+		match self {
+			Schedule::Every(_) => Some(now),
+			Schedule::Cron(schedule) => schedule.after(&now).next(),
+		}
+	}
+
+	fn next_after(&self, tick: DateTime<Utc>) -> Option<DateTime<Utc>> {
+		match self {
+			Schedule::Every(period) => Some(tick + *period),
+			Schedule::Cron(schedule) => schedule.after(&tick).next(),
+		}
+	}
+
+	/// First tick of the original schedule that is still ahead of `now`.
+	fn next_ahead(&self, tick: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+		match self {
+			Schedule::Every(period) => {
+				let period = chrono::Duration::from_std(*period).ok()?;
+				if period.is_zero() {
+					return Some(now);
+				}
+				let missed = (now - tick).num_milliseconds() / period.num_milliseconds().max(1);
+				Some(tick + period * (missed as i32 + 1))
+			}
+			Schedule::Cron(schedule) => schedule.after(&now).next(),
+		}
+	}
+}
+
+/// What to do with ticks that passed while the task was still running. Runs
+/// never overlap, the next one only starts once the previous one returned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum MissedTickPolicy {
+	/// Run once for every missed tick, back to back, until caught up.
+	#[serde(rename = "burst")]
+	Burst,
+	/// Drop the missed ticks and continue with the next one still ahead.
+	#[default]
+	#[serde(rename = "skip")]
+	Skip,
+	/// Restart the schedule from the end of the late run.
+	#[serde(rename = "delay")]
+	Delay,
+}
+
+impl MissedTickPolicy {
+	/// The tick to run after `tick`, whose run ended at `now`.
+	fn next_tick(
+		self,
+		schedule: &Schedule,
+		tick: DateTime<Utc>,
+		now: DateTime<Utc>,
+	) -> Option<DateTime<Utc>> {
+		match self {
+			MissedTickPolicy::Burst => schedule.next_after(tick),
+			MissedTickPolicy::Skip => schedule.next_ahead(tick, now),
+			MissedTickPolicy::Delay => match schedule.next_after(tick) {
+				Some(next) if next < now => schedule.next_after(now),
+				next => next,
+			},
+		}
+	}
+}
+
+// Our scheduler struct holds the schedule and a task function.
 pub struct CronSchedulerImpl<F, Fut>
 where
 	F: Fn() -> Fut + Send + Sync + 'static,
 	Fut: Future<Output = ()> + Send,
 {
-	interval: Duration,
-	task:     F,
+	schedule:           Schedule,
+	jitter:             Duration,
+	missed_tick_policy: MissedTickPolicy,
+	shutdown:           Shutdown,
+	task:               F,
 }
 
 impl<F, Fut> CronSchedulerImpl<F, Fut>
@@ -23,8 +112,50 @@ where
 	F: Fn() -> Fut + Send + Sync + 'static,
 	Fut: Future<Output = ()> + Send,
 {
-	pub fn new(interval: Duration, task: F) -> Self {
-		Self { interval, task }
+	pub fn new(schedule: Schedule, task: F) -> Self {
+		Self {
+			schedule,
+			jitter: Duration::ZERO,
+			missed_tick_policy: MissedTickPolicy::default(),
+			shutdown: Shutdown::new(),
+			task,
+		}
+	}
+
+	/// Delays every run by a random amount up to `jitter`, so replicas on
+	/// the same schedule do not fire in lockstep.
+	pub fn with_jitter(mut self, jitter: Duration) -> Self {
+		self.jitter = jitter;
+		self
+	}
+
+	pub fn with_missed_tick_policy(mut self, missed_tick_policy: MissedTickPolicy) -> Self {
+		self.missed_tick_policy = missed_tick_policy;
+		self
+	}
+
+	/// Stops the loop once `shutdown` fires. A run in progress is finished
+	/// first, a pending tick is not started.
+	pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+		self.shutdown = shutdown;
+		self
+	}
+
+	fn random_jitter(&self) -> Duration {
+		if self.jitter.is_zero() {
+			Duration::ZERO
+		} else {
+			self.jitter.mul_f64(rand::random::<f64>())
+		}
+	}
+
+	async fn sleep_until(&self, tick: DateTime<Utc>) {
+		let jitter = self.random_jitter();
+		if let Ok(delay) = (tick - Utc::now()).to_std() {
+			time::sleep(delay + jitter).await;
+		} else if !jitter.is_zero() {
+			time::sleep(jitter).await;
+		}
 	}
 }
 
@@ -35,12 +166,148 @@ where
 	Fut: Future<Output = ()> + Send,
 {
 	async fn start(&self) {
-		let mut ticker = time::interval(self.interval);
-		loop {
-			// Wait for the next tick.
-			ticker.tick().await;
-			// Execute the task.
+		let mut next = self.schedule.first(Utc::now());
+		while let Some(tick) = next {
+			// Wait for the next tick, unless we are asked to stop first.
+			tokio::select! {
+				_ = self.sleep_until(tick) => {},
+				_ = self.shutdown.wait() => break,
+			}
+			// Execute the task, ticks due meanwhile are handled by the missed
+			// tick policy.
 			(self.task)().await;
+			if self.shutdown.is_triggered() {
+				break;
+			}
+
+			next = self
+				.missed_tick_policy
+				.next_tick(&self.schedule, tick, Utc::now());
+		}
+		if self.shutdown.is_triggered() {
+			info!("Scheduler stopped for shutdown");
+		} else {
+			warn!("Schedule has no upcoming ticks, scheduler stopped");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		Arc,
+		atomic::{AtomicUsize, Ordering},
+	};
+
+	use chrono::TimeZone;
+
+	use super::*;
+
+	fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, second)
+			.unwrap()
+	}
+
+	#[test]
+	fn periodic_schedules_start_now_and_keep_their_period() {
+		let schedule = Schedule::Every(Duration::from_secs(60));
+
+		assert_eq!(schedule.first(at(9, 0, 30)), Some(at(9, 0, 30)));
+		assert_eq!(schedule.next_after(at(9, 0, 30)), Some(at(9, 1, 30)));
+		// Three and a half periods late, the next tick still ahead is 4 in.
+		assert_eq!(
+			schedule.next_ahead(at(9, 0, 0), at(9, 3, 30)),
+			Some(at(9, 4, 0))
+		);
+	}
+
+	#[test]
+	fn cron_schedules_follow_the_expression() {
+		let schedule = Schedule::cron("0 */5 * * * *").unwrap();
+
+		assert_eq!(schedule.first(at(9, 1, 0)), Some(at(9, 5, 0)));
+		assert_eq!(schedule.next_after(at(9, 5, 0)), Some(at(9, 10, 0)));
+		assert_eq!(
+			schedule.next_ahead(at(9, 5, 0), at(9, 17, 0)),
+			Some(at(9, 20, 0))
+		);
+		assert!(Schedule::cron("every five minutes").is_err());
+	}
+
+	#[test]
+	fn missed_tick_policies_pick_the_next_tick() {
+		let schedule = Schedule::Every(Duration::from_secs(60));
+		// The 09:00 run took two and a half minutes.
+		let (tick, now) = (at(9, 0, 0), at(9, 2, 30));
+
+		assert_eq!(
+			MissedTickPolicy::Burst.next_tick(&schedule, tick, now),
+			Some(at(9, 1, 0))
+		);
+		assert_eq!(
+			MissedTickPolicy::Skip.next_tick(&schedule, tick, now),
+			Some(at(9, 3, 0))
+		);
+		assert_eq!(
+			MissedTickPolicy::Delay.next_tick(&schedule, tick, now),
+			Some(at(9, 3, 30))
+		);
+
+		// A run that finished in time is followed by the next tick either way.
+		for policy in [
+			MissedTickPolicy::Burst,
+			MissedTickPolicy::Skip,
+			MissedTickPolicy::Delay,
+		] {
+			assert_eq!(
+				policy.next_tick(&schedule, tick, at(9, 0, 10)),
+				Some(at(9, 1, 0))
+			);
 		}
 	}
+
+	#[test]
+	fn jitter_stays_within_its_bound() {
+		let scheduler =
+			CronSchedulerImpl::new(Schedule::Every(Duration::from_secs(1)), || async {});
+		assert_eq!(scheduler.random_jitter(), Duration::ZERO);
+
+		let scheduler = scheduler.with_jitter(Duration::from_millis(100));
+		for _ in 0..100 {
+			assert!(scheduler.random_jitter() <= Duration::from_millis(100));
+		}
+	}
+
+	#[tokio::test]
+	async fn runs_never_overlap() {
+		let running = Arc::new(AtomicUsize::new(0));
+		let overlapped = Arc::new(AtomicUsize::new(0));
+		let runs = Arc::new(AtomicUsize::new(0));
+		let shutdown = Shutdown::new();
+
+		let scheduler = {
+			let (running, overlapped, runs) = (running.clone(), overlapped.clone(), runs.clone());
+			CronSchedulerImpl::new(Schedule::Every(Duration::from_millis(5)), move || {
+				let (running, overlapped, runs) =
+					(running.clone(), overlapped.clone(), runs.clone());
+				async move {
+					if running.fetch_add(1, Ordering::SeqCst) > 0 {
+						overlapped.fetch_add(1, Ordering::SeqCst);
+					}
+					time::sleep(Duration::from_millis(20)).await;
+					running.fetch_sub(1, Ordering::SeqCst);
+					runs.fetch_add(1, Ordering::SeqCst);
+				}
+			})
+			.with_missed_tick_policy(MissedTickPolicy::Burst)
+			.with_shutdown(shutdown.clone())
+		};
+
+		tokio::join!(scheduler.start(), async {
+			time::sleep(Duration::from_millis(100)).await;
+			shutdown.trigger();
+		});
+		assert!(runs.load(Ordering::SeqCst) >= 2);
+		assert_eq!(overlapped.load(Ordering::SeqCst), 0);
+	}
 }
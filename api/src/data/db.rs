@@ -1,11 +1,22 @@
+use async_trait::async_trait;
+use common::monitoring::health::HealthCheck;
 use mongodb::bson::doc;
 
-use super::notifications::Notification;
+use super::{
+	notifications::Notification,
+	outbox::OutboxEntry,
+	replays::ReplayRecord,
+	subscriptions::WebPushSubscription,
+};
 
 #[derive(Clone)]
 pub struct DbContext {
+	pub client: mongodb::Client,
 	pub db: mongodb::Database,
 	pub notifications_collection: mongodb::Collection<Notification>,
+	pub replays_collection: mongodb::Collection<ReplayRecord>,
+	pub outbox_collection: mongodb::Collection<OutboxEntry>,
+	pub subscriptions_collection: mongodb::Collection<WebPushSubscription>,
 }
 
 impl DbContext {
@@ -15,15 +26,40 @@ impl DbContext {
 
 		let db = client.database("notifications");
 		let notifications_collection = db.collection::<Notification>("notifications");
+		let replays_collection = db.collection::<ReplayRecord>("replays");
+		let outbox_collection = db.collection::<OutboxEntry>("outbox");
+		let subscriptions_collection =
+			db.collection::<WebPushSubscription>("webpush_subscriptions");
 
 		create_notifications_indexes(&notifications_collection).await?;
+		create_outbox_indexes(&outbox_collection).await?;
+		create_subscriptions_indexes(&subscriptions_collection).await?;
 		Ok(DbContext {
+			client,
 			db,
 			notifications_collection,
+			replays_collection,
+			outbox_collection,
+			subscriptions_collection,
 		})
 	}
 }
 
+#[async_trait]
+impl HealthCheck for DbContext {
+	fn name(&self) -> String {
+		"mongo".to_string()
+	}
+
+	async fn check(&self) -> Result<(), String> {
+		self.db
+			.run_command(doc! { "ping": 1 })
+			.await
+			.map(|_| ())
+			.map_err(|e| format!("ping failed: {}", e))
+	}
+}
+
 async fn create_notifications_indexes(
 	coll: &mongodb::Collection<Notification>,
 ) -> Result<(), mongodb::error::Error> {
@@ -32,6 +68,52 @@ async fn create_notifications_indexes(
 		.options(mongodb::options::IndexOptions::builder().build())
 		.build();
 
+	let lease_index = mongodb::IndexModel::builder()
+		.keys(doc! { "status": 1, "leaseExpiresAt": 1 })
+		.options(mongodb::options::IndexOptions::builder().build())
+		.build();
+
+	let inbox_index = mongodb::IndexModel::builder()
+		.keys(doc! { "recipient.id": 1, "channel": 1, "status": 1, "scheduledTime": -1 })
+		.options(mongodb::options::IndexOptions::builder().build())
+		.build();
+
 	coll.create_index(priority_status_index).await?;
+	coll.create_index(lease_index).await?;
+	coll.create_index(inbox_index).await?;
+	Ok(())
+}
+
+async fn create_outbox_indexes(
+	coll: &mongodb::Collection<OutboxEntry>,
+) -> Result<(), mongodb::error::Error> {
+	let lease_index = mongodb::IndexModel::builder()
+		.keys(doc! { "leaseExpiresAt": 1, "createdAt": 1 })
+		.options(mongodb::options::IndexOptions::builder().build())
+		.build();
+
+	coll.create_index(lease_index).await?;
+	Ok(())
+}
+
+async fn create_subscriptions_indexes(
+	coll: &mongodb::Collection<WebPushSubscription>,
+) -> Result<(), mongodb::error::Error> {
+	let endpoint_index = mongodb::IndexModel::builder()
+		.keys(doc! { "endpoint": 1 })
+		.options(
+			mongodb::options::IndexOptions::builder()
+				.unique(true)
+				.build(),
+		)
+		.build();
+
+	let recipient_index = mongodb::IndexModel::builder()
+		.keys(doc! { "recipientId": 1, "createdAt": 1 })
+		.options(mongodb::options::IndexOptions::builder().build())
+		.build();
+
+	coll.create_index(endpoint_index).await?;
+	coll.create_index(recipient_index).await?;
 	Ok(())
 }
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::{
+	Collection,
+	bson::{Document, doc},
+};
+use serde::Serialize;
+
+use crate::{
+	data::notifications::{Channel, Notification, Status, timestamp},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Selects a page of a recipient's inbox.
+pub struct InboxQuery {
+	pub recipient_id: String,
+	pub unread_only:  bool,
+	/// Lists archived items instead of the others.
+	pub archived:     bool,
+	pub limit:        i64,
+	pub offset:       u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InboxPage {
+	#[serde(rename = "items")]
+	pub items: Vec<Notification>,
+	/// Items matching the query, over all pages.
+	#[serde(rename = "total")]
+	pub total: u64,
+}
+
+/// The inbox of a recipient holds their in-app notifications once sent, so
+/// scheduled ones only show up when due.
+#[async_trait]
+pub trait InboxRepository: Send + Sync {
+	/// Newest first.
+	async fn list(&self, query: InboxQuery) -> AppResult<InboxPage>;
+	/// Counts unread items that are not archived.
+	async fn count_unread(&self, recipient_id: String) -> AppResult<u64>;
+	/// Keeps the time it was first read. `None` if the recipient has no such
+	/// item.
+	async fn mark_read(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>>;
+	/// Keeps the time it was first archived. `None` if the recipient has no
+	/// such item.
+	async fn archive(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>>;
+	/// Returns `false` if the recipient has no such item.
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<bool>;
+}
+
+pub struct InboxRepositoryImpl {
+	notifications: Collection<Notification>,
+}
+
+impl InboxRepositoryImpl {
+	pub fn new(notifications: Collection<Notification>) -> Self {
+		InboxRepositoryImpl { notifications }
+	}
+
+	/// Sets `field` to `now` on the item unless it is set already.
+	async fn stamp(
+		&self,
+		recipient_id: String,
+		id: String,
+		field: &str,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		let mut filter = inbox_filter(recipient_id);
+		filter.insert("_id", id);
+		let mut unset = filter.clone();
+		unset.insert(field, doc! { "$exists": false });
+		self.notifications
+			.update_one(unset, doc! { "$set": { field: timestamp(now) } })
+			.await
+			.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to update with err: {:?}", e))
+			})?;
+
+		self.notifications
+			.find_one(filter)
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))
+	}
+}
+
+fn inbox_filter(recipient_id: String) -> Document {
+	let channel_str: String = Channel::InApp.into();
+	let status_str: String = Status::Sent.into();
+	doc! { "recipient.id": recipient_id, "channel": channel_str, "status": status_str }
+}
+
+#[async_trait]
+impl InboxRepository for InboxRepositoryImpl {
+	async fn list(&self, query: InboxQuery) -> AppResult<InboxPage> {
+		let mut filter = inbox_filter(query.recipient_id);
+		filter.insert("archivedAt", doc! { "$exists": query.archived });
+		if query.unread_only {
+			filter.insert("readAt", doc! { "$exists": false });
+		}
+
+		let total = self
+			.notifications
+			.count_documents(filter.clone())
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to count: {}", e)))?;
+		let items = self
+			.notifications
+			.find(filter)
+			.sort(doc! { "scheduledTime": -1, "_id": -1 })
+			.skip(query.offset)
+			.limit(query.limit)
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?
+			.try_collect()
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))?;
+		Ok(InboxPage { items, total })
+	}
+
+	async fn count_unread(&self, recipient_id: String) -> AppResult<u64> {
+		let mut filter = inbox_filter(recipient_id);
+		filter.insert("readAt", doc! { "$exists": false });
+		filter.insert("archivedAt", doc! { "$exists": false });
+		self.notifications
+			.count_documents(filter)
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to count: {}", e)))
+	}
+
+	async fn mark_read(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		self.stamp(recipient_id, id, "readAt", now).await
+	}
+
+	async fn archive(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		self.stamp(recipient_id, id, "archivedAt", now).await
+	}
+
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<bool> {
+		let mut filter = inbox_filter(recipient_id);
+		filter.insert("_id", id);
+		let result = self.notifications.delete_one(filter).await.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to delete notification with err: {:?}", e))
+		})?;
+		Ok(result.deleted_count > 0)
+	}
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{
+	data::{
+		inbox::{InboxPage, InboxQuery, InboxRepository},
+		memory::MemoryStore,
+		notifications::{Channel, Notification, Status},
+	},
+	utils::types::AppResult,
+};
+
+pub struct MemoryInboxRepository {
+	store: Arc<MemoryStore>,
+}
+
+impl MemoryInboxRepository {
+	pub fn new(store: Arc<MemoryStore>) -> Self {
+		MemoryInboxRepository { store }
+	}
+
+	fn update(
+		&self,
+		recipient_id: &str,
+		id: &str,
+		update: impl FnOnce(&mut Notification),
+	) -> Option<Notification> {
+		let mut state = self.store.lock();
+		let notification = state
+			.notifications
+			.iter_mut()
+			.find(|n| in_inbox(n, recipient_id) && n.id.as_deref() == Some(id))?;
+		update(notification);
+		Some(notification.clone())
+	}
+}
+
+fn in_inbox(notification: &Notification, recipient_id: &str) -> bool {
+	notification.recipient.id == recipient_id
+		&& notification.channel == Channel::InApp
+		&& notification.status == Status::Sent
+}
+
+#[async_trait]
+impl InboxRepository for MemoryInboxRepository {
+	async fn list(&self, query: InboxQuery) -> AppResult<InboxPage> {
+		let mut items: Vec<Notification> = self
+			.store
+			.lock()
+			.notifications
+			.iter()
+			.filter(|n| {
+				in_inbox(n, &query.recipient_id)
+					&& n.archived_at.is_some() == query.archived
+					&& (!query.unread_only || n.read_at.is_none())
+			})
+			.cloned()
+			.collect();
+		items.sort_by(|a, b| (b.scheduled_time, &b.id).cmp(&(a.scheduled_time, &a.id)));
+
+		let total = items.len() as u64;
+		let items = items
+			.into_iter()
+			.skip(query.offset as usize)
+			.take(query.limit.max(0) as usize)
+			.collect();
+		Ok(InboxPage { items, total })
+	}
+
+	async fn count_unread(&self, recipient_id: String) -> AppResult<u64> {
+		Ok(self
+			.store
+			.lock()
+			.notifications
+			.iter()
+			.filter(|n| {
+				in_inbox(n, &recipient_id) && n.read_at.is_none() && n.archived_at.is_none()
+			})
+			.count() as u64)
+	}
+
+	async fn mark_read(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		Ok(self.update(&recipient_id, &id, |n| {
+			n.read_at.get_or_insert(now);
+		}))
+	}
+
+	async fn archive(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		Ok(self.update(&recipient_id, &id, |n| {
+			n.archived_at.get_or_insert(now);
+		}))
+	}
+
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<bool> {
+		let mut state = self.store.lock();
+		let before = state.notifications.len();
+		state
+			.notifications
+			.retain(|n| !in_inbox(n, &recipient_id) || n.id.as_deref() != Some(id.as_str()));
+		Ok(state.notifications.len() < before)
+	}
+}
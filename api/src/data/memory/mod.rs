@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::data::{
+	notifications::Notification,
+	outbox::OutboxEntry,
+	replays::ReplayRecord,
+	subscriptions::WebPushSubscription,
+};
+
+pub mod inbox;
+pub mod notifications;
+pub mod outbox;
+pub mod replays;
+pub mod subscriptions;
+
+/// Process-local storage shared by the in-memory repositories. Every
+/// operation runs under one lock, which gives the outbox the same atomicity
+/// as a Mongo transaction.
+#[derive(Default)]
+pub struct MemoryStore {
+	state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+	notifications: Vec<Notification>,
+	outbox:        Vec<OutboxEntry>,
+	replays:       Vec<ReplayRecord>,
+	subscriptions: Vec<WebPushSubscription>,
+}
+
+impl MemoryStore {
+	pub fn new() -> Arc<Self> {
+		Arc::new(Self::default())
+	}
+
+	#[cfg(test)]
+	pub fn notifications(&self) -> Vec<Notification> {
+		self.lock().notifications.clone()
+	}
+
+	#[cfg(test)]
+	pub fn outbox(&self) -> Vec<OutboxEntry> {
+		self.lock().outbox.clone()
+	}
+
+	fn lock(&self) -> MutexGuard<'_, MemoryState> {
+		// A panic while holding the lock cannot leave a half applied change
+		// behind, every operation mutates in place after its checks.
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
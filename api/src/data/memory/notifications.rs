@@ -0,0 +1,526 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+
+use crate::{
+	data::{
+		memory::MemoryStore,
+		new_id,
+		notifications::{
+			ClaimOptions,
+			Daytime,
+			DeadLetterFilter,
+			GetMessagesOptions,
+			Notification,
+			NotificationRepository,
+			Status,
+			has_effective_priority,
+			recipient_local_hour,
+		},
+		replays::ReplayRecord,
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Keeps notifications in a [`MemoryStore`], with the same filters and
+/// claiming rules as the Mongo repository.
+pub struct MemoryNotificationRepository {
+	store:   Arc<MemoryStore>,
+	daytime: Daytime,
+}
+
+impl MemoryNotificationRepository {
+	pub fn new(store: Arc<MemoryStore>, daytime: Daytime) -> Self {
+		MemoryNotificationRepository { store, daytime }
+	}
+
+	fn is_daytime(&self, notification: &Notification, now: DateTime<Utc>) -> bool {
+		self.daytime.contains(recipient_local_hour(
+			now,
+			&notification.recipient.timezone_offset,
+		))
+	}
+}
+
+/// Due at `now`, taking the retry backoff into account.
+fn is_due(notification: &Notification, now: DateTime<Utc>) -> bool {
+	notification.scheduled_time <= now
+		&& notification
+			.next_attempt_at
+			.is_none_or(|next_attempt_at| next_attempt_at <= now)
+}
+
+#[async_trait]
+impl NotificationRepository for MemoryNotificationRepository {
+	async fn create(&self, notification: Notification) -> AppResult<Notification> {
+		let notification = Notification {
+			id: Some(new_id()),
+			attempts: 0,
+			replays: 0,
+			next_attempt_at: None,
+			last_error: None,
+			failed_at: None,
+			failure_reason: None,
+			read_at: None,
+			archived_at: None,
+			..notification
+		};
+		self.store.lock().notifications.push(notification.clone());
+		Ok(notification)
+	}
+
+	async fn get_messages(
+		&self,
+		opts: GetMessagesOptions,
+	) -> AppResult<Box<dyn Stream<Item = Result<Notification, AppError>> + Send + Unpin>> {
+		let limit = opts.limit.unwrap_or(i64::MAX).max(0) as usize;
+		let state = self.store.lock();
+		let notifications: Vec<Result<Notification, AppError>> = state
+			.notifications
+			.iter()
+			.filter(|n| {
+				opts.channel
+					.as_ref()
+					.is_none_or(|channel| n.channel == *channel)
+			})
+			.filter(|n| {
+				opts.priority
+					.as_ref()
+					.is_none_or(|priority| n.priority == *priority)
+			})
+			.filter(|n| {
+				opts.status
+					.as_ref()
+					.is_none_or(|status| n.status == *status)
+			})
+			.filter(|n| match opts.scheduled_time {
+				Some(scheduled_time) => {
+					is_due(n, scheduled_time)
+						&& (!opts.respect_nighttime.unwrap_or(false)
+							|| self.is_daytime(n, scheduled_time))
+				}
+				None => true,
+			})
+			.take(limit)
+			.cloned()
+			.map(Ok)
+			.collect();
+
+		Ok(Box::new(futures::stream::iter(notifications)))
+	}
+
+	async fn claim_messages(&self, opts: ClaimOptions) -> AppResult<Vec<Notification>> {
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+
+		let mut state = self.store.lock();
+		let mut candidates = Vec::new();
+		for (index, n) in state.notifications.iter().enumerate() {
+			let claimable = match n.status {
+				Status::Pending => is_due(n, opts.now),
+				// Claimed by a replica that did not finish in time.
+				Status::Processing => n.lease_expires_at.is_some_and(|lease| lease < opts.now),
+				_ => false,
+			};
+			if claimable
+				&& has_effective_priority(n, &opts.priority, opts.now, opts.aging_step)?
+				&& (!opts.respect_nighttime || self.is_daytime(n, opts.now))
+			{
+				candidates.push(index);
+			}
+		}
+		candidates.sort_by_key(|index| state.notifications[*index].scheduled_time);
+		candidates.truncate(opts.limit.max(0) as usize);
+
+		Ok(candidates
+			.into_iter()
+			.map(|index| {
+				let notification = &mut state.notifications[index];
+				notification.status = Status::Processing;
+				notification.owner = Some(opts.owner.clone());
+				notification.lease_expires_at = Some(lease_expires_at);
+				notification.clone()
+			})
+			.collect())
+	}
+
+	async fn update_message_status(
+		&self,
+		id: String,
+		owner: &str,
+		status: Status,
+	) -> AppResult<bool> {
+		let mut state = self.store.lock();
+		let Some(notification) = state.notifications.iter_mut().find(|n| {
+			n.id.as_deref() == Some(id.as_str())
+				&& n.status == Status::Processing
+				&& n.owner.as_deref() == Some(owner)
+		}) else {
+			return Ok(false);
+		};
+		if status == Status::Failed {
+			notification.failed_at = Some(Utc::now());
+		}
+		notification.status = status;
+		notification.owner = None;
+		notification.lease_expires_at = None;
+		Ok(true)
+	}
+
+	async fn replay_failed(
+		&self,
+		id: String,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> AppResult<Option<ReplayRecord>> {
+		let mut state = self.store.lock();
+		let Some(notification) = state
+			.notifications
+			.iter_mut()
+			.find(|n| n.id.as_deref() == Some(id.as_str()) && n.status == Status::Failed)
+		else {
+			return Ok(None);
+		};
+
+		let previous = notification.clone();
+		notification.status = Status::Pending;
+		notification.attempts = 0;
+		notification.replays += 1;
+		if let Some(scheduled_time) = scheduled_time {
+			notification.scheduled_time = scheduled_time;
+		}
+		notification.next_attempt_at = None;
+		notification.last_error = None;
+		notification.failed_at = None;
+		notification.failure_reason = None;
+
+		let record = ReplayRecord::new(new_id(), previous, requested_by, scheduled_time);
+		state.replays.push(record.clone());
+		Ok(Some(record))
+	}
+
+	async fn purge_failed(&self, filter: DeadLetterFilter) -> AppResult<u64> {
+		let mut state = self.store.lock();
+		let before = state.notifications.len();
+		state.notifications.retain(|n| {
+			let matches = n.status == Status::Failed
+				&& filter
+					.id
+					.as_ref()
+					.is_none_or(|id| n.id.as_ref() == Some(id))
+				&& filter
+					.channel
+					.as_ref()
+					.is_none_or(|channel| n.channel == *channel)
+				&& filter
+					.priority
+					.as_ref()
+					.is_none_or(|priority| n.priority == *priority)
+				&& filter.failed_before.is_none_or(|failed_before| {
+					n.failed_at
+						.is_some_and(|failed_at| failed_at < failed_before)
+				});
+			!matches
+		});
+		Ok((before - state.notifications.len()) as u64)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use chrono::TimeZone;
+	use futures::TryStreamExt;
+
+	use super::*;
+	use crate::{data::notifications::Priority, testing::notification};
+
+	fn repository() -> MemoryNotificationRepository {
+		MemoryNotificationRepository::new(MemoryStore::new(), Daytime::default())
+	}
+
+	fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+	}
+
+	fn claim(priority: Priority, now: DateTime<Utc>) -> ClaimOptions {
+		ClaimOptions {
+			priority,
+			limit: 10,
+			now,
+			owner: "replica-1".to_string(),
+			lease: Duration::from_secs(60),
+			respect_nighttime: false,
+			aging_step: None,
+		}
+	}
+
+	async fn ids(
+		repository: &MemoryNotificationRepository,
+		opts: GetMessagesOptions,
+	) -> Vec<String> {
+		repository
+			.get_messages(opts)
+			.await
+			.unwrap()
+			.map_ok(|n| n.content)
+			.try_collect()
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn get_messages_filters_by_status_and_scheduled_time() {
+		let repository = repository();
+		for (content, scheduled_time, status) in [
+			("due", at(10, 0), Status::Pending),
+			("future", at(12, 0), Status::Pending),
+			("sent", at(10, 0), Status::Sent),
+		] {
+			repository
+				.create(Notification {
+					content: content.to_string(),
+					status,
+					..notification(Priority::Normal, scheduled_time)
+				})
+				.await
+				.unwrap();
+		}
+
+		let due = ids(
+			&repository,
+			GetMessagesOptions {
+				status: Some(Status::Pending),
+				scheduled_time: Some(at(11, 0)),
+				..Default::default()
+			},
+		)
+		.await;
+		assert_eq!(due, vec!["due"]);
+		assert_eq!(
+			ids(&repository, GetMessagesOptions::default()).await.len(),
+			3
+		);
+	}
+
+	#[tokio::test]
+	async fn get_messages_waits_for_retry_backoff() {
+		let repository = repository();
+		let created = repository
+			.create(notification(Priority::Normal, at(9, 0)))
+			.await
+			.unwrap();
+		repository.store.lock().notifications[0].next_attempt_at = Some(at(11, 0));
+
+		let opts = |now| GetMessagesOptions {
+			scheduled_time: Some(now),
+			..Default::default()
+		};
+		assert!(ids(&repository, opts(at(10, 0))).await.is_empty());
+		assert_eq!(
+			ids(&repository, opts(at(11, 0))).await,
+			vec![created.content]
+		);
+	}
+
+	#[tokio::test]
+	async fn quiet_hours_use_the_recipient_offset() {
+		let repository = repository();
+		for offset in ["+00:00", "+09:00"] {
+			let mut n = notification(Priority::Normal, at(1, 0));
+			n.content = offset.to_string();
+			n.recipient.timezone_offset = offset.to_string();
+			repository.create(n).await.unwrap();
+		}
+
+		// 03:00 UTC is night at +00:00 and noon at +09:00.
+		let awake = ids(
+			&repository,
+			GetMessagesOptions {
+				scheduled_time: Some(at(3, 0)),
+				respect_nighttime: Some(true),
+				..Default::default()
+			},
+		)
+		.await;
+		assert_eq!(awake, vec!["+09:00"]);
+
+		let claimed = repository
+			.claim_messages(ClaimOptions {
+				respect_nighttime: true,
+				..claim(Priority::Normal, at(3, 0))
+			})
+			.await
+			.unwrap();
+		assert_eq!(claimed.len(), 1);
+		assert_eq!(claimed[0].content, "+09:00");
+	}
+
+	#[tokio::test]
+	async fn claim_takes_due_work_once_until_the_lease_expires() {
+		let repository = repository();
+		for minute in [30, 10, 20] {
+			repository
+				.create(notification(Priority::High, at(9, minute)))
+				.await
+				.unwrap();
+		}
+		repository
+			.create(notification(Priority::Low, at(9, 0)))
+			.await
+			.unwrap();
+
+		let claimed = repository
+			.claim_messages(claim(Priority::High, at(10, 0)))
+			.await
+			.unwrap();
+		let times: Vec<_> = claimed.iter().map(|n| n.scheduled_time).collect();
+		assert_eq!(times, vec![at(9, 10), at(9, 20), at(9, 30)]);
+		assert!(
+			claimed
+				.iter()
+				.all(|n| n.status == Status::Processing && n.owner.as_deref() == Some("replica-1"))
+		);
+
+		let again = repository
+			.claim_messages(claim(Priority::High, at(10, 0)))
+			.await
+			.unwrap();
+		assert!(again.is_empty());
+
+		let expired = repository
+			.claim_messages(claim(Priority::High, at(10, 2)))
+			.await
+			.unwrap();
+		assert_eq!(expired.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn aged_notifications_are_claimed_at_a_higher_priority() {
+		let repository = repository();
+		repository
+			.create(notification(Priority::Low, at(9, 48)))
+			.await
+			.unwrap();
+		let aging = |priority| ClaimOptions {
+			aging_step: Some(Duration::from_secs(5 * 60)),
+			..claim(priority, at(10, 0))
+		};
+
+		// Waited 12 minutes: two steps up from low, not yet three.
+		assert!(
+			repository
+				.claim_messages(aging(Priority::Critical))
+				.await
+				.unwrap()
+				.is_empty()
+		);
+		assert!(
+			repository
+				.claim_messages(aging(Priority::Low))
+				.await
+				.unwrap()
+				.is_empty()
+		);
+		assert_eq!(
+			repository
+				.claim_messages(aging(Priority::High))
+				.await
+				.unwrap()
+				.len(),
+			1
+		);
+	}
+
+	#[tokio::test]
+	async fn replay_and_purge_only_touch_failed_notifications() {
+		let repository = repository();
+		let failed = repository
+			.create(notification(Priority::Normal, at(9, 0)))
+			.await
+			.unwrap();
+		let pending = repository
+			.create(notification(Priority::Normal, at(9, 0)))
+			.await
+			.unwrap();
+		{
+			let mut state = repository.store.lock();
+			let n = &mut state.notifications[0];
+			n.status = Status::Failed;
+			n.attempts = 5;
+			n.failed_at = Some(at(9, 30));
+		}
+
+		assert!(
+			repository
+				.replay_failed(pending.id.clone().unwrap(), "ops".to_string(), None)
+				.await
+				.unwrap()
+				.is_none()
+		);
+		let record = repository
+			.replay_failed(
+				failed.id.clone().unwrap(),
+				"ops".to_string(),
+				Some(at(12, 0)),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(record.previous_attempts, 5);
+		assert_eq!(record.previous_failed_at, Some(at(9, 30)));
+		assert_eq!(repository.store.lock().replays.len(), 1);
+		let replayed = repository.store.notifications()[0].clone();
+		assert_eq!(replayed.status, Status::Pending);
+		assert_eq!(replayed.attempts, 0);
+		assert_eq!(replayed.scheduled_time, at(12, 0));
+		assert!(replayed.failed_at.is_none());
+
+		repository.store.lock().notifications[0].status = Status::Failed;
+		repository.store.lock().notifications[0].failed_at = Some(at(9, 30));
+		let filter = |failed_before| DeadLetterFilter {
+			failed_before: Some(failed_before),
+			..Default::default()
+		};
+		assert_eq!(repository.purge_failed(filter(at(9, 0))).await.unwrap(), 0);
+		assert_eq!(repository.purge_failed(filter(at(10, 0))).await.unwrap(), 1);
+		assert_eq!(repository.store.notifications().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn every_transition_to_failed_is_purgeable() {
+		let repository = repository();
+		let id = repository
+			.create(notification(Priority::Normal, at(9, 0)))
+			.await
+			.unwrap()
+			.id
+			.unwrap();
+		repository
+			.claim_messages(claim(Priority::Normal, at(10, 0)))
+			.await
+			.unwrap();
+		let stale = repository
+			.update_message_status(id.clone(), "replica-2", Status::Sent)
+			.await
+			.unwrap();
+		assert!(!stale, "only the owner of the claim completes it");
+		repository
+			.update_message_status(id, "replica-1", Status::Failed)
+			.await
+			.unwrap();
+
+		let purged = repository
+			.purge_failed(DeadLetterFilter {
+				failed_before: Some(Utc::now() + chrono::Duration::seconds(1)),
+				..Default::default()
+			})
+			.await
+			.unwrap();
+		assert_eq!(purged, 1);
+	}
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+	data::{
+		memory::MemoryStore,
+		new_id,
+		notifications::{AttemptFailure, Notification, Status, apply_failed_attempt},
+		outbox::{ClaimOutboxOptions, OutboxEntry, OutboxRepository},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct MemoryOutboxRepository {
+	store: Arc<MemoryStore>,
+}
+
+impl MemoryOutboxRepository {
+	pub fn new(store: Arc<MemoryStore>) -> Self {
+		MemoryOutboxRepository { store }
+	}
+
+	/// Removes `entry` and applies `update` to its notification if it is still
+	/// queued, both under the store lock. Returns `false`, changing nothing,
+	/// if the entry is no longer claimed by its owner.
+	fn settle(&self, entry: &OutboxEntry, update: impl FnOnce(&mut Notification)) -> bool {
+		let mut state = self.store.lock();
+		let Some(index) = state
+			.outbox
+			.iter()
+			.position(|e| e.id == entry.id && e.owner == entry.owner)
+		else {
+			return false;
+		};
+		state.outbox.remove(index);
+		if let Some(notification) = state.notifications.iter_mut().find(|n| {
+			n.id.as_deref() == Some(entry.notification_id.as_str()) && n.status == Status::Queued
+		}) {
+			update(notification);
+		}
+		true
+	}
+}
+
+#[async_trait]
+impl OutboxRepository for MemoryOutboxRepository {
+	async fn enqueue(
+		&self,
+		notification: &Notification,
+		owner: &str,
+		entry: OutboxEntry,
+	) -> AppResult<bool> {
+		let id = notification
+			.id
+			.clone()
+			.ok_or_else(|| AppError::ServiceError("Notification has no id".to_string()))?;
+
+		let mut state = self.store.lock();
+		let Some(stored) = state.notifications.iter_mut().find(|n| {
+			n.id.as_deref() == Some(id.as_str())
+				&& n.status == Status::Processing
+				&& n.owner.as_deref() == Some(owner)
+		}) else {
+			return Ok(false);
+		};
+		stored.status = Status::Queued;
+		stored.owner = None;
+		stored.lease_expires_at = None;
+		state.outbox.push(OutboxEntry {
+			id: Some(new_id()),
+			..entry
+		});
+		Ok(true)
+	}
+
+	async fn claim(&self, opts: ClaimOutboxOptions) -> AppResult<Vec<OutboxEntry>> {
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+
+		let mut state = self.store.lock();
+		let mut candidates: Vec<usize> = state
+			.outbox
+			.iter()
+			.enumerate()
+			.filter(|(_, e)| e.lease_expires_at.is_none_or(|lease| lease < opts.now))
+			.map(|(index, _)| index)
+			.collect();
+		candidates.sort_by_key(|index| state.outbox[*index].created_at);
+		candidates.truncate(opts.limit.max(0) as usize);
+
+		Ok(candidates
+			.into_iter()
+			.map(|index| {
+				let entry = &mut state.outbox[index];
+				entry.owner = Some(opts.owner.clone());
+				entry.lease_expires_at = Some(lease_expires_at);
+				entry.clone()
+			})
+			.collect())
+	}
+
+	async fn mark_published(&self, entry: &OutboxEntry) -> AppResult<bool> {
+		Ok(self.settle(entry, |notification| notification.status = Status::Sent))
+	}
+
+	async fn mark_failed(&self, entry: &OutboxEntry, failure: AttemptFailure) -> AppResult<bool> {
+		Ok(self.settle(entry, |notification| {
+			apply_failed_attempt(notification, &failure)
+		}))
+	}
+
+	async fn cancel(&self, notification_id: &str) -> AppResult<bool> {
+		let mut state = self.store.lock();
+		let Some(notification) = state.notifications.iter_mut().find(|n| {
+			n.id.as_deref() == Some(notification_id)
+				&& matches!(n.status, Status::Pending | Status::Queued)
+		}) else {
+			return Ok(false);
+		};
+		notification.status = Status::Cancelled;
+		notification.owner = None;
+		notification.lease_expires_at = None;
+		state
+			.outbox
+			.retain(|e| e.notification_id != notification_id);
+		Ok(true)
+	}
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+	data::{
+		memory::MemoryStore,
+		replays::{ReplayRecord, ReplayRepository},
+	},
+	utils::types::AppResult,
+};
+
+pub struct MemoryReplayRepository {
+	store: Arc<MemoryStore>,
+}
+
+impl MemoryReplayRepository {
+	pub fn new(store: Arc<MemoryStore>) -> Self {
+		MemoryReplayRepository { store }
+	}
+}
+
+#[async_trait]
+impl ReplayRepository for MemoryReplayRepository {
+	async fn list(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>> {
+		let mut records: Vec<ReplayRecord> = self
+			.store
+			.lock()
+			.replays
+			.iter()
+			.filter(|r| {
+				notification_id
+					.as_ref()
+					.is_none_or(|id| r.notification_id == *id)
+			})
+			.cloned()
+			.collect();
+		records.sort_by_key(|r| std::cmp::Reverse(r.replayed_at));
+		Ok(records)
+	}
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+	data::{
+		memory::MemoryStore,
+		new_id,
+		subscriptions::{SubscriptionRepository, WebPushSubscription},
+	},
+	utils::types::AppResult,
+};
+
+pub struct MemorySubscriptionRepository {
+	store: Arc<MemoryStore>,
+}
+
+impl MemorySubscriptionRepository {
+	pub fn new(store: Arc<MemoryStore>) -> Self {
+		MemorySubscriptionRepository { store }
+	}
+}
+
+#[async_trait]
+impl SubscriptionRepository for MemorySubscriptionRepository {
+	async fn upsert(&self, subscription: WebPushSubscription) -> AppResult<WebPushSubscription> {
+		let mut state = self.store.lock();
+		if let Some(existing) = state
+			.subscriptions
+			.iter_mut()
+			.find(|s| s.endpoint == subscription.endpoint)
+		{
+			existing.recipient_id = subscription.recipient_id;
+			existing.p256dh = subscription.p256dh;
+			existing.auth = subscription.auth;
+			return Ok(existing.clone());
+		}
+
+		let subscription = WebPushSubscription {
+			id: Some(new_id()),
+			..subscription
+		};
+		state.subscriptions.push(subscription.clone());
+		Ok(subscription)
+	}
+
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>> {
+		let mut subscriptions: Vec<WebPushSubscription> = self
+			.store
+			.lock()
+			.subscriptions
+			.iter()
+			.filter(|s| s.recipient_id == recipient_id)
+			.cloned()
+			.collect();
+		subscriptions.sort_by_key(|s| s.created_at);
+		Ok(subscriptions)
+	}
+
+	async fn delete(&self, recipient_id: String, endpoint: String) -> AppResult<bool> {
+		let mut state = self.store.lock();
+		let before = state.subscriptions.len();
+		state
+			.subscriptions
+			.retain(|s| s.recipient_id != recipient_id || s.endpoint != endpoint);
+		Ok(state.subscriptions.len() < before)
+	}
+}
@@ -1,2 +1,15 @@
 pub(crate) mod db;
+pub mod inbox;
+pub mod memory;
 pub mod notifications;
+pub mod outbox;
+pub mod postgres;
+pub mod replays;
+pub mod sqlite;
+pub mod subscriptions;
+
+/// Id of a new row for the SQL and in-memory stores, Mongo keeps its own
+/// ObjectIds.
+pub(crate) fn new_id() -> String {
+	uuid::Uuid::new_v4().simple().to_string()
+}
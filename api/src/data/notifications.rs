@@ -1,155 +1,287 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Timelike, Utc};
+pub use common::notification::{Channel, Notification, Priority, Recipient, Status};
 use futures::{Stream, StreamExt, TryStreamExt};
 use mongodb::{
+	Client,
 	Collection,
 	bson,
 	bson::{doc, oid::ObjectId},
 	error::{ErrorKind, WriteFailure::WriteError},
+	options::ReturnDocument,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::utils::{errors::AppError, types::AppResult};
+use crate::{
+	data::replays::ReplayRecord,
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub const RETRIES_EXHAUSTED: &str = "retries exhausted";
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub enum Channel {
-	#[serde(rename = "push")]
-	Push,
-	#[serde(rename = "email")]
-	Email,
+pub struct AttemptFailure {
+	pub attempts:        u32,
+	/// `None` means no further attempts, the notification is marked as failed.
+	pub next_attempt_at: Option<DateTime<Utc>>,
+	pub last_error:      String,
 }
-impl From<String> for Channel {
-	fn from(s: String) -> Self {
-		match s.as_str() {
-			"push" => Channel::Push,
-			"email" => Channel::Email,
-			_ => panic!("Invalid notification type"),
-		}
-	}
+
+#[derive(Default)]
+pub struct GetMessagesOptions {
+	pub channel:           Option<Channel>,
+	pub priority:          Option<Priority>,
+	pub status:            Option<Status>,
+	pub limit:             Option<i64>,
+	pub scheduled_time:    Option<DateTime<Utc>>,
+	pub respect_nighttime: Option<bool>,
 }
-impl From<Channel> for String {
-	fn from(val: Channel) -> Self {
-		match val {
-			Channel::Push => "push".to_string(),
-			Channel::Email => "email".to_string(),
-		}
+
+pub struct ClaimOptions {
+	/// Effective priority to claim, see `aging_step`.
+	pub priority:          Priority,
+	pub limit:             i64,
+	pub now:               DateTime<Utc>,
+	pub owner:             String,
+	pub lease:             std::time::Duration,
+	pub respect_nighttime: bool,
+	/// Notifications move up one priority level for every `aging_step` they
+	/// have been waiting past their scheduled time. `None` disables aging.
+	pub aging_step:        Option<std::time::Duration>,
+}
+
+/// Selects failed notifications, all set fields must match.
+#[derive(Default)]
+pub struct DeadLetterFilter {
+	pub id:            Option<String>,
+	pub channel:       Option<Channel>,
+	pub priority:      Option<Priority>,
+	pub failed_before: Option<DateTime<Utc>>,
+	/// Must be set to select every failed notification, so a forgotten
+	/// filter does not.
+	pub all:           bool,
+}
+
+impl DeadLetterFilter {
+	pub fn is_empty(&self) -> bool {
+		self.id.is_none()
+			&& self.channel.is_none()
+			&& self.priority.is_none()
+			&& self.failed_before.is_none()
 	}
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub enum Status {
-	#[serde(rename = "pending")]
-	Pending,
-	#[serde(rename = "sent")]
-	Sent,
-	#[serde(rename = "failed")]
-	Failed,
-	#[serde(rename = "cancelled")]
-	Cancelled,
+#[async_trait]
+pub trait NotificationRepository: Send + Sync {
+	async fn create(&self, notification: Notification) -> AppResult<Notification>;
+	async fn get_messages(
+		&self,
+		opts: GetMessagesOptions,
+	) -> AppResult<Box<dyn Stream<Item = Result<Notification, AppError>> + Send + Unpin>>;
+	/// Atomically moves up to `limit` due notifications to processing under
+	/// the given owner. Notifications whose lease expired are claimed again.
+	async fn claim_messages(&self, opts: ClaimOptions) -> AppResult<Vec<Notification>>;
+	/// Finishes a notification claimed by `owner`. Returns `false`, writing
+	/// nothing, if another replica took it over after the lease expired. Also
+	/// stamps `failedAt` when moving to failed, so the notification ages out
+	/// of the dead letters like any other.
+	async fn update_message_status(
+		&self,
+		id: String,
+		owner: &str,
+		status: Status,
+	) -> AppResult<bool>;
+	/// Moves a failed notification back to pending with a fresh attempt
+	/// budget and records the replay in the same transaction. Returns the
+	/// record, or `None` if no failed notification with this id exists.
+	async fn replay_failed(
+		&self,
+		id: String,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> AppResult<Option<ReplayRecord>>;
+	async fn purge_failed(&self, filter: DeadLetterFilter) -> AppResult<u64>;
+}
+
+/// Recipient local hours, `start_hour..end_hour`, in which notifications may
+/// be delivered when nighttime is respected.
+#[derive(Clone, Copy, Debug)]
+pub struct Daytime {
+	pub start_hour: i32,
+	pub end_hour:   i32,
 }
 
-impl From<String> for Status {
-	fn from(s: String) -> Self {
-		match s.as_str() {
-			"pending" => Status::Pending,
-			"sent" => Status::Sent,
-			"failed" => Status::Failed,
-			"cancelled" => Status::Cancelled,
-			_ => panic!("Invalid notification status"),
+impl Default for Daytime {
+	fn default() -> Self {
+		Daytime {
+			start_hour: 8,
+			end_hour:   22,
 		}
 	}
 }
 
-impl From<Status> for String {
-	fn from(val: Status) -> Self {
-		match val {
-			Status::Pending => "pending".to_string(),
-			Status::Sent => "sent".to_string(),
-			Status::Failed => "failed".to_string(),
-			Status::Cancelled => "cancelled".to_string(),
-		}
+impl Daytime {
+	pub fn contains(&self, hour: i32) -> bool {
+		(self.start_hour..self.end_hour).contains(&hour)
 	}
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub enum Priority {
-	#[serde(rename = "high")]
-	High,
-	#[serde(rename = "low")]
-	Low,
+/// Hour of `now` in the recipient's timezone, computed like
+/// [`user_local_hour`]: only the hours of the offset count and the result is
+/// not wrapped around midnight.
+pub(crate) fn recipient_local_hour(now: DateTime<Utc>, timezone_offset: &str) -> i32 {
+	let offset_hours = timezone_offset
+		.get(0..3)
+		.and_then(|hours| hours.parse::<i32>().ok())
+		.unwrap_or(0);
+	now.hour() as i32 + offset_hours
 }
 
-impl From<String> for Priority {
-	fn from(s: String) -> Self {
-		match s.as_str() {
-			"high" => Priority::High,
-			"low" => Priority::Low,
-			_ => panic!("Invalid notification priority"),
-		}
-	}
+/// Timestamps are stored and compared as strings, so every one written or
+/// queried is formatted the way serde writes the model's `DateTime`s.
+pub(crate) fn timestamp(time: DateTime<Utc>) -> String {
+	time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
 }
 
-impl From<Priority> for String {
-	fn from(val: Priority) -> Self {
-		match val {
-			Priority::High => "high".to_string(),
-			Priority::Low => "low".to_string(),
-		}
+/// Hour of `now` in the recipient's timezone, as an aggregation expression.
+fn user_local_hour(now: &str) -> bson::Document {
+	doc! {
+		"$add": [
+			{"$hour": {"$toDate": now }},
+			{"$divide": [
+				{"$toInt": {"$substr": ["$recipient.timezone_offset", 0, 3]}},
+				1
+			]}
+		]
 	}
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Recipient {
-	#[serde(rename = "id")]
-	pub id:              String,
-	#[serde(rename = "timezone_offset")]
-	pub timezone_offset: String,
+/// Matches notifications whose priority, after aging, equals `priority`. A
+/// notification `n` levels below has aged into it once it waited between `n`
+/// and `n + 1` aging steps, the most urgent level also takes anything older.
+fn effective_priority_filter(
+	priority: &Priority,
+	now: DateTime<Utc>,
+	aging_step: Option<std::time::Duration>,
+) -> AppResult<bson::Document> {
+	let Some(aging_step) = aging_step else {
+		let priority_str: String = priority.clone().into();
+		return Ok(doc! { "priority": priority_str });
+	};
+	let step = chrono::Duration::from_std(aging_step)
+		.map_err(|e| AppError::ServiceError(format!("Invalid aging step: {}", e)))?;
+
+	let clauses = Priority::ALL
+		.iter()
+		.filter(|original| original.rank() >= priority.rank())
+		.map(|original| {
+			let levels = (original.rank() - priority.rank()) as i32;
+			let original_str: String = original.clone().into();
+			let mut scheduled_time = doc! { "$lte": timestamp(now - step * levels) };
+			if priority.rank() > 0 {
+				scheduled_time.insert("$gt", timestamp(now - step * (levels + 1)));
+			}
+			doc! { "priority": original_str, "scheduledTime": scheduled_time }
+		})
+		.collect::<Vec<_>>();
+
+	Ok(doc! { "$or": clauses })
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Notification {
-	#[serde(rename = "_id")]
-	pub id:             Option<String>,
-	#[serde(rename = "content")]
-	pub content:        String,
-	#[serde(rename = "channel")]
-	pub channel:        Channel, // "push" or "email"
-	#[serde(rename = "recipient")]
-	pub recipient:      Recipient, // email or device token, but for simplicity, using String
-	#[serde(rename = "scheduledTime")]
-	pub scheduled_time: DateTime<Utc>,
-	#[serde(rename = "priority")]
-	pub priority:       Priority,
-	#[serde(rename = "status")]
-	pub status:         Status, // "pending", "sent", "failed", "cancelled"
+/// In-process equivalent of [`effective_priority_filter`].
+pub(crate) fn has_effective_priority(
+	notification: &Notification,
+	priority: &Priority,
+	now: DateTime<Utc>,
+	aging_step: Option<std::time::Duration>,
+) -> AppResult<bool> {
+	let Some(aging_step) = aging_step else {
+		return Ok(notification.priority == *priority);
+	};
+	let step = chrono::Duration::from_std(aging_step)
+		.map_err(|e| AppError::ServiceError(format!("Invalid aging step: {}", e)))?;
+
+	let original = notification.priority.rank();
+	if original < priority.rank() {
+		return Ok(false);
+	}
+	let levels = (original - priority.rank()) as i32;
+	let aged_in = notification.scheduled_time <= now - step * levels;
+	let aged_past = priority.rank() > 0 && notification.scheduled_time <= now - step * (levels + 1);
+	Ok(aged_in && !aged_past)
 }
 
-#[derive(Default)]
-pub struct GetMessagesOptions {
-	pub priority:          Option<Priority>,
-	pub status:            Option<Status>,
-	pub limit:             Option<i64>,
-	pub scheduled_time:    Option<DateTime<Utc>>,
-	pub respect_nighttime: Option<bool>,
+/// Update applied to a notification after a failed delivery attempt: either
+/// back to pending until `nextAttemptAt` or, once retries are exhausted, to
+/// failed.
+pub(crate) fn failed_attempt_update(failure: &AttemptFailure) -> bson::Document {
+	match failure.next_attempt_at {
+		Some(next_attempt_at) => {
+			let status_str: String = Status::Pending.into();
+			doc! {
+				"$set": {
+					"status": status_str,
+					"attempts": failure.attempts,
+					"nextAttemptAt": timestamp(next_attempt_at),
+					"lastError": failure.last_error.clone(),
+				},
+				"$unset": { "owner": "", "leaseExpiresAt": "" },
+			}
+		}
+		None => {
+			let status_str: String = Status::Failed.into();
+			doc! {
+				"$set": {
+					"status": status_str,
+					"attempts": failure.attempts,
+					"lastError": failure.last_error.clone(),
+					"failedAt": timestamp(Utc::now()),
+					"failureReason": RETRIES_EXHAUSTED,
+				},
+				"$unset": { "nextAttemptAt": "", "owner": "", "leaseExpiresAt": "" },
+			}
+		}
+	}
 }
 
-#[async_trait]
-pub trait NotificationRepository: Send + Sync {
-	async fn create(&self, notification: Notification) -> AppResult<Notification>;
-	async fn get_messages(
-		&self,
-		opts: GetMessagesOptions,
-	) -> AppResult<Box<dyn Stream<Item = Result<Notification, AppError>> + Send + Unpin>>;
-	async fn update_message_status(&self, id: String, status: Status) -> AppResult<()>;
+/// In-process equivalent of [`failed_attempt_update`].
+pub(crate) fn apply_failed_attempt(notification: &mut Notification, failure: &AttemptFailure) {
+	notification.attempts = failure.attempts;
+	notification.last_error = Some(failure.last_error.clone());
+	notification.owner = None;
+	notification.lease_expires_at = None;
+	match failure.next_attempt_at {
+		Some(next_attempt_at) => {
+			notification.status = Status::Pending;
+			notification.next_attempt_at = Some(next_attempt_at);
+		}
+		None => {
+			notification.status = Status::Failed;
+			notification.next_attempt_at = None;
+			notification.failed_at = Some(Utc::now());
+			notification.failure_reason = Some(RETRIES_EXHAUSTED.to_string());
+		}
+	}
 }
 
 pub struct NotificationRepositoryImpl {
+	client:        Client,
 	notifications: Collection<Notification>,
+	replays:       Collection<ReplayRecord>,
+	daytime:       Daytime,
 }
 
 impl NotificationRepositoryImpl {
-	pub fn new(notifications: Collection<Notification>) -> Self {
-		NotificationRepositoryImpl { notifications }
+	pub fn new(
+		client: Client,
+		notifications: Collection<Notification>,
+		replays: Collection<ReplayRecord>,
+		daytime: Daytime,
+	) -> Self {
+		NotificationRepositoryImpl {
+			client,
+			notifications,
+			replays,
+			daytime,
+		}
 	}
 }
 
@@ -157,7 +289,18 @@ impl NotificationRepositoryImpl {
 impl NotificationRepository for NotificationRepositoryImpl {
 	async fn create(&self, notification: Notification) -> AppResult<Notification> {
 		let id = Some(ObjectId::new().to_hex());
-		let notification = Notification { id, ..notification };
+		let notification = Notification {
+			id,
+			attempts: 0,
+			replays: 0,
+			next_attempt_at: None,
+			last_error: None,
+			failed_at: None,
+			failure_reason: None,
+			read_at: None,
+			archived_at: None,
+			..notification
+		};
 		let result = self.notifications.insert_one(notification.clone()).await;
 		match result {
 			Ok(_) => Ok(notification),
@@ -179,6 +322,11 @@ impl NotificationRepository for NotificationRepositoryImpl {
 		let mut match_stage = doc! {};
 		let mut pipeline = vec![];
 
+		if let Some(channel) = opts.channel {
+			let channel_str: String = channel.into();
+			match_stage.insert("channel", channel_str);
+		}
+
 		if let Some(priority) = opts.priority {
 			let priority_str: String = priority.into();
 			match_stage.insert("priority", priority_str);
@@ -190,31 +338,28 @@ impl NotificationRepository for NotificationRepositoryImpl {
 		}
 
 		if let Some(scheduled_time) = opts.scheduled_time {
-			let scheduled_time_str = scheduled_time.to_rfc3339();
+			let scheduled_time_str = timestamp(scheduled_time);
 			match_stage.insert("scheduledTime", doc! { "$lte": scheduled_time_str.clone() });
+			// Retries are only due once their backoff has elapsed.
+			match_stage.insert(
+				"$or",
+				vec![
+					doc! { "nextAttemptAt": { "$exists": false } },
+					doc! { "nextAttemptAt": { "$lte": scheduled_time_str.clone() } },
+				],
+			);
 
 			if opts.respect_nighttime.unwrap_or(false) {
-				let start_hour = 8;
-				let end_hour = 22;
-
 				pipeline.push(doc! {
 					"$addFields": {
-						"userLocalHour": {
-							"$add": [
-								{"$hour": {"$toDate": scheduled_time_str }},
-								{"$divide": [
-									{"$toInt": {"$substr": ["$recipient.timezone_offset", 0, 3]}},
-									1
-								]}
-							]
-						}
+						"userLocalHour": user_local_hour(&scheduled_time_str)
 					}
 				});
 				pipeline.push(doc! {
 					"$match": {
 						"userLocalHour": {
-							"$gte": start_hour,
-							"$lt": end_hour
+							"$gte": self.daytime.start_hour,
+							"$lt": self.daytime.end_hour
 						}
 					}
 				});
@@ -242,10 +387,90 @@ impl NotificationRepository for NotificationRepositoryImpl {
 		Ok(Box::new(mapped_stream))
 	}
 
-	async fn update_message_status(&self, id: String, status: Status) -> AppResult<()> {
-		let filter = doc! { "_id": id };
+	async fn claim_messages(&self, opts: ClaimOptions) -> AppResult<Vec<Notification>> {
+		let now_str = timestamp(opts.now);
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+		let pending_str: String = Status::Pending.into();
+		let processing_str: String = Status::Processing.into();
+
+		let mut filter = doc! {
+			"$and": [effective_priority_filter(&opts.priority, opts.now, opts.aging_step)?],
+			"$or": [
+				{
+					"status": pending_str,
+					"scheduledTime": { "$lte": now_str.clone() },
+					"$or": [
+						{ "nextAttemptAt": { "$exists": false } },
+						{ "nextAttemptAt": { "$lte": now_str.clone() } },
+					],
+				},
+				// Claimed by a replica that did not finish in time.
+				{
+					"status": processing_str.clone(),
+					"leaseExpiresAt": { "$lt": now_str.clone() },
+				},
+			],
+		};
+		if opts.respect_nighttime {
+			filter.insert(
+				"$expr",
+				doc! {
+					"$let": {
+						"vars": { "hour": user_local_hour(&now_str) },
+						"in": { "$and": [
+							{ "$gte": ["$$hour", self.daytime.start_hour] },
+							{ "$lt": ["$$hour", self.daytime.end_hour] },
+						] },
+					}
+				},
+			);
+		}
+		let update = doc! { "$set": {
+			"status": processing_str,
+			"owner": opts.owner,
+			"leaseExpiresAt": timestamp(lease_expires_at),
+		} };
+
+		let mut claimed = Vec::new();
+		while (claimed.len() as i64) < opts.limit {
+			let notification = self
+				.notifications
+				.find_one_and_update(filter.clone(), update.clone())
+				.sort(doc! { "scheduledTime": 1 })
+				.return_document(ReturnDocument::After)
+				.await
+				.map_err(|e| {
+					AppError::RepositoryError(format!("Failed to claim with err: {:?}", e))
+				})?;
+			match notification {
+				Some(notification) => claimed.push(notification),
+				None => break,
+			}
+		}
+
+		Ok(claimed)
+	}
+
+	async fn update_message_status(
+		&self,
+		id: String,
+		owner: &str,
+		status: Status,
+	) -> AppResult<bool> {
+		let processing_str: String = Status::Processing.into();
+		let filter = doc! { "_id": id, "status": processing_str, "owner": owner };
+		let failed = status == Status::Failed;
 		let status_str: String = status.into();
-		let update = doc! { "$set": { "status": status_str } };
+		let mut set = doc! { "status": status_str };
+		if failed {
+			set.insert("failedAt", timestamp(Utc::now()));
+		}
+		let update = doc! {
+			"$set": set,
+			"$unset": { "owner": "", "leaseExpiresAt": "" },
+		};
 		let result = self
 			.notifications
 			.update_one(filter, update)
@@ -253,13 +478,105 @@ impl NotificationRepository for NotificationRepositoryImpl {
 			.map_err(|e| {
 				AppError::RepositoryError(format!("Failed to update with err: {:?}", e))
 			})?;
+		Ok(result.matched_count > 0)
+	}
+
+	async fn replay_failed(
+		&self,
+		id: String,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> AppResult<Option<ReplayRecord>> {
+		let failed_str: String = Status::Failed.into();
+		let pending_str: String = Status::Pending.into();
+		let filter = doc! { "_id": id, "status": failed_str };
+		let map_err = |e| AppError::RepositoryError(format!("Failed to replay with err: {:?}", e));
+
+		let mut set = doc! { "status": pending_str, "attempts": 0 };
+		if let Some(scheduled_time) = scheduled_time {
+			set.insert("scheduledTime", timestamp(scheduled_time));
+		}
+		let update = doc! {
+			"$set": set,
+			"$inc": { "replays": 1 },
+			"$unset": {
+				"nextAttemptAt": "",
+				"lastError": "",
+				"failedAt": "",
+				"failureReason": "",
+			},
+		};
+
+		// Dropping the session before commit aborts the transaction.
+		let mut session = self.client.start_session().await.map_err(map_err)?;
+		session.start_transaction().await.map_err(map_err)?;
+		let Some(previous) = self
+			.notifications
+			.find_one_and_update(filter, update)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?
+		else {
+			return Ok(None);
+		};
+
+		let record = ReplayRecord::new(
+			ObjectId::new().to_hex(),
+			previous,
+			requested_by,
+			scheduled_time,
+		);
+		self.replays
+			.insert_one(&record)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		session.commit_transaction().await.map_err(map_err)?;
+		Ok(Some(record))
+	}
+
+	async fn purge_failed(&self, filter: DeadLetterFilter) -> AppResult<u64> {
+		let failed_str: String = Status::Failed.into();
+		let mut query = doc! { "status": failed_str };
+
+		if let Some(id) = filter.id {
+			query.insert("_id", id);
+		}
+		if let Some(channel) = filter.channel {
+			let channel_str: String = channel.into();
+			query.insert("channel", channel_str);
+		}
+		if let Some(priority) = filter.priority {
+			let priority_str: String = priority.into();
+			query.insert("priority", priority_str);
+		}
+		if let Some(failed_before) = filter.failed_before {
+			query.insert("failedAt", doc! { "$lt": timestamp(failed_before) });
+		}
+
+		let result =
+			self.notifications.delete_many(query).await.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to purge with err: {:?}", e))
+			})?;
+		Ok(result.deleted_count)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	#[test]
+	fn timestamps_are_formatted_like_the_stored_model() {
+		let whole = Utc.with_ymd_and_hms(2026, 10, 19, 8, 0, 0).unwrap();
+		let fraction = whole + chrono::Duration::milliseconds(250);
 
-		if result.modified_count == 0 {
-			Err(AppError::RepositoryError(
-				"No documents matched the query".to_string(),
-			))
-		} else {
-			Ok(())
+		for time in [whole, fraction] {
+			let serialized = serde_json::to_value(time).unwrap();
+			assert_eq!(serialized, timestamp(time).as_str());
 		}
+		assert_eq!(timestamp(whole), "2026-10-19T08:00:00Z");
 	}
 }
@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{
+	Client,
+	ClientSession,
+	Collection,
+	bson::{doc, oid::ObjectId},
+	options::ReturnDocument,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	data::notifications::{
+		AttemptFailure,
+		Channel,
+		Notification,
+		Priority,
+		Status,
+		failed_attempt_update,
+		timestamp,
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Outbound message written in the same transaction as the status change
+/// that produced it. The relay publishes it and removes it afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+	#[serde(rename = "_id")]
+	pub id:               Option<String>,
+	#[serde(rename = "notificationId")]
+	pub notification_id:  String,
+	#[serde(rename = "channel")]
+	pub channel:          Channel,
+	#[serde(rename = "priority")]
+	pub priority:         Priority,
+	#[serde(rename = "recipient")]
+	pub recipient:        String,
+	#[serde(rename = "payload")]
+	pub payload:          String,
+	/// Same for every publish of this entry, so a relay retry is dropped as a
+	/// duplicate, but differs between attempts and replays.
+	#[serde(rename = "dedupKey")]
+	pub dedup_key:        String,
+	/// Delivery attempts of the notification before this one.
+	#[serde(rename = "attempts")]
+	pub attempts:         u32,
+	#[serde(rename = "createdAt")]
+	pub created_at:       DateTime<Utc>,
+	#[serde(rename = "owner", default, skip_serializing_if = "Option::is_none")]
+	pub owner:            Option<String>,
+	#[serde(
+		rename = "leaseExpiresAt",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub lease_expires_at: Option<DateTime<Utc>>,
+}
+
+pub struct ClaimOutboxOptions {
+	pub limit: i64,
+	pub now:   DateTime<Utc>,
+	pub owner: String,
+	pub lease: Duration,
+}
+
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+	/// Marks a claimed notification as queued and stores its outbound
+	/// message atomically. Returns `false` if the notification is no longer
+	/// claimed by `owner`, in which case nothing is written.
+	async fn enqueue(
+		&self,
+		notification: &Notification,
+		owner: &str,
+		entry: OutboxEntry,
+	) -> AppResult<bool>;
+	async fn claim(&self, opts: ClaimOutboxOptions) -> AppResult<Vec<OutboxEntry>>;
+	/// Removes a published entry and marks its notification as sent. Safe to
+	/// repeat, a notification that is no longer queued is left untouched.
+	/// Returns `false`, writing nothing, if the entry is no longer claimed by
+	/// the owner it was claimed with.
+	async fn mark_published(&self, entry: &OutboxEntry) -> AppResult<bool>;
+	/// Removes an entry that could not be published and records the failed
+	/// attempt on its notification. Returns `false` like `mark_published`.
+	async fn mark_failed(&self, entry: &OutboxEntry, failure: AttemptFailure) -> AppResult<bool>;
+	/// Cancels a pending or queued notification and drops its outbound
+	/// message atomically. Returns `false` if it is in any other state.
+	async fn cancel(&self, notification_id: &str) -> AppResult<bool>;
+}
+
+pub struct OutboxRepositoryImpl {
+	client:        Client,
+	notifications: Collection<Notification>,
+	outbox:        Collection<OutboxEntry>,
+}
+
+impl OutboxRepositoryImpl {
+	pub fn new(
+		client: Client,
+		notifications: Collection<Notification>,
+		outbox: Collection<OutboxEntry>,
+	) -> Self {
+		OutboxRepositoryImpl {
+			client,
+			notifications,
+			outbox,
+		}
+	}
+
+	/// Dropping the session before commit aborts the transaction.
+	async fn start_transaction(&self) -> AppResult<ClientSession> {
+		let mut session =
+			self.client.start_session().await.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to start session: {}", e))
+			})?;
+		session.start_transaction().await.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to start transaction: {}", e))
+		})?;
+		Ok(session)
+	}
+}
+
+fn map_err(e: mongodb::error::Error) -> AppError {
+	AppError::RepositoryError(format!("Outbox transaction failed with err: {:?}", e))
+}
+
+#[async_trait]
+impl OutboxRepository for OutboxRepositoryImpl {
+	async fn enqueue(
+		&self,
+		notification: &Notification,
+		owner: &str,
+		entry: OutboxEntry,
+	) -> AppResult<bool> {
+		let id = notification
+			.id
+			.clone()
+			.ok_or_else(|| AppError::ServiceError("Notification has no id".to_string()))?;
+		let processing_str: String = Status::Processing.into();
+		let queued_str: String = Status::Queued.into();
+		let entry = OutboxEntry {
+			id: Some(ObjectId::new().to_hex()),
+			..entry
+		};
+
+		let mut session = self.start_transaction().await?;
+		let result = self
+			.notifications
+			.update_one(
+				doc! { "_id": id, "status": processing_str, "owner": owner },
+				doc! {
+					"$set": { "status": queued_str },
+					"$unset": { "owner": "", "leaseExpiresAt": "" },
+				},
+			)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		if result.modified_count == 0 {
+			return Ok(false);
+		}
+
+		self.outbox
+			.insert_one(entry)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		session.commit_transaction().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn claim(&self, opts: ClaimOutboxOptions) -> AppResult<Vec<OutboxEntry>> {
+		let now_str = timestamp(opts.now);
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+		let filter = doc! {
+			"$or": [
+				{ "leaseExpiresAt": { "$exists": false } },
+				{ "leaseExpiresAt": { "$lt": now_str } },
+			]
+		};
+		let update = doc! { "$set": {
+			"owner": opts.owner,
+			"leaseExpiresAt": timestamp(lease_expires_at),
+		} };
+
+		let mut claimed = Vec::new();
+		while (claimed.len() as i64) < opts.limit {
+			let entry = self
+				.outbox
+				.find_one_and_update(filter.clone(), update.clone())
+				.sort(doc! { "createdAt": 1 })
+				.return_document(ReturnDocument::After)
+				.await
+				.map_err(|e| {
+					AppError::RepositoryError(format!("Failed to claim outbox with err: {:?}", e))
+				})?;
+			match entry {
+				Some(entry) => claimed.push(entry),
+				None => break,
+			}
+		}
+
+		Ok(claimed)
+	}
+
+	async fn mark_published(&self, entry: &OutboxEntry) -> AppResult<bool> {
+		let queued_str: String = Status::Queued.into();
+		let sent_str: String = Status::Sent.into();
+
+		let mut session = self.start_transaction().await?;
+		let result = self
+			.outbox
+			.delete_one(doc! { "_id": entry.id.clone(), "owner": entry.owner.clone() })
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		if result.deleted_count == 0 {
+			return Ok(false);
+		}
+		self.notifications
+			.update_one(
+				doc! { "_id": entry.notification_id.clone(), "status": queued_str },
+				doc! { "$set": { "status": sent_str } },
+			)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		session.commit_transaction().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn mark_failed(&self, entry: &OutboxEntry, failure: AttemptFailure) -> AppResult<bool> {
+		let queued_str: String = Status::Queued.into();
+
+		let mut session = self.start_transaction().await?;
+		let result = self
+			.outbox
+			.delete_one(doc! { "_id": entry.id.clone(), "owner": entry.owner.clone() })
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		if result.deleted_count == 0 {
+			return Ok(false);
+		}
+		self.notifications
+			.update_one(
+				doc! { "_id": entry.notification_id.clone(), "status": queued_str },
+				failed_attempt_update(&failure),
+			)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		session.commit_transaction().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn cancel(&self, notification_id: &str) -> AppResult<bool> {
+		let pending_str: String = Status::Pending.into();
+		let queued_str: String = Status::Queued.into();
+		let cancelled_str: String = Status::Cancelled.into();
+
+		let mut session = self.start_transaction().await?;
+		let result = self
+			.notifications
+			.update_one(
+				doc! {
+					"_id": notification_id,
+					"status": { "$in": [pending_str, queued_str] },
+				},
+				doc! {
+					"$set": { "status": cancelled_str },
+					"$unset": { "owner": "", "leaseExpiresAt": "" },
+				},
+			)
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		if result.modified_count == 0 {
+			return Ok(false);
+		}
+
+		self.outbox
+			.delete_many(doc! { "notificationId": notification_id })
+			.session(&mut session)
+			.await
+			.map_err(map_err)?;
+		session.commit_transaction().await.map_err(map_err)?;
+		Ok(true)
+	}
+}
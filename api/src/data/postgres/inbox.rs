@@ -0,0 +1,247 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+	data::{
+		inbox::{InboxPage, InboxQuery, InboxRepository},
+		notifications::{Channel, Notification, Status},
+		postgres::notifications::notification_from_row,
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Matches the items of one recipient, bound as `$1` to `$3`.
+const INBOX_FILTER: &str = "recipient_id = $1 AND channel = $2 AND status = $3";
+
+pub struct PgInboxRepository {
+	pool: PgPool,
+}
+
+impl PgInboxRepository {
+	pub fn new(pool: PgPool) -> Self {
+		PgInboxRepository { pool }
+	}
+
+	/// Sets `column` to `now` on the item unless it is set already.
+	async fn stamp(
+		&self,
+		recipient_id: String,
+		id: String,
+		column: &str,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		let row = sqlx::query(&format!(
+			"UPDATE notifications SET {column} = COALESCE({column}, $4) WHERE {INBOX_FILTER} AND \
+			 id = $5 RETURNING *"
+		))
+		.bind(recipient_id)
+		.bind(String::from(Channel::InApp))
+		.bind(String::from(Status::Sent))
+		.bind(now)
+		.bind(id)
+		.fetch_optional(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to update with err: {:?}", e)))?;
+
+		row.map(|row| notification_from_row(&row))
+			.transpose()
+			.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))
+	}
+}
+
+#[async_trait]
+impl InboxRepository for PgInboxRepository {
+	async fn list(&self, query: InboxQuery) -> AppResult<InboxPage> {
+		let filter = format!(
+			"{INBOX_FILTER} AND (archived_at IS NOT NULL) = $4 AND (NOT $5 OR read_at IS NULL)"
+		);
+		let total: i64 = sqlx::query_scalar(&format!(
+			"SELECT COUNT(*) FROM notifications WHERE {filter}"
+		))
+		.bind(&query.recipient_id)
+		.bind(String::from(Channel::InApp))
+		.bind(String::from(Status::Sent))
+		.bind(query.archived)
+		.bind(query.unread_only)
+		.fetch_one(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to count: {}", e)))?;
+
+		let rows = sqlx::query(&format!(
+			"SELECT * FROM notifications WHERE {filter} ORDER BY scheduled_time DESC, id DESC \
+			 LIMIT $6 OFFSET $7"
+		))
+		.bind(&query.recipient_id)
+		.bind(String::from(Channel::InApp))
+		.bind(String::from(Status::Sent))
+		.bind(query.archived)
+		.bind(query.unread_only)
+		.bind(query.limit)
+		.bind(query.offset as i64)
+		.fetch_all(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?;
+
+		let items = rows
+			.iter()
+			.map(notification_from_row)
+			.collect::<Result<_, _>>()
+			.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))?;
+		Ok(InboxPage {
+			items,
+			total: total as u64,
+		})
+	}
+
+	async fn count_unread(&self, recipient_id: String) -> AppResult<u64> {
+		let count: i64 = sqlx::query_scalar(&format!(
+			"SELECT COUNT(*) FROM notifications WHERE {INBOX_FILTER} AND read_at IS NULL AND \
+			 archived_at IS NULL"
+		))
+		.bind(recipient_id)
+		.bind(String::from(Channel::InApp))
+		.bind(String::from(Status::Sent))
+		.fetch_one(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to count: {}", e)))?;
+		Ok(count as u64)
+	}
+
+	async fn mark_read(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		self.stamp(recipient_id, id, "read_at", now).await
+	}
+
+	async fn archive(
+		&self,
+		recipient_id: String,
+		id: String,
+		now: DateTime<Utc>,
+	) -> AppResult<Option<Notification>> {
+		self.stamp(recipient_id, id, "archived_at", now).await
+	}
+
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<bool> {
+		let result = sqlx::query(&format!(
+			"DELETE FROM notifications WHERE {INBOX_FILTER} AND id = $4"
+		))
+		.bind(recipient_id)
+		.bind(String::from(Channel::InApp))
+		.bind(String::from(Status::Sent))
+		.bind(id)
+		.execute(&self.pool)
+		.await
+		.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to delete notification with err: {:?}", e))
+		})?;
+		Ok(result.rows_affected() > 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Duration;
+
+	use super::*;
+	use crate::{
+		data::{
+			notifications::{NotificationRepository, Priority},
+			postgres::{notifications::PgNotificationRepository, test_context},
+		},
+		testing::notification,
+	};
+
+	#[tokio::test]
+	#[ignore = "needs TEST_DATABASE_URL"]
+	async fn sent_in_app_notifications_are_paged_and_stamped_once() {
+		let db = test_context().await;
+		let notifications = PgNotificationRepository::new(db.pool.clone(), Default::default());
+		let repository = PgInboxRepository::new(db.pool);
+		let now = Utc::now();
+
+		let mut ids = vec![];
+		for (minutes, channel, status) in [
+			(3, Channel::InApp, Status::Sent),
+			(2, Channel::InApp, Status::Sent),
+			(1, Channel::InApp, Status::Pending),
+			(1, Channel::Email, Status::Sent),
+		] {
+			let created = notifications
+				.create(Notification {
+					channel,
+					status,
+					..notification(Priority::Normal, now - Duration::minutes(minutes))
+				})
+				.await
+				.unwrap();
+			ids.push(created.id.unwrap());
+		}
+
+		let query = |offset| InboxQuery {
+			recipient_id: "user-1".to_string(),
+			unread_only: false,
+			archived: false,
+			limit: 1,
+			offset,
+		};
+		let page = repository.list(query(0)).await.unwrap();
+		assert_eq!(page.total, 2);
+		assert_eq!(page.items[0].id.as_ref(), Some(&ids[1]));
+		let page = repository.list(query(1)).await.unwrap();
+		assert_eq!(page.items[0].id.as_ref(), Some(&ids[0]));
+
+		let read = repository
+			.mark_read("user-1".to_string(), ids[0].clone(), now)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			read.read_at.map(|at| at.timestamp_micros()),
+			Some(now.timestamp_micros())
+		);
+		let again = repository
+			.mark_read(
+				"user-1".to_string(),
+				ids[0].clone(),
+				now + Duration::minutes(1),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(again.read_at, read.read_at);
+		assert_eq!(
+			repository.count_unread("user-1".to_string()).await.unwrap(),
+			1
+		);
+
+		assert!(
+			repository
+				.archive("user-1".to_string(), ids[2].clone(), now)
+				.await
+				.unwrap()
+				.is_none(),
+			"not delivered yet"
+		);
+		assert!(
+			!repository
+				.delete("user-1".to_string(), ids[3].clone())
+				.await
+				.unwrap()
+		);
+		assert!(
+			repository
+				.delete("user-1".to_string(), ids[1].clone())
+				.await
+				.unwrap()
+		);
+		assert_eq!(
+			repository.count_unread("user-1".to_string()).await.unwrap(),
+			0
+		);
+	}
+}
@@ -0,0 +1,99 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use common::{monitoring::health::HealthCheck, notification::UnknownValue};
+use sqlx::{
+	PgPool,
+	postgres::{PgPoolOptions, PgRow},
+};
+
+pub mod inbox;
+pub mod notifications;
+pub mod outbox;
+pub mod replays;
+pub mod subscriptions;
+
+pub struct PgOptions {
+	pub url:             String,
+	pub max_connections: u32,
+}
+
+/// Connection pool shared by the Postgres repositories.
+#[derive(Clone)]
+pub struct PgContext {
+	pub pool: PgPool,
+}
+
+impl PgContext {
+	/// Connects and applies pending migrations from `migrations/postgres`.
+	pub async fn new(opts: PgOptions) -> Result<Self, sqlx::Error> {
+		let pool = PgPoolOptions::new()
+			.max_connections(opts.max_connections)
+			.connect(&opts.url)
+			.await?;
+		sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+		Ok(PgContext { pool })
+	}
+}
+
+#[async_trait]
+impl HealthCheck for PgContext {
+	fn name(&self) -> String {
+		"postgres".to_string()
+	}
+
+	async fn check(&self) -> Result<(), String> {
+		sqlx::query("SELECT 1")
+			.execute(&self.pool)
+			.await
+			.map(|_| ())
+			.map_err(|e| format!("ping failed: {}", e))
+	}
+}
+
+/// Reads a non negative counter stored as `INTEGER`.
+fn get_u32(row: &PgRow, column: &str) -> Result<u32, sqlx::Error> {
+	use sqlx::Row;
+
+	let value: i32 = row.try_get(column)?;
+	u32::try_from(value).map_err(|e| sqlx::Error::ColumnDecode {
+		index:  column.to_string(),
+		source: Box::new(e),
+	})
+}
+
+/// Reads a channel, priority or status stored by its name.
+fn get_parsed<T>(row: &PgRow, column: &str) -> Result<T, sqlx::Error>
+where
+	T: FromStr<Err = UnknownValue>,
+{
+	use sqlx::Row;
+
+	let value: String = row.try_get(column)?;
+	value.parse().map_err(|e| sqlx::Error::ColumnDecode {
+		index:  column.to_string(),
+		source: Box::new(e),
+	})
+}
+
+/// A fresh schema in the database at `TEST_DATABASE_URL`, for the tests that
+/// need a running Postgres. Run them with `cargo test -- --ignored`.
+#[cfg(test)]
+pub(crate) async fn test_context() -> PgContext {
+	let url = std::env::var("TEST_DATABASE_URL")
+		.expect("TEST_DATABASE_URL must point at a Postgres database");
+	let schema = format!("test_{}", crate::data::new_id());
+	let pool = PgPool::connect(&url).await.unwrap();
+	sqlx::query(&format!("CREATE SCHEMA {}", schema))
+		.execute(&pool)
+		.await
+		.unwrap();
+
+	let separator = if url.contains('?') { '&' } else { '?' };
+	PgContext::new(PgOptions {
+		url:             format!("{}{}options=-c%20search_path%3D{}", url, separator, schema),
+		max_connections: 2,
+	})
+	.await
+	.unwrap()
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use sqlx::{PgConnection, PgPool, Row, postgres::PgRow};
+
+use crate::{
+	data::{
+		postgres::get_u32,
+		replays::{ReplayRecord, ReplayRepository},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct PgReplayRepository {
+	pool: PgPool,
+}
+
+impl PgReplayRepository {
+	pub fn new(pool: PgPool) -> Self {
+		PgReplayRepository { pool }
+	}
+}
+
+fn record_from_row(row: &PgRow) -> Result<ReplayRecord, sqlx::Error> {
+	Ok(ReplayRecord {
+		id:                 Some(row.try_get("id")?),
+		notification_id:    row.try_get("notification_id")?,
+		requested_by:       row.try_get("requested_by")?,
+		replayed_at:        row.try_get("replayed_at")?,
+		previous_attempts:  get_u32(row, "previous_attempts")?,
+		previous_error:     row.try_get("previous_error")?,
+		previous_failed_at: row.try_get("previous_failed_at")?,
+		scheduled_time:     row.try_get("scheduled_time")?,
+	})
+}
+
+/// Runs inside the replaying transaction, see
+/// `NotificationRepository::replay_failed`.
+pub(crate) async fn insert_replay(
+	conn: &mut PgConnection,
+	record: &ReplayRecord,
+) -> Result<(), sqlx::Error> {
+	sqlx::query(
+		"INSERT INTO replays (id, notification_id, requested_by, replayed_at, previous_attempts, \
+		 previous_error, previous_failed_at, scheduled_time) VALUES ($1, $2, $3, $4, $5, $6, $7, \
+		 $8)",
+	)
+	.bind(&record.id)
+	.bind(&record.notification_id)
+	.bind(&record.requested_by)
+	.bind(record.replayed_at)
+	.bind(record.previous_attempts as i32)
+	.bind(&record.previous_error)
+	.bind(record.previous_failed_at)
+	.bind(record.scheduled_time)
+	.execute(conn)
+	.await?;
+	Ok(())
+}
+
+#[async_trait]
+impl ReplayRepository for PgReplayRepository {
+	async fn list(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>> {
+		let rows = sqlx::query(
+			"SELECT * FROM replays WHERE $1::TEXT IS NULL OR notification_id = $1 ORDER BY \
+			 replayed_at DESC",
+		)
+		.bind(notification_id)
+		.fetch_all(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?;
+
+		rows.iter()
+			.map(record_from_row)
+			.collect::<Result<_, _>>()
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))
+	}
+}
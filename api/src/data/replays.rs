@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::{Collection, bson::doc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	data::notifications::Notification,
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Audit entry written every time a dead-lettered notification is replayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayRecord {
+	#[serde(rename = "_id")]
+	pub id:                 Option<String>,
+	#[serde(rename = "notificationId")]
+	pub notification_id:    String,
+	#[serde(rename = "requestedBy")]
+	pub requested_by:       String,
+	#[serde(rename = "replayedAt")]
+	pub replayed_at:        DateTime<Utc>,
+	#[serde(rename = "previousAttempts")]
+	pub previous_attempts:  u32,
+	#[serde(rename = "previousError", default)]
+	pub previous_error:     Option<String>,
+	#[serde(rename = "previousFailedAt", default)]
+	pub previous_failed_at: Option<DateTime<Utc>>,
+	#[serde(rename = "scheduledTime", default)]
+	pub scheduled_time:     Option<DateTime<Utc>>,
+}
+
+impl ReplayRecord {
+	/// `previous` is the notification as it was before the replay.
+	pub fn new(
+		id: String,
+		previous: Notification,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> Self {
+		ReplayRecord {
+			id: Some(id),
+			notification_id: previous.id.unwrap_or_default(),
+			requested_by,
+			replayed_at: Utc::now(),
+			previous_attempts: previous.attempts,
+			previous_error: previous.last_error,
+			previous_failed_at: previous.failed_at,
+			scheduled_time,
+		}
+	}
+}
+
+/// Replays are recorded by `NotificationRepository::replay_failed`, in the
+/// same write that re-queues the notification.
+#[async_trait]
+pub trait ReplayRepository: Send + Sync {
+	async fn list(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>>;
+}
+
+pub struct ReplayRepositoryImpl {
+	replays: Collection<ReplayRecord>,
+}
+
+impl ReplayRepositoryImpl {
+	pub fn new(replays: Collection<ReplayRecord>) -> Self {
+		ReplayRepositoryImpl { replays }
+	}
+}
+
+#[async_trait]
+impl ReplayRepository for ReplayRepositoryImpl {
+	async fn list(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>> {
+		let filter = match notification_id {
+			Some(notification_id) => doc! { "notificationId": notification_id },
+			None => doc! {},
+		};
+
+		self.replays
+			.find(filter)
+			.sort(doc! { "replayedAt": -1 })
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?
+			.try_collect()
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))
+	}
+}
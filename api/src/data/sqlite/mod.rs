@@ -0,0 +1,101 @@
+use std::{str::FromStr, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::{monitoring::health::HealthCheck, notification::UnknownValue};
+use sqlx::{
+	Row,
+	SqlitePool,
+	sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+};
+
+pub mod inbox;
+pub mod notifications;
+pub mod outbox;
+pub mod replays;
+pub mod subscriptions;
+
+/// Pool shared by the SQLite repositories, for single node deployments.
+#[derive(Clone)]
+pub struct SqliteContext {
+	pub pool: SqlitePool,
+}
+
+impl SqliteContext {
+	/// Opens, or creates, the database at `path` and applies pending
+	/// migrations from `migrations/sqlite`. `:memory:` keeps it in memory.
+	pub async fn new(path: &str) -> Result<Self, sqlx::Error> {
+		let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path))?
+			.create_if_missing(true)
+			.journal_mode(SqliteJournalMode::Wal)
+			.foreign_keys(true)
+			.busy_timeout(Duration::from_secs(5));
+		// SQLite has a single writer. One connection serializes the
+		// repositories, so a transaction never fails to upgrade to a write
+		// lock, and keeps an in-memory database alive.
+		let pool = SqlitePoolOptions::new()
+			.max_connections(1)
+			.idle_timeout(None)
+			.max_lifetime(None)
+			.connect_with(options)
+			.await?;
+		sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+		Ok(SqliteContext { pool })
+	}
+}
+
+#[async_trait]
+impl HealthCheck for SqliteContext {
+	fn name(&self) -> String {
+		"sqlite".to_string()
+	}
+
+	async fn check(&self) -> Result<(), String> {
+		sqlx::query("SELECT 1")
+			.execute(&self.pool)
+			.await
+			.map(|_| ())
+			.map_err(|e| format!("query failed: {}", e))
+	}
+}
+
+fn millis(time: DateTime<Utc>) -> i64 {
+	time.timestamp_millis()
+}
+
+fn from_millis(column: &str, value: i64) -> Result<DateTime<Utc>, sqlx::Error> {
+	DateTime::from_timestamp_millis(value).ok_or_else(|| sqlx::Error::ColumnDecode {
+		index:  column.to_string(),
+		source: format!("timestamp out of range: {}", value).into(),
+	})
+}
+
+fn get_time(row: &SqliteRow, column: &str) -> Result<DateTime<Utc>, sqlx::Error> {
+	from_millis(column, row.try_get(column)?)
+}
+
+fn get_optional_time(row: &SqliteRow, column: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+	let value: Option<i64> = row.try_get(column)?;
+	value.map(|value| from_millis(column, value)).transpose()
+}
+
+/// Reads a non negative counter stored as `INTEGER`.
+fn get_u32(row: &SqliteRow, column: &str) -> Result<u32, sqlx::Error> {
+	let value: i64 = row.try_get(column)?;
+	u32::try_from(value).map_err(|e| sqlx::Error::ColumnDecode {
+		index:  column.to_string(),
+		source: Box::new(e),
+	})
+}
+
+/// Reads a channel, priority or status stored by its name.
+fn get_parsed<T>(row: &SqliteRow, column: &str) -> Result<T, sqlx::Error>
+where
+	T: FromStr<Err = UnknownValue>,
+{
+	let value: String = row.try_get(column)?;
+	value.parse().map_err(|e| sqlx::Error::ColumnDecode {
+		index:  column.to_string(),
+		source: Box::new(e),
+	})
+}
@@ -0,0 +1,611 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Timelike, Utc};
+use futures::Stream;
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool, sqlite::SqliteRow};
+
+use crate::{
+	data::{
+		new_id,
+		notifications::{
+			ClaimOptions,
+			Daytime,
+			DeadLetterFilter,
+			GetMessagesOptions,
+			Notification,
+			NotificationRepository,
+			Priority,
+			Recipient,
+			Status,
+		},
+		replays::ReplayRecord,
+		sqlite::{
+			get_optional_time,
+			get_parsed,
+			get_time,
+			get_u32,
+			millis,
+			replays::insert_replay,
+		},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct SqliteNotificationRepository {
+	pool:    SqlitePool,
+	daytime: Daytime,
+}
+
+impl SqliteNotificationRepository {
+	pub fn new(pool: SqlitePool, daytime: Daytime) -> Self {
+		SqliteNotificationRepository { pool, daytime }
+	}
+
+	/// Restricts the query to recipients whose local hour at `now` is within
+	/// the daytime, computed like the Mongo repository: only the hours of the
+	/// offset count and the result is not wrapped around midnight.
+	fn push_daytime_filter(&self, query: &mut QueryBuilder<'_, Sqlite>, now: DateTime<Utc>) {
+		let local_hour = "CAST(SUBSTR(recipient_timezone_offset, 1, 3) AS INTEGER)";
+		query
+			.push(" AND ")
+			.push_bind(now.hour() as i32)
+			.push(format!(" + {} >= ", local_hour))
+			.push_bind(self.daytime.start_hour)
+			.push(" AND ")
+			.push_bind(now.hour() as i32)
+			.push(format!(" + {} < ", local_hour))
+			.push_bind(self.daytime.end_hour);
+	}
+}
+
+pub(crate) fn notification_from_row(row: &SqliteRow) -> Result<Notification, sqlx::Error> {
+	Ok(Notification {
+		id:               Some(row.try_get("id")?),
+		content:          row.try_get("content")?,
+		channel:          get_parsed(row, "channel")?,
+		recipient:        Recipient {
+			id:              row.try_get("recipient_id")?,
+			timezone_offset: row.try_get("recipient_timezone_offset")?,
+		},
+		scheduled_time:   get_time(row, "scheduled_time")?,
+		priority:         get_parsed(row, "priority")?,
+		status:           get_parsed(row, "status")?,
+		attempts:         get_u32(row, "attempts")?,
+		replays:          get_u32(row, "replays")?,
+		next_attempt_at:  get_optional_time(row, "next_attempt_at")?,
+		last_error:       row.try_get("last_error")?,
+		failed_at:        get_optional_time(row, "failed_at")?,
+		failure_reason:   row.try_get("failure_reason")?,
+		owner:            row.try_get("owner")?,
+		lease_expires_at: get_optional_time(row, "lease_expires_at")?,
+		read_at:          get_optional_time(row, "read_at")?,
+		archived_at:      get_optional_time(row, "archived_at")?,
+	})
+}
+
+fn read_rows(rows: Vec<SqliteRow>) -> AppResult<Vec<Notification>> {
+	rows.iter()
+		.map(|row| {
+			notification_from_row(row)
+				.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))
+		})
+		.collect()
+}
+
+/// Due at `now`, taking the retry backoff into account.
+fn push_due_filter(query: &mut QueryBuilder<'_, Sqlite>, now: DateTime<Utc>) {
+	query
+		.push("scheduled_time <= ")
+		.push_bind(millis(now))
+		.push(" AND (next_attempt_at IS NULL OR next_attempt_at <= ")
+		.push_bind(millis(now))
+		.push(")");
+}
+
+/// Matches notifications whose priority, after aging, equals `priority`. See
+/// the Mongo repository for the rules.
+fn push_effective_priority_filter(
+	query: &mut QueryBuilder<'_, Sqlite>,
+	priority: &Priority,
+	now: DateTime<Utc>,
+	aging_step: Option<std::time::Duration>,
+) -> AppResult<()> {
+	let Some(aging_step) = aging_step else {
+		let priority_str: String = priority.clone().into();
+		query.push("priority = ").push_bind(priority_str);
+		return Ok(());
+	};
+	let step = chrono::Duration::from_std(aging_step)
+		.map_err(|e| AppError::ServiceError(format!("Invalid aging step: {}", e)))?;
+
+	query.push("(");
+	let originals = Priority::ALL
+		.iter()
+		.filter(|original| original.rank() >= priority.rank());
+	for (i, original) in originals.enumerate() {
+		let levels = (original.rank() - priority.rank()) as i32;
+		let original_str: String = original.clone().into();
+		if i > 0 {
+			query.push(" OR ");
+		}
+		query
+			.push("(priority = ")
+			.push_bind(original_str)
+			.push(" AND scheduled_time <= ")
+			.push_bind(millis(now - step * levels));
+		if priority.rank() > 0 {
+			query
+				.push(" AND scheduled_time > ")
+				.push_bind(millis(now - step * (levels + 1)));
+		}
+		query.push(")");
+	}
+	query.push(")");
+	Ok(())
+}
+
+#[async_trait]
+impl NotificationRepository for SqliteNotificationRepository {
+	async fn create(&self, notification: Notification) -> AppResult<Notification> {
+		let id = Some(new_id());
+		let notification = Notification {
+			id,
+			attempts: 0,
+			replays: 0,
+			next_attempt_at: None,
+			last_error: None,
+			failed_at: None,
+			failure_reason: None,
+			read_at: None,
+			archived_at: None,
+			..notification
+		};
+
+		let channel_str: String = notification.channel.clone().into();
+		let priority_str: String = notification.priority.clone().into();
+		let status_str: String = notification.status.clone().into();
+		let result = sqlx::query(
+			"INSERT INTO notifications (id, content, channel, recipient_id, \
+			 recipient_timezone_offset, scheduled_time, priority, status, owner, \
+			 lease_expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		)
+		.bind(&notification.id)
+		.bind(&notification.content)
+		.bind(channel_str)
+		.bind(&notification.recipient.id)
+		.bind(&notification.recipient.timezone_offset)
+		.bind(millis(notification.scheduled_time))
+		.bind(priority_str)
+		.bind(status_str)
+		.bind(&notification.owner)
+		.bind(notification.lease_expires_at.map(millis))
+		.execute(&self.pool)
+		.await;
+
+		match result {
+			Ok(_) => Ok(notification),
+			Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::DuplicateKey),
+			Err(_) => Err(AppError::RepositoryError(
+				"Failed to create notification".to_string(),
+			)),
+		}
+	}
+
+	async fn get_messages(
+		&self,
+		opts: GetMessagesOptions,
+	) -> AppResult<Box<dyn Stream<Item = Result<Notification, AppError>> + Send + Unpin>> {
+		let mut query = QueryBuilder::new("SELECT * FROM notifications WHERE TRUE");
+
+		if let Some(channel) = opts.channel {
+			let channel_str: String = channel.into();
+			query.push(" AND channel = ").push_bind(channel_str);
+		}
+
+		if let Some(priority) = opts.priority {
+			let priority_str: String = priority.into();
+			query.push(" AND priority = ").push_bind(priority_str);
+		}
+
+		if let Some(status) = opts.status {
+			let status_str: String = status.into();
+			query.push(" AND status = ").push_bind(status_str);
+		}
+
+		if let Some(scheduled_time) = opts.scheduled_time {
+			query.push(" AND ");
+			push_due_filter(&mut query, scheduled_time);
+			if opts.respect_nighttime.unwrap_or(false) {
+				self.push_daytime_filter(&mut query, scheduled_time);
+			}
+		}
+
+		if let Some(limit) = opts.limit {
+			query.push(" LIMIT ").push_bind(limit);
+		}
+
+		let rows = query
+			.build()
+			.fetch_all(&self.pool)
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to query: {}", e)))?;
+		let notifications = read_rows(rows)?;
+
+		Ok(Box::new(futures::stream::iter(
+			notifications.into_iter().map(Ok),
+		)))
+	}
+
+	async fn claim_messages(&self, opts: ClaimOptions) -> AppResult<Vec<Notification>> {
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+		let pending_str: String = Status::Pending.into();
+		let processing_str: String = Status::Processing.into();
+
+		// A single statement, SQLite runs it under the database write lock.
+		let mut query = QueryBuilder::new("UPDATE notifications SET status = ");
+		query
+			.push_bind(processing_str.clone())
+			.push(", owner = ")
+			.push_bind(opts.owner)
+			.push(", lease_expires_at = ")
+			.push_bind(millis(lease_expires_at))
+			.push(" WHERE id IN (SELECT id FROM notifications WHERE ");
+		push_effective_priority_filter(&mut query, &opts.priority, opts.now, opts.aging_step)?;
+		query
+			.push(" AND ((status = ")
+			.push_bind(pending_str)
+			.push(" AND ");
+		push_due_filter(&mut query, opts.now);
+		// Claimed by a replica that did not finish in time.
+		query
+			.push(") OR (status = ")
+			.push_bind(processing_str)
+			.push(" AND lease_expires_at < ")
+			.push_bind(millis(opts.now))
+			.push("))");
+		if opts.respect_nighttime {
+			self.push_daytime_filter(&mut query, opts.now);
+		}
+		query
+			.push(" ORDER BY scheduled_time LIMIT ")
+			.push_bind(opts.limit)
+			.push(") RETURNING *");
+
+		let rows =
+			query.build().fetch_all(&self.pool).await.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to claim with err: {:?}", e))
+			})?;
+		let mut claimed = read_rows(rows)?;
+		claimed.sort_by_key(|n| n.scheduled_time);
+		Ok(claimed)
+	}
+
+	async fn update_message_status(
+		&self,
+		id: String,
+		owner: &str,
+		status: Status,
+	) -> AppResult<bool> {
+		let failed_at = (status == Status::Failed).then(|| millis(Utc::now()));
+		let status_str: String = status.into();
+		let processing_str: String = Status::Processing.into();
+		let result = sqlx::query(
+			"UPDATE notifications SET status = ?, failed_at = COALESCE(?, failed_at), owner = \
+			 NULL, lease_expires_at = NULL WHERE id = ? AND status = ? AND owner = ?",
+		)
+		.bind(status_str)
+		.bind(failed_at)
+		.bind(id)
+		.bind(processing_str)
+		.bind(owner)
+		.execute(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to update with err: {:?}", e)))?;
+		Ok(result.rows_affected() > 0)
+	}
+
+	async fn replay_failed(
+		&self,
+		id: String,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> AppResult<Option<ReplayRecord>> {
+		let failed_str: String = Status::Failed.into();
+		let pending_str: String = Status::Pending.into();
+		let map_err = |e| AppError::RepositoryError(format!("Failed to replay with err: {:?}", e));
+
+		let mut tx = self.pool.begin().await.map_err(map_err)?;
+		let Some(row) = sqlx::query("SELECT * FROM notifications WHERE id = ? AND status = ?")
+			.bind(&id)
+			.bind(&failed_str)
+			.fetch_optional(&mut *tx)
+			.await
+			.map_err(map_err)?
+		else {
+			return Ok(None);
+		};
+		let previous = notification_from_row(&row)
+			.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))?;
+
+		sqlx::query(
+			"UPDATE notifications SET status = ?, attempts = 0, replays = replays + 1, \
+			 scheduled_time = COALESCE(?, scheduled_time), next_attempt_at = NULL, last_error = \
+			 NULL, failed_at = NULL, failure_reason = NULL WHERE id = ?",
+		)
+		.bind(pending_str)
+		.bind(scheduled_time.map(millis))
+		.bind(&id)
+		.execute(&mut *tx)
+		.await
+		.map_err(map_err)?;
+
+		let record = ReplayRecord::new(new_id(), previous, requested_by, scheduled_time);
+		insert_replay(&mut tx, &record).await.map_err(map_err)?;
+		tx.commit().await.map_err(map_err)?;
+		Ok(Some(record))
+	}
+
+	async fn purge_failed(&self, filter: DeadLetterFilter) -> AppResult<u64> {
+		let failed_str: String = Status::Failed.into();
+		let mut query = QueryBuilder::new("DELETE FROM notifications WHERE status = ");
+		query.push_bind(failed_str);
+
+		if let Some(id) = filter.id {
+			query.push(" AND id = ").push_bind(id);
+		}
+		if let Some(channel) = filter.channel {
+			let channel_str: String = channel.into();
+			query.push(" AND channel = ").push_bind(channel_str);
+		}
+		if let Some(priority) = filter.priority {
+			let priority_str: String = priority.into();
+			query.push(" AND priority = ").push_bind(priority_str);
+		}
+		if let Some(failed_before) = filter.failed_before {
+			query
+				.push(" AND failed_at < ")
+				.push_bind(millis(failed_before));
+		}
+
+		let result =
+			query.build().execute(&self.pool).await.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to purge with err: {:?}", e))
+			})?;
+		Ok(result.rows_affected())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use chrono::TimeZone;
+	use futures::TryStreamExt;
+
+	use super::*;
+	use crate::{
+		data::{
+			replays::ReplayRepository,
+			sqlite::{SqliteContext, replays::SqliteReplayRepository},
+		},
+		testing::notification,
+	};
+
+	async fn repository() -> SqliteNotificationRepository {
+		let db = SqliteContext::new(":memory:").await.unwrap();
+		SqliteNotificationRepository::new(db.pool, Daytime::default())
+	}
+
+	fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+	}
+
+	fn claim(priority: Priority, now: DateTime<Utc>) -> ClaimOptions {
+		ClaimOptions {
+			priority,
+			limit: 10,
+			now,
+			owner: "replica-1".to_string(),
+			lease: Duration::from_secs(60),
+			respect_nighttime: true,
+			aging_step: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn claims_due_daytime_notifications_once() {
+		let repository = repository().await;
+		for (content, offset, scheduled_time) in [
+			("night", "+00:00", at(1, 0)),
+			("day", "+09:00", at(1, 0)),
+			("future", "+09:00", at(4, 0)),
+		] {
+			let mut n = notification(Priority::Normal, scheduled_time);
+			n.content = content.to_string();
+			n.recipient.timezone_offset = offset.to_string();
+			repository.create(n).await.unwrap();
+		}
+
+		// 03:00 UTC is night at +00:00 and noon at +09:00.
+		let claimed = repository
+			.claim_messages(claim(Priority::Normal, at(3, 0)))
+			.await
+			.unwrap();
+		assert_eq!(claimed.len(), 1);
+		assert_eq!(claimed[0].content, "day");
+		assert_eq!(claimed[0].status, Status::Processing);
+		assert_eq!(
+			claimed[0].lease_expires_at,
+			Some(at(3, 1)),
+			"lease round trips through the millisecond columns"
+		);
+
+		let again = repository
+			.claim_messages(claim(Priority::Normal, at(3, 0)))
+			.await
+			.unwrap();
+		assert!(again.is_empty());
+
+		// Once the lease expired the notification is claimed again.
+		let reclaimed = repository
+			.claim_messages(claim(Priority::Normal, at(3, 2)))
+			.await
+			.unwrap();
+		assert_eq!(reclaimed.len(), 1);
+	}
+
+	#[tokio::test]
+	async fn aged_notifications_are_claimed_at_a_higher_priority() {
+		let repository = repository().await;
+		let mut n = notification(Priority::Low, at(1, 0));
+		n.recipient.timezone_offset = "+09:00".to_string();
+		repository.create(n).await.unwrap();
+
+		// Waiting two hours moves it up from low to high.
+		let aged = |priority| ClaimOptions {
+			aging_step: Some(Duration::from_secs(60 * 60)),
+			..claim(priority, at(3, 30))
+		};
+		for priority in [Priority::Critical, Priority::Normal, Priority::Low] {
+			assert!(
+				repository
+					.claim_messages(aged(priority))
+					.await
+					.unwrap()
+					.is_empty()
+			);
+		}
+		let claimed = repository
+			.claim_messages(aged(Priority::High))
+			.await
+			.unwrap();
+		assert_eq!(claimed.len(), 1);
+	}
+
+	/// Claims the notification as replica-1 and fails it.
+	async fn fail(repository: &SqliteNotificationRepository, id: &str, now: DateTime<Utc>) {
+		let claimed = repository
+			.claim_messages(ClaimOptions {
+				respect_nighttime: false,
+				..claim(Priority::Normal, now)
+			})
+			.await
+			.unwrap();
+		assert_eq!(claimed.len(), 1);
+		assert!(
+			repository
+				.update_message_status(id.to_string(), "replica-1", Status::Failed)
+				.await
+				.unwrap()
+		);
+	}
+
+	#[tokio::test]
+	async fn replay_and_purge_only_touch_failed_notifications() {
+		let repository = repository().await;
+		let created = repository
+			.create(notification(Priority::Normal, at(1, 0)))
+			.await
+			.unwrap();
+		let id = created.id.clone().unwrap();
+
+		assert!(
+			repository
+				.replay_failed(id.clone(), "ops".to_string(), None)
+				.await
+				.unwrap()
+				.is_none()
+		);
+
+		fail(&repository, &id, at(2, 0)).await;
+		let record = repository
+			.replay_failed(id.clone(), "ops".to_string(), Some(at(5, 0)))
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(record.notification_id, id);
+		assert!(record.previous_failed_at.is_some());
+		let recorded = SqliteReplayRepository::new(repository.pool.clone())
+			.list(Some(id.clone()))
+			.await
+			.unwrap();
+		assert_eq!(recorded.len(), 1);
+		assert_eq!(recorded[0].requested_by, "ops");
+
+		let stored: Vec<Notification> = repository
+			.get_messages(GetMessagesOptions::default())
+			.await
+			.unwrap()
+			.try_collect()
+			.await
+			.unwrap();
+		assert_eq!(stored[0].status, Status::Pending);
+		assert_eq!(stored[0].scheduled_time, at(5, 0));
+
+		fail(&repository, &id, at(6, 0)).await;
+		let purged = repository
+			.purge_failed(DeadLetterFilter {
+				id: Some(id),
+				..Default::default()
+			})
+			.await
+			.unwrap();
+		assert_eq!(purged, 1);
+	}
+
+	#[tokio::test]
+	async fn unknown_stored_names_fail_to_decode() {
+		let repository = repository().await;
+		let created = repository
+			.create(notification(Priority::Normal, at(1, 0)))
+			.await
+			.unwrap();
+		sqlx::query("UPDATE notifications SET status = 'lost' WHERE id = ?")
+			.bind(created.id)
+			.execute(&repository.pool)
+			.await
+			.unwrap();
+
+		let Err(AppError::SerialError(error)) =
+			repository.get_messages(GetMessagesOptions::default()).await
+		else {
+			panic!("a stored status none of the variants have decoded");
+		};
+		assert!(error.ends_with("unknown status \"lost\""), "{}", error);
+	}
+
+	#[tokio::test]
+	async fn a_lost_lease_leaves_the_new_owner_in_charge() {
+		let repository = repository().await;
+		let mut n = notification(Priority::Normal, at(1, 0));
+		n.recipient.timezone_offset = "+09:00".to_string();
+		let id = repository.create(n).await.unwrap().id.unwrap();
+
+		repository
+			.claim_messages(claim(Priority::Normal, at(3, 0)))
+			.await
+			.unwrap();
+		let reclaimed = repository
+			.claim_messages(ClaimOptions {
+				owner: "replica-2".to_string(),
+				..claim(Priority::Normal, at(3, 2))
+			})
+			.await
+			.unwrap();
+		assert_eq!(reclaimed.len(), 1);
+
+		assert!(
+			!repository
+				.update_message_status(id.clone(), "replica-1", Status::Sent)
+				.await
+				.unwrap()
+		);
+		assert!(
+			repository
+				.update_message_status(id, "replica-2", Status::Sent)
+				.await
+				.unwrap()
+		);
+	}
+}
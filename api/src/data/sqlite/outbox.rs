@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{Row, Sqlite, SqlitePool, Transaction, sqlite::SqliteRow};
+
+use crate::{
+	data::{
+		new_id,
+		notifications::{AttemptFailure, Notification, RETRIES_EXHAUSTED, Status},
+		outbox::{ClaimOutboxOptions, OutboxEntry, OutboxRepository},
+		sqlite::{get_optional_time, get_parsed, get_time, get_u32, millis},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct SqliteOutboxRepository {
+	pool: SqlitePool,
+}
+
+impl SqliteOutboxRepository {
+	pub fn new(pool: SqlitePool) -> Self {
+		SqliteOutboxRepository { pool }
+	}
+
+	/// Dropping the transaction before commit rolls it back.
+	async fn begin(&self) -> AppResult<Transaction<'static, Sqlite>> {
+		self.pool
+			.begin()
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to start transaction: {}", e)))
+	}
+}
+
+fn map_err(e: sqlx::Error) -> AppError {
+	AppError::RepositoryError(format!("Outbox transaction failed with err: {:?}", e))
+}
+
+fn entry_from_row(row: &SqliteRow) -> Result<OutboxEntry, sqlx::Error> {
+	Ok(OutboxEntry {
+		id:               Some(row.try_get("id")?),
+		notification_id:  row.try_get("notification_id")?,
+		channel:          get_parsed(row, "channel")?,
+		priority:         get_parsed(row, "priority")?,
+		recipient:        row.try_get("recipient")?,
+		payload:          row.try_get("payload")?,
+		dedup_key:        row.try_get("dedup_key")?,
+		attempts:         get_u32(row, "attempts")?,
+		created_at:       get_time(row, "created_at")?,
+		owner:            row.try_get("owner")?,
+		lease_expires_at: get_optional_time(row, "lease_expires_at")?,
+	})
+}
+
+#[async_trait]
+impl OutboxRepository for SqliteOutboxRepository {
+	async fn enqueue(
+		&self,
+		notification: &Notification,
+		owner: &str,
+		entry: OutboxEntry,
+	) -> AppResult<bool> {
+		let id = notification
+			.id
+			.clone()
+			.ok_or_else(|| AppError::ServiceError("Notification has no id".to_string()))?;
+		let processing_str: String = Status::Processing.into();
+		let queued_str: String = Status::Queued.into();
+		let channel_str: String = entry.channel.into();
+		let priority_str: String = entry.priority.into();
+
+		let mut tx = self.begin().await?;
+		let result = sqlx::query(
+			"UPDATE notifications SET status = ?, owner = NULL, lease_expires_at = NULL WHERE id \
+			 = ? AND status = ? AND owner = ?",
+		)
+		.bind(queued_str)
+		.bind(id)
+		.bind(processing_str)
+		.bind(owner)
+		.execute(&mut *tx)
+		.await
+		.map_err(map_err)?;
+		if result.rows_affected() == 0 {
+			return Ok(false);
+		}
+
+		sqlx::query(
+			"INSERT INTO outbox (id, notification_id, channel, priority, recipient, payload, \
+			 dedup_key, attempts, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		)
+		.bind(new_id())
+		.bind(entry.notification_id)
+		.bind(channel_str)
+		.bind(priority_str)
+		.bind(entry.recipient)
+		.bind(entry.payload)
+		.bind(entry.dedup_key)
+		.bind(entry.attempts as i32)
+		.bind(millis(entry.created_at))
+		.execute(&mut *tx)
+		.await
+		.map_err(map_err)?;
+		tx.commit().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn claim(&self, opts: ClaimOutboxOptions) -> AppResult<Vec<OutboxEntry>> {
+		let lease_expires_at = opts.now
+			+ chrono::Duration::from_std(opts.lease)
+				.map_err(|e| AppError::ServiceError(format!("Invalid lease: {}", e)))?;
+
+		let rows = sqlx::query(
+			"UPDATE outbox SET owner = ?, lease_expires_at = ? WHERE id IN (SELECT id FROM outbox \
+			 WHERE lease_expires_at IS NULL OR lease_expires_at < ? ORDER BY created_at LIMIT ?) \
+			 RETURNING *",
+		)
+		.bind(opts.owner)
+		.bind(millis(lease_expires_at))
+		.bind(millis(opts.now))
+		.bind(opts.limit)
+		.fetch_all(&self.pool)
+		.await
+		.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to claim outbox with err: {:?}", e))
+		})?;
+
+		let mut claimed = rows
+			.iter()
+			.map(entry_from_row)
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))?;
+		claimed.sort_by_key(|entry| entry.created_at);
+		Ok(claimed)
+	}
+
+	async fn mark_published(&self, entry: &OutboxEntry) -> AppResult<bool> {
+		let queued_str: String = Status::Queued.into();
+		let sent_str: String = Status::Sent.into();
+
+		let mut tx = self.begin().await?;
+		let result = sqlx::query("DELETE FROM outbox WHERE id = ? AND owner = ?")
+			.bind(&entry.id)
+			.bind(&entry.owner)
+			.execute(&mut *tx)
+			.await
+			.map_err(map_err)?;
+		if result.rows_affected() == 0 {
+			return Ok(false);
+		}
+		sqlx::query("UPDATE notifications SET status = ? WHERE id = ? AND status = ?")
+			.bind(sent_str)
+			.bind(&entry.notification_id)
+			.bind(queued_str)
+			.execute(&mut *tx)
+			.await
+			.map_err(map_err)?;
+		tx.commit().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn mark_failed(&self, entry: &OutboxEntry, failure: AttemptFailure) -> AppResult<bool> {
+		let queued_str: String = Status::Queued.into();
+
+		let mut tx = self.begin().await?;
+		let result = sqlx::query("DELETE FROM outbox WHERE id = ? AND owner = ?")
+			.bind(&entry.id)
+			.bind(&entry.owner)
+			.execute(&mut *tx)
+			.await
+			.map_err(map_err)?;
+		if result.rows_affected() == 0 {
+			return Ok(false);
+		}
+
+		// Back to pending until `next_attempt_at` or, once retries are
+		// exhausted, failed.
+		let (status, failed_at, failure_reason) = match failure.next_attempt_at {
+			Some(_) => (Status::Pending, None, None),
+			None => (Status::Failed, Some(Utc::now()), Some(RETRIES_EXHAUSTED)),
+		};
+		let status_str: String = status.into();
+		sqlx::query(
+			"UPDATE notifications SET status = ?, attempts = ?, next_attempt_at = ?, last_error = \
+			 ?, failed_at = ?, failure_reason = ?, owner = NULL, lease_expires_at = NULL WHERE id \
+			 = ? AND status = ?",
+		)
+		.bind(status_str)
+		.bind(failure.attempts as i32)
+		.bind(failure.next_attempt_at.map(millis))
+		.bind(failure.last_error)
+		.bind(failed_at.map(millis))
+		.bind(failure_reason)
+		.bind(&entry.notification_id)
+		.bind(queued_str)
+		.execute(&mut *tx)
+		.await
+		.map_err(map_err)?;
+		tx.commit().await.map_err(map_err)?;
+		Ok(true)
+	}
+
+	async fn cancel(&self, notification_id: &str) -> AppResult<bool> {
+		let pending_str: String = Status::Pending.into();
+		let queued_str: String = Status::Queued.into();
+		let cancelled_str: String = Status::Cancelled.into();
+
+		let mut tx = self.begin().await?;
+		let result = sqlx::query(
+			"UPDATE notifications SET status = ?, owner = NULL, lease_expires_at = NULL WHERE id \
+			 = ? AND status IN (?, ?)",
+		)
+		.bind(cancelled_str)
+		.bind(notification_id)
+		.bind(pending_str)
+		.bind(queued_str)
+		.execute(&mut *tx)
+		.await
+		.map_err(map_err)?;
+		if result.rows_affected() == 0 {
+			return Ok(false);
+		}
+
+		sqlx::query("DELETE FROM outbox WHERE notification_id = ?")
+			.bind(notification_id)
+			.execute(&mut *tx)
+			.await
+			.map_err(map_err)?;
+		tx.commit().await.map_err(map_err)?;
+		Ok(true)
+	}
+}
@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use sqlx::{Row, SqliteConnection, SqlitePool, sqlite::SqliteRow};
+
+use crate::{
+	data::{
+		replays::{ReplayRecord, ReplayRepository},
+		sqlite::{get_optional_time, get_time, get_u32, millis},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct SqliteReplayRepository {
+	pool: SqlitePool,
+}
+
+impl SqliteReplayRepository {
+	pub fn new(pool: SqlitePool) -> Self {
+		SqliteReplayRepository { pool }
+	}
+}
+
+fn record_from_row(row: &SqliteRow) -> Result<ReplayRecord, sqlx::Error> {
+	Ok(ReplayRecord {
+		id:                 Some(row.try_get("id")?),
+		notification_id:    row.try_get("notification_id")?,
+		requested_by:       row.try_get("requested_by")?,
+		replayed_at:        get_time(row, "replayed_at")?,
+		previous_attempts:  get_u32(row, "previous_attempts")?,
+		previous_error:     row.try_get("previous_error")?,
+		previous_failed_at: get_optional_time(row, "previous_failed_at")?,
+		scheduled_time:     get_optional_time(row, "scheduled_time")?,
+	})
+}
+
+/// Runs inside the replaying transaction, see
+/// `NotificationRepository::replay_failed`.
+pub(crate) async fn insert_replay(
+	conn: &mut SqliteConnection,
+	record: &ReplayRecord,
+) -> Result<(), sqlx::Error> {
+	sqlx::query(
+		"INSERT INTO replays (id, notification_id, requested_by, replayed_at, previous_attempts, \
+		 previous_error, previous_failed_at, scheduled_time) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+	)
+	.bind(&record.id)
+	.bind(&record.notification_id)
+	.bind(&record.requested_by)
+	.bind(millis(record.replayed_at))
+	.bind(record.previous_attempts as i32)
+	.bind(&record.previous_error)
+	.bind(record.previous_failed_at.map(millis))
+	.bind(record.scheduled_time.map(millis))
+	.execute(conn)
+	.await?;
+	Ok(())
+}
+
+#[async_trait]
+impl ReplayRepository for SqliteReplayRepository {
+	async fn list(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>> {
+		let rows = sqlx::query(
+			"SELECT * FROM replays WHERE ?1 IS NULL OR notification_id = ?1 ORDER BY replayed_at \
+			 DESC",
+		)
+		.bind(notification_id)
+		.fetch_all(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?;
+
+		rows.iter()
+			.map(record_from_row)
+			.collect::<Result<_, _>>()
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))
+	}
+}
@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+use crate::{
+	data::{
+		new_id,
+		sqlite::{get_time, millis},
+		subscriptions::{SubscriptionRepository, WebPushSubscription},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct SqliteSubscriptionRepository {
+	pool: SqlitePool,
+}
+
+impl SqliteSubscriptionRepository {
+	pub fn new(pool: SqlitePool) -> Self {
+		SqliteSubscriptionRepository { pool }
+	}
+}
+
+fn subscription_from_row(row: &SqliteRow) -> Result<WebPushSubscription, sqlx::Error> {
+	Ok(WebPushSubscription {
+		id:           Some(row.try_get("id")?),
+		recipient_id: row.try_get("recipient_id")?,
+		endpoint:     row.try_get("endpoint")?,
+		p256dh:       row.try_get("p256dh")?,
+		auth:         row.try_get("auth")?,
+		created_at:   get_time(row, "created_at")?,
+	})
+}
+
+#[async_trait]
+impl SubscriptionRepository for SqliteSubscriptionRepository {
+	async fn upsert(&self, subscription: WebPushSubscription) -> AppResult<WebPushSubscription> {
+		let row = sqlx::query(
+			"INSERT INTO webpush_subscriptions (id, recipient_id, endpoint, p256dh, auth, \
+			 created_at) VALUES (?, ?, ?, ?, ?, ?) ON CONFLICT (endpoint) DO UPDATE SET \
+			 recipient_id = EXCLUDED.recipient_id, p256dh = EXCLUDED.p256dh, auth = EXCLUDED.auth \
+			 RETURNING *",
+		)
+		.bind(new_id())
+		.bind(&subscription.recipient_id)
+		.bind(&subscription.endpoint)
+		.bind(&subscription.p256dh)
+		.bind(&subscription.auth)
+		.bind(millis(subscription.created_at))
+		.fetch_one(&self.pool)
+		.await
+		.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to store subscription with err: {:?}", e))
+		})?;
+
+		subscription_from_row(&row)
+			.map_err(|e| AppError::SerialError(format!("Failed to deserialize: {}", e)))
+	}
+
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>> {
+		let rows = sqlx::query(
+			"SELECT * FROM webpush_subscriptions WHERE recipient_id = ? ORDER BY created_at",
+		)
+		.bind(recipient_id)
+		.fetch_all(&self.pool)
+		.await
+		.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?;
+
+		rows.iter()
+			.map(subscription_from_row)
+			.collect::<Result<_, _>>()
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))
+	}
+
+	async fn delete(&self, recipient_id: String, endpoint: String) -> AppResult<bool> {
+		let result = sqlx::query(
+			"DELETE FROM webpush_subscriptions WHERE recipient_id = ? AND endpoint = ?",
+		)
+		.bind(recipient_id)
+		.bind(endpoint)
+		.execute(&self.pool)
+		.await
+		.map_err(|e| {
+			AppError::RepositoryError(format!("Failed to delete subscription with err: {:?}", e))
+		})?;
+		Ok(result.rows_affected() > 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+
+	use super::*;
+	use crate::data::sqlite::SqliteContext;
+
+	fn subscription(recipient_id: &str, auth: &str) -> WebPushSubscription {
+		WebPushSubscription {
+			id:           None,
+			recipient_id: recipient_id.to_string(),
+			endpoint:     "https://push.example.com/send/abc".to_string(),
+			p256dh:       "key".to_string(),
+			auth:         auth.to_string(),
+			created_at:   Utc::now(),
+		}
+	}
+
+	#[tokio::test]
+	async fn endpoints_are_stored_once_and_deleted_per_recipient() {
+		let sqlite = SqliteContext::new(":memory:").await.unwrap();
+		let repository = SqliteSubscriptionRepository::new(sqlite.pool);
+
+		let first = repository
+			.upsert(subscription("user-1", "first"))
+			.await
+			.unwrap();
+		// The browser now belongs to another recipient.
+		let second = repository
+			.upsert(subscription("user-2", "second"))
+			.await
+			.unwrap();
+		assert_eq!(second.id, first.id);
+		assert_eq!(second.auth, "second");
+		assert!(
+			repository
+				.list("user-1".to_string())
+				.await
+				.unwrap()
+				.is_empty()
+		);
+
+		let endpoint = second.endpoint.clone();
+		assert!(
+			!repository
+				.delete("user-1".to_string(), endpoint.clone())
+				.await
+				.unwrap()
+		);
+		assert!(
+			repository
+				.delete("user-2".to_string(), endpoint)
+				.await
+				.unwrap()
+		);
+		assert!(
+			repository
+				.list("user-2".to_string())
+				.await
+				.unwrap()
+				.is_empty()
+		);
+	}
+}
@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use mongodb::{
+	Collection,
+	bson::{doc, oid::ObjectId},
+	options::ReturnDocument,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	data::notifications::timestamp,
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// A browser's Web Push subscription, as handed out by its push service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebPushSubscription {
+	#[serde(rename = "_id")]
+	pub id:           Option<String>,
+	#[serde(rename = "recipientId")]
+	pub recipient_id: String,
+	/// Unique per browser profile, the key subscriptions are stored under.
+	#[serde(rename = "endpoint")]
+	pub endpoint:     String,
+	/// The browser's P-256 public key, base64url encoded.
+	#[serde(rename = "p256dh")]
+	pub p256dh:       String,
+	/// The browser's authentication secret, base64url encoded.
+	#[serde(rename = "auth")]
+	pub auth:         String,
+	#[serde(rename = "createdAt")]
+	pub created_at:   DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait SubscriptionRepository: Send + Sync {
+	/// Stores the subscription, replacing the one with the same endpoint. A
+	/// browser shared by several recipients keeps only the latest one.
+	async fn upsert(&self, subscription: WebPushSubscription) -> AppResult<WebPushSubscription>;
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>>;
+	/// Returns `false` if the recipient had no subscription for `endpoint`.
+	async fn delete(&self, recipient_id: String, endpoint: String) -> AppResult<bool>;
+}
+
+pub struct SubscriptionRepositoryImpl {
+	subscriptions: Collection<WebPushSubscription>,
+}
+
+impl SubscriptionRepositoryImpl {
+	pub fn new(subscriptions: Collection<WebPushSubscription>) -> Self {
+		SubscriptionRepositoryImpl { subscriptions }
+	}
+}
+
+#[async_trait]
+impl SubscriptionRepository for SubscriptionRepositoryImpl {
+	async fn upsert(&self, subscription: WebPushSubscription) -> AppResult<WebPushSubscription> {
+		self.subscriptions
+			.find_one_and_update(
+				doc! { "endpoint": &subscription.endpoint },
+				doc! {
+					"$set": {
+						"recipientId": &subscription.recipient_id,
+						"p256dh": &subscription.p256dh,
+						"auth": &subscription.auth,
+					},
+					"$setOnInsert": {
+						"_id": ObjectId::new().to_hex(),
+						"createdAt": timestamp(subscription.created_at),
+					},
+				},
+			)
+			.upsert(true)
+			.return_document(ReturnDocument::After)
+			.await
+			.map_err(|e| {
+				AppError::RepositoryError(format!("Failed to store subscription with err: {:?}", e))
+			})?
+			.ok_or_else(|| AppError::RepositoryError("Upsert returned no document".to_string()))
+	}
+
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>> {
+		self.subscriptions
+			.find(doc! { "recipientId": recipient_id })
+			.sort(doc! { "createdAt": 1 })
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to find: {}", e)))?
+			.try_collect()
+			.await
+			.map_err(|e| AppError::RepositoryError(format!("Failed to read document: {}", e)))
+	}
+
+	async fn delete(&self, recipient_id: String, endpoint: String) -> AppResult<bool> {
+		let result = self
+			.subscriptions
+			.delete_one(doc! { "recipientId": recipient_id, "endpoint": endpoint })
+			.await
+			.map_err(|e| {
+				AppError::RepositoryError(format!(
+					"Failed to delete subscription with err: {:?}",
+					e
+				))
+			})?;
+		Ok(result.deleted_count > 0)
+	}
+}
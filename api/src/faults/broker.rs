@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::faults::{Component, FaultInjector};
+
+use crate::{
+	faults::into_result,
+	messaging::broker::Broker,
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct FaultyBroker {
+	inner:  Arc<dyn Broker>,
+	faults: Arc<FaultInjector>,
+}
+
+impl FaultyBroker {
+	pub fn new(inner: Arc<dyn Broker>, faults: Arc<FaultInjector>) -> Self {
+		FaultyBroker { inner, faults }
+	}
+}
+
+#[async_trait]
+impl Broker for FaultyBroker {
+	async fn send_message(
+		&self,
+		channel: String,
+		recipient: String,
+		payload: String,
+		dedup_key: String,
+	) -> AppResult<()> {
+		let fault = self
+			.faults
+			.inject(Component::Broker, Some(channel.as_str()))
+			.await;
+		into_result(fault, AppError::ServiceError)?;
+
+		self.inner
+			.send_message(channel, recipient, payload, dedup_key)
+			.await
+	}
+}
@@ -0,0 +1,17 @@
+//! Decorators that route calls through the shared [`FaultInjector`] before
+//! reaching the real implementation.
+
+use common::faults::InjectedFault;
+
+use crate::utils::{errors::AppError, types::AppResult};
+
+pub mod broker;
+pub mod repository;
+
+fn into_result(fault: Option<InjectedFault>, map: fn(String) -> AppError) -> AppResult<()> {
+	match fault {
+		Some(InjectedFault::Error(e)) => Err(map(e)),
+		Some(InjectedFault::Nack) => Err(map("Injected nack".to_string())),
+		None => Ok(()),
+	}
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::faults::{Component, FaultInjector};
+use futures::Stream;
+
+use crate::{
+	data::{
+		notifications::{
+			ClaimOptions,
+			DeadLetterFilter,
+			GetMessagesOptions,
+			Notification,
+			NotificationRepository,
+			Status,
+		},
+		replays::ReplayRecord,
+	},
+	faults::into_result,
+	utils::{errors::AppError, types::AppResult},
+};
+
+pub struct FaultyNotificationRepository {
+	inner:  Arc<dyn NotificationRepository>,
+	faults: Arc<FaultInjector>,
+}
+
+impl FaultyNotificationRepository {
+	pub fn new(inner: Arc<dyn NotificationRepository>, faults: Arc<FaultInjector>) -> Self {
+		FaultyNotificationRepository { inner, faults }
+	}
+
+	async fn inject(&self, channel: Option<String>) -> AppResult<()> {
+		let fault = self
+			.faults
+			.inject(Component::Repository, channel.as_deref())
+			.await;
+		into_result(fault, AppError::RepositoryError)
+	}
+}
+
+#[async_trait]
+impl NotificationRepository for FaultyNotificationRepository {
+	async fn create(&self, notification: Notification) -> AppResult<Notification> {
+		self.inject(Some(notification.channel.clone().into()))
+			.await?;
+		self.inner.create(notification).await
+	}
+
+	async fn get_messages(
+		&self,
+		opts: GetMessagesOptions,
+	) -> AppResult<Box<dyn Stream<Item = Result<Notification, AppError>> + Send + Unpin>> {
+		self.inject(opts.channel.clone().map(Into::into)).await?;
+		self.inner.get_messages(opts).await
+	}
+
+	async fn claim_messages(&self, opts: ClaimOptions) -> AppResult<Vec<Notification>> {
+		self.inject(None).await?;
+		self.inner.claim_messages(opts).await
+	}
+
+	async fn update_message_status(
+		&self,
+		id: String,
+		owner: &str,
+		status: Status,
+	) -> AppResult<bool> {
+		self.inject(None).await?;
+		self.inner.update_message_status(id, owner, status).await
+	}
+
+	async fn replay_failed(
+		&self,
+		id: String,
+		requested_by: String,
+		scheduled_time: Option<DateTime<Utc>>,
+	) -> AppResult<Option<ReplayRecord>> {
+		self.inject(None).await?;
+		self.inner
+			.replay_failed(id, requested_by, scheduled_time)
+			.await
+	}
+
+	async fn purge_failed(&self, filter: DeadLetterFilter) -> AppResult<u64> {
+		self.inject(filter.channel.clone().map(Into::into)).await?;
+		self.inner.purge_failed(filter).await
+	}
+}
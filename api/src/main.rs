@@ -1,83 +1,169 @@
 #![deny(unused_imports)]
 
-use std::{env, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use common::{axum, axum_prometheus::PrometheusMetricLayer, monitoring, tokio};
+use common::{
+	axum,
+	axum_prometheus::metrics::set_global_recorder,
+	faults::FaultInjector,
+	monitoring::{self, health::Health},
+	shutdown::Shutdown,
+	tokio,
+};
 use env_logger::{Builder, Env, Target};
 use log::info;
 
-use crate::{crone::build_notification_scheduler_future, data::notifications::Priority};
+use crate::{
+	crone::{
+		build_dead_letter_purge_future,
+		build_notification_scheduler_future,
+		build_outbox_relay_future,
+	},
+	services::{notifications::ClaimSettings, outbox::RelaySettings},
+};
 
 mod api;
 mod app_state;
+mod config;
 mod crone;
 mod data;
+mod faults;
 mod messaging;
 mod server;
 mod services;
+#[cfg(test)]
+mod testing;
 mod utils;
 
 #[tokio::main]
 async fn main() {
-	// Load environment variables from the .env file
-	dotenvy::from_filename("api/src/app.env").ok();
+	let config: config::Config = common::config::load().unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	});
 
-	// Initialize the logger
-	println!("RUST_LOG: {}", env::var("RUST_LOG").unwrap());
-	Builder::from_env(Env::default())
+	// Initialize the logger, RUST_LOG wins over the configured level
+	Builder::from_env(Env::default().default_filter_or(&config.log.level))
 		.target(Target::Stdout)
 		.init();
 
-	// Get environment variables
-	let port: String = env::var("APP_PORT").unwrap_or("8080".to_string());
-	let prometheus_port: String = env::var("PROMETHEUS_PORT").unwrap_or("9090".to_string());
-	let mongo_uri: String =
-		env::var("MONGO_URI").unwrap_or("mongodb://localhost:27017".to_string());
-	let nats_url: String = env::var("NATS_URL").unwrap_or("localhost:4222".to_string());
-	let host: String = env::var("HOST").unwrap_or("0.0.0.0".to_string());
+	let port = config.server.port.to_string();
+	let prometheus_port = config.metrics.port.to_string();
+	let replica_id = config.claim.replica_id();
+	let fault_injector = Arc::new(
+		FaultInjector::new(config.faults.clone()).expect("Invalid fault injection config"),
+	);
+
+	// Set up Prometheus metrics, the local handlers count into them too
+	let (prometheus_layer, recorder) = monitoring::server::default_pair();
+	set_global_recorder(recorder.clone()).expect("Failed to set global recorder");
+
+	// Every channel is registered, [local] picks the handlers that deliver
+	let handlers = match config.broker.backend {
+		config::BrokerBackend::Local => {
+			info!("Delivering notifications in process");
+			consumer_host::register(&config.channels, recorder.clone())
+				.build(&config.local.handler)
+				.expect("Invalid local handler config")
+		}
+		_ => Vec::new(),
+	};
 
 	// Create the application state
 	let app_state = app_state::AppState::new(app_state::AppStateOptions {
-		mongo_url: mongo_uri.clone(),
-		nats_url:  nats_url.clone(),
+		storage:   config.storage.backend,
+		mongo_url: config.mongo.uri.clone(),
+		postgres:  config.postgres.options(),
+		sqlite:    config.sqlite.path.clone(),
+		broker:    config.broker.backend,
+		nats_url:  config.nats.url.clone(),
+		local:     handlers,
+		streams:   config.streams.settings(),
+		daytime:   config.scheduler.daytime(),
+		faults:    fault_injector.clone(),
+		services:  app_state::ServiceSettings {
+			retry_policy: config.retry.retry_policy(),
+			claim:        ClaimSettings {
+				owner: replica_id.clone(),
+				lease: config.claim.lease(),
+			},
+			relay:        RelaySettings {
+				owner:      replica_id.clone(),
+				lease:      config.claim.lease(),
+				batch_size: config.outbox.batch_size,
+			},
+			scheduling:   config.scheduler.scheduling_policy(),
+		},
 	})
 	.await;
 
-	// Set up Prometheus metrics
-	let (prometheus_layer, metric_handle) = PrometheusMetricLayer::pair();
 	let (mut app, app_listener) = server::server::create_server(
 		server::server::ServerOptions {
-			host: host.clone(),
+			host: config.server.host.clone(),
 			port: port.clone(),
 		},
 		app_state.clone(),
 	)
 	.await;
 	app = app.layer(prometheus_layer.clone());
-	let (prometheus, prometheus_listener) =
+	let (mut prometheus, prometheus_listener) =
 		monitoring::server::create_metrics_router(monitoring::server::ServerOptions {
-			host:          host.clone(),
+			host:          config.metrics.host.clone(),
 			port:          prometheus_port.clone(),
-			metric_handle: metric_handle.clone(),
+			metric_handle: recorder.handle(),
 		})
 		.await;
 
+	// Drain on SIGTERM/Ctrl+C: stop accepting requests and scheduling new
+	// work, let the current batches finish, then take the metrics down last.
+	let shutdown = Shutdown::new();
+	shutdown.listen_for_signals();
+	let metrics_shutdown = Shutdown::new();
+
+	let health = Arc::new(Health::new(
+		app_state.health_checks.clone(),
+		shutdown.clone(),
+	));
+	prometheus = prometheus
+		.merge(common::faults::routes(fault_injector.clone()))
+		.merge(monitoring::health::routes(health));
+
 	// Start the servers
-	let low_priority_scheduler_future = build_notification_scheduler_future(
+	let scheduler_future = build_notification_scheduler_future(
 		app_state.clone(),
-		Priority::Low,
-		Duration::from_secs(5),
+		config.scheduler.interval(),
+		config.scheduler.missed_tick_policy,
+		shutdown.clone(),
 	);
 
-	let high_priority_scheduler_future = build_notification_scheduler_future(
+	let outbox_relay_future = build_outbox_relay_future(
 		app_state.clone(),
-		Priority::High,
-		Duration::from_secs(1),
+		config.outbox.interval(),
+		shutdown.clone(),
 	);
 
+	let dead_letter_purge_future = {
+		let app_state = app_state.clone();
+		let shutdown = shutdown.clone();
+		let dead_letters = config.dead_letters.clone();
+		async move {
+			if let Some(schedule) = dead_letters.purge_schedule() {
+				build_dead_letter_purge_future(
+					app_state,
+					schedule,
+					dead_letters.purge_jitter(),
+					dead_letters.retention(),
+					shutdown,
+				)
+				.await
+			}
+		}
+	};
+
 	let app_future = async {
 		info!("Server running on port: {}", port);
 		axum::serve(app_listener, app)
+			.with_graceful_shutdown(shutdown.wait())
 			.await
 			.expect("Server failed to start");
 	};
@@ -85,14 +171,25 @@ async fn main() {
 	let prom_future = async {
 		info!("Prometheus server running on port: {}", prometheus_port);
 		axum::serve(prometheus_listener, prometheus)
+			.with_graceful_shutdown(metrics_shutdown.wait())
 			.await
 			.expect("Prometheus server failed to start");
 	};
 
-	let _ = tokio::join!(
-		app_future,
-		prom_future,
-		low_priority_scheduler_future,
-		high_priority_scheduler_future
-	);
+	let drain_future = async {
+		tokio::join!(
+			app_future,
+			scheduler_future,
+			outbox_relay_future,
+			dead_letter_purge_future
+		);
+		info!("Drained, stopping metrics server");
+		metrics_shutdown.trigger();
+	};
+
+	shutdown
+		.run_with_deadline(Duration::from_secs(config.shutdown.timeout_secs), async {
+			tokio::join!(drain_future, prom_future)
+		})
+		.await;
 }
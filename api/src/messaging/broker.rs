@@ -1,10 +1,11 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use async_nats::{
 	HeaderMap,
 	jetstream::{Context, stream::Config},
 };
 use async_trait::async_trait;
+use common::{monitoring::health::NatsHealthCheck, notification};
 
 use crate::utils::{errors::AppError, types::AppResult};
 
@@ -19,6 +20,16 @@ pub trait Broker: Send + Sync {
 	) -> AppResult<()>;
 }
 
+const STREAMS: [&str; 7] = [
+	"notifications_email",
+	"notifications_push",
+	"notifications_webpush",
+	"notifications_webhook",
+	"notifications_sms",
+	"notifications_mqtt",
+	"notifications_chat",
+];
+
 pub struct NatsImpl {
 	client: async_nats::Client,
 	js:     Arc<Context>,
@@ -26,6 +37,14 @@ pub struct NatsImpl {
 
 pub struct NatsOptions {
 	pub nats_url: String,
+	pub streams:  StreamSettings,
+}
+
+/// Applied to the notification streams when they are first created.
+pub struct StreamSettings {
+	/// Window in which JetStream drops republished `Nats-Msg-Id`s.
+	pub duplicate_window: Duration,
+	pub max_age:          Duration,
 }
 
 impl NatsImpl {
@@ -36,10 +55,8 @@ impl NatsImpl {
 		let js = async_nats::jetstream::new(client.clone());
 		setup_streams(
 			&js,
-			vec![
-				"notifications_email".to_string(),
-				"notifications_push".to_string(),
-			],
+			STREAMS.iter().map(|stream| stream.to_string()).collect(),
+			&opts.streams,
 		)
 		.await
 		.expect("Failed to setup streams");
@@ -48,6 +65,13 @@ impl NatsImpl {
 			js: Arc::new(js),
 		}
 	}
+
+	pub fn health_check(&self) -> NatsHealthCheck {
+		NatsHealthCheck::new(
+			self.client.clone(),
+			STREAMS.iter().map(|stream| stream.to_string()).collect(),
+		)
+	}
 }
 
 #[async_trait]
@@ -62,7 +86,7 @@ impl Broker for NatsImpl {
 		let mut headers = HeaderMap::new();
 		headers.insert("Nats-Msg-Id", dedup_key);
 
-		let topic = format!("notifications_{}.{}", channel, recipient);
+		let topic = notification::subject(&channel, &recipient);
 		let publish_ack = self
 			.js
 			.publish_with_headers(topic, headers, payload.into())
@@ -81,14 +105,18 @@ impl Broker for NatsImpl {
 	}
 }
 
-async fn setup_streams(js: &Context, streams: Vec<String>) -> AppResult<()> {
+async fn setup_streams(
+	js: &Context,
+	streams: Vec<String>,
+	settings: &StreamSettings,
+) -> AppResult<()> {
 	for stream in streams {
 		let stream_config = Config {
 			name: stream.clone(),
 			subjects: vec![format!("{}.*", stream)],
 			retention: async_nats::jetstream::stream::RetentionPolicy::WorkQueue,
-			duplicate_window: std::time::Duration::from_secs(60),
-			max_age: std::time::Duration::from_secs(60 * 60 * 24),
+			duplicate_window: settings.duplicate_window,
+			max_age: settings.max_age,
 			..Default::default()
 		};
 
@@ -0,0 +1,42 @@
+use std::{
+	collections::HashMap,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// The dedup keys published within the duplicate window, like JetStream
+/// keeps them for a stream.
+pub struct DuplicateWindow {
+	recent: Mutex<HashMap<String, Instant>>,
+	window: Duration,
+}
+
+impl DuplicateWindow {
+	pub fn new(window: Duration) -> Self {
+		DuplicateWindow {
+			recent: Mutex::new(HashMap::new()),
+			window,
+		}
+	}
+
+	/// Whether `dedup_key` was recorded within the window.
+	pub fn contains(&self, dedup_key: &str) -> bool {
+		let now = Instant::now();
+		let mut recent = self.recent.lock().unwrap();
+		recent.retain(|_, seen| now.duration_since(*seen) < self.window);
+		recent.contains_key(dedup_key)
+	}
+
+	/// Records `dedup_key` and returns `false` if it was already seen within
+	/// the window.
+	pub fn remember(&self, dedup_key: String) -> bool {
+		let now = Instant::now();
+		let mut recent = self.recent.lock().unwrap();
+		recent.retain(|_, seen| now.duration_since(*seen) < self.window);
+		if recent.contains_key(&dedup_key) {
+			return false;
+		}
+		recent.insert(dedup_key, now);
+		true
+	}
+}
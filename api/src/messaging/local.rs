@@ -0,0 +1,164 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use consumer::{handler::MessageHandler, registry::ChannelHandler};
+use log::debug;
+
+use crate::{
+	messaging::{broker::Broker, dedup::DuplicateWindow},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// In-process stand-in for JetStream and the consumers, for single node
+/// deployments. Publishing hands the message to the handler of its channel
+/// and only succeeds once that handler did, so the outbox relay marks the
+/// notification Sent after the delivery and retries a failed one by the
+/// retry policy. Like JetStream, a dedup key delivered within the duplicate
+/// window is not delivered again.
+pub struct LocalBroker {
+	handlers: HashMap<&'static str, Arc<dyn MessageHandler>>,
+	recent:   DuplicateWindow,
+}
+
+impl LocalBroker {
+	pub fn new(handlers: Vec<ChannelHandler>, duplicate_window: Duration) -> Self {
+		LocalBroker {
+			handlers: handlers
+				.into_iter()
+				.map(|handler| (handler.channel, handler.handler))
+				.collect(),
+			recent:   DuplicateWindow::new(duplicate_window),
+		}
+	}
+}
+
+#[async_trait]
+impl Broker for LocalBroker {
+	async fn send_message(
+		&self,
+		channel: String,
+		recipient: String,
+		payload: String,
+		dedup_key: String,
+	) -> AppResult<()> {
+		let Some(handler) = self.handlers.get(channel.as_str()) else {
+			return Err(AppError::ServiceError(format!(
+				"No local handler delivers the {} channel",
+				channel
+			)));
+		};
+		if self.recent.contains(&dedup_key) {
+			debug!("Dropped duplicate for {}.{}", channel, recipient);
+			return Ok(());
+		}
+		handler.handle_message(&payload).await.map_err(|e| {
+			AppError::ServiceError(format!(
+				"Failed to deliver on {} for {}: {}",
+				channel, recipient, e
+			))
+		})?;
+		self.recent.remember(dedup_key);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use chrono::{Duration as ChronoDuration, Utc};
+	use consumer::handler::DeliveryError;
+
+	use super::*;
+	use crate::{
+		data::notifications::{Priority, Status},
+		testing::{TestApp, notification, settings},
+	};
+
+	/// Fails the first `failures` messages, records the ones delivered.
+	#[derive(Default)]
+	struct Flaky {
+		failures:  Mutex<usize>,
+		delivered: Mutex<Vec<String>>,
+	}
+
+	#[async_trait]
+	impl MessageHandler for Flaky {
+		async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+			let mut failures = self.failures.lock().unwrap();
+			if *failures > 0 {
+				*failures -= 1;
+				return Err(DeliveryError::Transient("unreachable".to_string()));
+			}
+			self.delivered.lock().unwrap().push(message.to_string());
+			Ok(())
+		}
+	}
+
+	fn broker(handler: Arc<Flaky>) -> LocalBroker {
+		LocalBroker::new(
+			vec![ChannelHandler {
+				channel: "email",
+				handler,
+			}],
+			Duration::from_secs(60),
+		)
+	}
+
+	async fn send(broker: &LocalBroker, channel: &str, payload: &str, dedup_key: &str) -> bool {
+		broker
+			.send_message(
+				channel.to_string(),
+				"user-1".to_string(),
+				payload.to_string(),
+				dedup_key.to_string(),
+			)
+			.await
+			.is_ok()
+	}
+
+	#[tokio::test]
+	async fn delivers_each_dedup_key_once_it_was_handled() {
+		let handler = Arc::new(Flaky {
+			failures: Mutex::new(1),
+			..Default::default()
+		});
+		let broker = broker(handler.clone());
+
+		assert!(!send(&broker, "email", "first", "a").await);
+		assert!(send(&broker, "email", "first", "a").await);
+		assert!(send(&broker, "email", "again", "a").await);
+		assert!(send(&broker, "email", "second", "b").await);
+		assert!(!send(&broker, "sms", "text", "c").await);
+
+		assert_eq!(*handler.delivered.lock().unwrap(), vec!["first", "second"]);
+	}
+
+	#[tokio::test]
+	async fn failed_deliveries_are_retried() {
+		let handler = Arc::new(Flaky {
+			failures: Mutex::new(1),
+			..Default::default()
+		});
+		let app = TestApp::with_broker(settings(), Arc::new(broker(handler.clone())));
+		let now = Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(notification(Priority::High, now), None)
+			.await
+			.unwrap();
+
+		app.deliver(now).await;
+		let failed = app.notification(&id);
+		assert_eq!(failed.status, Status::Pending);
+		assert_eq!(failed.attempts, 1);
+		assert!(failed.last_error.unwrap().contains("unreachable"));
+		assert!(handler.delivered.lock().unwrap().is_empty());
+
+		// Due again once the 5s base delay has passed.
+		app.deliver(now + ChronoDuration::seconds(6)).await;
+		assert_eq!(app.notification(&id).status, Status::Sent);
+		assert_eq!(handler.delivered.lock().unwrap().len(), 1);
+	}
+}
@@ -0,0 +1,105 @@
+use std::{sync::Mutex, time::Duration};
+
+use async_trait::async_trait;
+use common::notification;
+use log::debug;
+
+use crate::{
+	messaging::{broker::Broker, dedup::DuplicateWindow},
+	utils::types::AppResult,
+};
+
+#[derive(Clone, Debug)]
+pub struct PublishedMessage {
+	pub subject:   String,
+	pub payload:   String,
+	pub dedup_key: String,
+}
+
+/// Broker that keeps published messages in memory. Like JetStream, a message
+/// whose dedup key was published within the duplicate window is dropped.
+pub struct InMemoryBroker {
+	published: Mutex<Vec<PublishedMessage>>,
+	recent:    DuplicateWindow,
+}
+
+impl InMemoryBroker {
+	pub fn new(duplicate_window: Duration) -> Self {
+		InMemoryBroker {
+			published: Mutex::new(Vec::new()),
+			recent:    DuplicateWindow::new(duplicate_window),
+		}
+	}
+
+	#[cfg(test)]
+	pub fn published(&self) -> Vec<PublishedMessage> {
+		self.published.lock().unwrap().clone()
+	}
+}
+
+#[async_trait]
+impl Broker for InMemoryBroker {
+	async fn send_message(
+		&self,
+		channel: String,
+		recipient: String,
+		payload: String,
+		dedup_key: String,
+	) -> AppResult<()> {
+		if !self.recent.remember(dedup_key.clone()) {
+			debug!("Dropped duplicate for {}.{}", channel, recipient);
+			return Ok(());
+		}
+		let message = PublishedMessage {
+			subject: notification::subject(&channel, &recipient),
+			payload,
+			dedup_key,
+		};
+		debug!(
+			"Published {} to {}: {}",
+			message.dedup_key, message.subject, message.payload
+		);
+		self.published.lock().unwrap().push(message);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	async fn publish(broker: &InMemoryBroker, payload: &str, dedup_key: &str) {
+		broker
+			.send_message(
+				"email".to_string(),
+				"user-1".to_string(),
+				payload.to_string(),
+				dedup_key.to_string(),
+			)
+			.await
+			.unwrap();
+	}
+
+	fn payloads(broker: &InMemoryBroker) -> Vec<String> {
+		broker.published().into_iter().map(|m| m.payload).collect()
+	}
+
+	#[tokio::test]
+	async fn drops_dedup_keys_seen_within_the_window() {
+		let broker = InMemoryBroker::new(Duration::from_secs(60));
+		publish(&broker, "first", "a").await;
+		publish(&broker, "again", "a").await;
+		publish(&broker, "second", "b").await;
+
+		assert_eq!(payloads(&broker), vec!["first", "second"]);
+	}
+
+	#[tokio::test]
+	async fn publishes_dedup_keys_again_once_the_window_passed() {
+		let broker = InMemoryBroker::new(Duration::ZERO);
+		publish(&broker, "first", "a").await;
+		publish(&broker, "again", "a").await;
+
+		assert_eq!(payloads(&broker), vec!["first", "again"]);
+	}
+}
@@ -1 +1,4 @@
 pub mod broker;
+pub mod dedup;
+pub mod local;
+pub mod memory;
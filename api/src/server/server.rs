@@ -18,7 +18,11 @@ pub async fn create_server(opts: ServerOptions, app_state: Arc<AppState>) -> (Ro
 	(app, listener)
 }
 
-fn setup_routes(app_state: Arc<AppState>) -> Router {
-	let api_routes = Router::new().merge(api::notifications::routes(app_state.clone()));
+pub(crate) fn setup_routes(app_state: Arc<AppState>) -> Router {
+	let api_routes = Router::new()
+		.merge(api::notifications::routes(app_state.clone()))
+		.merge(api::dead_letters::routes(app_state.clone()))
+		.merge(api::inbox::routes(app_state.clone()))
+		.merge(api::subscriptions::routes(app_state.clone()));
 	Router::new().nest("/api/v1", api_routes)
 }
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use log::info;
+use serde::Serialize;
+
+use crate::{
+	data::{
+		notifications::{
+			Channel,
+			DeadLetterFilter,
+			GetMessagesOptions,
+			Notification,
+			NotificationRepository,
+			Priority,
+			Recipient,
+			Status,
+		},
+		replays::{ReplayRecord, ReplayRepository},
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// A failed notification as exposed by the dead-letter endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+	#[serde(rename = "id")]
+	pub id:             String,
+	#[serde(rename = "channel")]
+	pub channel:        Channel,
+	#[serde(rename = "priority")]
+	pub priority:       Priority,
+	#[serde(rename = "recipient")]
+	pub recipient:      Recipient,
+	#[serde(rename = "content")]
+	pub content:        String,
+	#[serde(rename = "scheduledTime")]
+	pub scheduled_time: DateTime<Utc>,
+	#[serde(rename = "attempts")]
+	pub attempts:       u32,
+	#[serde(rename = "lastError")]
+	pub last_error:     Option<String>,
+	#[serde(rename = "failureReason")]
+	pub failure_reason: Option<String>,
+	#[serde(rename = "failedAt")]
+	pub failed_at:      Option<DateTime<Utc>>,
+}
+
+impl From<Notification> for DeadLetter {
+	fn from(n: Notification) -> Self {
+		DeadLetter {
+			id:             n.id.unwrap_or_default(),
+			channel:        n.channel,
+			priority:       n.priority,
+			recipient:      n.recipient,
+			content:        n.content,
+			scheduled_time: n.scheduled_time,
+			attempts:       n.attempts,
+			last_error:     n.last_error,
+			failure_reason: n.failure_reason,
+			failed_at:      n.failed_at,
+		}
+	}
+}
+
+#[derive(Default)]
+pub struct DeadLetterQuery {
+	pub channel:  Option<Channel>,
+	pub priority: Option<Priority>,
+	pub limit:    Option<i64>,
+}
+
+pub struct ReplayOptions {
+	pub requested_by:   String,
+	/// Overrides the original schedule, `None` keeps it.
+	pub scheduled_time: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait DeadLetterService: Send + Sync {
+	async fn list(&self, query: DeadLetterQuery) -> AppResult<Vec<DeadLetter>>;
+	async fn replay(&self, id: String, opts: ReplayOptions) -> AppResult<ReplayRecord>;
+	async fn replay_matching(
+		&self,
+		query: DeadLetterQuery,
+		opts: ReplayOptions,
+	) -> AppResult<Vec<ReplayRecord>>;
+	async fn purge(&self, filter: DeadLetterFilter) -> AppResult<u64>;
+	async fn replays(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>>;
+}
+
+pub struct DeadLetterServiceImpl {
+	repository: Arc<dyn NotificationRepository>,
+	replays:    Arc<dyn ReplayRepository>,
+}
+
+impl DeadLetterServiceImpl {
+	pub fn new(
+		repository: Arc<dyn NotificationRepository>,
+		replays: Arc<dyn ReplayRepository>,
+	) -> Self {
+		DeadLetterServiceImpl {
+			repository,
+			replays,
+		}
+	}
+}
+
+#[async_trait]
+impl DeadLetterService for DeadLetterServiceImpl {
+	async fn list(&self, query: DeadLetterQuery) -> AppResult<Vec<DeadLetter>> {
+		let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+		if !(1..=MAX_LIMIT).contains(&limit) {
+			return Err(AppError::ValidationError(format!(
+				"limit must be between 1 and {}",
+				MAX_LIMIT
+			)));
+		}
+
+		self.repository
+			.get_messages(GetMessagesOptions {
+				channel: query.channel,
+				priority: query.priority,
+				status: Some(Status::Failed),
+				limit: Some(limit),
+				..Default::default()
+			})
+			.await?
+			.map_ok(DeadLetter::from)
+			.try_collect()
+			.await
+	}
+
+	async fn replay(&self, id: String, opts: ReplayOptions) -> AppResult<ReplayRecord> {
+		if opts.requested_by.trim().is_empty() {
+			return Err(AppError::ValidationError(
+				"requestedBy must not be empty".to_string(),
+			));
+		}
+
+		let record = self
+			.repository
+			.replay_failed(id.clone(), opts.requested_by, opts.scheduled_time)
+			.await?
+			.ok_or_else(|| AppError::NotFound(format!("No failed notification with id {}", id)))?;
+
+		info!(
+			"Notification {} replayed by {}, scheduled at {:?}",
+			id, record.requested_by, record.scheduled_time
+		);
+		Ok(record)
+	}
+
+	async fn replay_matching(
+		&self,
+		query: DeadLetterQuery,
+		opts: ReplayOptions,
+	) -> AppResult<Vec<ReplayRecord>> {
+		let dead_letters = self.list(query).await?;
+
+		let mut records = Vec::with_capacity(dead_letters.len());
+		for dead_letter in dead_letters {
+			let opts = ReplayOptions {
+				requested_by:   opts.requested_by.clone(),
+				scheduled_time: opts.scheduled_time,
+			};
+			match self.replay(dead_letter.id, opts).await {
+				Ok(record) => records.push(record),
+				// Replayed or purged concurrently, nothing to do.
+				Err(AppError::NotFound(_)) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(records)
+	}
+
+	async fn purge(&self, filter: DeadLetterFilter) -> AppResult<u64> {
+		if filter.is_empty() && !filter.all {
+			return Err(AppError::ValidationError(
+				"purge needs a filter, or all=true to purge every dead letter".to_string(),
+			));
+		}
+		let purged = self.repository.purge_failed(filter).await?;
+		info!("Purged {} dead-lettered notifications", purged);
+		Ok(purged)
+	}
+
+	async fn replays(&self, notification_id: Option<String>) -> AppResult<Vec<ReplayRecord>> {
+		self.replays.list(notification_id).await
+	}
+}
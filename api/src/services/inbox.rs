@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use log::info;
+
+use crate::{
+	data::{
+		inbox::{InboxPage, InboxQuery, InboxRepository},
+		notifications::Notification,
+	},
+	utils::{errors::AppError, types::AppResult},
+};
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Default)]
+pub struct ListInboxOptions {
+	pub limit:       Option<i64>,
+	pub offset:      Option<u64>,
+	pub unread_only: bool,
+	pub archived:    bool,
+}
+
+#[async_trait]
+pub trait InboxService: Send + Sync {
+	async fn list(&self, recipient_id: String, opts: ListInboxOptions) -> AppResult<InboxPage>;
+	async fn unread_count(&self, recipient_id: String) -> AppResult<u64>;
+	async fn mark_read(&self, recipient_id: String, id: String) -> AppResult<Notification>;
+	async fn archive(&self, recipient_id: String, id: String) -> AppResult<Notification>;
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<()>;
+}
+
+pub struct InboxServiceImpl {
+	repository: Arc<dyn InboxRepository>,
+}
+
+impl InboxServiceImpl {
+	pub fn new(repository: Arc<dyn InboxRepository>) -> Self {
+		InboxServiceImpl { repository }
+	}
+}
+
+fn not_found(recipient_id: &str, id: &str) -> AppError {
+	AppError::NotFound(format!("No inbox item {} for {}", id, recipient_id))
+}
+
+#[async_trait]
+impl InboxService for InboxServiceImpl {
+	async fn list(&self, recipient_id: String, opts: ListInboxOptions) -> AppResult<InboxPage> {
+		let limit = opts.limit.unwrap_or(DEFAULT_LIMIT);
+		if !(1..=MAX_LIMIT).contains(&limit) {
+			return Err(AppError::ValidationError(format!(
+				"limit must be between 1 and {}",
+				MAX_LIMIT
+			)));
+		}
+
+		self.repository
+			.list(InboxQuery {
+				recipient_id,
+				unread_only: opts.unread_only,
+				archived: opts.archived,
+				limit,
+				offset: opts.offset.unwrap_or(0),
+			})
+			.await
+	}
+
+	async fn unread_count(&self, recipient_id: String) -> AppResult<u64> {
+		self.repository.count_unread(recipient_id).await
+	}
+
+	async fn mark_read(&self, recipient_id: String, id: String) -> AppResult<Notification> {
+		self.repository
+			.mark_read(recipient_id.clone(), id.clone(), Utc::now())
+			.await?
+			.ok_or_else(|| not_found(&recipient_id, &id))
+	}
+
+	async fn archive(&self, recipient_id: String, id: String) -> AppResult<Notification> {
+		self.repository
+			.archive(recipient_id.clone(), id.clone(), Utc::now())
+			.await?
+			.ok_or_else(|| not_found(&recipient_id, &id))
+	}
+
+	async fn delete(&self, recipient_id: String, id: String) -> AppResult<()> {
+		if self
+			.repository
+			.delete(recipient_id.clone(), id.clone())
+			.await?
+		{
+			info!("Deleted inbox item {} of {}", id, recipient_id);
+			Ok(())
+		} else {
+			Err(not_found(&recipient_id, &id))
+		}
+	}
+}
@@ -1 +1,7 @@
+pub mod dead_letters;
+pub mod inbox;
 pub mod notifications;
+pub mod outbox;
+pub mod retry;
+pub mod scheduling;
+pub mod subscriptions;
@@ -2,25 +2,40 @@ use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use common::tokio::time::sleep;
 use futures::stream::{FuturesUnordered, StreamExt};
-use log::{debug, error, info};
+use log::{debug, error, warn};
 
 use crate::{
 	data::{
 		notifications,
-		notifications::{GetMessagesOptions, Notification, Priority, Status},
+		notifications::{
+			Channel,
+			ClaimOptions,
+			GetMessagesOptions,
+			Notification,
+			Priority,
+			Status,
+		},
+		outbox::{OutboxEntry, OutboxRepository},
 	},
-	messaging::broker,
+	services::scheduling::SchedulingPolicy,
 	utils::{errors::AppError, types::AppResult},
 };
 
-const TESTING_ERROR_RATIO: u8 = 50; // 50% chance of error
-
 #[derive(Clone)]
 pub struct NotificationServiceImpl {
 	repository: Arc<dyn notifications::NotificationRepository>,
-	broker:     Arc<dyn broker::Broker>,
+	outbox:     Arc<dyn OutboxRepository>,
+	claim:      Arc<ClaimSettings>,
+	scheduling: Arc<SchedulingPolicy>,
+}
+
+/// Identifies this replica when claiming work, so several replicas can share
+/// the same collection without sending a notification twice.
+pub struct ClaimSettings {
+	pub owner: String,
+	/// How long a claim is held before other replicas may take it over.
+	pub lease: Duration,
 }
 
 #[async_trait]
@@ -31,81 +46,100 @@ pub trait NotificationService: Send + Sync {
 		force: Option<bool>,
 	) -> AppResult<String>;
 	async fn cancel_notification(&self, id: String) -> AppResult<()>;
-	async fn send_messages(
-		&self,
-		priority: Priority,
-		scheduled_time: DateTime<Utc>,
-	) -> AppResult<()>;
+	/// Claims due notifications across all priority levels, weighted by the
+	/// scheduling policy, and queues them for delivery.
+	async fn send_messages(&self, scheduled_time: DateTime<Utc>) -> AppResult<()>;
 	async fn get_all(&self) -> AppResult<Vec<Notification>>;
 }
 
 impl NotificationServiceImpl {
 	pub fn new(
 		repository: Arc<dyn notifications::NotificationRepository>,
-		broker: Arc<dyn broker::Broker>,
+		outbox: Arc<dyn OutboxRepository>,
+		claim: ClaimSettings,
+		scheduling: SchedulingPolicy,
 	) -> Self {
-		NotificationServiceImpl { repository, broker }
+		NotificationServiceImpl {
+			repository,
+			outbox,
+			claim: Arc::new(claim),
+			scheduling: Arc::new(scheduling),
+		}
 	}
 }
 
 impl NotificationServiceImpl {
-	// Simulate a random error for demonstration purposes
-	async fn wrapped_send_individual_message(&self, message: Notification) -> AppResult<()> {
-		let random_number = rand::random::<u8>() % 100;
-		if random_number < TESTING_ERROR_RATIO {
-			Err(AppError::ServiceError("Random error occurred".to_string()))
-		} else {
-			self.send_individual_message(message).await
-		}
-	}
-
-	async fn update_message_status(&self, id: String, status: Status) -> AppResult<()> {
-		self.repository.update_message_status(id, status).await
-	}
-
-	async fn send_individual_message(&self, message: Notification) -> AppResult<()> {
-		let message_string =
-			serde_json::to_string(&message).map_err(|e| AppError::ServiceError(e.to_string()))?;
-		self.broker
-			.send_message(
-				message.channel.into(),
-				message.recipient.id,
-				message_string,
-				message.id.unwrap(),
-			)
+	async fn claim(
+		&self,
+		priority: Priority,
+		limit: i64,
+		now: DateTime<Utc>,
+	) -> AppResult<Vec<Notification>> {
+		self.repository
+			.claim_messages(ClaimOptions {
+				priority,
+				limit,
+				now,
+				owner: self.claim.owner.clone(),
+				lease: self.claim.lease,
+				respect_nighttime: true,
+				aging_step: self.scheduling.aging_step,
+			})
 			.await
 	}
 
-	async fn try_send_message(&self, message: Notification) -> AppResult<bool> {
-		let mut attempts = 0;
-		let mut sent = false;
-
-		while attempts < 3 {
-			attempts += 1;
-
-			debug!(
-				"Sending message attempt {} for message {:?}",
-				attempts, message
-			);
-			match self.wrapped_send_individual_message(message.clone()).await {
-				Ok(_) => {
-					info!("Message sent successfully: {:?}", message);
-					sent = true;
-					break;
-				}
-				Err(e) => {
-					error!("Error sending message: {:?}", e);
-					sleep(Duration::from_secs(1)).await;
-				}
+	/// Hands a claimed notification over to the outbox. Publishing, and
+	/// rescheduling on failure, is done by the outbox relay. In-app
+	/// notifications have nothing to publish, marking them sent puts them in
+	/// the recipient's inbox.
+	async fn enqueue_message(&self, message: Notification) -> AppResult<bool> {
+		if message.channel == Channel::InApp {
+			let id = message
+				.id
+				.clone()
+				.ok_or_else(|| AppError::ServiceError("Notification has no id".to_string()))?;
+			let delivered = self
+				.repository
+				.update_message_status(id, &self.claim.owner, Status::Sent)
+				.await?;
+			if delivered {
+				debug!("Message delivered to the inbox: {:?}", message);
+			} else {
+				warn!("Lost claim before delivering message: {:?}", message);
 			}
+			return Ok(delivered);
 		}
 
-		if let Some(id) = message.id.clone() {
-			let status = if sent { Status::Sent } else { Status::Failed };
-			self.repository.update_message_status(id, status).await?;
-		}
+		let message_string =
+			serde_json::to_string(&message).map_err(|e| AppError::ServiceError(e.to_string()))?;
+		let id = message
+			.id
+			.clone()
+			.ok_or_else(|| AppError::ServiceError("Notification has no id".to_string()))?;
 
-		Ok(sent)
+		let entry = OutboxEntry {
+			id:               None,
+			notification_id:  id.clone(),
+			channel:          message.channel.clone(),
+			priority:         message.priority.clone(),
+			recipient:        message.recipient.id.clone(),
+			payload:          message_string,
+			dedup_key:        format!("{}:{}:{}", id, message.replays, message.attempts),
+			attempts:         message.attempts,
+			created_at:       Utc::now(),
+			owner:            None,
+			lease_expires_at: None,
+		};
+		let queued = self
+			.outbox
+			.enqueue(&message, &self.claim.owner, entry)
+			.await?;
+		if queued {
+			debug!("Message queued for delivery: {:?}", message);
+		} else {
+			warn!("Lost claim before queueing message: {:?}", message);
+		}
+		Ok(queued)
 	}
 }
 
@@ -116,52 +150,91 @@ impl NotificationService for NotificationServiceImpl {
 		notification: Notification,
 		force: Option<bool>,
 	) -> AppResult<String> {
-		let notification = self.repository.create(notification.clone()).await?;
+		let force = force.unwrap_or(false);
+		// Only the content, recipient, channel, schedule and priority are the
+		// client's to set.
+		let notification = Notification {
+			id: None,
+			status: Status::Pending,
+			attempts: 0,
+			replays: 0,
+			next_attempt_at: None,
+			last_error: None,
+			failed_at: None,
+			failure_reason: None,
+			owner: None,
+			lease_expires_at: None,
+			read_at: None,
+			archived_at: None,
+			..notification
+		};
+		let notification = if force {
+			// Claimed up front so no scheduler picks it up while it is sent.
+			Notification {
+				status: Status::Processing,
+				owner: Some(self.claim.owner.clone()),
+				lease_expires_at: Some(Utc::now() + self.claim.lease),
+				..notification
+			}
+		} else {
+			notification
+		};
+		let notification = self.repository.create(notification).await?;
 
-		if force.unwrap_or(false) {
-			let _ = self.try_send_message(notification.clone()).await?;
+		if force {
+			let _ = self.enqueue_message(notification.clone()).await?;
 		}
 
 		Ok(notification.id.unwrap())
 	}
 
+	/// Only pending or queued notifications can be cancelled, a queued one
+	/// loses its outbox entry so it is never published.
 	async fn cancel_notification(&self, id: String) -> AppResult<()> {
-		self.repository
-			.update_message_status(id, Status::Cancelled)
-			.await
+		if self.outbox.cancel(&id).await? {
+			Ok(())
+		} else {
+			Err(AppError::NotFound(format!(
+				"No pending or queued notification with id {}",
+				id
+			)))
+		}
 	}
 
-	async fn send_messages(
-		&self,
-		priority: Priority,
-		scheduled_time: DateTime<Utc>,
-	) -> AppResult<()> {
-		let pending_messages = self
-			.repository
-			.get_messages(GetMessagesOptions {
-				priority:          Some(priority),
-				status:            Some(Status::Pending),
-				limit:             Some(10),
-				scheduled_time:    Some(scheduled_time),
-				respect_nighttime: Some(true),
-			})
-			.await?;
+	async fn send_messages(&self, scheduled_time: DateTime<Utc>) -> AppResult<()> {
+		let mut remaining = self.scheduling.batch_size;
+		let mut claimed_messages = Vec::new();
 
-		let tasks = FuturesUnordered::from_iter(
-			pending_messages
-				.map(|result_message| {
-					let this = self.clone();
-					async move {
-						match result_message {
-							Ok(message) => this.try_send_message(message).await,
-							Err(e) => Err(e),
-						}
-					}
-				})
-				.collect::<Vec<_>>()
-				.await,
+		for (priority, quota) in self.scheduling.allocate(remaining) {
+			if quota == 0 {
+				continue;
+			}
+			let claimed = self.claim(priority, quota, scheduled_time).await?;
+			remaining -= claimed.len() as i64;
+			claimed_messages.extend(claimed);
+		}
+
+		// Capacity left by idle levels goes to whoever still has work, most
+		// urgent first, so a busy level is never held back by its weight alone.
+		for priority in Priority::ALL {
+			if remaining == 0 {
+				break;
+			}
+			let claimed = self.claim(priority, remaining, scheduled_time).await?;
+			remaining -= claimed.len() as i64;
+			claimed_messages.extend(claimed);
+		}
+		debug!(
+			"Claimed {} messages at {}",
+			claimed_messages.len(),
+			scheduled_time
 		);
 
+		let tasks = FuturesUnordered::from_iter(claimed_messages.into_iter().map(|message| {
+			let this = self.clone();
+			async move { this.enqueue_message(message).await }
+		}));
+
 		tasks
 			.for_each(|res| async {
 				if let Err(e) = res {
@@ -191,3 +264,197 @@ impl NotificationService for NotificationServiceImpl {
 		Ok(notifications)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use chrono::Duration;
+	use serde_json::Value;
+
+	use crate::{
+		data::notifications::{Channel, Priority, Status},
+		testing::{TestApp, notification},
+	};
+
+	/// A notification as consumers expect it, they decode the same file.
+	const PUBLISHED: &str = include_str!("../../../common/testdata/notification.json");
+
+	/// Field names with the JSON type of their values, nested ones included.
+	fn shape(value: &Value) -> Value {
+		match value {
+			Value::Object(fields) => fields
+				.iter()
+				.map(|(name, value)| (name.clone(), shape(value)))
+				.collect(),
+			Value::String(_) => "string".into(),
+			Value::Number(_) => "number".into(),
+			Value::Bool(_) => "bool".into(),
+			Value::Array(_) => "array".into(),
+			Value::Null => "null".into(),
+		}
+	}
+
+	#[tokio::test]
+	async fn due_notifications_are_published_once_and_marked_sent() {
+		let app = TestApp::new();
+		let now = chrono::Utc::now();
+		let service = app.state.notification_service.clone();
+		let due = service
+			.create_notification(
+				notification(Priority::Normal, now - Duration::minutes(1)),
+				None,
+			)
+			.await
+			.unwrap();
+		let later = service
+			.create_notification(
+				notification(Priority::Normal, now + Duration::hours(1)),
+				None,
+			)
+			.await
+			.unwrap();
+
+		app.deliver(now).await;
+		app.deliver(now).await;
+
+		let published = app.broker.published();
+		assert_eq!(published.len(), 1);
+		assert_eq!(published[0].dedup_key, format!("{}:0:0", due));
+		assert_eq!(app.notification(&due).status, Status::Sent);
+		assert_eq!(app.notification(&later).status, Status::Pending);
+		assert!(app.store.outbox().is_empty());
+	}
+
+	#[tokio::test]
+	async fn dotted_addresses_are_published_on_one_subject_token() {
+		let app = TestApp::new();
+		let now = chrono::Utc::now();
+		let mut email = notification(Priority::High, now - Duration::minutes(1));
+		email.channel = Channel::Email;
+		email.recipient.id = "first.last@example.com".to_string();
+		app.state
+			.notification_service
+			.create_notification(email, None)
+			.await
+			.unwrap();
+
+		app.deliver(now).await;
+
+		let published = app.broker.published();
+		assert_eq!(published.len(), 1);
+		assert_eq!(
+			published[0].subject,
+			"notifications_email.first%2Elast@example%2Ecom"
+		);
+	}
+
+	#[tokio::test]
+	async fn forced_notifications_skip_the_scheduler() {
+		let app = TestApp::new();
+		let now = chrono::Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(
+				notification(Priority::Low, now + Duration::hours(1)),
+				Some(true),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(app.notification(&id).status, Status::Queued);
+		app.state.outbox_relay_service.relay(now).await.unwrap();
+		assert_eq!(app.notification(&id).status, Status::Sent);
+	}
+
+	#[tokio::test]
+	async fn batch_capacity_is_shared_by_weight() {
+		let mut settings = crate::testing::settings();
+		settings.scheduling.batch_size = 3;
+		let app = TestApp::with_settings(settings);
+		let now = chrono::Utc::now();
+		for priority in [
+			Priority::Low,
+			Priority::Low,
+			Priority::Critical,
+			Priority::Critical,
+		] {
+			app.state
+				.notification_service
+				.create_notification(notification(priority, now - Duration::minutes(1)), None)
+				.await
+				.unwrap();
+		}
+
+		app.state
+			.notification_service
+			.send_messages(now)
+			.await
+			.unwrap();
+
+		let queued: Vec<Priority> = app
+			.store
+			.notifications()
+			.into_iter()
+			.filter(|n| n.status == Status::Queued)
+			.map(|n| n.priority)
+			.collect();
+		assert_eq!(queued.len(), 3);
+		assert_eq!(
+			queued.iter().filter(|p| **p == Priority::Critical).count(),
+			2
+		);
+	}
+
+	#[tokio::test]
+	async fn quota_of_idle_levels_goes_to_busy_ones() {
+		let mut settings = crate::testing::settings();
+		settings.scheduling.batch_size = 4;
+		let app = TestApp::with_settings(settings);
+		let now = chrono::Utc::now();
+		for _ in 0..5 {
+			app.state
+				.notification_service
+				.create_notification(
+					notification(Priority::Low, now - Duration::minutes(1)),
+					None,
+				)
+				.await
+				.unwrap();
+		}
+
+		app.state
+			.notification_service
+			.send_messages(now)
+			.await
+			.unwrap();
+
+		let queued = app
+			.store
+			.notifications()
+			.into_iter()
+			.filter(|n| n.status == Status::Queued)
+			.count();
+		assert_eq!(queued, 4);
+	}
+
+	#[tokio::test]
+	async fn published_payloads_match_what_consumers_decode() {
+		let app = TestApp::new();
+		let now = chrono::Utc::now();
+		app.state
+			.notification_service
+			.create_notification(
+				notification(Priority::High, now - Duration::minutes(1)),
+				None,
+			)
+			.await
+			.unwrap();
+
+		app.deliver(now).await;
+
+		let published = app.broker.published();
+		let payload: Value = serde_json::from_str(&published[0].payload).unwrap();
+		let expected: Value = serde_json::from_str(PUBLISHED).unwrap();
+		assert_eq!(shape(&payload), shape(&expected));
+	}
+}
@@ -0,0 +1,246 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{debug, error, warn};
+
+use crate::{
+	data::{
+		notifications::AttemptFailure,
+		outbox::{ClaimOutboxOptions, OutboxEntry, OutboxRepository},
+	},
+	messaging::broker,
+	services::retry::{RetryDecision, RetryPolicy},
+	utils::types::AppResult,
+};
+
+#[async_trait]
+pub trait OutboxRelayService: Send + Sync {
+	/// Publishes a batch of outbox entries and reconciles their
+	/// notifications.
+	async fn relay(&self, now: DateTime<Utc>) -> AppResult<()>;
+}
+
+pub struct RelaySettings {
+	pub owner:      String,
+	pub lease:      Duration,
+	pub batch_size: i64,
+}
+
+#[derive(Clone)]
+pub struct OutboxRelayServiceImpl {
+	outbox:       Arc<dyn OutboxRepository>,
+	broker:       Arc<dyn broker::Broker>,
+	retry_policy: Arc<RetryPolicy>,
+	settings:     Arc<RelaySettings>,
+}
+
+impl OutboxRelayServiceImpl {
+	pub fn new(
+		outbox: Arc<dyn OutboxRepository>,
+		broker: Arc<dyn broker::Broker>,
+		retry_policy: RetryPolicy,
+		settings: RelaySettings,
+	) -> Self {
+		OutboxRelayServiceImpl {
+			outbox,
+			broker,
+			retry_policy: Arc::new(retry_policy),
+			settings: Arc::new(settings),
+		}
+	}
+
+	/// Publishing is at-least-once: a crash after the publish but before the
+	/// entry is removed republishes it, which JetStream drops as a duplicate
+	/// of the same `Nats-Msg-Id`. The same goes for an entry whose lease ran
+	/// out mid-publish, its new owner settles it.
+	async fn publish(&self, entry: OutboxEntry) -> AppResult<()> {
+		let attempts = entry.attempts + 1;
+		let result = self
+			.broker
+			.send_message(
+				entry.channel.clone().into(),
+				entry.recipient.clone(),
+				entry.payload.clone(),
+				entry.dedup_key.clone(),
+			)
+			.await;
+
+		let settled = match result {
+			Ok(_) => {
+				debug!("Published notification {}", entry.notification_id);
+				self.outbox.mark_published(&entry).await?
+			}
+			Err(e) => {
+				error!(
+					"Error publishing notification {}: {:?}",
+					entry.notification_id, e
+				);
+				let next_attempt_at = match self.retry_policy.decide(
+					&entry.channel,
+					&entry.priority,
+					attempts,
+					Utc::now(),
+				) {
+					RetryDecision::RetryAt(at) => Some(at),
+					RetryDecision::GiveUp => None,
+				};
+				self.outbox
+					.mark_failed(
+						&entry,
+						AttemptFailure {
+							attempts,
+							next_attempt_at,
+							last_error: e.to_string(),
+						},
+					)
+					.await?
+			}
+		};
+		if !settled {
+			warn!(
+				"Lost lease on the outbox entry of notification {}",
+				entry.notification_id
+			);
+		}
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl OutboxRelayService for OutboxRelayServiceImpl {
+	async fn relay(&self, now: DateTime<Utc>) -> AppResult<()> {
+		let entries = self
+			.outbox
+			.claim(ClaimOutboxOptions {
+				limit: self.settings.batch_size,
+				now,
+				owner: self.settings.owner.clone(),
+				lease: self.settings.lease,
+			})
+			.await?;
+
+		let tasks = FuturesUnordered::from_iter(entries.into_iter().map(|entry| {
+			let this = self.clone();
+			async move { this.publish(entry).await }
+		}));
+
+		tasks
+			.for_each(|res| async {
+				if let Err(e) = res {
+					error!("Error relaying outbox entry: {:?}", e);
+				}
+			})
+			.await;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, Utc};
+
+	use super::*;
+	use crate::{
+		data::{
+			memory::outbox::MemoryOutboxRepository,
+			notifications::{Priority, RETRIES_EXHAUSTED, Status},
+		},
+		testing::{TestApp, notification, settings},
+	};
+
+	#[tokio::test]
+	async fn failed_publishes_back_off_and_then_succeed() {
+		let app = TestApp::new();
+		let now = Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(notification(Priority::High, now), None)
+			.await
+			.unwrap();
+
+		app.fail_broker();
+		app.deliver(now).await;
+		let failed = app.notification(&id);
+		assert_eq!(failed.status, Status::Pending);
+		assert_eq!(failed.attempts, 1);
+		// Base delay of 5s, counted from when the publish failed.
+		let next_attempt_at = failed.next_attempt_at.unwrap();
+		assert!(next_attempt_at >= now + Duration::seconds(5));
+		assert!(next_attempt_at < now + Duration::seconds(6));
+		assert!(failed.last_error.is_some());
+		assert!(app.store.outbox().is_empty());
+
+		// Not due again before the backoff has elapsed.
+		app.faults.set_config(Default::default()).unwrap();
+		app.deliver(now + Duration::seconds(1)).await;
+		assert_eq!(app.notification(&id).status, Status::Pending);
+
+		app.deliver(now + Duration::seconds(6)).await;
+		assert_eq!(app.notification(&id).status, Status::Sent);
+		assert_eq!(app.broker.published().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn exhausted_retries_dead_letter_the_notification() {
+		let mut settings = settings();
+		settings.retry_policy.default_max_attempts = 1;
+		let app = TestApp::with_settings(settings);
+		let now = Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(notification(Priority::High, now), None)
+			.await
+			.unwrap();
+
+		app.fail_broker();
+		app.deliver(now).await;
+
+		let failed = app.notification(&id);
+		assert_eq!(failed.status, Status::Failed);
+		assert_eq!(failed.failure_reason.as_deref(), Some(RETRIES_EXHAUSTED));
+		assert!(failed.failed_at.is_some());
+		assert!(failed.next_attempt_at.is_none());
+	}
+
+	#[tokio::test]
+	async fn entries_reclaimed_by_another_relay_are_left_to_it() {
+		let app = TestApp::new();
+		let now = Utc::now();
+		let id = app
+			.state
+			.notification_service
+			.create_notification(notification(Priority::High, now), None)
+			.await
+			.unwrap();
+		app.state
+			.notification_service
+			.send_messages(now)
+			.await
+			.unwrap();
+
+		let outbox = MemoryOutboxRepository::new(app.store.clone());
+		let claim = |owner: &str, now| ClaimOutboxOptions {
+			limit: 10,
+			now,
+			owner: owner.to_string(),
+			lease: std::time::Duration::from_secs(60),
+		};
+		let stale = outbox.claim(claim("relay-1", now)).await.unwrap().remove(0);
+		let current = outbox
+			.claim(claim("relay-2", now + Duration::minutes(2)))
+			.await
+			.unwrap();
+		assert_eq!(current.len(), 1);
+
+		assert!(!outbox.mark_published(&stale).await.unwrap());
+		assert_eq!(app.notification(&id).status, Status::Queued);
+		assert_eq!(app.store.outbox().len(), 1);
+		assert!(outbox.mark_published(&current[0]).await.unwrap());
+		assert_eq!(app.notification(&id).status, Status::Sent);
+	}
+}
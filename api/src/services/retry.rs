@@ -0,0 +1,176 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+	data::notifications::{Channel, Priority},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Decides when a failed notification should be attempted again and when it
+/// should be given up on.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+	pub base_delay:               Duration,
+	pub max_delay:                Duration,
+	/// Fraction of the computed delay that is randomized, 0.0 disables jitter.
+	pub jitter:                   f64,
+	pub default_max_attempts:     u32,
+	pub max_attempts_by_priority: HashMap<Priority, u32>,
+	pub max_attempts_by_channel:  HashMap<Channel, u32>,
+}
+
+pub enum RetryDecision {
+	RetryAt(DateTime<Utc>),
+	GiveUp,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		RetryPolicy {
+			base_delay:               Duration::from_secs(5),
+			max_delay:                Duration::from_secs(60 * 15),
+			jitter:                   0.2,
+			default_max_attempts:     5,
+			max_attempts_by_priority: HashMap::new(),
+			max_attempts_by_channel:  HashMap::new(),
+		}
+	}
+}
+
+impl RetryPolicy {
+	pub fn validate(&self) -> AppResult<()> {
+		if !(0.0..=1.0).contains(&self.jitter) {
+			return Err(AppError::ValidationError(format!(
+				"retry jitter must be between 0 and 1, got {}",
+				self.jitter
+			)));
+		}
+		if self.base_delay > self.max_delay {
+			return Err(AppError::ValidationError(
+				"retry base delay must not exceed max delay".to_string(),
+			));
+		}
+		Ok(())
+	}
+
+	/// Channel overrides win over priority overrides, which win over the
+	/// default.
+	pub fn max_attempts(&self, channel: &Channel, priority: &Priority) -> u32 {
+		self.max_attempts_by_channel
+			.get(channel)
+			.or_else(|| self.max_attempts_by_priority.get(priority))
+			.copied()
+			.unwrap_or(self.default_max_attempts)
+	}
+
+	/// `attempts` is the number of attempts made so far, including the one
+	/// that just failed.
+	pub fn decide(
+		&self,
+		channel: &Channel,
+		priority: &Priority,
+		attempts: u32,
+		now: DateTime<Utc>,
+	) -> RetryDecision {
+		if attempts >= self.max_attempts(channel, priority) {
+			return RetryDecision::GiveUp;
+		}
+
+		let delay =
+			chrono::Duration::from_std(self.backoff(attempts)).unwrap_or(chrono::Duration::MAX);
+		RetryDecision::RetryAt(now + delay)
+	}
+
+	fn backoff(&self, attempts: u32) -> Duration {
+		let exponent = attempts.saturating_sub(1).min(31);
+		let delay = self
+			.base_delay
+			.saturating_mul(1 << exponent)
+			.min(self.max_delay);
+
+		if self.jitter == 0.0 {
+			return delay;
+		}
+		let factor = 1.0 - self.jitter * rand::random::<f64>();
+		delay.mul_f64(factor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::config::load_from;
+
+	use super::*;
+	use crate::config::Config;
+
+	fn policy(jitter: f64) -> RetryPolicy {
+		RetryPolicy {
+			base_delay: Duration::from_secs(5),
+			max_delay: Duration::from_secs(60),
+			jitter,
+			..RetryPolicy::default()
+		}
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_the_max_delay() {
+		let policy = policy(0.0);
+		let delays: Vec<_> = (1..=6).map(|attempts| policy.backoff(attempts)).collect();
+
+		assert_eq!(
+			delays,
+			[5, 10, 20, 40, 60, 60].map(Duration::from_secs).to_vec()
+		);
+		assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(60));
+	}
+
+	#[test]
+	fn jitter_only_shortens_the_delay_by_its_fraction() {
+		let policy = policy(0.2);
+		for _ in 0..100 {
+			let delay = policy.backoff(3);
+			assert!(delay <= Duration::from_secs(20));
+			assert!(delay >= Duration::from_secs(16));
+		}
+	}
+
+	#[test]
+	fn decide_gives_up_once_the_attempts_are_used() {
+		let mut policy = policy(0.0);
+		policy.default_max_attempts = 3;
+		policy
+			.max_attempts_by_priority
+			.insert(Priority::Critical, 5);
+		policy.max_attempts_by_channel.insert(Channel::Push, 1);
+		let now = Utc::now();
+
+		match policy.decide(&Channel::Email, &Priority::Low, 2, now) {
+			RetryDecision::RetryAt(at) => assert_eq!(at, now + chrono::Duration::seconds(10)),
+			RetryDecision::GiveUp => panic!("expected a retry"),
+		}
+		assert!(matches!(
+			policy.decide(&Channel::Email, &Priority::Low, 3, now),
+			RetryDecision::GiveUp
+		));
+		assert!(matches!(
+			policy.decide(&Channel::Email, &Priority::Critical, 4, now),
+			RetryDecision::RetryAt(_)
+		));
+		assert!(matches!(
+			policy.decide(&Channel::Push, &Priority::Critical, 1, now),
+			RetryDecision::GiveUp
+		));
+	}
+
+	#[test]
+	fn unknown_override_keys_are_config_errors() {
+		let vars = [(
+			"APP__RETRY__MAX_ATTEMPTS_BY_PRIORITY__URGENT".to_string(),
+			"3".to_string(),
+		)];
+		let error = load_from::<Config>(None, vars).err().unwrap();
+
+		assert!(error.0.starts_with("retry.max_attempts_by_priority"));
+	}
+}
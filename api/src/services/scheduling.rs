@@ -0,0 +1,153 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+	data::notifications::Priority,
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// How a scheduler tick splits its send capacity between priority levels.
+#[derive(Clone, Debug)]
+pub struct SchedulingPolicy {
+	/// Relative share of each level, levels without a weight only get
+	/// capacity the others leave unused.
+	pub weights:    HashMap<Priority, u32>,
+	/// Notifications claimed per tick across all levels.
+	pub batch_size: i64,
+	/// Waiting time after which a notification is served one level higher.
+	pub aging_step: Option<Duration>,
+}
+
+impl Default for SchedulingPolicy {
+	fn default() -> Self {
+		SchedulingPolicy {
+			weights:    HashMap::from([
+				(Priority::Critical, 8),
+				(Priority::High, 4),
+				(Priority::Normal, 2),
+				(Priority::Low, 1),
+			]),
+			batch_size: 20,
+			aging_step: Some(Duration::from_secs(5 * 60)),
+		}
+	}
+}
+
+impl SchedulingPolicy {
+	pub fn validate(&self) -> AppResult<()> {
+		if self.batch_size <= 0 {
+			return Err(AppError::ValidationError(
+				"scheduler batch size must be positive".to_string(),
+			));
+		}
+		if self.aging_step.is_some_and(|step| step.is_zero()) {
+			return Err(AppError::ValidationError(
+				"priority aging step must not be zero".to_string(),
+			));
+		}
+		Ok(())
+	}
+
+	/// Splits `capacity` proportionally to the weights, handing the rounding
+	/// remainder to the levels with the largest fractional share.
+	pub fn allocate(&self, capacity: i64) -> Vec<(Priority, i64)> {
+		let weight = |p: &Priority| self.weights.get(p).copied().unwrap_or(0) as i64;
+		let total: i64 = Priority::ALL.iter().map(weight).sum();
+		if total == 0 {
+			return Priority::ALL.iter().map(|p| (p.clone(), 0)).collect();
+		}
+
+		let mut shares: Vec<(Priority, i64, i64)> = Priority::ALL
+			.iter()
+			.map(|p| {
+				let exact = capacity * weight(p);
+				(p.clone(), exact / total, exact % total)
+			})
+			.collect();
+
+		let allocated: i64 = shares.iter().map(|(_, quota, _)| quota).sum();
+		let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+		by_remainder.sort_by_key(|&i| std::cmp::Reverse(shares[i].2));
+		for &i in by_remainder.iter().take((capacity - allocated) as usize) {
+			shares[i].1 += 1;
+		}
+
+		shares.into_iter().map(|(p, quota, _)| (p, quota)).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn quotas(policy: &SchedulingPolicy, capacity: i64) -> Vec<i64> {
+		policy
+			.allocate(capacity)
+			.into_iter()
+			.map(|(_, quota)| quota)
+			.collect()
+	}
+
+	fn weighted(weights: &[(Priority, u32)]) -> SchedulingPolicy {
+		SchedulingPolicy {
+			weights: weights.iter().cloned().collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn capacity_is_split_by_weight_most_urgent_first() {
+		let policy = SchedulingPolicy::default();
+		let levels: Vec<_> = policy.allocate(15).into_iter().map(|(p, _)| p).collect();
+
+		assert_eq!(levels, Priority::ALL.to_vec());
+		assert_eq!(quotas(&policy, 15), vec![8, 4, 2, 1]);
+		assert_eq!(quotas(&policy, 30), vec![16, 8, 4, 2]);
+	}
+
+	#[test]
+	fn rounding_remainders_go_to_the_largest_fractions() {
+		let policy = SchedulingPolicy::default();
+
+		// 10.67, 5.33, 2.67 and 1.33: critical and normal round up.
+		assert_eq!(quotas(&policy, 20), vec![11, 5, 3, 1]);
+		// 3.73, 1.87, 0.93 and 0.47.
+		assert_eq!(quotas(&policy, 7), vec![4, 2, 1, 0]);
+		for capacity in 0..50 {
+			assert_eq!(quotas(&policy, capacity).iter().sum::<i64>(), capacity);
+		}
+	}
+
+	#[test]
+	fn tiny_batches_go_to_the_heaviest_levels() {
+		let policy = SchedulingPolicy::default();
+
+		assert_eq!(quotas(&policy, 0), vec![0, 0, 0, 0]);
+		assert_eq!(quotas(&policy, 1), vec![1, 0, 0, 0]);
+		assert_eq!(quotas(&policy, 2), vec![1, 1, 0, 0]);
+
+		// Equal fractions keep the most urgent level first.
+		let even = weighted(&[(Priority::High, 1), (Priority::Low, 1)]);
+		assert_eq!(quotas(&even, 1), vec![0, 1, 0, 0]);
+		assert_eq!(quotas(&even, 3), vec![0, 2, 0, 1]);
+	}
+
+	#[test]
+	fn levels_without_weight_get_no_quota() {
+		let policy = weighted(&[(Priority::Normal, 3)]);
+		assert_eq!(quotas(&policy, 5), vec![0, 0, 5, 0]);
+
+		let unweighted = weighted(&[]);
+		assert_eq!(quotas(&unweighted, 5), vec![0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn batch_size_must_be_positive() {
+		let mut policy = SchedulingPolicy::default();
+		assert!(policy.validate().is_ok());
+
+		policy.batch_size = 0;
+		assert!(policy.validate().is_err());
+		policy.batch_size = -1;
+		assert!(policy.validate().is_err());
+	}
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use log::info;
+
+use crate::{
+	data::subscriptions::{SubscriptionRepository, WebPushSubscription},
+	utils::{errors::AppError, types::AppResult},
+};
+
+/// Length of an uncompressed P-256 public key.
+const P256DH_LEN: usize = 65;
+const AUTH_LEN: usize = 16;
+
+pub struct SubscribeOptions {
+	pub endpoint: String,
+	pub p256dh:   String,
+	pub auth:     String,
+}
+
+#[async_trait]
+pub trait SubscriptionService: Send + Sync {
+	async fn subscribe(
+		&self,
+		recipient_id: String,
+		opts: SubscribeOptions,
+	) -> AppResult<WebPushSubscription>;
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>>;
+	async fn unsubscribe(&self, recipient_id: String, endpoint: String) -> AppResult<()>;
+}
+
+pub struct SubscriptionServiceImpl {
+	repository: Arc<dyn SubscriptionRepository>,
+}
+
+impl SubscriptionServiceImpl {
+	pub fn new(repository: Arc<dyn SubscriptionRepository>) -> Self {
+		SubscriptionServiceImpl { repository }
+	}
+}
+
+/// Browsers encode the keys as unpadded base64url, some libraries pad them.
+fn validate_key(name: &str, value: &str, len: usize) -> AppResult<()> {
+	match URL_SAFE_NO_PAD.decode(value.trim_end_matches('=')) {
+		Ok(key) if key.len() == len => Ok(()),
+		_ => Err(AppError::ValidationError(format!(
+			"{} must be {} base64url encoded bytes",
+			name, len
+		))),
+	}
+}
+
+#[async_trait]
+impl SubscriptionService for SubscriptionServiceImpl {
+	async fn subscribe(
+		&self,
+		recipient_id: String,
+		opts: SubscribeOptions,
+	) -> AppResult<WebPushSubscription> {
+		if !opts.endpoint.starts_with("https://") {
+			return Err(AppError::ValidationError(
+				"endpoint must be an https URL".to_string(),
+			));
+		}
+		validate_key("keys.p256dh", &opts.p256dh, P256DH_LEN)?;
+		validate_key("keys.auth", &opts.auth, AUTH_LEN)?;
+
+		let subscription = self
+			.repository
+			.upsert(WebPushSubscription {
+				id: None,
+				recipient_id,
+				endpoint: opts.endpoint,
+				p256dh: opts.p256dh,
+				auth: opts.auth,
+				created_at: Utc::now(),
+			})
+			.await?;
+		info!(
+			"Stored web push subscription for {}",
+			subscription.recipient_id
+		);
+		Ok(subscription)
+	}
+
+	async fn list(&self, recipient_id: String) -> AppResult<Vec<WebPushSubscription>> {
+		self.repository.list(recipient_id).await
+	}
+
+	async fn unsubscribe(&self, recipient_id: String, endpoint: String) -> AppResult<()> {
+		if self
+			.repository
+			.delete(recipient_id.clone(), endpoint)
+			.await?
+		{
+			info!("Removed web push subscription of {}", recipient_id);
+			Ok(())
+		} else {
+			Err(AppError::NotFound(format!(
+				"No subscription with that endpoint for {}",
+				recipient_id
+			)))
+		}
+	}
+}
@@ -0,0 +1,205 @@
+//! Runs the services and the router against in-memory storage and broker, so
+//! tests need neither Mongo nor NATS.
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use common::{
+	axum::{
+		Router,
+		body::{Body, to_bytes},
+		http::{Method, Request, StatusCode, header},
+	},
+	faults::{Component, FaultConfig, FaultInjector, FaultKind, FaultRule},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{
+	app_state::{AppParts, AppState, ServiceSettings},
+	data::{
+		memory::{
+			MemoryStore,
+			inbox::MemoryInboxRepository,
+			notifications::MemoryNotificationRepository,
+			outbox::MemoryOutboxRepository,
+			replays::MemoryReplayRepository,
+			subscriptions::MemorySubscriptionRepository,
+		},
+		notifications::{Channel, Daytime, Notification, Priority, Recipient, Status},
+	},
+	faults::broker::FaultyBroker,
+	messaging::{broker::Broker, memory::InMemoryBroker},
+	server::server::setup_routes,
+	services::{
+		notifications::ClaimSettings,
+		outbox::RelaySettings,
+		retry::RetryPolicy,
+		scheduling::SchedulingPolicy,
+	},
+};
+
+pub const OWNER: &str = "test-replica";
+
+pub struct TestApp {
+	pub state:  Arc<AppState>,
+	pub store:  Arc<MemoryStore>,
+	pub broker: Arc<InMemoryBroker>,
+	pub faults: Arc<FaultInjector>,
+	router:     Router,
+}
+
+/// Deterministic settings: no retry jitter, no priority aging.
+pub fn settings() -> ServiceSettings {
+	ServiceSettings {
+		retry_policy: RetryPolicy {
+			jitter: 0.0,
+			..Default::default()
+		},
+		claim:        ClaimSettings {
+			owner: OWNER.to_string(),
+			lease: Duration::from_secs(60),
+		},
+		relay:        RelaySettings {
+			owner:      OWNER.to_string(),
+			lease:      Duration::from_secs(60),
+			batch_size: 50,
+		},
+		scheduling:   SchedulingPolicy {
+			aging_step: None,
+			..Default::default()
+		},
+	}
+}
+
+impl TestApp {
+	pub fn new() -> Self {
+		Self::with_settings(settings())
+	}
+
+	/// Every local hour counts as daytime, so results do not depend on when
+	/// the tests run.
+	pub fn with_settings(settings: ServiceSettings) -> Self {
+		Self::build(settings, None)
+	}
+
+	/// Publishes through `broker`, leaving [`TestApp::broker`] empty.
+	pub fn with_broker(settings: ServiceSettings, broker: Arc<dyn Broker>) -> Self {
+		Self::build(settings, Some(broker))
+	}
+
+	fn build(settings: ServiceSettings, publisher: Option<Arc<dyn Broker>>) -> Self {
+		let store = MemoryStore::new();
+		let broker = Arc::new(InMemoryBroker::new(Duration::from_secs(60)));
+		let publisher = publisher.unwrap_or_else(|| broker.clone());
+		let faults = Arc::new(FaultInjector::default());
+		let daytime = Daytime {
+			start_hour: 0,
+			end_hour:   24,
+		};
+		let notifications = Arc::new(MemoryNotificationRepository::new(store.clone(), daytime));
+
+		let state = AppState::from_parts(
+			AppParts {
+				notifications,
+				outbox: Arc::new(MemoryOutboxRepository::new(store.clone())),
+				replays: Arc::new(MemoryReplayRepository::new(store.clone())),
+				subscriptions: Arc::new(MemorySubscriptionRepository::new(store.clone())),
+				inbox: Arc::new(MemoryInboxRepository::new(store.clone())),
+				broker: Arc::new(FaultyBroker::new(publisher, faults.clone())),
+				health_checks: Vec::new(),
+			},
+			settings,
+		);
+		let router = setup_routes(state.clone());
+
+		TestApp {
+			state,
+			store,
+			broker,
+			faults,
+			router,
+		}
+	}
+
+	/// Makes every publish fail until the faults are reset.
+	pub fn fail_broker(&self) {
+		self.faults
+			.set_config(FaultConfig {
+				enabled: true,
+				rules: vec![FaultRule {
+					component: Component::Broker,
+					channel:   None,
+					kind:      FaultKind::Error,
+					rate:      1.0,
+				}],
+				..Default::default()
+			})
+			.unwrap();
+	}
+
+	pub fn notification(&self, id: &str) -> Notification {
+		self.store
+			.notifications()
+			.into_iter()
+			.find(|n| n.id.as_deref() == Some(id))
+			.expect("notification exists")
+	}
+
+	/// Runs one scheduler tick followed by one relay pass.
+	pub async fn deliver(&self, now: DateTime<Utc>) {
+		self.state
+			.notification_service
+			.send_messages(now)
+			.await
+			.unwrap();
+		self.state.outbox_relay_service.relay(now).await.unwrap();
+	}
+
+	pub async fn request(
+		&self,
+		method: Method,
+		uri: &str,
+		body: Option<Value>,
+	) -> (StatusCode, Value) {
+		let request = Request::builder().method(method).uri(uri);
+		let request = match body {
+			Some(body) => request
+				.header(header::CONTENT_TYPE, "application/json")
+				.body(Body::from(body.to_string())),
+			None => request.body(Body::empty()),
+		}
+		.unwrap();
+
+		let response = self.router.clone().oneshot(request).await.unwrap();
+		let status = response.status();
+		let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+		let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+		(status, body)
+	}
+}
+
+pub fn notification(priority: Priority, scheduled_time: DateTime<Utc>) -> Notification {
+	Notification {
+		id: None,
+		content: "hello".to_string(),
+		channel: Channel::Email,
+		recipient: Recipient {
+			id:              "user-1".to_string(),
+			timezone_offset: "+00:00".to_string(),
+		},
+		scheduled_time,
+		priority,
+		status: Status::Pending,
+		attempts: 0,
+		replays: 0,
+		next_attempt_at: None,
+		last_error: None,
+		failed_at: None,
+		failure_reason: None,
+		owner: None,
+		lease_expires_at: None,
+		read_at: None,
+		archived_at: None,
+	}
+}
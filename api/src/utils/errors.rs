@@ -9,6 +9,8 @@ use thiserror::Error;
 pub enum AppError {
 	#[error("Duplicate key")]
 	DuplicateKey,
+	#[error("Not found: {0}")]
+	NotFound(String),
 	#[error("Validation error: {0}")]
 	ValidationError(String),
 	#[error("Service error: {0}")]
@@ -26,6 +28,7 @@ impl IntoResponse for AppError {
 			AppError::ServiceError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
 			AppError::RepositoryError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
 			AppError::DuplicateKey => (StatusCode::CONFLICT, self.to_string()),
+			AppError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
 			AppError::SerialError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
 		};
 		let body = Json(serde_json::json!({ "error": error_message }));
@@ -1,3 +1,4 @@
 pub mod errors;
+#[allow(dead_code)]
 pub mod notifications;
 pub mod types;
@@ -0,0 +1,156 @@
+use std::{collections::HashMap, time::Duration};
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::Deserialize;
+
+use crate::handler::chat::{
+	ChatOptions,
+	format::{ChatFormat, Identity},
+};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub chat:     ChatConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("chat_consumer"),
+			chat:     ChatConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Logs each message.
+	#[default]
+	#[serde(rename = "stdout")]
+	StdOut,
+	/// Posts each message to the recipient's chat webhook, see
+	/// [`ChatConfig`].
+	#[serde(rename = "chat")]
+	Chat,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+	pub format:               ChatFormat,
+	/// Incoming-webhook URL per recipient id, e.g. `ops = "https://..."`.
+	/// Env overrides lowercase the recipient id.
+	pub webhooks:             HashMap<String, String>,
+	pub username:             Option<String>,
+	pub icon_url:             Option<String>,
+	pub connect_timeout_secs: u64,
+	pub timeout_secs:         u64,
+	/// Rate limits up to this long are waited out, longer ones send the
+	/// message back for redelivery. Must stay below `consumer.ack_wait_secs`.
+	pub max_retry_after_secs: u64,
+}
+
+impl Default for ChatConfig {
+	fn default() -> Self {
+		ChatConfig {
+			format:               ChatFormat::default(),
+			webhooks:             HashMap::new(),
+			username:             None,
+			icon_url:             None,
+			connect_timeout_secs: 5,
+			timeout_secs:         10,
+			max_retry_after_secs: 30,
+		}
+	}
+}
+
+impl ChatConfig {
+	pub fn options(&self) -> ChatOptions {
+		ChatOptions {
+			format:          self.format,
+			webhooks:        self.webhooks.clone(),
+			identity:        Identity {
+				username: self.username.clone(),
+				icon_url: self.icon_url.clone(),
+			},
+			connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+			timeout:         Duration::from_secs(self.timeout_secs),
+			max_retry_after: Duration::from_secs(self.max_retry_after_secs),
+		}
+	}
+}
+
+/// The sections the chat handlers are built from, also part of hosts that
+/// run other channels next to chat.
+pub struct Sections<'a> {
+	pub chat: &'a ChatConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		if consumer.uses(name(HandlerKind::Chat)) {
+			if self.chat.webhooks.is_empty() {
+				return Err(ConfigError("chat.webhooks must not be empty".to_string()));
+			}
+			require_positive("chat.connect_timeout_secs", self.chat.connect_timeout_secs)?;
+			require_positive("chat.timeout_secs", self.chat.timeout_secs)?;
+			if self.chat.max_retry_after_secs >= consumer.ack_wait_secs {
+				return Err(ConfigError(
+					"chat.max_retry_after_secs must be below consumer.ack_wait_secs".to_string(),
+				));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections { chat: &self.chat }
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
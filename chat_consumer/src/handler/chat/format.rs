@@ -0,0 +1,201 @@
+use common::notification::{Notification, Priority};
+use serde::{Deserialize, Serialize};
+
+/// Longest text Slack accepts in a section block.
+const SLACK_SECTION_LIMIT: usize = 3000;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum ChatFormat {
+	/// Block Kit message, also understood by most Slack-compatible hooks.
+	#[default]
+	#[serde(rename = "slack")]
+	Slack,
+	/// Message attachment, colored by priority.
+	#[serde(rename = "mattermost")]
+	Mattermost,
+}
+
+/// Name and avatar the message is posted under, the webhook's own are used
+/// when unset. Slack ignores both for app webhooks.
+#[derive(Clone, Debug, Default)]
+pub struct Identity {
+	pub username: Option<String>,
+	pub icon_url: Option<String>,
+}
+
+/// Priority and schedule, shown below the content.
+fn details(notification: &Notification) -> Vec<(&'static str, String)> {
+	vec![
+		("Priority", notification.priority.clone().into()),
+		(
+			"Scheduled",
+			notification
+				.scheduled_time
+				.format("%Y-%m-%dT%H:%M:%SZ")
+				.to_string(),
+		),
+	]
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ChatMessage {
+	Slack(SlackMessage),
+	Mattermost(MattermostMessage),
+}
+
+#[derive(Serialize)]
+pub struct SlackMessage {
+	/// Shown in notifications and clients without block support.
+	#[serde(rename = "text")]
+	text:     String,
+	#[serde(rename = "blocks")]
+	blocks:   Vec<SlackBlock>,
+	#[serde(rename = "username", skip_serializing_if = "Option::is_none")]
+	username: Option<String>,
+	#[serde(rename = "icon_url", skip_serializing_if = "Option::is_none")]
+	icon_url: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum SlackBlock {
+	#[serde(rename = "section")]
+	Section {
+		#[serde(rename = "text")]
+		text: SlackText,
+	},
+	#[serde(rename = "context")]
+	Context {
+		#[serde(rename = "elements")]
+		elements: Vec<SlackText>,
+	},
+}
+
+#[derive(Serialize)]
+struct SlackText {
+	#[serde(rename = "type")]
+	kind: &'static str,
+	#[serde(rename = "text")]
+	text: String,
+}
+
+impl SlackText {
+	fn mrkdwn(text: String) -> Self {
+		SlackText {
+			kind: "mrkdwn",
+			text,
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct MattermostMessage {
+	#[serde(rename = "username", skip_serializing_if = "Option::is_none")]
+	username:    Option<String>,
+	#[serde(rename = "icon_url", skip_serializing_if = "Option::is_none")]
+	icon_url:    Option<String>,
+	#[serde(rename = "attachments")]
+	attachments: Vec<MattermostAttachment>,
+}
+
+#[derive(Serialize)]
+struct MattermostAttachment {
+	#[serde(rename = "fallback")]
+	fallback: String,
+	#[serde(rename = "color")]
+	color:    &'static str,
+	#[serde(rename = "text")]
+	text:     String,
+	#[serde(rename = "fields")]
+	fields:   Vec<MattermostField>,
+	#[serde(rename = "footer", skip_serializing_if = "Option::is_none")]
+	footer:   Option<String>,
+}
+
+#[derive(Serialize)]
+struct MattermostField {
+	#[serde(rename = "short")]
+	short: bool,
+	#[serde(rename = "title")]
+	title: &'static str,
+	#[serde(rename = "value")]
+	value: String,
+}
+
+/// `&`, `<` and `>` are control characters in Slack's mrkdwn.
+fn escape_slack(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+fn truncate(text: String, limit: usize) -> String {
+	match text.char_indices().nth(limit.saturating_sub(1)) {
+		Some((end, _)) if text.chars().count() > limit => format!("{}…", &text[..end]),
+		_ => text,
+	}
+}
+
+fn priority_color(priority: &Priority) -> &'static str {
+	match priority {
+		Priority::Critical => "#d32f2f",
+		Priority::High => "#f57c00",
+		Priority::Normal => "#1976d2",
+		Priority::Low => "#9e9e9e",
+	}
+}
+
+pub fn format(format: ChatFormat, notification: &Notification, identity: &Identity) -> ChatMessage {
+	match format {
+		ChatFormat::Slack => ChatMessage::Slack(slack(notification, identity)),
+		ChatFormat::Mattermost => ChatMessage::Mattermost(mattermost(notification, identity)),
+	}
+}
+
+fn slack(notification: &Notification, identity: &Identity) -> SlackMessage {
+	let content = escape_slack(&notification.content);
+	let mut blocks = vec![SlackBlock::Section {
+		text: SlackText::mrkdwn(truncate(content.clone(), SLACK_SECTION_LIMIT)),
+	}];
+	let details: Vec<String> = details(notification)
+		.into_iter()
+		.map(|(title, value)| format!("*{}:* {}", title, escape_slack(&value)))
+		.collect();
+	blocks.push(SlackBlock::Context {
+		elements: vec![SlackText::mrkdwn(details.join("  ·  "))],
+	});
+
+	SlackMessage {
+		text: content,
+		blocks,
+		username: identity.username.clone(),
+		icon_url: identity.icon_url.clone(),
+	}
+}
+
+fn mattermost(notification: &Notification, identity: &Identity) -> MattermostMessage {
+	let fields = details(notification)
+		.into_iter()
+		.map(|(title, value)| MattermostField {
+			short: true,
+			title,
+			value,
+		})
+		.collect();
+
+	MattermostMessage {
+		username:    identity.username.clone(),
+		icon_url:    identity.icon_url.clone(),
+		attachments: vec![MattermostAttachment {
+			fallback: notification.content.clone(),
+			color: priority_color(&notification.priority),
+			text: notification.content.clone(),
+			fields,
+			footer: notification
+				.id
+				.as_ref()
+				.map(|id| format!("Notification {}", id)),
+		}],
+	}
+}
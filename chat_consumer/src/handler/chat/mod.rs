@@ -0,0 +1,465 @@
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use consumer::handler::parse_notification;
+use log::{debug, warn};
+use reqwest::{StatusCode, Url, header::HeaderMap};
+use tokio::time::Instant;
+
+use crate::{
+	app_state::AppState,
+	handler::{
+		DeliveryError,
+		MessageHandler,
+		chat::format::{ChatFormat, Identity, format},
+		retry_after,
+	},
+	metrics::metrics::{CHAT_CONSUMER_CONSUMED_MESSAGES, CHAT_CONSUMER_RATE_LIMITED},
+};
+
+pub mod format;
+
+/// Requests per message, the first one plus retries after a rate limit.
+const MAX_ATTEMPTS: usize = 3;
+/// Wait after a 429 that did not say for how long.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+pub struct ChatOptions {
+	pub format:          ChatFormat,
+	/// Incoming-webhook URL per recipient id.
+	pub webhooks:        HashMap<String, String>,
+	pub identity:        Identity,
+	pub connect_timeout: Duration,
+	pub timeout:         Duration,
+	/// Longest rate limit waited out before the message is handed back for
+	/// redelivery.
+	pub max_retry_after: Duration,
+}
+
+/// Posts each notification to the incoming webhook of its recipient.
+///
+/// A 429 blocks the webhook for as long as the platform asks, through
+/// `Retry-After` (Slack) or `X-RateLimit-Reset` (Mattermost). Short waits
+/// are slept through and the request is repeated, longer ones send the
+/// message back to be redelivered once the limit is over, without calling
+/// the webhook again.
+pub struct ChatHandlerImpl {
+	client:             reqwest::Client,
+	format:             ChatFormat,
+	webhooks:           HashMap<String, Url>,
+	identity:           Identity,
+	max_retry_after:    Duration,
+	rate_limited_until: Mutex<HashMap<Url, Instant>>,
+	app_state:          Arc<AppState>,
+}
+
+impl ChatHandlerImpl {
+	pub fn new(opts: ChatOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let mut webhooks = HashMap::new();
+		for (recipient_id, url) in opts.webhooks {
+			// The URL is the credential, it is kept out of the error.
+			let url = Url::parse(&url)
+				.ok()
+				.filter(|url| matches!(url.scheme(), "http" | "https"))
+				.ok_or_else(|| format!("invalid webhook URL for {}", recipient_id))?;
+			webhooks.insert(recipient_id, url);
+		}
+		let client = reqwest::Client::builder()
+			.connect_timeout(opts.connect_timeout)
+			.timeout(opts.timeout)
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(ChatHandlerImpl {
+			client,
+			format: opts.format,
+			webhooks,
+			identity: opts.identity,
+			max_retry_after: opts.max_retry_after,
+			rate_limited_until: Mutex::default(),
+			app_state,
+		})
+	}
+
+	fn blocked_for(&self, url: &Url) -> Duration {
+		self.rate_limited_until
+			.lock()
+			.unwrap()
+			.get(url)
+			.map(|until| until.saturating_duration_since(Instant::now()))
+			.unwrap_or_default()
+	}
+
+	fn block(&self, url: &Url, retry_after: Duration) {
+		self.rate_limited_until
+			.lock()
+			.unwrap()
+			.insert(url.clone(), Instant::now() + retry_after);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(CHAT_CONSUMER_RATE_LIMITED)
+		{
+			metric.increment(1);
+		}
+	}
+
+	/// Sleeps through a short rate limit, hands the message back for the
+	/// rest of a long one.
+	async fn wait_for(&self, url: &Url) -> Result<(), DeliveryError> {
+		let wait = self.blocked_for(url);
+		if wait > self.max_retry_after {
+			return Err(DeliveryError::RetryAfter(
+				wait,
+				"webhook is rate limited".to_string(),
+			));
+		}
+		if !wait.is_zero() {
+			debug!("Waiting {:?} for the chat rate limit", wait);
+			tokio::time::sleep(wait).await;
+		}
+		Ok(())
+	}
+}
+
+/// How long a 429 blocks the webhook: `Retry-After`, or Mattermost's
+/// `X-RateLimit-Reset`, which counts the seconds until the window resets.
+fn rate_limit(headers: &HeaderMap) -> Duration {
+	retry_after(headers)
+		.or_else(|| {
+			headers
+				.get("x-ratelimit-reset")?
+				.to_str()
+				.ok()?
+				.trim()
+				.parse()
+				.ok()
+				.map(Duration::from_secs)
+		})
+		.unwrap_or(DEFAULT_RETRY_AFTER)
+}
+
+/// Outages may pass, any other rejection, e.g. a removed webhook or an
+/// archived channel, would be repeated.
+fn classify(status: StatusCode) -> DeliveryError {
+	let error = format!("webhook returned {}", status);
+	if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+		DeliveryError::Transient(error)
+	} else {
+		DeliveryError::Permanent(error)
+	}
+}
+
+#[async_trait]
+impl MessageHandler for ChatHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_notification(message)?;
+		let url = self
+			.webhooks
+			.get(&notification.recipient.id)
+			.ok_or_else(|| {
+				DeliveryError::Permanent(format!(
+					"no webhook for recipient {}",
+					notification.recipient.id
+				))
+			})?;
+		let payload = format(self.format, &notification, &self.identity);
+
+		for attempt in 1..=MAX_ATTEMPTS {
+			self.wait_for(url).await?;
+			let response = self
+				.client
+				.post(url.clone())
+				.json(&payload)
+				.send()
+				.await
+				.map_err(|e| {
+					DeliveryError::Transient(format!("request failed: {}", e.without_url()))
+				})?;
+
+			let status = response.status();
+			if status == StatusCode::TOO_MANY_REQUESTS {
+				let retry_after = rate_limit(response.headers());
+				warn!(
+					"Chat webhook of {} is rate limited for {:?}, attempt {}",
+					notification.recipient.id, retry_after, attempt
+				);
+				self.block(url, retry_after);
+				continue;
+			}
+			if !status.is_success() {
+				return Err(classify(status));
+			}
+
+			debug!(
+				"Posted notification {} to the chat of {}",
+				notification.id.as_deref().unwrap_or("-"),
+				notification.recipient.id
+			);
+			if let Some(metric) = self
+				.app_state
+				.metrics
+				.get_counter(CHAT_CONSUMER_CONSUMED_MESSAGES)
+			{
+				metric.increment(1);
+			}
+			return Ok(());
+		}
+		Err(DeliveryError::RetryAfter(
+			self.blocked_for(url),
+			format!("still rate limited after {} attempts", MAX_ATTEMPTS),
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::VecDeque;
+
+	use axum::{
+		Json,
+		Router,
+		extract::State,
+		http::{HeaderName, HeaderValue},
+		response::IntoResponse,
+		routing::post,
+	};
+	use common::{
+		axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder,
+		monitoring::server::default_pair,
+	};
+	use serde_json::{Value, json};
+	use tokio::net::TcpListener;
+
+	use super::*;
+	use crate::metrics::metrics::setup_metrics;
+
+	/// Status and extra header of the next replies, 200 once they run out.
+	type Replies = Arc<Mutex<VecDeque<(StatusCode, Option<(&'static str, &'static str)>)>>>;
+	type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+
+	/// Incoming-webhook stand-in, records the path and body of every POST.
+	async fn start_webhooks(replies: Replies) -> (String, Requests) {
+		let requests: Requests = Arc::default();
+		let app = Router::new()
+			.route(
+				"/hooks/{key}",
+				post(
+					|State((replies, requests)): State<(Replies, Requests)>,
+					 uri: axum::http::Uri,
+					 Json(body): Json<Value>| async move {
+						requests
+							.lock()
+							.unwrap()
+							.push((uri.path().to_string(), body));
+						let (status, header) = replies
+							.lock()
+							.unwrap()
+							.pop_front()
+							.unwrap_or((StatusCode::OK, None));
+						let mut response = (status, "ok").into_response();
+						if let Some((name, value)) = header {
+							response.headers_mut().insert(
+								HeaderName::from_static(name),
+								HeaderValue::from_static(value),
+							);
+						}
+						response
+					},
+				),
+			)
+			.with_state((replies, requests.clone()));
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+		tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+		(url, requests)
+	}
+
+	fn handler(url: &str, format: ChatFormat) -> (ChatHandlerImpl, Arc<PrometheusRecorder>) {
+		let (_, recorder) = default_pair();
+		let handler = ChatHandlerImpl::new(
+			ChatOptions {
+				format,
+				webhooks: HashMap::from([
+					("ops".to_string(), format!("{}/ops", url)),
+					("billing".to_string(), format!("{}/billing", url)),
+				]),
+				identity: Identity {
+					username: Some("Scheduler".to_string()),
+					icon_url: None,
+				},
+				connect_timeout: Duration::from_secs(1),
+				timeout: Duration::from_secs(1),
+				max_retry_after: Duration::from_secs(2),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(setup_metrics(recorder.clone())),
+			}),
+		)
+		.unwrap();
+		(handler, recorder)
+	}
+
+	fn notification(recipient: &str, content: &str) -> String {
+		json!({
+			"_id": "n-1",
+			"content": content,
+			"channel": "chat",
+			"recipient": {"id": recipient, "timezone_offset": "+00:00"},
+			"scheduledTime": "2026-10-19T08:00:00Z",
+			"priority": "critical",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn posts_slack_blocks_to_the_recipient_webhook() {
+		let (url, requests) = start_webhooks(Replies::default()).await;
+		let (handler, _) = handler(&url, ChatFormat::Slack);
+
+		assert_eq!(
+			handler
+				.handle_message(&notification("billing", "Disk <80%> & rising"))
+				.await,
+			Ok(())
+		);
+
+		let requests = requests.lock().unwrap();
+		assert_eq!(requests[0].0, "/hooks/billing");
+		assert_eq!(
+			requests[0].1,
+			json!({
+				"text": "Disk &lt;80%&gt; &amp; rising",
+				"username": "Scheduler",
+				"blocks": [
+					{"type": "section", "text": {"type": "mrkdwn", "text": "Disk &lt;80%&gt; &amp; rising"}},
+					{"type": "context", "elements": [{
+						"type": "mrkdwn",
+						"text": "*Priority:* critical  ·  *Scheduled:* 2026-10-19T08:00:00Z",
+					}]},
+				],
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn posts_mattermost_attachments() {
+		let (url, requests) = start_webhooks(Replies::default()).await;
+		let (handler, _) = handler(&url, ChatFormat::Mattermost);
+
+		assert_eq!(
+			handler
+				.handle_message(&notification("ops", "Replica lag over 30s"))
+				.await,
+			Ok(())
+		);
+
+		let requests = requests.lock().unwrap();
+		assert_eq!(requests[0].0, "/hooks/ops");
+		assert_eq!(
+			requests[0].1,
+			json!({
+				"username": "Scheduler",
+				"attachments": [{
+					"fallback": "Replica lag over 30s",
+					"color": "#d32f2f",
+					"text": "Replica lag over 30s",
+					"fields": [
+						{"short": true, "title": "Priority", "value": "critical"},
+						{"short": true, "title": "Scheduled", "value": "2026-10-19T08:00:00Z"},
+					],
+					"footer": "Notification n-1",
+				}],
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn waits_out_short_rate_limits() {
+		let replies = Replies::new(Mutex::new(VecDeque::from([(
+			StatusCode::TOO_MANY_REQUESTS,
+			Some(("retry-after", "1")),
+		)])));
+		let (url, requests) = start_webhooks(replies).await;
+		let (handler, recorder) = handler(&url, ChatFormat::Slack);
+
+		let started = Instant::now();
+		assert_eq!(
+			handler.handle_message(&notification("ops", "hi")).await,
+			Ok(())
+		);
+		assert!(started.elapsed() >= Duration::from_secs(1));
+		assert_eq!(requests.lock().unwrap().len(), 2);
+		let rendered = recorder.handle().render();
+		assert!(rendered.contains("chat_consumer_rate_limited 1"));
+		assert!(rendered.contains("chat_consumer_consumed_messages 1"));
+	}
+
+	#[tokio::test]
+	async fn long_rate_limits_and_rejections() {
+		let replies = Replies::new(Mutex::new(VecDeque::from([
+			(
+				StatusCode::TOO_MANY_REQUESTS,
+				Some(("x-ratelimit-reset", "60")),
+			),
+			(StatusCode::NOT_FOUND, None),
+			(StatusCode::BAD_GATEWAY, None),
+		])));
+		let (url, requests) = start_webhooks(replies).await;
+		let (handler, _) = handler(&url, ChatFormat::Mattermost);
+
+		for _ in 0..2 {
+			// The webhook stays blocked, other recipients are not.
+			match handler.handle_message(&notification("ops", "hi")).await {
+				Err(DeliveryError::RetryAfter(delay, _)) => {
+					assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60))
+				}
+				result => panic!("{:?}", result),
+			}
+		}
+		assert_eq!(requests.lock().unwrap().len(), 1);
+
+		assert!(matches!(
+			handler.handle_message(&notification("billing", "hi")).await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert!(matches!(
+			handler.handle_message(&notification("billing", "hi")).await,
+			Err(DeliveryError::Transient(_))
+		));
+		assert!(matches!(
+			handler.handle_message(&notification("sales", "hi")).await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert_eq!(requests.lock().unwrap().len(), 3);
+	}
+
+	#[test]
+	fn rate_limits_prefer_retry_after() {
+		let headers = |pairs: &[(&'static str, &'static str)]| {
+			let mut headers = HeaderMap::new();
+			for (name, value) in pairs {
+				headers.insert(*name, HeaderValue::from_static(value));
+			}
+			headers
+		};
+
+		assert_eq!(
+			rate_limit(&headers(&[
+				("retry-after", "5"),
+				("x-ratelimit-reset", "60")
+			])),
+			Duration::from_secs(5)
+		);
+		assert_eq!(
+			rate_limit(&headers(&[("x-ratelimit-reset", "60")])),
+			Duration::from_secs(60)
+		);
+		assert_eq!(rate_limit(&headers(&[])), DEFAULT_RETRY_AFTER);
+	}
+}
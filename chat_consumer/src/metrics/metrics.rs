@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use common::{
+	axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder,
+	monitoring::metrics::Metrics,
+};
+
+pub const CHAT_CONSUMER_CONSUMED_MESSAGES: &str = "chat_consumer_consumed_messages";
+/// 429 replies from the chat platform.
+pub const CHAT_CONSUMER_RATE_LIMITED: &str = "chat_consumer_rate_limited";
+
+pub fn setup_metrics(prometheus_recorder: Arc<PrometheusRecorder>) -> Metrics {
+	let metrics = Metrics::new(prometheus_recorder);
+	for counter in [CHAT_CONSUMER_CONSUMED_MESSAGES, CHAT_CONSUMER_RATE_LIMITED] {
+		metrics.register_counter(counter).unwrap();
+	}
+
+	metrics
+}
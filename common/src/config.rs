@@ -0,0 +1,341 @@
+use std::{env, fmt, fs};
+
+use serde::{Deserialize, de::DeserializeOwned};
+use toml::{Table, Value};
+
+/// Path of the TOML file to load, unset means defaults plus env overrides.
+pub const CONFIG_FILE_VAR: &str = "CONFIG_FILE";
+/// Env vars starting with this prefix override file values, sections are
+/// separated by a double underscore: `APP__SCHEDULER__INTERVAL_MS=500`.
+pub const ENV_PREFIX: &str = "APP__";
+
+#[derive(Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid configuration: {}", self.0)
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+pub trait Validate {
+	fn validate(&self) -> Result<(), ConfigError>;
+}
+
+/// Loads the file named by [`CONFIG_FILE_VAR`], applies the [`ENV_PREFIX`]
+/// overrides from the process environment and validates the result.
+pub fn load<T: DeserializeOwned + Validate>() -> Result<T, ConfigError> {
+	let path = env::var(CONFIG_FILE_VAR).ok();
+	load_from(path.as_deref(), env::vars())
+}
+
+pub fn load_from<T: DeserializeOwned + Validate>(
+	path: Option<&str>,
+	vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<T, ConfigError> {
+	let mut table = match path {
+		Some(path) => {
+			let raw = fs::read_to_string(path)
+				.map_err(|e| ConfigError(format!("failed to read {}: {}", path, e)))?;
+			raw.parse::<Table>()
+				.map_err(|e| ConfigError(format!("failed to parse {}: {}", path, e)))?
+		}
+		None => Table::new(),
+	};
+
+	let mut overrides = Vec::new();
+	for (name, raw) in vars {
+		if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+			let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+			override_value(&mut table, &path, parse_env_value(&raw))
+				.map_err(|e| ConfigError(format!("{}: {}", name, e)))?;
+			overrides.push((path, raw));
+		}
+	}
+
+	let config: T = loop {
+		match serde_path_to_error::deserialize(Value::Table(table.clone())) {
+			Ok(config) => break config,
+			Err(e) => {
+				// An env value that parsed as a number or boolean is kept as
+				// the raw string where a string is expected, so a password of
+				// digits does not need quoting.
+				let key = e.path().to_string();
+				let expects_string = e.inner().message().ends_with("expected a string");
+				match overrides.iter().position(|(path, _)| path.join(".") == key) {
+					Some(i) if expects_string => {
+						let (path, raw) = overrides.swap_remove(i);
+						override_value(&mut table, &path, Value::String(raw))
+							.map_err(ConfigError)?;
+					}
+					_ => return Err(ConfigError(format!("{}: {}", key, e.inner().message()))),
+				}
+			}
+		}
+	};
+	config.validate()?;
+	Ok(config)
+}
+
+/// Env values are read as TOML scalars or arrays where they parse as one,
+/// anything else is taken as a plain string. Scalars are turned back into the
+/// raw string when the config expects one, see [`load_from`].
+fn parse_env_value(raw: &str) -> Value {
+	format!("value = {}", raw)
+		.parse::<Table>()
+		.ok()
+		.and_then(|mut table| table.remove("value"))
+		.unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn override_value(table: &mut Table, path: &[String], value: Value) -> Result<(), String> {
+	let (last, sections) = match path.split_last() {
+		Some((last, sections)) if !last.is_empty() => (last, sections),
+		_ => return Err("empty key".to_string()),
+	};
+	let mut current = table;
+	for section in sections {
+		current = match current
+			.entry(section.clone())
+			.or_insert_with(|| Value::Table(Table::new()))
+		{
+			Value::Table(table) => table,
+			_ => return Err(format!("{} is not a section", section)),
+		};
+	}
+	current.insert(last.clone(), value);
+	Ok(())
+}
+
+/// Logger settings, `RUST_LOG` still takes precedence when set.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+	pub level: String,
+}
+
+impl Default for LogConfig {
+	fn default() -> Self {
+		LogConfig {
+			level: "info".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+	pub host: String,
+	pub port: u16,
+}
+
+impl Default for MetricsConfig {
+	fn default() -> Self {
+		MetricsConfig {
+			host: "0.0.0.0".to_string(),
+			port: 9090,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NatsConfig {
+	pub url: String,
+}
+
+impl Default for NatsConfig {
+	fn default() -> Self {
+		NatsConfig {
+			url: "localhost:4222".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+	/// How long in-flight work may take to drain once a signal arrives.
+	pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+	fn default() -> Self {
+		ShutdownConfig { timeout_secs: 30 }
+	}
+}
+
+/// Fails with `<name> must be greater than 0` for zero values.
+pub fn require_positive<N: PartialOrd + Default>(name: &str, value: N) -> Result<(), ConfigError> {
+	if value > N::default() {
+		Ok(())
+	} else {
+		Err(ConfigError(format!("{} must be greater than 0", name)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Debug, Default, Deserialize)]
+	#[serde(default, deny_unknown_fields)]
+	struct TestConfig {
+		name:     String,
+		password: String,
+		server:   TestServer,
+	}
+
+	#[derive(Debug, Default, Deserialize)]
+	#[serde(default, deny_unknown_fields)]
+	struct TestServer {
+		port:    u16,
+		ratio:   f64,
+		enabled: bool,
+		hosts:   Vec<String>,
+		tls:     TestTls,
+	}
+
+	#[derive(Debug, Default, Deserialize)]
+	#[serde(default, deny_unknown_fields)]
+	struct TestTls {
+		cert: String,
+	}
+
+	impl Validate for TestConfig {
+		fn validate(&self) -> Result<(), ConfigError> {
+			require_positive("server.port", self.server.port)
+		}
+	}
+
+	fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+		vars.iter()
+			.map(|(name, value)| (name.to_string(), value.to_string()))
+			.collect()
+	}
+
+	fn load(file: Option<&str>, env: &[(&str, &str)]) -> Result<TestConfig, ConfigError> {
+		let path = file.map(|contents| {
+			let path = env::temp_dir().join(format!(
+				"config-test-{}-{}.toml",
+				std::process::id(),
+				rand::random::<u32>()
+			));
+			fs::write(&path, contents).unwrap();
+			path
+		});
+		let config = load_from(path.as_ref().and_then(|p| p.to_str()), vars(env));
+		if let Some(path) = path {
+			fs::remove_file(path).unwrap();
+		}
+		config
+	}
+
+	#[test]
+	fn env_values_are_parsed_as_toml_scalars_and_arrays() {
+		assert_eq!(parse_env_value("8080"), Value::Integer(8080));
+		assert_eq!(parse_env_value("0.5"), Value::Float(0.5));
+		assert_eq!(parse_env_value("true"), Value::Boolean(true));
+		assert_eq!(
+			parse_env_value("[\"a\", \"b\"]"),
+			Value::Array(vec![Value::from("a"), Value::from("b")])
+		);
+		assert_eq!(parse_env_value("localhost"), Value::from("localhost"));
+		assert_eq!(parse_env_value("\"8080\""), Value::from("8080"));
+		assert_eq!(parse_env_value(""), Value::from(""));
+	}
+
+	#[test]
+	fn overrides_create_missing_sections() {
+		let mut table = Table::new();
+		let path = |key: &str| key.split('.').map(str::to_string).collect::<Vec<_>>();
+
+		override_value(&mut table, &path("server.tls.cert"), Value::from("a.pem")).unwrap();
+		override_value(&mut table, &path("name"), Value::from("api")).unwrap();
+
+		assert_eq!(table["server"]["tls"]["cert"].as_str(), Some("a.pem"));
+		assert_eq!(table["name"].as_str(), Some("api"));
+		assert_eq!(
+			override_value(&mut table, &path("name.first"), Value::from("x")),
+			Err("name is not a section".to_string())
+		);
+		assert_eq!(
+			override_value(&mut table, &path("server."), Value::from("x")),
+			Err("empty key".to_string())
+		);
+	}
+
+	#[test]
+	fn env_overrides_win_over_the_file() {
+		let config = load(
+			Some("name = \"file\"\n[server]\nport = 80\nhosts = [\"a\"]\n"),
+			&[
+				("APP__SERVER__PORT", "8080"),
+				("APP__SERVER__RATIO", "0.25"),
+				("APP__SERVER__ENABLED", "true"),
+				("APP__SERVER__TLS__CERT", "/etc/cert.pem"),
+				("OTHER__NAME", "ignored"),
+			],
+		)
+		.unwrap();
+
+		assert_eq!(config.name, "file");
+		assert_eq!(config.server.port, 8080);
+		assert_eq!(config.server.ratio, 0.25);
+		assert!(config.server.enabled);
+		assert_eq!(config.server.hosts, vec!["a"]);
+		assert_eq!(config.server.tls.cert, "/etc/cert.pem");
+	}
+
+	#[test]
+	fn scalar_looking_env_values_stay_strings_where_strings_are_expected() {
+		let config = load(
+			None,
+			&[
+				("APP__PASSWORD", "0123456"),
+				("APP__NAME", "true"),
+				("APP__SERVER__PORT", "8080"),
+				("APP__SERVER__TLS__CERT", "1.50"),
+			],
+		)
+		.unwrap();
+
+		assert_eq!(config.password, "0123456");
+		assert_eq!(config.name, "true");
+		assert_eq!(config.server.port, 8080);
+		assert_eq!(config.server.tls.cert, "1.50");
+	}
+
+	#[test]
+	fn unknown_keys_and_bad_values_name_their_path() {
+		let error = load(None, &[("APP__SERVER__PORTS", "80")]).err().unwrap();
+		assert!(
+			error.0.starts_with("server.ports: unknown field `ports`"),
+			"{}",
+			error
+		);
+
+		let error = load(None, &[("APP__SERVER__PORT", "http")]).err().unwrap();
+		assert!(
+			error.0.starts_with("server.port: invalid type"),
+			"{}",
+			error
+		);
+
+		let error = load(Some("[server\n"), &[]).err().unwrap();
+		assert!(error.0.starts_with("failed to parse"), "{}", error);
+
+		let error = load(Some("name = \"api\"\n"), &[("APP__NAME__FIRST", "x")])
+			.err()
+			.unwrap();
+		assert_eq!(error.0, "APP__NAME__FIRST: name is not a section");
+	}
+
+	#[test]
+	fn loaded_configs_are_validated() {
+		let error = load(None, &[]).err().unwrap();
+		assert_eq!(error.0, "server.port must be greater than 0");
+	}
+}
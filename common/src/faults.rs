@@ -0,0 +1,295 @@
+use std::{
+	sync::{Arc, RwLock},
+	time::Duration,
+};
+
+use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use serde::{Deserialize, Serialize};
+
+/// Part of the system a fault rule applies to.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Component {
+	#[serde(rename = "broker")]
+	Broker,
+	#[serde(rename = "repository")]
+	Repository,
+	#[serde(rename = "consumer")]
+	Consumer,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum FaultKind {
+	#[serde(rename = "error")]
+	Error,
+	#[serde(rename = "latency")]
+	Latency {
+		#[serde(rename = "delayMs")]
+		delay_ms: u64,
+	},
+	#[serde(rename = "nack")]
+	Nack,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FaultRule {
+	#[serde(rename = "component")]
+	pub component: Component,
+	/// Restricts the rule to one channel, `None` matches every channel.
+	#[serde(rename = "channel", default)]
+	pub channel:   Option<String>,
+	#[serde(rename = "kind")]
+	pub kind:      FaultKind,
+	/// Probability in the 0.0..=1.0 range that the fault fires.
+	#[serde(rename = "rate")]
+	pub rate:      f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FaultConfig {
+	#[serde(rename = "enabled", default)]
+	pub enabled: bool,
+	#[serde(rename = "rules", default)]
+	pub rules:   Vec<FaultRule>,
+	/// Serves `/admin/faults` to replace the rules at runtime. The route is
+	/// unauthenticated, so it is off unless set, and only read at startup.
+	#[serde(rename = "admin", default)]
+	pub admin:   bool,
+}
+
+impl FaultConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		for rule in &self.rules {
+			if !(0.0..=1.0).contains(&rule.rate) {
+				return Err(format!(
+					"fault rate must be between 0 and 1, got {}",
+					rule.rate
+				));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InjectedFault {
+	Error(String),
+	Nack,
+}
+
+/// Chaos testing switchboard. Disabled unless explicitly configured, in which
+/// case every call site asks it whether to misbehave.
+#[derive(Default)]
+pub struct FaultInjector {
+	config: RwLock<FaultConfig>,
+}
+
+impl FaultInjector {
+	pub fn new(config: FaultConfig) -> Result<Self, String> {
+		config.validate()?;
+		Ok(FaultInjector {
+			config: RwLock::new(config),
+		})
+	}
+
+	pub fn config(&self) -> FaultConfig {
+		self.config.read().unwrap().clone()
+	}
+
+	/// Replaces the rules, `admin` keeps its startup value.
+	pub fn set_config(&self, config: FaultConfig) -> Result<(), String> {
+		config.validate()?;
+		let mut current = self.config.write().unwrap();
+		*current = FaultConfig {
+			admin: current.admin,
+			..config
+		};
+		Ok(())
+	}
+
+	/// Applies every matching rule. Latency faults are served in place, the
+	/// first error or nack that fires is returned to the caller.
+	pub async fn inject(
+		&self,
+		component: Component,
+		channel: Option<&str>,
+	) -> Option<InjectedFault> {
+		let rules: Vec<FaultRule> = {
+			let config = self.config.read().unwrap();
+			if !config.enabled {
+				return None;
+			}
+			config
+				.rules
+				.iter()
+				.filter(|rule| rule.component == component)
+				.filter(|rule| match (&rule.channel, channel) {
+					(None, _) => true,
+					(Some(expected), Some(actual)) => expected == actual,
+					(Some(_), None) => false,
+				})
+				.cloned()
+				.collect()
+		};
+
+		for rule in rules {
+			if rand::random::<f64>() >= rule.rate {
+				continue;
+			}
+			match rule.kind {
+				FaultKind::Latency { delay_ms } => {
+					tokio::time::sleep(Duration::from_millis(delay_ms)).await
+				}
+				FaultKind::Error => {
+					return Some(InjectedFault::Error(format!(
+						"Injected {:?} fault",
+						component
+					)));
+				}
+				FaultKind::Nack => return Some(InjectedFault::Nack),
+			}
+		}
+		None
+	}
+}
+
+/// Admin routes to inspect and replace the fault configuration at runtime,
+/// empty unless `admin` is configured.
+pub fn routes(injector: Arc<FaultInjector>) -> Router {
+	if !injector.config().admin {
+		return Router::new();
+	}
+	Router::new()
+		.route("/admin/faults", get(get_config).put(put_config))
+		.with_state(injector)
+}
+
+async fn get_config(State(injector): State<Arc<FaultInjector>>) -> impl IntoResponse {
+	Json(injector.config())
+}
+
+async fn put_config(
+	State(injector): State<Arc<FaultInjector>>,
+	Json(config): Json<FaultConfig>,
+) -> impl IntoResponse {
+	match injector.set_config(config) {
+		Ok(_) => (StatusCode::OK, Json(injector.config())).into_response(),
+		Err(e) => (
+			StatusCode::BAD_REQUEST,
+			Json(serde_json::json!({ "error": e })),
+		)
+			.into_response(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{body::Body, http::Request};
+	use tower::ServiceExt;
+
+	use super::*;
+
+	fn rule(component: Component, channel: Option<&str>, rate: f64) -> FaultRule {
+		FaultRule {
+			component,
+			channel: channel.map(str::to_string),
+			kind: FaultKind::Nack,
+			rate,
+		}
+	}
+
+	fn injector(rules: Vec<FaultRule>) -> FaultInjector {
+		FaultInjector::new(FaultConfig {
+			enabled: true,
+			rules,
+			admin: false,
+		})
+		.unwrap()
+	}
+
+	#[tokio::test]
+	async fn rules_only_fire_for_their_component_and_channel() {
+		let injector = injector(vec![rule(Component::Consumer, Some("push"), 1.0)]);
+
+		assert_eq!(
+			injector.inject(Component::Consumer, Some("push")).await,
+			Some(InjectedFault::Nack)
+		);
+		assert_eq!(
+			injector.inject(Component::Consumer, Some("email")).await,
+			None
+		);
+		assert_eq!(injector.inject(Component::Consumer, None).await, None);
+		assert_eq!(injector.inject(Component::Broker, Some("push")).await, None);
+
+		let injector = self::injector(vec![rule(Component::Broker, None, 1.0)]);
+		assert_eq!(
+			injector.inject(Component::Broker, Some("sms")).await,
+			Some(InjectedFault::Nack)
+		);
+		assert_eq!(
+			injector.inject(Component::Broker, None).await,
+			Some(InjectedFault::Nack)
+		);
+	}
+
+	#[tokio::test]
+	async fn rules_fire_at_their_rate_and_only_when_enabled() {
+		let never = injector(vec![rule(Component::Repository, None, 0.0)]);
+		for _ in 0..100 {
+			assert_eq!(never.inject(Component::Repository, None).await, None);
+		}
+
+		let mut config = injector(vec![rule(Component::Repository, None, 1.0)]).config();
+		config.enabled = false;
+		let disabled = FaultInjector::new(config).unwrap();
+		assert_eq!(disabled.inject(Component::Repository, None).await, None);
+	}
+
+	#[test]
+	fn rates_outside_zero_to_one_are_rejected() {
+		let injector = injector(Vec::new());
+
+		assert!(
+			FaultInjector::new(FaultConfig {
+				rules: vec![rule(Component::Broker, None, 1.5)],
+				..Default::default()
+			})
+			.is_err()
+		);
+		assert!(
+			injector
+				.set_config(FaultConfig {
+					rules: vec![rule(Component::Broker, None, -0.1)],
+					..Default::default()
+				})
+				.is_err()
+		);
+	}
+
+	#[tokio::test]
+	async fn admin_routes_are_only_served_when_configured() {
+		let request = || {
+			Request::builder()
+				.uri("/admin/faults")
+				.body(Body::empty())
+				.unwrap()
+		};
+
+		let injector = Arc::new(FaultInjector::default());
+		let response = routes(injector).oneshot(request()).await.unwrap();
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+		let injector = Arc::new(
+			FaultInjector::new(FaultConfig {
+				admin: true,
+				..Default::default()
+			})
+			.unwrap(),
+		);
+		injector.set_config(FaultConfig::default()).unwrap();
+		assert!(injector.config().admin);
+		let response = routes(injector).oneshot(request()).await.unwrap();
+		assert_eq!(response.status(), StatusCode::OK);
+	}
+}
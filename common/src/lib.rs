@@ -1,4 +1,8 @@
+pub mod config;
+pub mod faults;
 pub mod monitoring;
+pub mod notification;
+pub mod shutdown;
 pub use axum;
 pub use axum_prometheus;
 pub use tokio;
@@ -0,0 +1,127 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_nats::connection::State;
+use async_trait::async_trait;
+use axum::{Json, Router, extract::State as AxumState, http::StatusCode, routing::get};
+use futures::future::join_all;
+use serde::Serialize;
+
+use crate::shutdown::Shutdown;
+
+/// A dependency the binary cannot serve traffic without.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+	fn name(&self) -> String;
+	async fn check(&self) -> Result<(), String>;
+}
+
+/// Checks that take longer than this count as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Health {
+	checks:   Vec<Arc<dyn HealthCheck>>,
+	shutdown: Shutdown,
+}
+
+#[derive(Serialize)]
+struct Readiness {
+	#[serde(rename = "status")]
+	status: &'static str,
+	#[serde(rename = "checks")]
+	checks: BTreeMap<String, String>,
+}
+
+impl Health {
+	/// Readiness turns false as soon as `shutdown` fires, so traffic is
+	/// moved away while the binary drains.
+	pub fn new(checks: Vec<Arc<dyn HealthCheck>>, shutdown: Shutdown) -> Self {
+		Health { checks, shutdown }
+	}
+
+	async fn readiness(&self) -> (bool, Readiness) {
+		if self.shutdown.is_triggered() {
+			return (
+				false,
+				Readiness {
+					status: "draining",
+					checks: BTreeMap::new(),
+				},
+			);
+		}
+
+		let results = join_all(self.checks.iter().map(|check| async move {
+			let result = match tokio::time::timeout(CHECK_TIMEOUT, check.check()).await {
+				Ok(result) => result,
+				Err(_) => Err(format!("timed out after {:?}", CHECK_TIMEOUT)),
+			};
+			(check.name(), result)
+		}))
+		.await;
+
+		let ready = results.iter().all(|(_, result)| result.is_ok());
+		let checks = results
+			.into_iter()
+			.map(|(name, result)| (name, result.err().unwrap_or_else(|| "ok".to_string())))
+			.collect();
+		let status = if ready { "ok" } else { "unavailable" };
+		(ready, Readiness { status, checks })
+	}
+}
+
+/// `/healthz` reports that the process is up, `/readyz` that every check
+/// passes and the binary is not shutting down.
+pub fn routes(health: Arc<Health>) -> Router {
+	Router::new()
+		.route("/healthz", get(|| async { "ok" }))
+		.route("/readyz", get(readyz))
+		.with_state(health)
+}
+
+async fn readyz(AxumState(health): AxumState<Arc<Health>>) -> (StatusCode, Json<Readiness>) {
+	let (ready, readiness) = health.readiness().await;
+	let status = if ready {
+		StatusCode::OK
+	} else {
+		StatusCode::SERVICE_UNAVAILABLE
+	};
+	(status, Json(readiness))
+}
+
+/// Passes while the client is connected and every stream can be looked up.
+pub struct NatsHealthCheck {
+	client:  async_nats::Client,
+	js:      async_nats::jetstream::Context,
+	streams: Vec<String>,
+}
+
+impl NatsHealthCheck {
+	pub fn new(client: async_nats::Client, streams: Vec<String>) -> Self {
+		let js = async_nats::jetstream::new(client.clone());
+		NatsHealthCheck {
+			client,
+			js,
+			streams,
+		}
+	}
+}
+
+#[async_trait]
+impl HealthCheck for NatsHealthCheck {
+	fn name(&self) -> String {
+		"nats".to_string()
+	}
+
+	async fn check(&self) -> Result<(), String> {
+		let state = self.client.connection_state();
+		if state != State::Connected {
+			return Err(format!("connection is {}", state));
+		}
+		for stream in &self.streams {
+			self.js
+				.get_stream(stream)
+				.await
+				.map_err(|e| format!("stream {} unavailable: {}", stream, e))?;
+		}
+		Ok(())
+	}
+}
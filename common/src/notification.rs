@@ -0,0 +1,319 @@
+//! The notification as stored by the API and published to the channel
+//! streams, consumers decode messages into the same types.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A stored channel, status or priority name none of the variants have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownValue {
+	kind:  &'static str,
+	value: String,
+}
+
+impl UnknownValue {
+	fn new(kind: &'static str, value: &str) -> Self {
+		UnknownValue {
+			kind,
+			value: value.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for UnknownValue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "unknown {} {:?}", self.kind, self.value)
+	}
+}
+
+impl std::error::Error for UnknownValue {}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Channel {
+	#[serde(rename = "push")]
+	Push,
+	#[serde(rename = "email")]
+	Email,
+	/// Browser notifications, delivered to every Web Push subscription of
+	/// the recipient.
+	#[serde(rename = "webpush")]
+	WebPush,
+	/// Signed HTTP callbacks to the URL configured for the recipient.
+	#[serde(rename = "webhook")]
+	Webhook,
+	/// Text messages to the phone number in the recipient id.
+	#[serde(rename = "sms")]
+	Sms,
+	/// Published to the MQTT topic of the device in the recipient id.
+	#[serde(rename = "mqtt")]
+	Mqtt,
+	/// Slack- or Mattermost-compatible incoming webhook of the recipient.
+	#[serde(rename = "chat")]
+	Chat,
+	/// Kept in the recipient's inbox in the app, nothing is published.
+	#[serde(rename = "inapp")]
+	InApp,
+}
+impl FromStr for Channel {
+	type Err = UnknownValue;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"push" => Ok(Channel::Push),
+			"email" => Ok(Channel::Email),
+			"webpush" => Ok(Channel::WebPush),
+			"webhook" => Ok(Channel::Webhook),
+			"sms" => Ok(Channel::Sms),
+			"mqtt" => Ok(Channel::Mqtt),
+			"chat" => Ok(Channel::Chat),
+			"inapp" => Ok(Channel::InApp),
+			_ => Err(UnknownValue::new("channel", s)),
+		}
+	}
+}
+impl From<Channel> for String {
+	fn from(val: Channel) -> Self {
+		match val {
+			Channel::Push => "push".to_string(),
+			Channel::Email => "email".to_string(),
+			Channel::WebPush => "webpush".to_string(),
+			Channel::Webhook => "webhook".to_string(),
+			Channel::Sms => "sms".to_string(),
+			Channel::Mqtt => "mqtt".to_string(),
+			Channel::Chat => "chat".to_string(),
+			Channel::InApp => "inapp".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Status {
+	#[serde(rename = "pending")]
+	Pending,
+	#[serde(rename = "sent")]
+	Sent,
+	#[serde(rename = "failed")]
+	Failed,
+	#[serde(rename = "cancelled")]
+	Cancelled,
+	#[serde(rename = "processing")]
+	Processing,
+	/// Handed over to the outbox, waiting for the relay to publish it.
+	#[serde(rename = "queued")]
+	Queued,
+}
+
+impl FromStr for Status {
+	type Err = UnknownValue;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"pending" => Ok(Status::Pending),
+			"sent" => Ok(Status::Sent),
+			"failed" => Ok(Status::Failed),
+			"cancelled" => Ok(Status::Cancelled),
+			"processing" => Ok(Status::Processing),
+			"queued" => Ok(Status::Queued),
+			_ => Err(UnknownValue::new("status", s)),
+		}
+	}
+}
+
+impl From<Status> for String {
+	fn from(val: Status) -> Self {
+		match val {
+			Status::Pending => "pending".to_string(),
+			Status::Sent => "sent".to_string(),
+			Status::Failed => "failed".to_string(),
+			Status::Cancelled => "cancelled".to_string(),
+			Status::Processing => "processing".to_string(),
+			Status::Queued => "queued".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Priority {
+	#[serde(rename = "critical")]
+	Critical,
+	#[serde(rename = "high")]
+	High,
+	#[serde(rename = "normal")]
+	Normal,
+	#[serde(rename = "low")]
+	Low,
+}
+
+impl Priority {
+	/// All levels, most urgent first.
+	pub const ALL: [Priority; 4] = [
+		Priority::Critical,
+		Priority::High,
+		Priority::Normal,
+		Priority::Low,
+	];
+
+	/// Position in [`Priority::ALL`], 0 being the most urgent.
+	pub fn rank(&self) -> usize {
+		match self {
+			Priority::Critical => 0,
+			Priority::High => 1,
+			Priority::Normal => 2,
+			Priority::Low => 3,
+		}
+	}
+}
+
+impl FromStr for Priority {
+	type Err = UnknownValue;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"critical" => Ok(Priority::Critical),
+			"high" => Ok(Priority::High),
+			"normal" => Ok(Priority::Normal),
+			"low" => Ok(Priority::Low),
+			_ => Err(UnknownValue::new("priority", s)),
+		}
+	}
+}
+
+impl From<Priority> for String {
+	fn from(val: Priority) -> Self {
+		match val {
+			Priority::Critical => "critical".to_string(),
+			Priority::High => "high".to_string(),
+			Priority::Normal => "normal".to_string(),
+			Priority::Low => "low".to_string(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Recipient {
+	#[serde(rename = "id")]
+	pub id:              String,
+	#[serde(rename = "timezone_offset")]
+	pub timezone_offset: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+	#[serde(rename = "_id")]
+	pub id:               Option<String>,
+	#[serde(rename = "content")]
+	pub content:          String,
+	#[serde(rename = "channel")]
+	pub channel:          Channel, // "push" or "email"
+	#[serde(rename = "recipient")]
+	pub recipient:        Recipient, // email or device token, but for simplicity, using String
+	#[serde(rename = "scheduledTime")]
+	pub scheduled_time:   DateTime<Utc>,
+	#[serde(rename = "priority")]
+	pub priority:         Priority,
+	/// Pending until claimed, then processing, queued, and sent or failed,
+	/// unless cancelled first.
+	#[serde(rename = "status")]
+	pub status:           Status,
+	#[serde(rename = "attempts", default)]
+	pub attempts:         u32,
+	/// Times the notification was replayed from the dead letters.
+	#[serde(rename = "replays", default)]
+	pub replays:          u32,
+	#[serde(
+		rename = "nextAttemptAt",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub next_attempt_at:  Option<DateTime<Utc>>,
+	#[serde(rename = "lastError", default, skip_serializing_if = "Option::is_none")]
+	pub last_error:       Option<String>,
+	#[serde(rename = "failedAt", default, skip_serializing_if = "Option::is_none")]
+	pub failed_at:        Option<DateTime<Utc>>,
+	#[serde(
+		rename = "failureReason",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub failure_reason:   Option<String>,
+	/// Replica currently processing the notification.
+	#[serde(rename = "owner", default, skip_serializing_if = "Option::is_none")]
+	pub owner:            Option<String>,
+	#[serde(
+		rename = "leaseExpiresAt",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub lease_expires_at: Option<DateTime<Utc>>,
+	/// When the recipient first read the notification in their inbox.
+	#[serde(rename = "readAt", default, skip_serializing_if = "Option::is_none")]
+	pub read_at:          Option<DateTime<Utc>>,
+	#[serde(
+		rename = "archivedAt",
+		default,
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub archived_at:      Option<DateTime<Utc>>,
+}
+
+/// Subject a notification for `recipient` is published to on the stream of
+/// `channel`. The recipient is a single token, so ids like email addresses
+/// cannot spill into further tokens. Consumers read the recipient from the
+/// payload, never back from the subject.
+pub fn subject(channel: &str, recipient: &str) -> String {
+	format!("notifications_{}.{}", channel, subject_token(recipient))
+}
+
+/// Percent-encodes what NATS reads into a subject token: the `.` separator,
+/// the `*` and `>` wildcards, whitespace and control characters, and `%`
+/// itself so distinct ids keep distinct tokens.
+pub fn subject_token(value: &str) -> String {
+	let mut token = String::with_capacity(value.len());
+	for c in value.chars() {
+		if matches!(c, '.' | '*' | '>' | '%') || c.is_whitespace() || c.is_control() {
+			let mut bytes = [0; 4];
+			for byte in c.encode_utf8(&mut bytes).bytes() {
+				token.push_str(&format!("%{:02X}", byte));
+			}
+		} else {
+			token.push(c);
+		}
+	}
+	token
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn stored_names_parse_back() {
+		for priority in Priority::ALL {
+			let name: String = priority.clone().into();
+			assert_eq!(name.parse::<Priority>(), Ok(priority));
+		}
+		assert_eq!("inapp".parse::<Channel>(), Ok(Channel::InApp));
+		assert_eq!("queued".parse::<Status>(), Ok(Status::Queued));
+	}
+
+	#[test]
+	fn unknown_names_are_errors() {
+		let error = "urgent".parse::<Priority>().unwrap_err();
+		assert_eq!(error.to_string(), "unknown priority \"urgent\"");
+		assert!("fax".parse::<Channel>().is_err());
+		assert!("done".parse::<Status>().is_err());
+	}
+
+	#[test]
+	fn recipients_are_a_single_subject_token() {
+		assert_eq!(
+			subject("email", "first.last@example.com"),
+			"notifications_email.first%2Elast@example%2Ecom"
+		);
+		assert_eq!(subject_token("a*b >c%"), "a%2Ab%20%3Ec%25");
+		assert_eq!(subject_token("customer1"), "customer1");
+		assert_ne!(subject_token("a.b"), subject_token("a%2Eb"));
+	}
+}
@@ -0,0 +1,91 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+
+/// Cloneable shutdown flag. Every clone observes the same trigger.
+#[derive(Clone)]
+pub struct Shutdown {
+	sender:   Arc<watch::Sender<bool>>,
+	receiver: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Shutdown {
+	pub fn new() -> Self {
+		let (sender, receiver) = watch::channel(false);
+		Shutdown {
+			sender: Arc::new(sender),
+			receiver,
+		}
+	}
+
+	pub fn trigger(&self) {
+		self.sender.send_replace(true);
+	}
+
+	pub fn is_triggered(&self) -> bool {
+		*self.receiver.borrow()
+	}
+
+	/// Resolves once shutdown has been triggered.
+	/// The future does not borrow `self`, so it can be handed to
+	/// `axum::serve(..).with_graceful_shutdown`.
+	pub fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
+		let mut receiver = self.receiver.clone();
+		async move {
+			// The sender lives as long as any clone, so this only errors if
+			// every clone is gone, in which case nobody is left to wait anyway.
+			let _ = receiver.wait_for(|triggered| *triggered).await;
+		}
+	}
+
+	/// Triggers shutdown on SIGTERM or Ctrl+C.
+	pub fn listen_for_signals(&self) {
+		let shutdown = self.clone();
+		tokio::spawn(async move {
+			wait_for_signal().await;
+			log::info!("Shutdown signal received, draining");
+			shutdown.trigger();
+		});
+	}
+
+	/// Drives `future` to completion, but gives up `deadline` after shutdown
+	/// was triggered. Returns `None` if the deadline was hit.
+	pub async fn run_with_deadline<F: Future>(
+		&self,
+		deadline: Duration,
+		future: F,
+	) -> Option<F::Output> {
+		tokio::select! {
+			output = future => Some(output),
+			_ = async {
+				self.wait().await;
+				tokio::time::sleep(deadline).await;
+			} => {
+				log::warn!("Shutdown deadline of {:?} exceeded, exiting", deadline);
+				None
+			}
+		}
+	}
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+	use tokio::signal::unix::{SignalKind, signal};
+
+	let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+	tokio::select! {
+		_ = terminate.recv() => {},
+		_ = tokio::signal::ctrl_c() => {},
+	}
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+	let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,264 @@
+use std::{fmt, marker::PhantomData, time::Duration};
+
+use common::{
+	config::{ConfigError, require_positive},
+	notification,
+};
+use serde::{
+	Deserialize,
+	Deserializer,
+	de::{self, IntoDeserializer, SeqAccess, Visitor, value::SeqAccessDeserializer},
+};
+
+/// The `[consumer]` section shared by every consumer binary, `H` being the
+/// binary's handler names.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(
+	default,
+	deny_unknown_fields,
+	bound(deserialize = "H: Deserialize<'de> + Default")
+)]
+pub struct ConsumerConfig<H> {
+	pub recipient_id:   String,
+	/// Consumes the messages of every recipient of the channel, like the
+	/// addresses of an email consumer. `recipient_id` then only names the
+	/// consumer. On by default, as every channel's recipients are addresses,
+	/// numbers or devices one consumer delivers to.
+	pub all_recipients: bool,
+	/// One handler or a list of them, each consumes the stream of its own
+	/// channel, e.g. `handler = ["fcm", "webpush"]`.
+	#[serde(deserialize_with = "one_or_many")]
+	pub handler:        Vec<H>,
+	pub ack_wait_secs:  u64,
+	/// Deliveries per message before JetStream gives up on it, -1 for no
+	/// limit.
+	pub max_deliver:    i64,
+	/// Redelivery delays after a failed delivery, the last one repeats. The
+	/// first one also replaces `ack_wait_secs`, so it must not be shorter.
+	pub backoff_secs:   Vec<u64>,
+	/// Messages handled at the same time, per handler.
+	pub concurrency:    usize,
+	/// Messages requested from JetStream per pull, at most `concurrency` so
+	/// none waits for a worker unacked.
+	pub batch_size:     usize,
+}
+
+impl<H: Default> ConsumerConfig<H> {
+	/// Defaults consuming every recipient as `recipient_id` with the default
+	/// handler.
+	pub fn new(recipient_id: &str) -> Self {
+		ConsumerConfig {
+			recipient_id:   recipient_id.to_string(),
+			all_recipients: true,
+			handler:        vec![H::default()],
+			ack_wait_secs:  60,
+			max_deliver:    -1,
+			backoff_secs:   Vec::new(),
+			concurrency:    10,
+			batch_size:     10,
+		}
+	}
+}
+
+impl<H: Default> Default for ConsumerConfig<H> {
+	fn default() -> Self {
+		ConsumerConfig::new("consumer")
+	}
+}
+
+impl<H: PartialEq> ConsumerConfig<H> {
+	/// Subject of the messages consumed from the stream of `channel`.
+	pub fn filter_subject(&self, channel: &str) -> String {
+		if self.all_recipients {
+			format!("notifications_{}.*", channel)
+		} else {
+			notification::subject(channel, &self.recipient_id)
+		}
+	}
+
+	pub fn ack_wait(&self) -> Duration {
+		Duration::from_secs(self.ack_wait_secs)
+	}
+
+	pub fn backoff(&self) -> Vec<Duration> {
+		self.backoff_secs
+			.iter()
+			.map(|secs| Duration::from_secs(*secs))
+			.collect()
+	}
+
+	/// Whether `handler` runs, so its own section needs checking.
+	pub fn uses(&self, handler: H) -> bool {
+		self.handler.contains(&handler)
+	}
+
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		if self.recipient_id.is_empty() {
+			return Err(ConfigError(
+				"consumer.recipient_id must not be empty".to_string(),
+			));
+		}
+		if self.handler.is_empty() {
+			return Err(ConfigError(
+				"consumer.handler must not be empty".to_string(),
+			));
+		}
+		if (1..self.handler.len()).any(|i| self.handler[..i].contains(&self.handler[i])) {
+			return Err(ConfigError(
+				"consumer.handler must not list a handler twice".to_string(),
+			));
+		}
+		require_positive("consumer.ack_wait_secs", self.ack_wait_secs)?;
+		require_positive("consumer.concurrency", self.concurrency)?;
+		require_positive("consumer.batch_size", self.batch_size)?;
+		if self.batch_size > self.concurrency {
+			return Err(ConfigError(
+				"consumer.batch_size must not exceed consumer.concurrency".to_string(),
+			));
+		}
+		self.validate_redelivery()
+	}
+
+	/// Checks the redelivery settings JetStream would reject or silently
+	/// change: a backoff replaces the ack wait with its first step, and needs
+	/// a delivery limit above its length.
+	fn validate_redelivery(&self) -> Result<(), ConfigError> {
+		if self.max_deliver == 0 || self.max_deliver < -1 {
+			return Err(ConfigError(
+				"consumer.max_deliver must be -1 or greater than 0".to_string(),
+			));
+		}
+		if self.backoff_secs.contains(&0) {
+			return Err(ConfigError(
+				"consumer.backoff_secs must be greater than 0".to_string(),
+			));
+		}
+		if self.max_deliver > 0 && self.max_deliver as usize <= self.backoff_secs.len() {
+			return Err(ConfigError(
+				"consumer.max_deliver must be greater than the number of consumer.backoff_secs"
+					.to_string(),
+			));
+		}
+		if self
+			.backoff_secs
+			.first()
+			.is_some_and(|first| *first < self.ack_wait_secs)
+		{
+			return Err(ConfigError(
+				"consumer.backoff_secs must not start below consumer.ack_wait_secs".to_string(),
+			));
+		}
+		Ok(())
+	}
+}
+
+/// Reads one handler or a list of them. Unlike an untagged enum, it keeps
+/// the error of a misspelt handler name.
+fn one_or_many<'de, D, H>(deserializer: D) -> Result<Vec<H>, D::Error>
+where
+	D: Deserializer<'de>,
+	H: Deserialize<'de>,
+{
+	struct OneOrMany<H>(PhantomData<H>);
+
+	impl<'de, H: Deserialize<'de>> Visitor<'de> for OneOrMany<H> {
+		type Value = Vec<H>;
+
+		fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			f.write_str("a handler or a list of handlers")
+		}
+
+		fn visit_str<E: de::Error>(self, handler: &str) -> Result<Self::Value, E> {
+			H::deserialize(handler.into_deserializer()).map(|handler| vec![handler])
+		}
+
+		fn visit_seq<A: SeqAccess<'de>>(self, handlers: A) -> Result<Self::Value, A::Error> {
+			Vec::deserialize(SeqAccessDeserializer::new(handlers))
+		}
+	}
+
+	deserializer.deserialize_any(OneOrMany(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn filters_one_recipient_or_all_of_them() {
+		let mut config = ConsumerConfig::<()>::new("first.last@example.com");
+		assert_eq!(config.filter_subject("email"), "notifications_email.*");
+
+		config.all_recipients = false;
+		assert_eq!(
+			config.filter_subject("email"),
+			"notifications_email.first%2Elast@example%2Ecom"
+		);
+	}
+
+	fn redelivery(
+		ack_wait_secs: u64,
+		max_deliver: i64,
+		backoff_secs: &[u64],
+	) -> ConsumerConfig<()> {
+		ConsumerConfig {
+			ack_wait_secs,
+			max_deliver,
+			backoff_secs: backoff_secs.to_vec(),
+			..ConsumerConfig::new("consumer")
+		}
+	}
+
+	#[test]
+	fn accepts_redelivery_jetstream_keeps() {
+		for config in [
+			redelivery(60, -1, &[]),
+			redelivery(60, 1, &[]),
+			redelivery(60, 4, &[60, 300, 900]),
+			redelivery(60, -1, &[120]),
+		] {
+			config.validate_redelivery().unwrap();
+		}
+	}
+
+	#[test]
+	fn rejects_redelivery_jetstream_would_refuse_or_change() {
+		for (config, error) in [
+			(
+				redelivery(60, 0, &[]),
+				"consumer.max_deliver must be -1 or greater than 0",
+			),
+			(
+				redelivery(60, -2, &[]),
+				"consumer.max_deliver must be -1 or greater than 0",
+			),
+			(
+				redelivery(60, -1, &[60, 0]),
+				"consumer.backoff_secs must be greater than 0",
+			),
+			(
+				redelivery(60, 3, &[60, 300, 900]),
+				"consumer.max_deliver must be greater than the number of consumer.backoff_secs",
+			),
+			(
+				redelivery(60, -1, &[30, 300]),
+				"consumer.backoff_secs must not start below consumer.ack_wait_secs",
+			),
+		] {
+			assert_eq!(config.validate_redelivery().unwrap_err().0, error);
+		}
+	}
+
+	#[test]
+	fn batches_fit_the_worker_pool() {
+		let mut config = ConsumerConfig::<()>::new("consumer");
+		config.batch_size = config.concurrency;
+		config.validate().unwrap();
+
+		config.batch_size = config.concurrency + 1;
+		assert_eq!(
+			config.validate().unwrap_err().0,
+			"consumer.batch_size must not exceed consumer.concurrency"
+		);
+	}
+}
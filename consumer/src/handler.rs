@@ -0,0 +1,117 @@
+use std::{fmt, time::Duration};
+
+use async_trait::async_trait;
+use axum::http::{HeaderMap, header::RETRY_AFTER};
+use common::notification::Notification;
+
+/// Why a message could not be delivered, decides whether it is redelivered.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeliveryError {
+	/// Worth another attempt later, after the consumer's backoff, e.g. a 5xx
+	/// reply or a lost connection.
+	Transient(String),
+	/// Another attempt would fail the same way, e.g. a 4xx reply.
+	Permanent(String),
+	/// Worth another attempt once the delay passed, e.g. a 429 that said
+	/// when to come back.
+	RetryAfter(Duration, String),
+}
+
+impl fmt::Display for DeliveryError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DeliveryError::Transient(e) => write!(f, "transient: {}", e),
+			DeliveryError::Permanent(e) => write!(f, "permanent: {}", e),
+			DeliveryError::RetryAfter(delay, e) => {
+				write!(f, "retry after {}s: {}", delay.as_secs(), e)
+			}
+		}
+	}
+}
+
+/// Delivers the messages of one channel. The message is acked on `Ok`,
+/// nacked or terminated depending on the [`DeliveryError`] otherwise.
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError>;
+}
+
+/// Delay of a `Retry-After` header given in seconds, HTTP dates are ignored.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+	headers
+		.get(RETRY_AFTER)?
+		.to_str()
+		.ok()?
+		.trim()
+		.parse()
+		.ok()
+		.map(Duration::from_secs)
+}
+
+/// Decodes a message as published by the API. Messages that do not match
+/// [`Notification`] would fail the same way on every attempt.
+pub fn parse_notification(message: &str) -> Result<Notification, DeliveryError> {
+	serde_json::from_str(message)
+		.map_err(|e| DeliveryError::Permanent(format!("invalid notification: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+	use common::notification::{Channel, Priority, Status};
+	use serde_json::Value;
+
+	use super::*;
+
+	/// A notification as the API publishes it, `api` checks its side against
+	/// the same file.
+	const PUBLISHED: &str = include_str!("../../common/testdata/notification.json");
+
+	#[test]
+	fn decodes_notifications_as_published() {
+		let notification = parse_notification(PUBLISHED).unwrap();
+
+		assert_eq!(notification.id.as_deref(), Some("6713a5c2e4b0a1f2c3d4e5f6"));
+		assert_eq!(notification.content, "Your order has shipped");
+		assert_eq!(notification.channel, Channel::Push);
+		assert_eq!(notification.recipient.id, "device-token-1");
+		assert_eq!(notification.recipient.timezone_offset, "+02:00");
+		assert_eq!(
+			notification.scheduled_time.to_rfc3339(),
+			"2026-10-19T08:00:00+00:00"
+		);
+		assert_eq!(notification.priority, Priority::High);
+		assert_eq!(notification.status, Status::Processing);
+	}
+
+	#[test]
+	fn messages_missing_required_fields_are_permanent() {
+		let mut published: Value = serde_json::from_str(PUBLISHED).unwrap();
+		for field in [
+			"content",
+			"channel",
+			"recipient",
+			"scheduledTime",
+			"priority",
+			"status",
+		] {
+			let mut message = published.clone();
+			message.as_object_mut().unwrap().remove(field);
+			assert!(
+				matches!(
+					parse_notification(&message.to_string()),
+					Err(DeliveryError::Permanent(_))
+				),
+				"{} is required",
+				field
+			);
+		}
+
+		// Unknown values are rejected, unknown fields ignored so the API can
+		// add optional ones.
+		published["channel"] = "carrier-pigeon".into();
+		assert!(parse_notification(&published.to_string()).is_err());
+		published["channel"] = "push".into();
+		published["trace"] = "abc".into();
+		assert!(parse_notification(&published.to_string()).is_ok());
+	}
+}
@@ -0,0 +1,14 @@
+//! What every consumer binary shares: its `[consumer]` config, NATS
+//! consumption, health, metrics and shutdown. The channel crates register
+//! their handlers with a [`registry::Handlers`] and config picks among them.
+//! Each crate's own binary hosts its channel, `consumer_host` registers every
+//! crate and runs the channels its config lists, and the api's local broker
+//! calls the same handlers in process. Adding a channel means a crate with
+//! its handlers and a `register` function, registered by the host.
+
+pub mod config;
+pub mod handler;
+pub mod nats;
+pub mod registry;
+pub mod runtime;
+pub mod std_out;
@@ -0,0 +1,416 @@
+use std::{fmt::Debug, sync::Arc};
+
+use async_nats::jetstream::{AckKind, Message};
+use common::{
+	faults::{Component, FaultInjector},
+	monitoring::health::NatsHealthCheck,
+	shutdown::Shutdown,
+};
+use futures::{Stream, StreamExt};
+use log::{error, info, warn};
+use tokio::{
+	sync::Semaphore,
+	task::JoinSet,
+	time::{Duration, Instant, interval_at},
+};
+
+use crate::handler::{DeliveryError, MessageHandler};
+
+// Define a NATS consumer that uses JetStream.
+pub struct NatsConsumer {
+	client:      async_nats::Client,
+	consumer:    async_nats::jetstream::consumer::PullConsumer,
+	channel:     String,
+	concurrency: usize,
+	batch_size:  usize,
+	worker:      Worker,
+}
+
+pub struct NatsConsumerOptions {
+	pub nats_url:       String,
+	pub ack_wait:       Duration,
+	/// Deliveries per message before JetStream gives up on it, -1 for no
+	/// limit.
+	pub max_deliver:    i64,
+	/// Redelivery delays after a nack without delay or a missed ack.
+	pub backoff:        Vec<Duration>,
+	/// Messages handled at the same time.
+	pub concurrency:    usize,
+	/// Messages requested from JetStream per pull.
+	pub batch_size:     usize,
+	/// Name of the JetStream consumer, shared by the replicas consuming the
+	/// same messages.
+	pub name:           String,
+	pub channel:        String,
+	pub filter_subject: String,
+	pub faults:         Arc<FaultInjector>,
+}
+
+/// What every worker needs to handle a message on its own.
+#[derive(Clone)]
+struct Worker {
+	channel:           String,
+	faults:            Arc<FaultInjector>,
+	/// How often a message still being handled is reported as in progress,
+	/// well within the ack wait so JetStream does not redeliver it.
+	progress_interval: Duration,
+}
+
+impl NatsConsumer {
+	/// Connects to NATS, creates a JetStream context, and subscribes to the
+	/// channel's stream.
+	pub async fn new(opts: NatsConsumerOptions) -> Self {
+		// Connect to the NATS server.
+		let client = async_nats::connect(&opts.nats_url)
+			.await
+			.expect("Failed to connect to NATS");
+		// Create a JetStream context.
+		let js = async_nats::jetstream::new(client.clone());
+		// Subscribe to the channel's stream.
+		let stream = js
+			.get_stream(format!("notifications_{}", opts.channel,))
+			.await
+			.expect("Failed to get stream");
+		let consumer_config = async_nats::jetstream::consumer::pull::Config {
+			name: Some(opts.name.clone()),
+			ack_policy: async_nats::jetstream::consumer::AckPolicy::Explicit,
+			ack_wait: opts.ack_wait,
+			max_deliver: opts.max_deliver,
+			backoff: opts.backoff,
+			filter_subject: opts.filter_subject.clone(),
+			..Default::default()
+		};
+		let consumer = stream
+			.create_consumer(consumer_config)
+			.await
+			.expect("Failed to create consumer");
+
+		Self {
+			client,
+			consumer,
+			channel: opts.channel.clone(),
+			concurrency: opts.concurrency,
+			batch_size: opts.batch_size,
+			worker: Worker {
+				channel:           opts.channel,
+				faults:            opts.faults,
+				progress_interval: opts.ack_wait / 2,
+			},
+		}
+	}
+
+	pub fn health_check(&self) -> NatsHealthCheck {
+		NatsHealthCheck::new(
+			self.client.clone(),
+			vec![format!("notifications_{}", self.channel)],
+		)
+	}
+
+	/// Consumes until `shutdown` fires, handling up to `concurrency` messages
+	/// at once. The messages being handled are finished and acked first,
+	/// anything not yet handled is redelivered later.
+	pub async fn start(&mut self, handler: Arc<dyn MessageHandler>, shutdown: Shutdown) {
+		let messages = self
+			.consumer
+			.stream()
+			.max_messages_per_batch(self.batch_size)
+			.messages()
+			.await
+			.expect("Failed to get messages stream");
+		let worker = self.worker.clone();
+		run_pool(messages, self.concurrency, shutdown, move |msg| {
+			let worker = worker.clone();
+			let handler = handler.clone();
+			async move { worker.process(handler.as_ref(), msg).await }
+		})
+		.await;
+		info!("Nats consumer stopped");
+	}
+}
+
+/// Runs `process` on the messages of `messages` until it ends or
+/// `shutdown` fires, at most `concurrency` at once, then waits for the ones
+/// still being processed.
+async fn run_pool<S, T, E, F, Fut>(
+	mut messages: S,
+	concurrency: usize,
+	shutdown: Shutdown,
+	process: F,
+) where
+	S: Stream<Item = Result<T, E>> + Unpin,
+	E: Debug,
+	F: Fn(T) -> Fut,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	let workers = Arc::new(Semaphore::new(concurrency));
+	let mut in_flight = JoinSet::new();
+
+	loop {
+		// Only take a message once a worker is free. A pull still hands out
+		// up to `batch_size` messages, which is why it must not exceed
+		// `concurrency`: the surplus would wait here unacked while its ack
+		// wait runs out.
+		let permit = tokio::select! {
+			permit = workers.clone().acquire_owned() => permit.expect("Worker pool closed"),
+			_ = shutdown.wait() => break,
+		};
+		let message_result = tokio::select! {
+			message_result = messages.next() => match message_result {
+				Some(message_result) => message_result,
+				None => break,
+			},
+			_ = shutdown.wait() => break,
+		};
+		while in_flight.try_join_next().is_some() {}
+
+		match message_result {
+			Ok(msg) => {
+				let processing = process(msg);
+				in_flight.spawn(async move {
+					processing.await;
+					drop(permit);
+				});
+			}
+			Err(e) => error!("Error receiving message: {:?}", e),
+		}
+	}
+
+	if !in_flight.is_empty() {
+		info!("Waiting for {} in-flight messages", in_flight.len());
+	}
+	while in_flight.join_next().await.is_some() {}
+}
+
+/// Tells JetStream what to do with a handled message. Transient failures
+/// are redelivered after the configured backoff, possibly to another
+/// replica, permanent ones never.
+fn ack_kind(result: &Result<(), DeliveryError>) -> AckKind {
+	match result {
+		Ok(()) => AckKind::Ack,
+		Err(DeliveryError::Transient(_)) => AckKind::Nak(None),
+		Err(DeliveryError::RetryAfter(delay, _)) => AckKind::Nak(Some(*delay)),
+		Err(DeliveryError::Permanent(_)) => AckKind::Term,
+	}
+}
+
+impl Worker {
+	async fn process(&self, handler: &dyn MessageHandler, msg: Message) {
+		if self.is_faulted().await {
+			if let Err(e) = msg.ack_with(AckKind::Nak(None)).await {
+				error!("Failed to nack message: {:?}", e);
+			}
+			return;
+		}
+		let payload_str = match std::str::from_utf8(&msg.payload) {
+			Ok(payload_str) => payload_str,
+			Err(e) => {
+				error!("Received message with invalid UTF-8: {:?}", e);
+				if let Err(e) = msg.ack_with(AckKind::Term).await {
+					error!("Failed to terminate message: {:?}", e);
+				}
+				return;
+			}
+		};
+
+		let report_progress = || async {
+			if let Err(e) = msg.ack_with(AckKind::Progress).await {
+				warn!("Failed to report progress: {:?}", e);
+			}
+		};
+		let result = self.handle(handler, payload_str, report_progress).await;
+		match &result {
+			Ok(()) => {}
+			Err(DeliveryError::Transient(e)) => {
+				warn!("Failed to deliver message, will retry: {}", e)
+			}
+			Err(DeliveryError::RetryAfter(delay, e)) => warn!(
+				"Failed to deliver message, will retry in {}s: {}",
+				delay.as_secs(),
+				e
+			),
+			Err(DeliveryError::Permanent(e)) => {
+				error!("Failed to deliver message, giving up: {}", e)
+			}
+		}
+		let ack = ack_kind(&result);
+		if let Err(e) = msg.ack_with(ack).await {
+			error!("Failed to reply {:?} to message: {:?}", ack, e);
+		}
+	}
+
+	/// Runs the handler, calling `report_progress` while it takes longer than
+	/// `progress_interval`.
+	async fn handle<P, Fut>(
+		&self,
+		handler: &dyn MessageHandler,
+		payload: &str,
+		mut report_progress: P,
+	) -> Result<(), DeliveryError>
+	where
+		P: FnMut() -> Fut,
+		Fut: Future<Output = ()>,
+	{
+		let handling = handler.handle_message(payload);
+		tokio::pin!(handling);
+		let mut progress = interval_at(
+			Instant::now() + self.progress_interval,
+			self.progress_interval,
+		);
+		loop {
+			tokio::select! {
+				result = &mut handling => return result,
+				_ = progress.tick() => report_progress().await,
+			}
+		}
+	}
+
+	async fn is_faulted(&self) -> bool {
+		match self
+			.faults
+			.inject(Component::Consumer, Some(self.channel.as_str()))
+			.await
+		{
+			Some(fault) => {
+				warn!("Nacking message because of injected fault: {:?}", fault);
+				true
+			}
+			None => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		Mutex,
+		atomic::{AtomicUsize, Ordering},
+	};
+
+	use async_trait::async_trait;
+	use common::faults::FaultInjector;
+
+	use super::*;
+
+	/// Takes `delay` to deliver every message.
+	struct Slow {
+		delay: Duration,
+	}
+
+	#[async_trait]
+	impl MessageHandler for Slow {
+		async fn handle_message(&self, _: &str) -> Result<(), DeliveryError> {
+			tokio::time::sleep(self.delay).await;
+			Ok(())
+		}
+	}
+
+	fn worker(progress_interval: Duration) -> Worker {
+		Worker {
+			channel: "push".to_string(),
+			faults: Arc::new(FaultInjector::default()),
+			progress_interval,
+		}
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn reports_progress_while_the_handler_runs() {
+		let handler = Slow {
+			delay: Duration::from_secs(35),
+		};
+		let reports = AtomicUsize::new(0);
+
+		let result = worker(Duration::from_secs(10))
+			.handle(&handler, "{}", || async {
+				reports.fetch_add(1, Ordering::SeqCst);
+			})
+			.await;
+
+		assert_eq!(result, Ok(()));
+		assert_eq!(reports.load(Ordering::SeqCst), 3);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn quick_handlers_report_no_progress() {
+		let handler = Slow {
+			delay: Duration::from_secs(5),
+		};
+		let reports = AtomicUsize::new(0);
+
+		worker(Duration::from_secs(10))
+			.handle(&handler, "{}", || async {
+				reports.fetch_add(1, Ordering::SeqCst);
+			})
+			.await
+			.unwrap();
+
+		assert_eq!(reports.load(Ordering::SeqCst), 0);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn handles_at_most_concurrency_messages_at_once() {
+		let running = Arc::new(AtomicUsize::new(0));
+		let most = Arc::new(AtomicUsize::new(0));
+		let handled = Arc::new(Mutex::new(Vec::new()));
+		let messages = futures::stream::iter((0..10).map(Ok::<_, String>));
+
+		run_pool(messages, 3, Shutdown::new(), |message| {
+			let (running, most, handled) = (running.clone(), most.clone(), handled.clone());
+			async move {
+				let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+				most.fetch_max(now, Ordering::SeqCst);
+				tokio::time::sleep(Duration::from_secs(1)).await;
+				running.fetch_sub(1, Ordering::SeqCst);
+				handled.lock().unwrap().push(message);
+			}
+		})
+		.await;
+
+		assert_eq!(most.load(Ordering::SeqCst), 3);
+		let mut handled = handled.lock().unwrap().clone();
+		handled.sort();
+		assert_eq!(handled, (0..10).collect::<Vec<_>>());
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn finishes_in_flight_messages_on_shutdown() {
+		let shutdown = Shutdown::new();
+		let handled = Arc::new(AtomicUsize::new(0));
+		let messages =
+			futures::stream::iter((0..2).map(Ok::<_, String>)).chain(futures::stream::pending());
+
+		let pool = run_pool(messages, 2, shutdown.clone(), |_| {
+			let handled = handled.clone();
+			async move {
+				tokio::time::sleep(Duration::from_secs(5)).await;
+				handled.fetch_add(1, Ordering::SeqCst);
+			}
+		});
+		let stop = async {
+			tokio::time::sleep(Duration::from_secs(1)).await;
+			shutdown.trigger();
+		};
+		tokio::join!(pool, stop);
+
+		assert_eq!(handled.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn outcomes_map_to_ack_nak_or_term() {
+		assert!(matches!(ack_kind(&Ok(())), AckKind::Ack));
+		assert!(matches!(
+			ack_kind(&Err(DeliveryError::Transient("timeout".to_string()))),
+			AckKind::Nak(None)
+		));
+		assert!(matches!(
+			ack_kind(&Err(DeliveryError::RetryAfter(
+				Duration::from_secs(30),
+				"rate limited".to_string()
+			))),
+			AckKind::Nak(Some(delay)) if delay == Duration::from_secs(30)
+		));
+		assert!(matches!(
+			ack_kind(&Err(DeliveryError::Permanent("bad request".to_string()))),
+			AckKind::Term
+		));
+	}
+}
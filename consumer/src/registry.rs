@@ -0,0 +1,166 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::handler::MessageHandler;
+
+type Build<'a> = Box<dyn FnOnce() -> Result<Arc<dyn MessageHandler>, String> + 'a>;
+
+struct Registration<'a, H> {
+	handler: H,
+	channel: &'static str,
+	build:   Build<'a>,
+}
+
+/// A handler built for a channel, consumed by its own NATS consumer.
+pub struct ChannelHandler {
+	pub channel: &'static str,
+	pub handler: Arc<dyn MessageHandler>,
+}
+
+/// The handlers a binary registered, of which the configured ones are
+/// built.
+pub struct Handlers<'a, H> {
+	registered: Vec<Registration<'a, H>>,
+}
+
+impl<'a, H: Copy + Debug + PartialEq> Handlers<'a, H> {
+	pub fn new() -> Self {
+		Handlers {
+			registered: Vec::new(),
+		}
+	}
+
+	/// Registers `handler` as consuming `channel`, `build` only runs when
+	/// it is configured.
+	pub fn register<F>(mut self, handler: H, channel: &'static str, build: F) -> Self
+	where
+		F: FnOnce() -> Result<Arc<dyn MessageHandler>, String> + 'a,
+	{
+		self.registered.push(Registration {
+			handler,
+			channel,
+			build: Box::new(build),
+		});
+		self
+	}
+
+	/// Builds the `selected` handlers in order. Two of them cannot share a
+	/// channel, they would compete for the same consumer.
+	pub fn build(mut self, selected: &[H]) -> Result<Vec<ChannelHandler>, String> {
+		let mut built: Vec<(H, ChannelHandler)> = Vec::new();
+		for handler in selected {
+			let index = self
+				.registered
+				.iter()
+				.position(|r| r.handler == *handler)
+				.ok_or_else(|| format!("handler {:?} is not registered", handler))?;
+			let registration = self.registered.swap_remove(index);
+			if let Some((other, _)) = built
+				.iter()
+				.find(|(_, built)| built.channel == registration.channel)
+			{
+				return Err(format!(
+					"handlers {:?} and {:?} both consume the {} channel",
+					other, handler, registration.channel
+				));
+			}
+			let message_handler =
+				(registration.build)().map_err(|e| format!("handler {:?}: {}", handler, e))?;
+			built.push((
+				*handler,
+				ChannelHandler {
+					channel: registration.channel,
+					handler: message_handler,
+				},
+			));
+		}
+		Ok(built.into_iter().map(|(_, built)| built).collect())
+	}
+}
+
+impl<H: Copy + Debug + PartialEq> Default for Handlers<'_, H> {
+	fn default() -> Self {
+		Handlers::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+
+	use async_trait::async_trait;
+
+	use super::*;
+	use crate::handler::DeliveryError;
+
+	#[derive(Clone, Copy, Debug, PartialEq)]
+	enum Kind {
+		Log,
+		Mobile,
+		Browser,
+	}
+
+	struct Noop;
+
+	#[async_trait]
+	impl MessageHandler for Noop {
+		async fn handle_message(&self, _: &str) -> Result<(), DeliveryError> {
+			Ok(())
+		}
+	}
+
+	fn handlers(built: &Cell<usize>) -> Handlers<'_, Kind> {
+		let build = move || {
+			built.set(built.get() + 1);
+			Ok(Arc::new(Noop) as Arc<dyn MessageHandler>)
+		};
+		Handlers::new()
+			.register(Kind::Log, "push", build)
+			.register(Kind::Mobile, "push", build)
+			.register(Kind::Browser, "webpush", build)
+	}
+
+	#[test]
+	fn builds_only_the_selected_handlers() {
+		let built = Cell::new(0);
+		let channels: Vec<_> = handlers(&built)
+			.build(&[Kind::Browser, Kind::Mobile])
+			.unwrap()
+			.into_iter()
+			.map(|h| h.channel)
+			.collect();
+
+		assert_eq!(channels, vec!["webpush", "push"]);
+		assert_eq!(built.get(), 2);
+	}
+
+	#[test]
+	fn rejects_handlers_sharing_a_channel() {
+		let built = Cell::new(0);
+		let error = handlers(&built)
+			.build(&[Kind::Log, Kind::Mobile])
+			.err()
+			.unwrap();
+
+		assert_eq!(
+			error,
+			"handlers Log and Mobile both consume the push channel"
+		);
+	}
+
+	#[test]
+	fn rejects_unregistered_handlers_and_failed_builds() {
+		let error = Handlers::new()
+			.register(Kind::Log, "push", || Err("missing key".to_string()))
+			.build(&[Kind::Mobile])
+			.err()
+			.unwrap();
+		assert_eq!(error, "handler Mobile is not registered");
+
+		let error = Handlers::new()
+			.register(Kind::Log, "push", || Err("missing key".to_string()))
+			.build(&[Kind::Log])
+			.err()
+			.unwrap();
+		assert_eq!(error, "handler Log: missing key");
+	}
+}
@@ -0,0 +1,158 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use common::{
+	axum_prometheus::{
+		PrometheusMetricLayer,
+		metrics::set_global_recorder,
+		metrics_exporter_prometheus::PrometheusRecorder,
+	},
+	config::{LogConfig, MetricsConfig, NatsConfig, ShutdownConfig},
+	faults::{self, FaultConfig, FaultInjector},
+	monitoring::{
+		self,
+		health::{Health, HealthCheck},
+	},
+	notification,
+	shutdown::Shutdown,
+};
+use env_logger::{Builder, Env, Target};
+use futures::future::join_all;
+use log::info;
+
+use crate::{
+	config::ConsumerConfig,
+	nats::{NatsConsumer, NatsConsumerOptions},
+	registry::Handlers,
+};
+
+/// The sections of a binary's config the runtime reads.
+pub struct Settings<'a, H> {
+	pub metrics:  &'a MetricsConfig,
+	pub nats:     &'a NatsConfig,
+	pub consumer: &'a ConsumerConfig<H>,
+	pub shutdown: &'a ShutdownConfig,
+}
+
+/// Everything a consumer binary runs besides its handlers: logging, metrics,
+/// fault injection, health, the NATS consumers and graceful shutdown.
+pub struct Runtime {
+	layer:    PrometheusMetricLayer<'static>,
+	recorder: Arc<PrometheusRecorder>,
+	faults:   Arc<FaultInjector>,
+}
+
+impl Runtime {
+	/// Sets up the logger and the global metrics recorder, call once at
+	/// startup.
+	pub fn init(log: &LogConfig, faults: &FaultConfig) -> Self {
+		// Initialize the logger, RUST_LOG wins over the configured level
+		Builder::from_env(Env::default().default_filter_or(&log.level))
+			.target(Target::Stdout)
+			.init();
+
+		let faults =
+			Arc::new(FaultInjector::new(faults.clone()).expect("Invalid fault injection config"));
+
+		// Set up Prometheus metrics
+		let (layer, recorder) = monitoring::server::default_pair();
+		set_global_recorder(recorder.clone()).expect("Failed to set global recorder");
+
+		Runtime {
+			layer,
+			recorder,
+			faults,
+		}
+	}
+
+	/// The recorder the binary registers its own metrics with.
+	pub fn recorder(&self) -> Arc<PrometheusRecorder> {
+		self.recorder.clone()
+	}
+
+	/// Consumes the channel of every configured handler until SIGTERM/Ctrl+C,
+	/// then drains the in-flight messages within the shutdown timeout.
+	pub async fn run<H: Copy + Debug + PartialEq>(
+		self,
+		settings: Settings<'_, H>,
+		handlers: Handlers<'_, H>,
+	) {
+		let handlers = handlers
+			.build(&settings.consumer.handler)
+			.expect("Invalid handler config");
+		let recipient_id = &settings.consumer.recipient_id;
+
+		// Create a NATS consumer per channel
+		let mut consumers = Vec::new();
+		for handler in handlers {
+			let filter_subject = settings.consumer.filter_subject(handler.channel);
+			let consumer = NatsConsumer::new(NatsConsumerOptions {
+				nats_url:       settings.nats.url.clone(),
+				ack_wait:       settings.consumer.ack_wait(),
+				max_deliver:    settings.consumer.max_deliver,
+				backoff:        settings.consumer.backoff(),
+				concurrency:    settings.consumer.concurrency,
+				batch_size:     settings.consumer.batch_size,
+				name:           notification::subject_token(recipient_id),
+				filter_subject: filter_subject.clone(),
+				channel:        handler.channel.to_string(),
+				faults:         self.faults.clone(),
+			})
+			.await;
+			consumers.push((consumer, handler.handler, filter_subject));
+		}
+
+		// Create the Prometheus server
+		let prometheus_port = settings.metrics.port.to_string();
+		let (mut prometheus, prometheus_listener) =
+			monitoring::server::create_metrics_router(monitoring::server::ServerOptions {
+				host:          settings.metrics.host.clone(),
+				port:          prometheus_port.clone(),
+				metric_handle: self.recorder.handle(),
+			})
+			.await;
+
+		// Stop consuming on SIGTERM/Ctrl+C, keep the metrics up until the
+		// in-flight messages are done.
+		let shutdown = Shutdown::new();
+		shutdown.listen_for_signals();
+		let metrics_shutdown = Shutdown::new();
+
+		let checks = consumers
+			.iter()
+			.map(|(consumer, _, _)| Arc::new(consumer.health_check()) as Arc<dyn HealthCheck>)
+			.collect();
+		let health = Arc::new(Health::new(checks, shutdown.clone()));
+		prometheus = prometheus
+			.merge(faults::routes(self.faults.clone()))
+			.merge(monitoring::health::routes(health))
+			.layer(self.layer);
+
+		// Start the futures
+		let prom_future = async {
+			info!("Prometheus server running on port: {}", prometheus_port);
+			axum::serve(prometheus_listener, prometheus)
+				.with_graceful_shutdown(metrics_shutdown.wait())
+				.await
+				.expect("Prometheus server failed to start");
+		};
+
+		let nats_future = async {
+			join_all(
+				consumers
+					.iter_mut()
+					.map(|(consumer, handler, filter_subject)| {
+						info!("Nats consumer started for subject: {}", filter_subject);
+						consumer.start(handler.clone(), shutdown.clone())
+					}),
+			)
+			.await;
+			metrics_shutdown.trigger();
+		};
+
+		shutdown
+			.run_with_deadline(Duration::from_secs(settings.shutdown.timeout_secs), async {
+				tokio::join!(prom_future, nats_future)
+			})
+			.await;
+	}
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use common::monitoring::metrics::Metrics;
+use log::info;
+
+use crate::handler::{DeliveryError, MessageHandler};
+
+/// Logs each message and counts it as consumed, for local development.
+pub struct StdOutHandlerImpl {
+	metrics: Arc<Metrics>,
+	counter: &'static str,
+}
+
+impl StdOutHandlerImpl {
+	pub fn new(metrics: Arc<Metrics>, counter: &'static str) -> Self {
+		StdOutHandlerImpl { metrics, counter }
+	}
+}
+
+#[async_trait]
+impl MessageHandler for StdOutHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		info!("Received: {}", message);
+		if let Some(metric) = self.metrics.get_counter(self.counter) {
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,245 @@
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::{
+	Deserialize,
+	de::{IntoDeserializer, value},
+};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<Handler>,
+	pub channels: Channels,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("consumer_host"),
+			channels: Channels::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+/// The handler sections of every channel, `[channels.<section>]` with the
+/// section named as in the binary of its channel, `http` being the SMS
+/// gateway.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Channels {
+	pub fcm:     push_consumer::config::FcmConfig,
+	pub apns:    push_consumer::config::ApnsConfig,
+	pub webpush: push_consumer::config::WebPushConfig,
+	pub smtp:    email_consumer::config::SmtpConfig,
+	pub webhook: webhook_consumer::config::WebhookConfig,
+	pub smpp:    sms_consumer::config::SmppConfig,
+	pub http:    sms_consumer::config::HttpGatewayConfig,
+	pub mqtt:    mqtt_consumer::config::MqttConfig,
+	pub chat:    chat_consumer::config::ChatConfig,
+}
+
+/// A handler of one of the channel crates, configured as
+/// `<channel>.<handler>` with the handler named as in that channel's binary,
+/// e.g. `push.fcm` or `sms.http`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum Handler {
+	Push(push_consumer::config::HandlerKind),
+	Email(email_consumer::config::HandlerKind),
+	Webhook(webhook_consumer::config::HandlerKind),
+	Sms(sms_consumer::config::HandlerKind),
+	Mqtt(mqtt_consumer::config::HandlerKind),
+	Chat(chat_consumer::config::HandlerKind),
+}
+
+/// Logs email messages, like `email_consumer` does by default.
+impl Default for Handler {
+	fn default() -> Self {
+		Handler::Email(Default::default())
+	}
+}
+
+impl Handler {
+	/// The stdout handler of every channel that has one.
+	pub fn stdout() -> Vec<Handler> {
+		vec![
+			Handler::Push(push_consumer::config::HandlerKind::StdOut),
+			Handler::Email(email_consumer::config::HandlerKind::StdOut),
+			Handler::Sms(sms_consumer::config::HandlerKind::StdOut),
+			Handler::Mqtt(mqtt_consumer::config::HandlerKind::StdOut),
+			Handler::Chat(chat_consumer::config::HandlerKind::StdOut),
+		]
+	}
+}
+
+impl TryFrom<String> for Handler {
+	type Error = String;
+
+	fn try_from(name: String) -> Result<Self, Self::Error> {
+		fn kind<'de, K: Deserialize<'de>>(name: &'de str) -> Result<K, String> {
+			K::deserialize(IntoDeserializer::<value::Error>::into_deserializer(name))
+				.map_err(|e| e.to_string())
+		}
+
+		let Some((channel, handler)) = name.split_once('.') else {
+			return Err(format!(
+				"handler {} must be <channel>.<handler>, e.g. push.fcm",
+				name
+			));
+		};
+		match channel {
+			"push" => kind(handler).map(Handler::Push),
+			"email" => kind(handler).map(Handler::Email),
+			"webhook" => kind(handler).map(Handler::Webhook),
+			"sms" => kind(handler).map(Handler::Sms),
+			"mqtt" => kind(handler).map(Handler::Mqtt),
+			"chat" => kind(handler).map(Handler::Chat),
+			_ => Err(format!("handler {} is not of a known channel", name)),
+		}
+	}
+}
+
+impl Channels {
+	pub fn push(&self) -> push_consumer::config::Sections<'_> {
+		push_consumer::config::Sections {
+			fcm:     &self.fcm,
+			apns:    &self.apns,
+			webpush: &self.webpush,
+		}
+	}
+
+	pub fn email(&self) -> email_consumer::config::Sections<'_> {
+		email_consumer::config::Sections { smtp: &self.smtp }
+	}
+
+	pub fn webhook(&self) -> webhook_consumer::config::Sections<'_> {
+		webhook_consumer::config::Sections {
+			webhook: &self.webhook,
+		}
+	}
+
+	pub fn sms(&self) -> sms_consumer::config::Sections<'_> {
+		sms_consumer::config::Sections {
+			smpp: &self.smpp,
+			http: &self.http,
+		}
+	}
+
+	pub fn mqtt(&self) -> mqtt_consumer::config::Sections<'_> {
+		mqtt_consumer::config::Sections { mqtt: &self.mqtt }
+	}
+
+	pub fn chat(&self) -> chat_consumer::config::Sections<'_> {
+		chat_consumer::config::Sections { chat: &self.chat }
+	}
+
+	/// Checks the sections of the handlers `consumer` runs, named by their
+	/// place under `[channels]`.
+	pub fn validate(&self, consumer: &ConsumerConfig<Handler>) -> Result<(), ConfigError> {
+		let validate = || {
+			self.push().validate(consumer, Handler::Push)?;
+			self.email().validate(consumer, Handler::Email)?;
+			self.webhook().validate(consumer, Handler::Webhook)?;
+			self.sms().validate(consumer, Handler::Sms)?;
+			self.mqtt().validate(consumer, Handler::Mqtt)?;
+			self.chat().validate(consumer, Handler::Chat)
+		};
+		validate().map_err(|e| ConfigError(format!("channels.{}", e.0)))
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.channels.validate(&self.consumer)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+		let vars = vars
+			.iter()
+			.map(|(name, value)| (name.to_string(), value.to_string()));
+		common::config::load_from(None, vars)
+	}
+
+	#[test]
+	fn handlers_are_named_by_channel() {
+		// Only the sections of configured handlers are checked.
+		let error = load(&[(
+			"APP__CONSUMER__HANDLER",
+			"[\"email.stdout\", \"push.webpush\"]",
+		)])
+		.unwrap_err();
+		assert_eq!(
+			error.0,
+			"channels.webpush.vapid_private_key must not be empty"
+		);
+
+		let config =
+			load(&[("APP__CONSUMER__HANDLER", "[\"email.smtp\", \"sms.stdout\"]")]).unwrap();
+		assert_eq!(
+			config.consumer.handler,
+			vec![
+				Handler::Email(email_consumer::config::HandlerKind::Smtp),
+				Handler::Sms(sms_consumer::config::HandlerKind::StdOut),
+			]
+		);
+		assert_eq!(config.consumer.filter_subject("sms"), "notifications_sms.*");
+	}
+
+	#[test]
+	fn rejects_unknown_handlers() {
+		for (name, error) in [
+			(
+				"fcm",
+				"handler fcm must be <channel>.<handler>, e.g. push.fcm",
+			),
+			("fax.stdout", "handler fax.stdout is not of a known channel"),
+			("push.smtp", "unknown variant `smtp`"),
+		] {
+			let message = load(&[("APP__CONSUMER__HANDLER", name)]).unwrap_err().0;
+			assert!(
+				message.starts_with("consumer.handler") && message.contains(error),
+				"{}",
+				message
+			);
+		}
+	}
+
+	#[test]
+	fn shipped_config_is_valid() {
+		common::config::load_from::<Config>(Some("config.toml"), Vec::new()).unwrap();
+	}
+}
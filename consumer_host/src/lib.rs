@@ -0,0 +1,32 @@
+//! Every channel crate in one process. The `consumer_host` binary consumes
+//! the channels its config lists from NATS, the API registers the same
+//! handlers to deliver in process, see [`register`].
+
+use std::sync::Arc;
+
+use common::axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder;
+use consumer::registry::Handlers;
+
+use crate::config::{Channels, Handler};
+
+pub mod config;
+
+/// Registers the handlers of every channel crate, built from the sections
+/// in `channels` once they are picked.
+pub fn register(channels: &Channels, recorder: Arc<PrometheusRecorder>) -> Handlers<'_, Handler> {
+	let handlers = Handlers::new();
+	let handlers =
+		push_consumer::register(handlers, channels.push(), recorder.clone(), Handler::Push);
+	let handlers =
+		email_consumer::register(handlers, channels.email(), recorder.clone(), Handler::Email);
+	let handlers = webhook_consumer::register(
+		handlers,
+		channels.webhook(),
+		recorder.clone(),
+		Handler::Webhook,
+	);
+	let handlers = sms_consumer::register(handlers, channels.sms(), recorder.clone(), Handler::Sms);
+	let handlers =
+		mqtt_consumer::register(handlers, channels.mqtt(), recorder.clone(), Handler::Mqtt);
+	chat_consumer::register(handlers, channels.chat(), recorder, Handler::Chat)
+}
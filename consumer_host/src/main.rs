@@ -0,0 +1,27 @@
+use consumer::runtime::{Runtime, Settings};
+use consumer_host::config::Config;
+
+#[tokio::main]
+async fn main() {
+	let config: Config = common::config::load().unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	});
+
+	let runtime = Runtime::init(&config.log, &config.faults);
+
+	// Every channel is registered, config picks the handlers that run.
+	let handlers = consumer_host::register(&config.channels, runtime.recorder());
+
+	runtime
+		.run(
+			Settings {
+				metrics:  &config.metrics,
+				nats:     &config.nats,
+				consumer: &config.consumer,
+				shutdown: &config.shutdown,
+			},
+			handlers,
+		)
+		.await;
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::Deserialize;
+
+use crate::handler::smtp::{SmtpOptions, SmtpTls};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub smtp:     SmtpConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("email_consumer"),
+			smtp:     SmtpConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Logs each message.
+	#[default]
+	#[serde(rename = "stdout")]
+	StdOut,
+	/// Sends each message as an email, see [`SmtpConfig`].
+	#[serde(rename = "smtp")]
+	Smtp,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+	pub host:         String,
+	pub port:         u16,
+	pub tls:          SmtpTls,
+	/// Authenticates only when both username and password are set.
+	pub username:     Option<String>,
+	pub password:     Option<String>,
+	pub timeout_secs: u64,
+	pub from:         String,
+	pub reply_to:     Option<String>,
+	pub subject:      String,
+}
+
+impl Default for SmtpConfig {
+	fn default() -> Self {
+		SmtpConfig {
+			host:         "localhost".to_string(),
+			port:         587,
+			tls:          SmtpTls::default(),
+			username:     None,
+			password:     None,
+			timeout_secs: 30,
+			from:         "notifications@localhost".to_string(),
+			reply_to:     None,
+			subject:      "New notification".to_string(),
+		}
+	}
+}
+
+impl SmtpConfig {
+	pub fn options(&self) -> SmtpOptions {
+		SmtpOptions {
+			host:        self.host.clone(),
+			port:        self.port,
+			tls:         self.tls,
+			credentials: self.username.clone().zip(self.password.clone()),
+			timeout:     Duration::from_secs(self.timeout_secs),
+			from:        self.from.clone(),
+			reply_to:    self.reply_to.clone(),
+			subject:     self.subject.clone(),
+		}
+	}
+}
+
+/// The sections the email handlers are built from, also part of hosts that
+/// run other channels next to email.
+pub struct Sections<'a> {
+	pub smtp: &'a SmtpConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		if consumer.uses(name(HandlerKind::Smtp)) {
+			if self.smtp.host.is_empty() {
+				return Err(ConfigError("smtp.host must not be empty".to_string()));
+			}
+			require_positive("smtp.port", self.smtp.port)?;
+			require_positive("smtp.timeout_secs", self.smtp.timeout_secs)?;
+			if self.smtp.username.is_some() != self.smtp.password.is_some() {
+				return Err(ConfigError(
+					"smtp.username and smtp.password must be set together".to_string(),
+				));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections { smtp: &self.smtp }
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
@@ -1,5 +1,3 @@
-pub mod std_out;
+pub use consumer::handler::{DeliveryError, MessageHandler};
 
-pub trait MessageHandler {
-	fn handle_message(&self, message: &str);
-}
+pub mod smtp;
@@ -0,0 +1,349 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use common::notification::Notification;
+use consumer::handler::parse_notification;
+use lettre::{
+	AsyncSmtpTransport,
+	AsyncTransport,
+	Message,
+	Tokio1Executor,
+	message::{Mailbox, MultiPart},
+	transport::smtp::{
+		self,
+		authentication::Credentials,
+		client::{Tls, TlsParameters},
+	},
+};
+use log::debug;
+use serde::Deserialize;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler},
+	metrics::metrics::EMAIL_CONSUMER_CONSUMED_MESSAGES,
+};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum SmtpTls {
+	/// Plain text, only meant for local relays and tests.
+	#[serde(rename = "none")]
+	None,
+	/// Upgrades the connection with STARTTLS, fails if the server cannot.
+	#[default]
+	#[serde(rename = "starttls")]
+	StartTls,
+	/// TLS from the first byte, usually on port 465.
+	#[serde(rename = "tls")]
+	Implicit,
+}
+
+pub struct SmtpOptions {
+	pub host:        String,
+	pub port:        u16,
+	pub tls:         SmtpTls,
+	pub credentials: Option<(String, String)>,
+	pub timeout:     Duration,
+	pub from:        String,
+	pub reply_to:    Option<String>,
+	pub subject:     String,
+}
+
+/// Sends each notification as a text and HTML email to the address in
+/// `recipient.id`.
+pub struct SmtpHandlerImpl {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from:      Mailbox,
+	reply_to:  Option<Mailbox>,
+	subject:   String,
+	app_state: Arc<AppState>,
+}
+
+impl SmtpHandlerImpl {
+	pub fn new(opts: SmtpOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let from = opts
+			.from
+			.parse::<Mailbox>()
+			.map_err(|e| format!("invalid from address {:?}: {}", opts.from, e))?;
+		let reply_to = opts
+			.reply_to
+			.map(|reply_to| {
+				reply_to
+					.parse::<Mailbox>()
+					.map_err(|e| format!("invalid reply-to address {:?}: {}", reply_to, e))
+			})
+			.transpose()?;
+
+		let tls_parameters = || {
+			TlsParameters::new(opts.host.clone())
+				.map_err(|e| format!("invalid TLS parameters: {}", e))
+		};
+		let tls = match opts.tls {
+			SmtpTls::None => Tls::None,
+			SmtpTls::StartTls => Tls::Required(tls_parameters()?),
+			SmtpTls::Implicit => Tls::Wrapper(tls_parameters()?),
+		};
+		let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&opts.host)
+			.port(opts.port)
+			.tls(tls)
+			.timeout(Some(opts.timeout));
+		if let Some((username, password)) = opts.credentials {
+			builder = builder.credentials(Credentials::new(username, password));
+		}
+
+		Ok(SmtpHandlerImpl {
+			transport: builder.build(),
+			from,
+			reply_to,
+			subject: opts.subject,
+			app_state,
+		})
+	}
+
+	fn build_message(&self, notification: &Notification) -> Result<Message, DeliveryError> {
+		let to =
+			notification.recipient.id.parse::<Mailbox>().map_err(|e| {
+				DeliveryError::Permanent(format!("invalid recipient address: {}", e))
+			})?;
+
+		let mut builder = Message::builder()
+			.from(self.from.clone())
+			.to(to)
+			.subject(self.subject.clone());
+		if let Some(reply_to) = &self.reply_to {
+			builder = builder.reply_to(reply_to.clone());
+		}
+		builder
+			.multipart(MultiPart::alternative_plain_html(
+				notification.content.clone(),
+				to_html(&notification.content),
+			))
+			.map_err(|e| DeliveryError::Permanent(format!("failed to build message: {}", e)))
+	}
+}
+
+/// Escapes `text` and keeps its line breaks.
+fn to_html(text: &str) -> String {
+	let mut body = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => body.push_str("&amp;"),
+			'<' => body.push_str("&lt;"),
+			'>' => body.push_str("&gt;"),
+			'"' => body.push_str("&quot;"),
+			'\'' => body.push_str("&#39;"),
+			'\n' => body.push_str("<br>\n"),
+			c => body.push(c),
+		}
+	}
+	format!(
+		"<!DOCTYPE html>\n<html><body><p>{}</p></body></html>\n",
+		body
+	)
+}
+
+/// 5xx replies are final, anything else, 4xx replies, timeouts and
+/// connection problems included, may succeed on another attempt.
+fn classify(e: smtp::Error) -> DeliveryError {
+	if e.is_permanent() {
+		DeliveryError::Permanent(e.to_string())
+	} else {
+		DeliveryError::Transient(e.to_string())
+	}
+}
+
+#[async_trait]
+impl MessageHandler for SmtpHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_notification(message)?;
+		let email = self.build_message(&notification)?;
+
+		let response = self.transport.send(email).await.map_err(classify)?;
+		debug!(
+			"Sent notification {:?} to {}: {}",
+			notification.id,
+			notification.recipient.id,
+			response.code()
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(EMAIL_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use common::monitoring::{metrics::Metrics, server::default_pair};
+	use tokio::{
+		io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+		net::TcpListener,
+	};
+
+	use super::*;
+
+	/// Minimal SMTP server: answers `RCPT TO` with `rcpt_reply` and records
+	/// the data of every accepted message.
+	struct SmtpStandIn {
+		port:     u16,
+		messages: Arc<Mutex<Vec<String>>>,
+	}
+
+	impl SmtpStandIn {
+		async fn start(rcpt_reply: &'static str) -> Self {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let port = listener.local_addr().unwrap().port();
+			let messages = Arc::new(Mutex::new(Vec::new()));
+
+			let received = messages.clone();
+			tokio::spawn(async move {
+				while let Ok((stream, _)) = listener.accept().await {
+					let received = received.clone();
+					tokio::spawn(async move {
+						let (reader, mut writer) = stream.into_split();
+						let mut lines = BufReader::new(reader).lines();
+						writer.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+						while let Ok(Some(line)) = lines.next_line().await {
+							let command = line.to_ascii_uppercase();
+							let reply = if command.starts_with("EHLO") {
+								"250-stand-in\r\n250 8BITMIME\r\n".to_string()
+							} else if command.starts_with("RCPT") {
+								format!("{}\r\n", rcpt_reply)
+							} else if command.starts_with("DATA") {
+								writer.write_all(b"354 go ahead\r\n").await.unwrap();
+								let mut data = String::new();
+								while let Ok(Some(line)) = lines.next_line().await {
+									if line == "." {
+										break;
+									}
+									data.push_str(&line);
+									data.push('\n');
+								}
+								received.lock().unwrap().push(data);
+								"250 queued\r\n".to_string()
+							} else if command.starts_with("QUIT") {
+								writer.write_all(b"221 bye\r\n").await.unwrap();
+								break;
+							} else {
+								"250 ok\r\n".to_string()
+							};
+							writer.write_all(reply.as_bytes()).await.unwrap();
+						}
+					});
+				}
+			});
+
+			SmtpStandIn { port, messages }
+		}
+	}
+
+	fn handler(port: u16) -> SmtpHandlerImpl {
+		let (_, recorder) = default_pair();
+		let metrics = Metrics::new(recorder);
+		metrics
+			.register_counter(EMAIL_CONSUMER_CONSUMED_MESSAGES)
+			.unwrap();
+		SmtpHandlerImpl::new(
+			SmtpOptions {
+				host: "127.0.0.1".to_string(),
+				port,
+				tls: SmtpTls::None,
+				credentials: None,
+				timeout: Duration::from_secs(5),
+				from: "Notifications <notifications@example.com>".to_string(),
+				reply_to: Some("support@example.com".to_string()),
+				subject: "New notification".to_string(),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(metrics),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn payload(recipient: &str) -> String {
+		serde_json::json!({
+			"_id": "n-1",
+			"content": "Your code is <1234>\nIt expires soon",
+			"channel": "email",
+			"recipient": { "id": recipient, "timezone_offset": "+00:00" },
+			"scheduledTime": "2024-01-01T00:00:00Z",
+			"priority": "high",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn sends_a_text_and_html_message() {
+		let server = SmtpStandIn::start("250 ok").await;
+
+		handler(server.port)
+			.handle_message(&payload("user@example.com"))
+			.await
+			.unwrap();
+
+		let messages = server.messages.lock().unwrap();
+		assert_eq!(messages.len(), 1);
+		let message = &messages[0];
+		assert!(message.contains("From: Notifications <notifications@example.com>"));
+		assert!(message.contains("Reply-To: support@example.com"));
+		assert!(message.contains("To: user@example.com"));
+		assert!(message.contains("Subject: New notification"));
+		assert!(message.contains("multipart/alternative"));
+		assert!(message.contains("Content-Type: text/plain"));
+		assert!(message.contains("Content-Type: text/html"));
+		assert!(message.contains("Your code is <1234>"));
+		assert!(message.contains("Your code is &lt;1234&gt;<br>"));
+	}
+
+	#[tokio::test]
+	async fn temporary_rejections_are_transient() {
+		let server = SmtpStandIn::start("450 mailbox busy").await;
+
+		let result = handler(server.port)
+			.handle_message(&payload("user@example.com"))
+			.await;
+		assert!(matches!(result, Err(DeliveryError::Transient(_))));
+	}
+
+	#[tokio::test]
+	async fn permanent_rejections_are_permanent() {
+		let server = SmtpStandIn::start("550 no such user").await;
+
+		let result = handler(server.port)
+			.handle_message(&payload("user@example.com"))
+			.await;
+		assert!(matches!(result, Err(DeliveryError::Permanent(_))));
+		assert!(server.messages.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn unreachable_servers_are_transient() {
+		let port = {
+			let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+			listener.local_addr().unwrap().port()
+		};
+
+		let result = handler(port)
+			.handle_message(&payload("user@example.com"))
+			.await;
+		assert!(matches!(result, Err(DeliveryError::Transient(_))));
+	}
+
+	#[tokio::test]
+	async fn malformed_notifications_are_permanent() {
+		let handler = handler(1);
+
+		for message in ["not json".to_string(), payload("not an address")] {
+			let result = handler.handle_message(&message).await;
+			assert!(matches!(result, Err(DeliveryError::Permanent(_))));
+		}
+	}
+}
@@ -0,0 +1,48 @@
+//! Email notifications over SMTP. The `email_consumer` binary hosts these
+//! handlers on their own, [`register`] adds them to any other host.
+
+use std::{fmt::Debug, sync::Arc};
+
+use common::axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder;
+use consumer::{registry::Handlers, std_out::StdOutHandlerImpl};
+
+use crate::{
+	config::{HandlerKind, Sections},
+	handler::smtp::SmtpHandlerImpl,
+	metrics::metrics::EMAIL_CONSUMER_CONSUMED_MESSAGES,
+};
+
+pub mod app_state;
+pub mod config;
+pub mod handler;
+pub mod metrics;
+
+pub const CHANNEL: &str = "email";
+
+/// Registers the email handlers under the names `name` gives them in the
+/// host's handler list. Only the configured ones are built, from `sections`.
+pub fn register<'a, H: Copy + Debug + PartialEq + 'a>(
+	handlers: Handlers<'a, H>,
+	sections: Sections<'a>,
+	recorder: Arc<PrometheusRecorder>,
+	name: fn(HandlerKind) -> H,
+) -> Handlers<'a, H> {
+	let metrics = Arc::new(metrics::metrics::setup_metrics(recorder));
+	let app_state = Arc::new(app_state::AppState {
+		metrics: metrics.clone(),
+	});
+
+	handlers
+		.register(name(HandlerKind::StdOut), CHANNEL, move || {
+			Ok(Arc::new(StdOutHandlerImpl::new(
+				metrics,
+				EMAIL_CONSUMER_CONSUMED_MESSAGES,
+			)))
+		})
+		.register(name(HandlerKind::Smtp), CHANNEL, move || {
+			Ok(Arc::new(SmtpHandlerImpl::new(
+				sections.smtp.options(),
+				app_state,
+			)?))
+		})
+}
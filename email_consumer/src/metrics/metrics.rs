@@ -10,9 +10,7 @@ pub const EMAIL_CONSUMER_CONSUMED_MESSAGES: &str = "email_consumer_consumed_mess
 pub fn setup_metrics(prometheus_recorder: Arc<PrometheusRecorder>) -> Metrics {
 	let metrics = Metrics::new(prometheus_recorder);
 	metrics
-		.register_counter(
-			EMAIL_CONSUMER_CONSUMED_MESSAGES,
-		)
+		.register_counter(EMAIL_CONSUMER_CONSUMED_MESSAGES)
 		.unwrap();
 
 	metrics
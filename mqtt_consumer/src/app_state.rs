@@ -0,0 +1,7 @@
+use std::sync::Arc;
+
+use common::monitoring::metrics::Metrics;
+
+pub struct AppState {
+	pub metrics: Arc<Metrics>,
+}
@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use rumqttc::QoS;
+use serde::Deserialize;
+
+use crate::handler::mqtt::{MqttOptions, RECIPIENT_PLACEHOLDER, tls::TlsOptions};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub mqtt:     MqttConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("mqtt_consumer"),
+			mqtt:     MqttConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Logs each message.
+	#[default]
+	#[serde(rename = "stdout")]
+	StdOut,
+	/// Publishes each message to an MQTT broker, see [`MqttConfig`].
+	#[serde(rename = "mqtt")]
+	Mqtt,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+	pub host:             String,
+	pub port:             u16,
+	/// Must be unique per broker, defaults to the host name.
+	pub client_id:        Option<String>,
+	pub username:         Option<String>,
+	pub password:         Option<String>,
+	/// `{recipient_id}` is replaced with the device id of the notification.
+	pub topic:            String,
+	/// 0, 1 or 2.
+	pub qos:              u8,
+	pub retain:           bool,
+	pub tls:              bool,
+	/// PEM files, the client certificate and key are only needed when the
+	/// broker authenticates clients.
+	pub ca_file:          String,
+	pub client_cert_file: Option<String>,
+	pub client_key_file:  Option<String>,
+	pub keep_alive_secs:  u64,
+	pub timeout_secs:     u64,
+}
+
+impl Default for MqttConfig {
+	fn default() -> Self {
+		MqttConfig {
+			host:             "localhost".to_string(),
+			port:             1883,
+			client_id:        None,
+			username:         None,
+			password:         None,
+			topic:            format!("devices/{}/notifications", RECIPIENT_PLACEHOLDER),
+			qos:              1,
+			retain:           false,
+			tls:              false,
+			ca_file:          String::new(),
+			client_cert_file: None,
+			client_key_file:  None,
+			keep_alive_secs:  30,
+			timeout_secs:     10,
+		}
+	}
+}
+
+impl MqttConfig {
+	pub fn client_id(&self) -> String {
+		self.client_id
+			.clone()
+			.or_else(|| std::env::var("HOSTNAME").ok())
+			.unwrap_or_else(|| format!("mqtt_consumer-{}", std::process::id()))
+	}
+
+	pub fn qos(&self) -> QoS {
+		match self.qos {
+			0 => QoS::AtMostOnce,
+			1 => QoS::AtLeastOnce,
+			_ => QoS::ExactlyOnce,
+		}
+	}
+
+	pub fn options(&self) -> MqttOptions {
+		let client_auth = self
+			.client_cert_file
+			.clone()
+			.zip(self.client_key_file.clone());
+		MqttOptions {
+			host:       self.host.clone(),
+			port:       self.port,
+			client_id:  self.client_id(),
+			username:   self.username.clone(),
+			password:   self.password.clone(),
+			topic:      self.topic.clone(),
+			qos:        self.qos(),
+			retain:     self.retain,
+			tls:        self.tls.then(|| TlsOptions {
+				ca_file: self.ca_file.clone(),
+				client_auth,
+			}),
+			keep_alive: Duration::from_secs(self.keep_alive_secs),
+			timeout:    Duration::from_secs(self.timeout_secs),
+		}
+	}
+
+	fn validate(&self) -> Result<(), ConfigError> {
+		if self.host.is_empty() {
+			return Err(ConfigError("mqtt.host must not be empty".to_string()));
+		}
+		require_positive("mqtt.port", self.port)?;
+		if !self.topic.contains(RECIPIENT_PLACEHOLDER) || self.topic.contains(['+', '#']) {
+			return Err(ConfigError(format!(
+				"mqtt.topic must contain {} and no wildcards",
+				RECIPIENT_PLACEHOLDER
+			)));
+		}
+		if self.qos > 2 {
+			return Err(ConfigError("mqtt.qos must be 0, 1 or 2".to_string()));
+		}
+		if self.tls && self.ca_file.is_empty() {
+			return Err(ConfigError(
+				"mqtt.ca_file must be set when tls is enabled".to_string(),
+			));
+		}
+		if self.client_cert_file.is_some() != self.client_key_file.is_some() {
+			return Err(ConfigError(
+				"mqtt.client_cert_file and mqtt.client_key_file must be set together".to_string(),
+			));
+		}
+		require_positive("mqtt.keep_alive_secs", self.keep_alive_secs)?;
+		require_positive("mqtt.timeout_secs", self.timeout_secs)
+	}
+}
+
+/// The sections the MQTT handlers are built from, also part of hosts that
+/// run other channels next to MQTT.
+pub struct Sections<'a> {
+	pub mqtt: &'a MqttConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		if consumer.uses(name(HandlerKind::Mqtt)) {
+			self.mqtt.validate()?;
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections { mqtt: &self.mqtt }
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
@@ -0,0 +1,3 @@
+pub use consumer::handler::{DeliveryError, MessageHandler};
+
+pub mod mqtt;
@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+
+use bytes::BytesMut;
+use rumqttc::{
+	ConnAck,
+	ConnectReturnCode,
+	Packet,
+	PubAck,
+	PubComp,
+	PubRec,
+	Publish,
+	QoS,
+	mqttbytes::Error,
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::{TcpListener, TcpStream},
+};
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// Client id and username of a connection.
+type Connect = (String, Option<String>);
+
+/// Local stand-in for an MQTT 3.1.1 broker. Accepts every connection,
+/// records publishes and, when `acks` is set, acknowledges them as their QoS
+/// requires.
+#[derive(Clone, Default)]
+pub struct Broker {
+	pub port:  u16,
+	acks:      bool,
+	connects:  Arc<Mutex<Vec<Connect>>>,
+	publishes: Arc<Mutex<Vec<Publish>>>,
+}
+
+impl Broker {
+	pub async fn start(acks: bool) -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let broker = Broker {
+			port: listener.local_addr().unwrap().port(),
+			acks,
+			..Broker::default()
+		};
+		let serving = broker.clone();
+		tokio::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				tokio::spawn(serving.clone().serve(stream));
+			}
+		});
+		broker
+	}
+
+	pub fn connects(&self) -> Vec<Connect> {
+		self.connects.lock().unwrap().clone()
+	}
+
+	pub fn publishes(&self) -> Vec<Publish> {
+		self.publishes.lock().unwrap().clone()
+	}
+
+	fn reply(&self, packet: Packet) -> Option<Packet> {
+		match packet {
+			Packet::Connect(connect) => {
+				self.connects
+					.lock()
+					.unwrap()
+					.push((connect.client_id, connect.login.map(|l| l.username)));
+				Some(Packet::ConnAck(ConnAck::new(
+					ConnectReturnCode::Success,
+					false,
+				)))
+			}
+			Packet::Publish(publish) => {
+				let (qos, pkid) = (publish.qos, publish.pkid);
+				self.publishes.lock().unwrap().push(publish);
+				match qos {
+					_ if !self.acks => None,
+					QoS::AtMostOnce => None,
+					QoS::AtLeastOnce => Some(Packet::PubAck(PubAck::new(pkid))),
+					QoS::ExactlyOnce => Some(Packet::PubRec(PubRec::new(pkid))),
+				}
+			}
+			Packet::PubRel(rel) => Some(Packet::PubComp(PubComp::new(rel.pkid))),
+			Packet::PingReq => Some(Packet::PingResp),
+			_ => None,
+		}
+	}
+
+	async fn serve(self, mut stream: TcpStream) {
+		let mut buf = BytesMut::new();
+		loop {
+			let packet = match Packet::read(&mut buf, MAX_PACKET_SIZE) {
+				Ok(packet) => packet,
+				Err(Error::InsufficientBytes(_)) => match stream.read_buf(&mut buf).await {
+					Ok(0) | Err(_) => return,
+					Ok(_) => continue,
+				},
+				Err(_) => return,
+			};
+			if let Some(reply) = self.reply(packet) {
+				let mut out = BytesMut::new();
+				reply.write(&mut out, MAX_PACKET_SIZE).unwrap();
+				if stream.write_all(&out).await.is_err() {
+					return;
+				}
+			}
+		}
+	}
+}
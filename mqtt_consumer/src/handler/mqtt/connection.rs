@@ -0,0 +1,106 @@
+use log::debug;
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, Outgoing, QoS};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::handler::DeliveryError;
+
+/// What the event loop reports about the publish in flight.
+enum Progress {
+	/// Written to the broker under this packet id, 0 for QoS 0.
+	Sent(u16),
+	/// `PUBACK` of a QoS 1 publish.
+	Acked(u16),
+	/// `PUBCOMP` ending a QoS 2 publish.
+	Completed(u16),
+	/// The connection is gone, the event loop stopped.
+	Failed(String),
+}
+
+/// One session with the broker. The event loop runs until the first
+/// connection error instead of reconnecting by itself, so a new connection
+/// never replays a publish that was already reported as failed.
+pub struct Connection {
+	client:     AsyncClient,
+	progress:   mpsc::UnboundedReceiver<Progress>,
+	event_loop: JoinHandle<()>,
+}
+
+impl Connection {
+	/// Connects lazily, on the first publish.
+	pub fn open(options: MqttOptions) -> Self {
+		let (client, mut event_loop) = AsyncClient::new(options, 10);
+		let (tx, progress) = mpsc::unbounded_channel();
+		let event_loop = tokio::spawn(async move {
+			loop {
+				let progress = match event_loop.poll().await {
+					Ok(Event::Outgoing(Outgoing::Publish(pkid))) => Progress::Sent(pkid),
+					Ok(Event::Incoming(Incoming::PubAck(ack))) => Progress::Acked(ack.pkid),
+					Ok(Event::Incoming(Incoming::PubComp(comp))) => Progress::Completed(comp.pkid),
+					Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+						debug!("Connected to the MQTT broker");
+						continue;
+					}
+					Ok(_) => continue,
+					Err(e) => {
+						let _ = tx.send(Progress::Failed(e.to_string()));
+						return;
+					}
+				};
+				if tx.send(progress).is_err() {
+					return;
+				}
+			}
+		});
+
+		Connection {
+			client,
+			progress,
+			event_loop,
+		}
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.event_loop.is_finished()
+	}
+
+	/// Returns once the broker took responsibility for the message, which for
+	/// QoS 0 is once it was written. Only one publish may be in flight.
+	pub async fn publish(
+		&mut self,
+		topic: String,
+		qos: QoS,
+		retain: bool,
+		payload: Vec<u8>,
+	) -> Result<(), DeliveryError> {
+		self.client
+			.publish(topic, qos, retain, payload)
+			.await
+			.map_err(|e| DeliveryError::Transient(format!("failed to queue publish: {}", e)))?;
+
+		let mut sent = None;
+		while let Some(progress) = self.progress.recv().await {
+			match (progress, sent) {
+				(Progress::Failed(e), _) => {
+					return Err(DeliveryError::Transient(format!(
+						"connection failed: {}",
+						e
+					)));
+				}
+				(Progress::Sent(_), None) if qos == QoS::AtMostOnce => return Ok(()),
+				(Progress::Sent(pkid), None) => sent = Some(pkid),
+				(Progress::Acked(pkid), Some(id)) if pkid == id && qos == QoS::AtLeastOnce => {
+					return Ok(());
+				}
+				(Progress::Completed(pkid), Some(id)) if pkid == id => return Ok(()),
+				_ => {}
+			}
+		}
+		Err(DeliveryError::Transient("connection closed".to_string()))
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		self.event_loop.abort();
+	}
+}
@@ -0,0 +1,293 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use consumer::handler::parse_notification;
+use log::{debug, info};
+use rumqttc::{QoS, TlsConfiguration, Transport, valid_topic};
+use tokio::sync::Mutex;
+
+use crate::{
+	app_state::AppState,
+	handler::{
+		DeliveryError,
+		MessageHandler,
+		mqtt::{
+			connection::Connection,
+			tls::{TlsOptions, client_config},
+		},
+	},
+	metrics::metrics::MQTT_CONSUMER_CONSUMED_MESSAGES,
+};
+
+#[cfg(test)]
+mod broker;
+pub mod connection;
+pub mod tls;
+
+/// Replaced with the recipient id in [`MqttOptions::topic`].
+pub const RECIPIENT_PLACEHOLDER: &str = "{recipient_id}";
+
+pub struct MqttOptions {
+	pub host:       String,
+	pub port:       u16,
+	pub client_id:  String,
+	pub username:   Option<String>,
+	pub password:   Option<String>,
+	/// Topic template, e.g. `devices/{recipient_id}/notifications`.
+	pub topic:      String,
+	pub qos:        QoS,
+	pub retain:     bool,
+	/// Plain TCP when unset.
+	pub tls:        Option<TlsOptions>,
+	pub keep_alive: Duration,
+	pub timeout:    Duration,
+}
+
+/// Republishes each notification to the topic of its device. One connection
+/// is opened on the first message and reused, it is reopened once the
+/// broker drops it. Messages are published one at a time and acked once the
+/// broker acknowledged them at the configured QoS.
+pub struct MqttHandlerImpl {
+	options:    rumqttc::MqttOptions,
+	topic:      String,
+	qos:        QoS,
+	retain:     bool,
+	timeout:    Duration,
+	connection: Mutex<Option<Connection>>,
+	app_state:  Arc<AppState>,
+}
+
+impl MqttHandlerImpl {
+	pub fn new(opts: MqttOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let mut options = rumqttc::MqttOptions::new(opts.client_id, opts.host, opts.port);
+		options
+			.set_keep_alive(opts.keep_alive)
+			.set_clean_session(true);
+		if let Some(username) = opts.username {
+			options.set_credentials(username, opts.password.unwrap_or_default());
+		}
+		if let Some(tls) = &opts.tls {
+			options.set_transport(Transport::tls_with_config(TlsConfiguration::Rustls(
+				client_config(tls)?,
+			)));
+		}
+
+		Ok(MqttHandlerImpl {
+			options,
+			topic: opts.topic,
+			qos: opts.qos,
+			retain: opts.retain,
+			timeout: opts.timeout,
+			connection: Mutex::new(None),
+			app_state,
+		})
+	}
+
+	/// Device ids are taken as one topic level, anything that would publish
+	/// elsewhere is rejected.
+	fn topic(&self, recipient_id: &str) -> Result<String, DeliveryError> {
+		if recipient_id.is_empty() || recipient_id.contains(['/', '+', '#']) {
+			return Err(DeliveryError::Permanent(format!(
+				"invalid device id {:?}",
+				recipient_id
+			)));
+		}
+		let topic = self.topic.replace(RECIPIENT_PLACEHOLDER, recipient_id);
+		if !valid_topic(&topic) {
+			return Err(DeliveryError::Permanent(format!(
+				"invalid topic {:?}",
+				topic
+			)));
+		}
+		Ok(topic)
+	}
+}
+
+#[async_trait]
+impl MessageHandler for MqttHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		// The message itself is published unchanged.
+		let notification = parse_notification(message)?;
+		let topic = self.topic(&notification.recipient.id)?;
+
+		let mut connection = self.connection.lock().await;
+		if connection.as_ref().is_none_or(|c| c.is_closed()) {
+			info!(
+				"Connecting to MQTT broker at {:?}",
+				self.options.broker_address()
+			);
+			*connection = Some(Connection::open(self.options.clone()));
+		}
+		let publish = connection.as_mut().unwrap().publish(
+			topic.clone(),
+			self.qos,
+			self.retain,
+			message.as_bytes().to_vec(),
+		);
+		// A connection left with a publish in flight could still ack it
+		// later, it is dropped so the next message starts clean.
+		match tokio::time::timeout(self.timeout, publish).await {
+			Ok(Ok(())) => {}
+			Ok(Err(e)) => {
+				*connection = None;
+				return Err(e);
+			}
+			Err(_) => {
+				*connection = None;
+				return Err(DeliveryError::Transient(
+					"timed out waiting for the broker".to_string(),
+				));
+			}
+		}
+
+		debug!(
+			"Published notification {} to {}",
+			notification.id.as_deref().unwrap_or("-"),
+			topic
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(MQTT_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::monitoring::server::default_pair;
+	use serde_json::json;
+
+	use super::{broker::Broker, *};
+	use crate::metrics::metrics::setup_metrics;
+
+	fn handler(port: u16, qos: QoS) -> MqttHandlerImpl {
+		let (_, recorder) = default_pair();
+		MqttHandlerImpl::new(
+			MqttOptions {
+				host: "127.0.0.1".to_string(),
+				port,
+				client_id: "notifications-test".to_string(),
+				username: Some("notifications".to_string()),
+				password: Some("secret".to_string()),
+				topic: "devices/{recipient_id}/notifications".to_string(),
+				qos,
+				retain: true,
+				tls: None,
+				keep_alive: Duration::from_secs(30),
+				timeout: Duration::from_millis(500),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(setup_metrics(recorder)),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn notification(device: &str) -> String {
+		json!({
+			"_id": "n-1",
+			"content": "Firmware update available",
+			"channel": "mqtt",
+			"recipient": {"id": device, "timezone_offset": "+00:00"},
+			"scheduledTime": "2026-10-19T08:00:00Z",
+			"priority": "normal",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn publishes_to_the_device_topic_at_every_qos() {
+		let broker = Broker::start(true).await;
+
+		for qos in [QoS::AtMostOnce, QoS::AtLeastOnce, QoS::ExactlyOnce] {
+			let handler = handler(broker.port, qos);
+			assert_eq!(
+				handler.handle_message(&notification("sensor-7")).await,
+				Ok(())
+			);
+			// The connection is reused for the next message.
+			assert_eq!(
+				handler.handle_message(&notification("sensor-8")).await,
+				Ok(())
+			);
+		}
+
+		assert_eq!(
+			broker.connects(),
+			vec![
+				(
+					"notifications-test".to_string(),
+					Some("notifications".to_string())
+				);
+				3
+			]
+		);
+		let publishes = broker.publishes();
+		assert_eq!(publishes.len(), 6);
+		assert_eq!(publishes[0].topic, "devices/sensor-7/notifications");
+		assert_eq!(publishes[1].topic, "devices/sensor-8/notifications");
+		assert!(publishes.iter().all(|p| p.retain));
+		let qos: Vec<_> = publishes.iter().map(|p| p.qos).collect();
+		assert_eq!(
+			qos,
+			[
+				QoS::AtMostOnce,
+				QoS::AtMostOnce,
+				QoS::AtLeastOnce,
+				QoS::AtLeastOnce,
+				QoS::ExactlyOnce,
+				QoS::ExactlyOnce,
+			]
+		);
+		assert_eq!(publishes[0].payload, notification("sensor-7").as_bytes());
+	}
+
+	#[tokio::test]
+	async fn missing_acks_and_lost_connections_are_transient() {
+		let broker = Broker::start(false).await;
+		let unacked = handler(broker.port, QoS::AtLeastOnce);
+		assert!(matches!(
+			unacked.handle_message(&notification("sensor-7")).await,
+			Err(DeliveryError::Transient(_))
+		));
+		// The next message gets a new connection.
+		assert!(matches!(
+			unacked.handle_message(&notification("sensor-7")).await,
+			Err(DeliveryError::Transient(_))
+		));
+		assert_eq!(broker.connects().len(), 2);
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let port = listener.local_addr().unwrap().port();
+		drop(listener);
+		assert!(matches!(
+			handler(port, QoS::AtLeastOnce)
+				.handle_message(&notification("sensor-7"))
+				.await,
+			Err(DeliveryError::Transient(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn invalid_devices_are_permanent() {
+		let broker = Broker::start(true).await;
+		let handler = handler(broker.port, QoS::AtLeastOnce);
+
+		for device in ["", "sensors/7", "sensor-+", "#"] {
+			assert!(matches!(
+				handler.handle_message(&notification(device)).await,
+				Err(DeliveryError::Permanent(_))
+			));
+		}
+		assert!(matches!(
+			handler.handle_message("not json").await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert!(broker.publishes().is_empty());
+	}
+}
@@ -0,0 +1,57 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use tokio_rustls::rustls::{
+	ClientConfig,
+	RootCertStore,
+	crypto::ring,
+	pki_types::{CertificateDer, PrivateKeyDer},
+};
+
+/// PEM files the TLS connection to the broker is set up from.
+#[derive(Clone, Debug)]
+pub struct TlsOptions {
+	/// CA the broker certificate is verified against.
+	pub ca_file:     String,
+	/// Client certificate and key, for brokers that require client
+	/// authentication.
+	pub client_auth: Option<(String, String)>,
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+	let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+	let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| format!("failed to read {}: {}", path, e))?;
+	if certs.is_empty() {
+		return Err(format!("no certificate in {}", path));
+	}
+	Ok(certs)
+}
+
+fn read_key(path: &str) -> Result<PrivateKeyDer<'static>, String> {
+	let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+	rustls_pemfile::private_key(&mut BufReader::new(file))
+		.map_err(|e| format!("failed to read {}: {}", path, e))?
+		.ok_or_else(|| format!("no private key in {}", path))
+}
+
+pub fn client_config(opts: &TlsOptions) -> Result<Arc<ClientConfig>, String> {
+	let mut roots = RootCertStore::empty();
+	for cert in read_certs(&opts.ca_file)? {
+		roots
+			.add(cert)
+			.map_err(|e| format!("invalid CA certificate in {}: {}", opts.ca_file, e))?;
+	}
+
+	let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+		.with_safe_default_protocol_versions()
+		.map_err(|e| format!("failed to set up TLS: {}", e))?
+		.with_root_certificates(roots);
+	let config = match &opts.client_auth {
+		Some((cert_file, key_file)) => builder
+			.with_client_auth_cert(read_certs(cert_file)?, read_key(key_file)?)
+			.map_err(|e| format!("invalid client certificate: {}", e))?,
+		None => builder.with_no_client_auth(),
+	};
+	Ok(Arc::new(config))
+}
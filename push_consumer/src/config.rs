@@ -0,0 +1,266 @@
+use std::time::Duration;
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::Deserialize;
+
+use crate::handler::{
+	apns::ApnsOptions,
+	fcm::{FcmOptions, ServiceAccount},
+	webpush::WebPushOptions,
+};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub fcm:      FcmConfig,
+	pub apns:     ApnsConfig,
+	pub webpush:  WebPushConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("push_consumer"),
+			fcm:      FcmConfig::default(),
+			apns:     ApnsConfig::default(),
+			webpush:  WebPushConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Logs each message.
+	#[default]
+	#[serde(rename = "stdout")]
+	StdOut,
+	/// Sends each message through Firebase Cloud Messaging, see [`FcmConfig`].
+	#[serde(rename = "fcm")]
+	Fcm,
+	/// Sends each message through Apple Push Notification service, see
+	/// [`ApnsConfig`].
+	#[serde(rename = "apns")]
+	Apns,
+	/// Sends each message to the recipient's browsers, see
+	/// [`WebPushConfig`]. Consumes the webpush stream instead of the push
+	/// one.
+	#[serde(rename = "webpush")]
+	WebPush,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FcmConfig {
+	/// Path to the service account key file downloaded from the Firebase
+	/// console.
+	pub service_account_file: String,
+	pub endpoint:             String,
+	pub title:                String,
+	pub timeout_secs:         u64,
+}
+
+impl Default for FcmConfig {
+	fn default() -> Self {
+		FcmConfig {
+			service_account_file: "service-account.json".to_string(),
+			endpoint:             "https://fcm.googleapis.com".to_string(),
+			title:                "New notification".to_string(),
+			timeout_secs:         10,
+		}
+	}
+}
+
+impl FcmConfig {
+	pub fn options(&self) -> Result<FcmOptions, String> {
+		Ok(FcmOptions {
+			service_account: ServiceAccount::from_file(&self.service_account_file)?,
+			endpoint:        self.endpoint.clone(),
+			title:           self.title.clone(),
+			timeout:         Duration::from_secs(self.timeout_secs),
+		})
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApnsConfig {
+	/// Path to the `.p8` signing key downloaded from the Apple developer
+	/// account.
+	pub key_file:     String,
+	pub key_id:       String,
+	pub team_id:      String,
+	pub topic:        String,
+	pub endpoint:     String,
+	pub title:        String,
+	pub timeout_secs: u64,
+}
+
+impl Default for ApnsConfig {
+	fn default() -> Self {
+		ApnsConfig {
+			key_file:     "apns_key.p8".to_string(),
+			key_id:       String::new(),
+			team_id:      String::new(),
+			topic:        String::new(),
+			endpoint:     "https://api.push.apple.com".to_string(),
+			title:        "New notification".to_string(),
+			timeout_secs: 10,
+		}
+	}
+}
+
+impl ApnsConfig {
+	pub fn options(&self) -> Result<ApnsOptions, String> {
+		Ok(ApnsOptions {
+			key:      std::fs::read(&self.key_file)
+				.map_err(|e| format!("failed to read {}: {}", self.key_file, e))?,
+			key_id:   self.key_id.clone(),
+			team_id:  self.team_id.clone(),
+			topic:    self.topic.clone(),
+			endpoint: self.endpoint.clone(),
+			title:    self.title.clone(),
+			timeout:  Duration::from_secs(self.timeout_secs),
+		})
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebPushConfig {
+	/// The API the subscriptions are read from and removed through.
+	pub api_url:           String,
+	/// Base64url encoded raw private key, e.g. from `npx web-push
+	/// generate-vapid-keys`. Browsers subscribe with the matching public key.
+	pub vapid_private_key: String,
+	pub vapid_subject:     String,
+	pub title:             String,
+	pub ttl_secs:          u64,
+	pub timeout_secs:      u64,
+}
+
+impl Default for WebPushConfig {
+	fn default() -> Self {
+		WebPushConfig {
+			api_url:           "http://localhost:8000".to_string(),
+			vapid_private_key: String::new(),
+			vapid_subject:     "mailto:notifications@localhost".to_string(),
+			title:             "New notification".to_string(),
+			ttl_secs:          24 * 60 * 60,
+			timeout_secs:      10,
+		}
+	}
+}
+
+impl WebPushConfig {
+	pub fn options(&self) -> WebPushOptions {
+		WebPushOptions {
+			api_url:           self.api_url.clone(),
+			vapid_private_key: self.vapid_private_key.clone(),
+			vapid_subject:     self.vapid_subject.clone(),
+			title:             self.title.clone(),
+			ttl:               Duration::from_secs(self.ttl_secs),
+			timeout:           Duration::from_secs(self.timeout_secs),
+		}
+	}
+}
+
+fn require_set(name: &str, value: &str) -> Result<(), ConfigError> {
+	if value.is_empty() {
+		return Err(ConfigError(format!("{} must not be empty", name)));
+	}
+	Ok(())
+}
+
+/// The sections the push handlers are built from, also part of hosts that
+/// run other channels next to push.
+pub struct Sections<'a> {
+	pub fcm:     &'a FcmConfig,
+	pub apns:    &'a ApnsConfig,
+	pub webpush: &'a WebPushConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		let uses = |handler| consumer.uses(name(handler));
+		if uses(HandlerKind::Fcm) {
+			require_set("fcm.service_account_file", &self.fcm.service_account_file)?;
+			require_set("fcm.endpoint", &self.fcm.endpoint)?;
+			require_positive("fcm.timeout_secs", self.fcm.timeout_secs)?;
+		}
+		if uses(HandlerKind::Apns) {
+			require_set("apns.key_file", &self.apns.key_file)?;
+			require_set("apns.key_id", &self.apns.key_id)?;
+			require_set("apns.team_id", &self.apns.team_id)?;
+			require_set("apns.topic", &self.apns.topic)?;
+			require_set("apns.endpoint", &self.apns.endpoint)?;
+			require_positive("apns.timeout_secs", self.apns.timeout_secs)?;
+		}
+		if uses(HandlerKind::WebPush) {
+			require_set("webpush.api_url", &self.webpush.api_url)?;
+			require_set("webpush.vapid_private_key", &self.webpush.vapid_private_key)?;
+			if !self.webpush.vapid_subject.starts_with("mailto:")
+				&& !self.webpush.vapid_subject.starts_with("https:")
+			{
+				return Err(ConfigError(
+					"webpush.vapid_subject must be a mailto: or https: URL".to_string(),
+				));
+			}
+			require_positive("webpush.timeout_secs", self.webpush.timeout_secs)?;
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections {
+			fcm:     &self.fcm,
+			apns:    &self.apns,
+			webpush: &self.webpush,
+		}
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
@@ -0,0 +1,336 @@
+use std::{
+	sync::{Arc, Mutex},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::debug;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler, is_urgent, parse_push, request_error},
+	metrics::metrics::PUSH_CONSUMER_CONSUMED_MESSAGES,
+};
+
+/// Apple rejects provider tokens older than an hour and throttles renewing
+/// them more often than every 20 minutes.
+const PROVIDER_TOKEN_LIFETIME: Duration = Duration::from_secs(50 * 60);
+
+pub struct ApnsOptions {
+	/// The PEM encoded `.p8` signing key.
+	pub key:      Vec<u8>,
+	pub key_id:   String,
+	pub team_id:  String,
+	/// The app bundle id, sent as `apns-topic`.
+	pub topic:    String,
+	/// `https://api.push.apple.com`, or `https://api.sandbox.push.apple.com`
+	/// for development builds of the app.
+	pub endpoint: String,
+	pub title:    String,
+	pub timeout:  Duration,
+}
+
+#[derive(Serialize)]
+struct ProviderClaims<'a> {
+	iss: &'a str,
+	iat: u64,
+}
+
+struct ProviderToken {
+	value:     String,
+	issued_at: Instant,
+}
+
+#[derive(Default, Deserialize)]
+struct ErrorResponse {
+	#[serde(default)]
+	reason: String,
+}
+
+/// Sends each notification to the APNs device token in `recipient.id` over
+/// HTTP/2, authenticated with a provider token signed by the team's key.
+pub struct ApnsHandlerImpl {
+	client:         reqwest::Client,
+	key:            EncodingKey,
+	key_id:         String,
+	team_id:        String,
+	topic:          String,
+	endpoint:       String,
+	title:          String,
+	provider_token: Mutex<Option<ProviderToken>>,
+	app_state:      Arc<AppState>,
+}
+
+impl ApnsHandlerImpl {
+	pub fn new(opts: ApnsOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let key = EncodingKey::from_ec_pem(&opts.key)
+			.map_err(|e| format!("invalid APNs signing key: {}", e))?;
+		// HTTP/2 is negotiated through ALPN, which APNs requires.
+		let client = reqwest::Client::builder()
+			.timeout(opts.timeout)
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(ApnsHandlerImpl {
+			client,
+			key,
+			key_id: opts.key_id,
+			team_id: opts.team_id,
+			topic: opts.topic,
+			endpoint: opts.endpoint.trim_end_matches('/').to_string(),
+			title: opts.title,
+			provider_token: Mutex::new(None),
+			app_state,
+		})
+	}
+
+	/// Returns the cached provider token, signing a new one when it is close
+	/// to expiring.
+	fn provider_token(&self) -> Result<String, DeliveryError> {
+		let mut cached = self.provider_token.lock().unwrap();
+		if let Some(token) = cached.as_ref()
+			&& token.issued_at.elapsed() < PROVIDER_TOKEN_LIFETIME
+		{
+			return Ok(token.value.clone());
+		}
+
+		let mut header = Header::new(Algorithm::ES256);
+		header.kid = Some(self.key_id.clone());
+		let claims = ProviderClaims {
+			iss: &self.team_id,
+			iat: SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.unwrap_or_default()
+				.as_secs(),
+		};
+		let value = jsonwebtoken::encode(&header, &claims, &self.key).map_err(|e| {
+			DeliveryError::Transient(format!("failed to sign provider token: {}", e))
+		})?;
+
+		*cached = Some(ProviderToken {
+			value:     value.clone(),
+			issued_at: Instant::now(),
+		});
+		Ok(value)
+	}
+
+	/// Maps an APNs error response onto a delivery decision, see
+	/// https://developer.apple.com/documentation/usernotifications/handling-notification-responses-from-apns.
+	fn classify(&self, status: StatusCode, body: &str) -> DeliveryError {
+		let reason = serde_json::from_str::<ErrorResponse>(body)
+			.unwrap_or_default()
+			.reason;
+		let error = format!("{} {}", status, reason);
+
+		match status {
+			// The provider token expired or was rejected. Most likely a key
+			// or team id mistake, the notification itself is fine.
+			StatusCode::FORBIDDEN => {
+				*self.provider_token.lock().unwrap() = None;
+				DeliveryError::Transient(error)
+			}
+			StatusCode::TOO_MANY_REQUESTS => DeliveryError::Transient(error),
+			// BadDeviceToken, DeviceTokenNotForTopic, Unregistered (410),
+			// PayloadTooLarge (413) and the like.
+			status if status.is_client_error() => DeliveryError::Permanent(error),
+			_ => DeliveryError::Transient(error),
+		}
+	}
+}
+
+#[async_trait]
+impl MessageHandler for ApnsHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_push(message)?;
+		let provider_token = self.provider_token()?;
+
+		let payload = json!({
+			"aps": {
+				"alert": {
+					"title": self.title,
+					"body": notification.content,
+				},
+				"sound": "default",
+			},
+			"notification_id": notification.id.clone().unwrap_or_default(),
+		});
+		let response = self
+			.client
+			.post(format!(
+				"{}/3/device/{}",
+				self.endpoint, notification.recipient.id
+			))
+			.bearer_auth(provider_token)
+			.header("apns-topic", &self.topic)
+			.header("apns-push-type", "alert")
+			.header(
+				"apns-priority",
+				if is_urgent(&notification) { "10" } else { "5" },
+			)
+			.json(&payload)
+			.send()
+			.await
+			.map_err(request_error)?;
+		let status = response.status();
+		if !status.is_success() {
+			let body = response.text().await.unwrap_or_default();
+			return Err(self.classify(status, &body));
+		}
+
+		debug!("Sent notification {:?} through APNs", notification.id);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::monitoring::{metrics::Metrics, server::default_pair};
+	use jsonwebtoken::{DecodingKey, Validation};
+	use serde_json::Value;
+
+	use super::*;
+	use crate::handler::test_server::TestServer;
+
+	const DEVICE_PATH: &str = "/3/device/device-token";
+
+	#[derive(Deserialize)]
+	struct Claims {
+		iss: String,
+	}
+
+	fn handler(server: &TestServer) -> ApnsHandlerImpl {
+		let (_, recorder) = default_pair();
+		let metrics = Metrics::new(recorder);
+		metrics
+			.register_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+			.unwrap();
+		ApnsHandlerImpl::new(
+			ApnsOptions {
+				key:      include_bytes!("testdata/apns_key.p8").to_vec(),
+				key_id:   "KEY1234567".to_string(),
+				team_id:  "TEAM123456".to_string(),
+				topic:    "com.example.app".to_string(),
+				endpoint: server.url.clone(),
+				title:    "New notification".to_string(),
+				timeout:  Duration::from_secs(5),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(metrics),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn payload(priority: &str) -> String {
+		json!({
+			"_id": "n-1",
+			"content": "Your order has shipped",
+			"channel": "push",
+			"recipient": { "id": "device-token", "timezone_offset": "+00:00" },
+			"scheduledTime": "2024-01-01T00:00:00Z",
+			"priority": priority,
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	fn apns_error(status: StatusCode, reason: &str) -> (StatusCode, Value) {
+		(status, json!({ "reason": reason }))
+	}
+
+	#[tokio::test]
+	async fn sends_with_a_provider_token() {
+		let server =
+			TestServer::start(vec![(DEVICE_PATH, vec![(StatusCode::OK, json!({}))])]).await;
+		let handler = handler(&server);
+
+		handler.handle_message(&payload("high")).await.unwrap();
+		handler.handle_message(&payload("low")).await.unwrap();
+
+		let requests = server.requests(DEVICE_PATH);
+		assert_eq!(requests.len(), 2);
+		let request = &requests[0];
+		assert_eq!(request.header("apns-topic"), Some("com.example.app"));
+		assert_eq!(request.header("apns-push-type"), Some("alert"));
+		assert_eq!(request.header("apns-priority"), Some("10"));
+		assert_eq!(requests[1].header("apns-priority"), Some("5"));
+
+		let token = request
+			.header("authorization")
+			.and_then(|v| v.strip_prefix("Bearer "))
+			.unwrap();
+		let mut validation = Validation::new(Algorithm::ES256);
+		validation.required_spec_claims.clear();
+		validation.validate_exp = false;
+		let decoded = jsonwebtoken::decode::<Claims>(
+			token,
+			&DecodingKey::from_ec_pem(include_bytes!("testdata/apns_key.pub.pem")).unwrap(),
+			&validation,
+		)
+		.unwrap();
+		assert_eq!(decoded.header.kid.as_deref(), Some("KEY1234567"));
+		assert_eq!(decoded.claims.iss, "TEAM123456");
+		assert_eq!(
+			requests[1].header("authorization"),
+			request.header("authorization")
+		);
+
+		let body = request.json();
+		assert_eq!(body["aps"]["alert"]["title"], "New notification");
+		assert_eq!(body["aps"]["alert"]["body"], "Your order has shipped");
+		assert_eq!(body["notification_id"], "n-1");
+	}
+
+	#[tokio::test]
+	async fn rejected_notifications_are_permanent() {
+		for (status, reason) in [
+			(StatusCode::BAD_REQUEST, "BadDeviceToken"),
+			(StatusCode::BAD_REQUEST, "DeviceTokenNotForTopic"),
+			(StatusCode::GONE, "Unregistered"),
+			(StatusCode::PAYLOAD_TOO_LARGE, "PayloadTooLarge"),
+		] {
+			let server =
+				TestServer::start(vec![(DEVICE_PATH, vec![apns_error(status, reason)])]).await;
+
+			let result = handler(&server).handle_message(&payload("high")).await;
+			assert!(
+				matches!(result, Err(DeliveryError::Permanent(_))),
+				"{}: {:?}",
+				reason,
+				result
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn throttling_outages_and_token_problems_are_transient() {
+		for (status, reason) in [
+			(StatusCode::TOO_MANY_REQUESTS, "TooManyRequests"),
+			(StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable"),
+			(StatusCode::INTERNAL_SERVER_ERROR, "InternalServerError"),
+			(StatusCode::FORBIDDEN, "ExpiredProviderToken"),
+		] {
+			let server =
+				TestServer::start(vec![(DEVICE_PATH, vec![apns_error(status, reason)])]).await;
+
+			let result = handler(&server).handle_message(&payload("high")).await;
+			assert!(
+				matches!(result, Err(DeliveryError::Transient(_))),
+				"{}: {:?}",
+				reason,
+				result
+			);
+		}
+	}
+}
@@ -0,0 +1,498 @@
+use std::{
+	sync::Arc,
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use common::notification::Notification;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::debug;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler, is_urgent, parse_push, request_error, retry_after},
+	metrics::metrics::PUSH_CONSUMER_CONSUMED_MESSAGES,
+};
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+/// Lifetime requested for the signed assertion, the maximum Google accepts.
+const ASSERTION_LIFETIME_SECS: u64 = 3600;
+/// Access tokens are renewed this long before they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The fields of a Google service account key file used to authenticate.
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccount {
+	#[serde(rename = "project_id")]
+	pub project_id:   String,
+	#[serde(rename = "client_email")]
+	pub client_email: String,
+	#[serde(rename = "private_key")]
+	pub private_key:  String,
+	#[serde(rename = "token_uri")]
+	pub token_uri:    String,
+}
+
+impl ServiceAccount {
+	pub fn from_file(path: &str) -> Result<Self, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("failed to read service account {}: {}", path, e))?;
+		serde_json::from_str(&contents)
+			.map_err(|e| format!("invalid service account {}: {}", path, e))
+	}
+}
+
+pub struct FcmOptions {
+	pub service_account: ServiceAccount,
+	/// Base URL of the FCM API, `https://fcm.googleapis.com` in production.
+	pub endpoint:        String,
+	pub title:           String,
+	pub timeout:         Duration,
+}
+
+#[derive(Serialize)]
+struct AssertionClaims<'a> {
+	iss:   &'a str,
+	scope: &'a str,
+	aud:   &'a str,
+	iat:   u64,
+	exp:   u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+	access_token: String,
+	expires_in:   u64,
+}
+
+struct AccessToken {
+	value:      String,
+	expires_at: Instant,
+}
+
+#[derive(Default, Deserialize)]
+struct ErrorResponse {
+	#[serde(default)]
+	error: ErrorBody,
+}
+
+#[derive(Default, Deserialize)]
+struct ErrorBody {
+	#[serde(default)]
+	status:  String,
+	#[serde(default)]
+	message: String,
+	#[serde(default)]
+	details: Vec<ErrorDetail>,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+	#[serde(rename = "errorCode")]
+	error_code: Option<String>,
+}
+
+/// Sends each notification to the FCM registration token in `recipient.id`
+/// through the HTTP v1 API, authenticated as a service account.
+pub struct FcmHandlerImpl {
+	client:          reqwest::Client,
+	service_account: ServiceAccount,
+	key:             EncodingKey,
+	send_url:        String,
+	title:           String,
+	/// Held while a new token is fetched so concurrent sends wait for it
+	/// instead of fetching their own.
+	access_token:    Mutex<Option<AccessToken>>,
+	app_state:       Arc<AppState>,
+}
+
+impl FcmHandlerImpl {
+	pub fn new(opts: FcmOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let key = EncodingKey::from_rsa_pem(opts.service_account.private_key.as_bytes())
+			.map_err(|e| format!("invalid service account private key: {}", e))?;
+		let client = reqwest::Client::builder()
+			.timeout(opts.timeout)
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(FcmHandlerImpl {
+			client,
+			send_url: format!(
+				"{}/v1/projects/{}/messages:send",
+				opts.endpoint.trim_end_matches('/'),
+				opts.service_account.project_id
+			),
+			service_account: opts.service_account,
+			key,
+			title: opts.title,
+			access_token: Mutex::new(None),
+			app_state,
+		})
+	}
+
+	/// Returns the cached OAuth access token, exchanging a freshly signed
+	/// assertion for a new one when it is about to expire. Failures are
+	/// transient: they are nearly always configuration or outages, and the
+	/// notification itself is fine.
+	async fn access_token(&self) -> Result<String, DeliveryError> {
+		let mut cached = self.access_token.lock().await;
+		if let Some(token) = cached.as_ref()
+			&& token.expires_at > Instant::now()
+		{
+			return Ok(token.value.clone());
+		}
+
+		let iat = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		let claims = AssertionClaims {
+			iss: &self.service_account.client_email,
+			scope: FCM_SCOPE,
+			aud: &self.service_account.token_uri,
+			iat,
+			exp: iat + ASSERTION_LIFETIME_SECS,
+		};
+		let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+			.map_err(|e| DeliveryError::Transient(format!("failed to sign assertion: {}", e)))?;
+
+		let response = self
+			.client
+			.post(&self.service_account.token_uri)
+			.form(&[("grant_type", JWT_BEARER_GRANT), ("assertion", &assertion)])
+			.send()
+			.await
+			.map_err(request_error)?;
+		let status = response.status();
+		if !status.is_success() {
+			let body = response.text().await.unwrap_or_default();
+			return Err(DeliveryError::Transient(format!(
+				"failed to get an access token: {} {}",
+				status, body
+			)));
+		}
+		let token: TokenResponse = response.json().await.map_err(|e| {
+			DeliveryError::Transient(format!("invalid access token response: {}", e))
+		})?;
+
+		let lifetime = Duration::from_secs(token.expires_in).saturating_sub(TOKEN_EXPIRY_MARGIN);
+		*cached = Some(AccessToken {
+			value:      token.access_token.clone(),
+			expires_at: Instant::now() + lifetime,
+		});
+		Ok(token.access_token)
+	}
+
+	fn message(&self, notification: &Notification) -> serde_json::Value {
+		json!({
+			"message": {
+				"token": notification.recipient.id,
+				"notification": {
+					"title": self.title,
+					"body": notification.content,
+				},
+				"data": {
+					"notification_id": notification.id.clone().unwrap_or_default(),
+				},
+				"android": {
+					"priority": if is_urgent(notification) { "HIGH" } else { "NORMAL" },
+				},
+			}
+		})
+	}
+
+	/// Maps an FCM error response onto a delivery decision, see
+	/// https://firebase.google.com/docs/reference/fcm/rest/v1/ErrorCode.
+	/// Throttling and outages honor the `Retry-After` FCM sends with them.
+	async fn classify(
+		&self,
+		status: StatusCode,
+		retry_after: Option<Duration>,
+		body: &str,
+	) -> DeliveryError {
+		let error = serde_json::from_str::<ErrorResponse>(body)
+			.unwrap_or_default()
+			.error;
+		let code = error
+			.details
+			.iter()
+			.find_map(|d| d.error_code.clone())
+			.unwrap_or(error.status);
+		let reason = format!("{} {}: {}", status, code, error.message);
+
+		match (status, code.as_str()) {
+			// The access token was revoked or expired early.
+			(StatusCode::UNAUTHORIZED, _) => {
+				*self.access_token.lock().await = None;
+				DeliveryError::Transient(reason)
+			}
+			(_, "UNREGISTERED" | "INVALID_ARGUMENT" | "SENDER_ID_MISMATCH") => {
+				DeliveryError::Permanent(reason)
+			}
+			(status, _) if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS => {
+				DeliveryError::Permanent(reason)
+			}
+			_ => match retry_after {
+				Some(delay) => DeliveryError::RetryAfter(delay, reason),
+				None => DeliveryError::Transient(reason),
+			},
+		}
+	}
+}
+
+#[async_trait]
+impl MessageHandler for FcmHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_push(message)?;
+		let access_token = self.access_token().await?;
+
+		let response = self
+			.client
+			.post(&self.send_url)
+			.bearer_auth(access_token)
+			.json(&self.message(&notification))
+			.send()
+			.await
+			.map_err(request_error)?;
+		let status = response.status();
+		if !status.is_success() {
+			let retry_after = retry_after(response.headers());
+			let body = response.text().await.unwrap_or_default();
+			return Err(self.classify(status, retry_after, &body).await);
+		}
+
+		debug!("Sent notification {:?} through FCM", notification.id);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::monitoring::{metrics::Metrics, server::default_pair};
+	use jsonwebtoken::{DecodingKey, Validation};
+	use reqwest::StatusCode;
+	use serde_json::Value;
+
+	use super::*;
+	use crate::handler::test_server::TestServer;
+
+	const SEND_PATH: &str = "/v1/projects/test-project/messages:send";
+
+	#[derive(Deserialize)]
+	struct Claims {
+		iss:   String,
+		scope: String,
+		aud:   String,
+	}
+
+	fn handler(server: &TestServer) -> FcmHandlerImpl {
+		let (_, recorder) = default_pair();
+		let metrics = Metrics::new(recorder);
+		metrics
+			.register_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+			.unwrap();
+		FcmHandlerImpl::new(
+			FcmOptions {
+				service_account: ServiceAccount {
+					project_id:   "test-project".to_string(),
+					client_email: "sender@test-project.iam.gserviceaccount.com".to_string(),
+					private_key:  include_str!("testdata/fcm_key.pem").to_string(),
+					token_uri:    format!("{}/token", server.url),
+				},
+				endpoint:        server.url.clone(),
+				title:           "New notification".to_string(),
+				timeout:         Duration::from_secs(5),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(metrics),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn token(value: &str) -> (StatusCode, Value) {
+		(
+			StatusCode::OK,
+			json!({ "access_token": value, "expires_in": 3600, "token_type": "Bearer" }),
+		)
+	}
+
+	fn sent() -> (StatusCode, Value) {
+		(
+			StatusCode::OK,
+			json!({ "name": "projects/test-project/messages/1" }),
+		)
+	}
+
+	fn fcm_error(status: StatusCode, code: &str) -> (StatusCode, Value) {
+		(
+			status,
+			json!({
+				"error": {
+					"code": status.as_u16(),
+					"message": "rejected",
+					"status": "FAILED",
+					"details": [{
+						"@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+						"errorCode": code,
+					}],
+				}
+			}),
+		)
+	}
+
+	fn payload() -> String {
+		json!({
+			"_id": "n-1",
+			"content": "Your order has shipped",
+			"channel": "push",
+			"recipient": { "id": "device-token", "timezone_offset": "+00:00" },
+			"scheduledTime": "2024-01-01T00:00:00Z",
+			"priority": "high",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn sends_with_a_cached_access_token() {
+		let server = TestServer::start(vec![
+			("/token", vec![token("access-1")]),
+			(SEND_PATH, vec![sent()]),
+		])
+		.await;
+		let handler = handler(&server);
+
+		handler.handle_message(&payload()).await.unwrap();
+		handler.handle_message(&payload()).await.unwrap();
+
+		let token_requests = server.requests("/token");
+		assert_eq!(token_requests.len(), 1);
+		let form = String::from_utf8(token_requests[0].body.to_vec()).unwrap();
+		let assertion = form
+			.split('&')
+			.find_map(|pair| pair.strip_prefix("assertion="))
+			.unwrap();
+		let mut validation = Validation::new(Algorithm::RS256);
+		validation.set_audience(&[format!("{}/token", server.url)]);
+		let claims = jsonwebtoken::decode::<Claims>(
+			assertion,
+			&DecodingKey::from_rsa_pem(include_bytes!("testdata/fcm_key.pub.pem")).unwrap(),
+			&validation,
+		)
+		.unwrap()
+		.claims;
+		assert_eq!(claims.iss, "sender@test-project.iam.gserviceaccount.com");
+		assert_eq!(claims.scope, FCM_SCOPE);
+		assert_eq!(claims.aud, format!("{}/token", server.url));
+
+		let sends = server.requests(SEND_PATH);
+		assert_eq!(sends.len(), 2);
+		assert_eq!(sends[0].header("authorization"), Some("Bearer access-1"));
+		let message = &sends[0].json()["message"];
+		assert_eq!(message["token"], "device-token");
+		assert_eq!(message["notification"]["title"], "New notification");
+		assert_eq!(message["notification"]["body"], "Your order has shipped");
+		assert_eq!(message["data"]["notification_id"], "n-1");
+		assert_eq!(message["android"]["priority"], "HIGH");
+	}
+
+	#[tokio::test]
+	async fn rejected_requests_are_permanent() {
+		for code in ["UNREGISTERED", "INVALID_ARGUMENT", "SENDER_ID_MISMATCH"] {
+			let server = TestServer::start(vec![
+				("/token", vec![token("access-1")]),
+				(SEND_PATH, vec![fcm_error(StatusCode::NOT_FOUND, code)]),
+			])
+			.await;
+
+			let result = handler(&server).handle_message(&payload()).await;
+			assert!(
+				matches!(result, Err(DeliveryError::Permanent(_))),
+				"{}: {:?}",
+				code,
+				result
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn throttling_and_outages_are_transient() {
+		for (status, code) in [
+			(StatusCode::TOO_MANY_REQUESTS, "QUOTA_EXCEEDED"),
+			(StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+			(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+		] {
+			let server = TestServer::start(vec![
+				("/token", vec![token("access-1")]),
+				(SEND_PATH, vec![fcm_error(status, code)]),
+			])
+			.await;
+
+			let result = handler(&server).handle_message(&payload()).await;
+			assert!(
+				matches!(result, Err(DeliveryError::Transient(_))),
+				"{}: {:?}",
+				code,
+				result
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn rejected_access_tokens_are_renewed() {
+		let server = TestServer::start(vec![
+			("/token", vec![token("access-1"), token("access-2")]),
+			(
+				SEND_PATH,
+				vec![
+					fcm_error(StatusCode::UNAUTHORIZED, "THIRD_PARTY_AUTH_ERROR"),
+					sent(),
+				],
+			),
+		])
+		.await;
+		let handler = handler(&server);
+
+		let result = handler.handle_message(&payload()).await;
+		assert!(matches!(result, Err(DeliveryError::Transient(_))));
+		handler.handle_message(&payload()).await.unwrap();
+
+		let sends = server.requests(SEND_PATH);
+		assert_eq!(sends[1].header("authorization"), Some("Bearer access-2"));
+	}
+
+	#[tokio::test]
+	async fn token_endpoint_failures_are_transient() {
+		let server = TestServer::start(vec![(
+			"/token",
+			vec![(StatusCode::BAD_REQUEST, json!({ "error": "invalid_grant" }))],
+		)])
+		.await;
+
+		let result = handler(&server).handle_message(&payload()).await;
+		assert!(matches!(result, Err(DeliveryError::Transient(_))));
+		assert!(server.requests(SEND_PATH).is_empty());
+	}
+
+	#[tokio::test]
+	async fn malformed_notifications_are_permanent() {
+		let server = TestServer::start(vec![]).await;
+
+		let result = handler(&server).handle_message("not json").await;
+		assert!(matches!(result, Err(DeliveryError::Permanent(_))));
+		assert!(server.requests("/token").is_empty());
+	}
+}
@@ -1,5 +1,33 @@
-pub mod std_out;
+use common::notification::{Notification, Priority};
+use consumer::handler::parse_notification;
+pub use consumer::handler::{DeliveryError, MessageHandler, retry_after};
 
-pub trait MessageHandler {
-	fn handle_message(&self, message: &str);
+pub mod apns;
+pub mod fcm;
+#[cfg(test)]
+mod test_server;
+pub mod webpush;
+
+/// Decodes a notification, whose recipient id is the device token or the
+/// recipient the Web Push subscriptions are stored under.
+pub(crate) fn parse_push(message: &str) -> Result<Notification, DeliveryError> {
+	let notification = parse_notification(message)?;
+	if notification.recipient.id.is_empty() {
+		return Err(DeliveryError::Permanent(
+			"notification has no device token".to_string(),
+		));
+	}
+	Ok(notification)
+}
+
+/// Critical and high priority pushes wake the device right away, the
+/// others may be batched by the provider to save power.
+pub(crate) fn is_urgent(notification: &Notification) -> bool {
+	matches!(notification.priority, Priority::Critical | Priority::High)
+}
+
+/// Errors reaching the provider at all, timeouts included, are worth
+/// another attempt.
+pub(crate) fn request_error(e: reqwest::Error) -> DeliveryError {
+	DeliveryError::Transient(format!("request failed: {}", e))
 }
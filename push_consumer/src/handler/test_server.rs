@@ -0,0 +1,109 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{Arc, Mutex},
+};
+
+use axum::{
+	Json,
+	Router,
+	body::{Bytes, to_bytes},
+	extract::{Request, State},
+	http::{HeaderMap, Method, StatusCode},
+	response::IntoResponse,
+};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+	pub method:  Method,
+	pub path:    String,
+	pub query:   Option<String>,
+	pub headers: HeaderMap,
+	pub body:    Bytes,
+}
+
+impl RecordedRequest {
+	pub fn json(&self) -> Value {
+		serde_json::from_slice(&self.body).unwrap()
+	}
+
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.get(name).and_then(|v| v.to_str().ok())
+	}
+}
+
+type Responses = Arc<Mutex<HashMap<String, VecDeque<(StatusCode, Value)>>>>;
+
+#[derive(Clone)]
+struct ServerState {
+	responses: Responses,
+	requests:  Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+/// Local stand-in for a push provider. Each path answers with its responses
+/// in order and keeps repeating the last one, unknown paths get a 404.
+pub struct TestServer {
+	pub url:  String,
+	requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl TestServer {
+	pub async fn start(routes: Vec<(&str, Vec<(StatusCode, Value)>)>) -> Self {
+		let responses: Responses = Arc::new(Mutex::new(
+			routes
+				.into_iter()
+				.map(|(path, responses)| (path.to_string(), responses.into()))
+				.collect(),
+		));
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let app = Router::new().fallback(respond).with_state(ServerState {
+			responses,
+			requests: requests.clone(),
+		});
+
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+		TestServer { url, requests }
+	}
+
+	pub fn requests(&self, path: &str) -> Vec<RecordedRequest> {
+		self.requests
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|r| r.path == path)
+			.cloned()
+			.collect()
+	}
+}
+
+async fn respond(State(state): State<ServerState>, request: Request) -> impl IntoResponse {
+	let method = request.method().clone();
+	let path = request.uri().path().to_string();
+	let query = request.uri().query().map(str::to_string);
+	let headers = request.headers().clone();
+	let body = to_bytes(request.into_body(), usize::MAX).await.unwrap();
+	state.requests.lock().unwrap().push(RecordedRequest {
+		method,
+		path: path.clone(),
+		query,
+		headers,
+		body,
+	});
+
+	let mut responses = state.responses.lock().unwrap();
+	match responses.get_mut(&path) {
+		Some(queue) if queue.len() > 1 => {
+			let (status, body) = queue.pop_front().unwrap();
+			(status, Json(body))
+		}
+		Some(queue) => {
+			let (status, body) = queue.front().cloned().unwrap();
+			(status, Json(body))
+		}
+		None => (StatusCode::NOT_FOUND, Json(Value::Null)),
+	}
+}
@@ -0,0 +1,640 @@
+use std::{
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use aes_gcm::{
+	Aes128Gcm,
+	KeyInit,
+	aead::{Aead, OsRng, rand_core::RngCore},
+};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hkdf::Hkdf;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::{debug, info, warn};
+use p256::{PublicKey, SecretKey, pkcs8::EncodePrivateKey};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler, is_urgent, parse_push, request_error, retry_after},
+	metrics::metrics::PUSH_CONSUMER_CONSUMED_MESSAGES,
+};
+
+/// Size of the single aes128gcm record a message is sent as.
+const RECORD_SIZE: u32 = 4096;
+/// Salt, record size, key id length and the 65 byte key id.
+const HEADER_LEN: usize = 16 + 4 + 1 + 65;
+/// Push services only have to accept 4096 byte bodies, which leaves this
+/// much for the payload once the header, the padding delimiter and the
+/// authentication tag are added.
+const MAX_PAYLOAD: usize = RECORD_SIZE as usize - HEADER_LEN - 1 - 16;
+/// Push services reject VAPID tokens valid for more than 24 hours.
+const VAPID_TOKEN_LIFETIME_SECS: u64 = 12 * 60 * 60;
+
+pub struct WebPushOptions {
+	/// Base URL of the API, which stores the subscriptions.
+	pub api_url:           String,
+	/// Raw P-256 private key, base64url encoded, as printed by the usual
+	/// VAPID key generators.
+	pub vapid_private_key: String,
+	/// Contact for the push services, a `mailto:` or `https:` URL.
+	pub vapid_subject:     String,
+	pub title:             String,
+	/// How long push services keep undelivered messages.
+	pub ttl:               Duration,
+	pub timeout:           Duration,
+}
+
+/// A subscription as listed by the API.
+#[derive(Deserialize)]
+struct Subscription {
+	#[serde(rename = "endpoint")]
+	endpoint: String,
+	#[serde(rename = "p256dh")]
+	p256dh:   String,
+	#[serde(rename = "auth")]
+	auth:     String,
+}
+
+#[derive(Serialize)]
+struct VapidClaims<'a> {
+	aud: &'a str,
+	exp: u64,
+	sub: &'a str,
+}
+
+enum Outcome {
+	Delivered,
+	/// The browser unsubscribed or the subscription expired.
+	Gone,
+	Failed(DeliveryError),
+}
+
+/// Sends each notification to every Web Push subscription of the recipient
+/// in `recipient.id`, encrypted per RFC 8291 and signed with VAPID (RFC
+/// 8292). Subscriptions the push service no longer knows are removed.
+pub struct WebPushHandlerImpl {
+	client:       reqwest::Client,
+	api_url:      Url,
+	vapid_key:    EncodingKey,
+	/// The uncompressed public key, base64url encoded.
+	vapid_public: String,
+	subject:      String,
+	title:        String,
+	ttl:          Duration,
+	app_state:    Arc<AppState>,
+}
+
+impl WebPushHandlerImpl {
+	pub fn new(opts: WebPushOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let api_url = Url::parse(&opts.api_url).map_err(|e| format!("invalid API URL: {}", e))?;
+		if api_url.cannot_be_a_base() {
+			return Err(format!("invalid API URL: {}", opts.api_url));
+		}
+		let secret = URL_SAFE_NO_PAD
+			.decode(opts.vapid_private_key.trim_end_matches('='))
+			.ok()
+			.and_then(|bytes| SecretKey::from_slice(&bytes).ok())
+			.ok_or_else(|| "invalid VAPID private key".to_string())?;
+		let der = secret
+			.to_pkcs8_der()
+			.map_err(|e| format!("invalid VAPID private key: {}", e))?;
+		let client = reqwest::Client::builder()
+			.timeout(opts.timeout)
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(WebPushHandlerImpl {
+			client,
+			api_url,
+			vapid_key: EncodingKey::from_ec_der(der.as_bytes()),
+			vapid_public: URL_SAFE_NO_PAD.encode(secret.public_key().to_sec1_bytes()),
+			subject: opts.vapid_subject,
+			title: opts.title,
+			ttl: opts.ttl,
+			app_state,
+		})
+	}
+
+	fn subscriptions_url(&self, recipient_id: &str) -> Url {
+		let mut url = self.api_url.clone();
+		url.path_segments_mut()
+			.expect("checked in new")
+			.pop_if_empty()
+			.extend([
+				"api",
+				"v1",
+				"recipients",
+				recipient_id,
+				"webpush-subscriptions",
+			]);
+		url
+	}
+
+	async fn subscriptions(&self, recipient_id: &str) -> Result<Vec<Subscription>, DeliveryError> {
+		let response = self
+			.client
+			.get(self.subscriptions_url(recipient_id))
+			.send()
+			.await
+			.map_err(request_error)?;
+		if !response.status().is_success() {
+			return Err(DeliveryError::Transient(format!(
+				"failed to list subscriptions: {}",
+				response.status()
+			)));
+		}
+		response
+			.json()
+			.await
+			.map_err(|e| DeliveryError::Transient(format!("invalid subscriptions response: {}", e)))
+	}
+
+	/// Best effort, a subscription left behind is removed the next time a
+	/// push to it fails.
+	async fn remove(&self, recipient_id: &str, endpoint: &str) {
+		let mut url = self.subscriptions_url(recipient_id);
+		url.query_pairs_mut().append_pair("endpoint", endpoint);
+		match self.client.delete(url).send().await {
+			Ok(response) if response.status().is_success() => {
+				info!("Removed expired web push subscription of {}", recipient_id)
+			}
+			// Already removed, e.g. by another replica.
+			Ok(response) if response.status() == StatusCode::NOT_FOUND => {}
+			Ok(response) => warn!(
+				"Failed to remove web push subscription of {}: {}",
+				recipient_id,
+				response.status()
+			),
+			Err(e) => warn!(
+				"Failed to remove web push subscription of {}: {}",
+				recipient_id, e
+			),
+		}
+	}
+
+	/// `t` is a JWT for the origin of the push service, `k` the key it is
+	/// verified with.
+	fn vapid_authorization(&self, endpoint: &Url) -> Result<String, DeliveryError> {
+		let audience = endpoint.origin().ascii_serialization();
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		let claims = VapidClaims {
+			aud: &audience,
+			exp: now + VAPID_TOKEN_LIFETIME_SECS,
+			sub: &self.subject,
+		};
+		let token = jsonwebtoken::encode(&Header::new(Algorithm::ES256), &claims, &self.vapid_key)
+			.map_err(|e| DeliveryError::Transient(format!("failed to sign VAPID token: {}", e)))?;
+		Ok(format!("vapid t={}, k={}", token, self.vapid_public))
+	}
+
+	async fn send(
+		&self,
+		subscription: &Subscription,
+		payload: &[u8],
+		urgent: bool,
+	) -> Result<Outcome, DeliveryError> {
+		let endpoint = Url::parse(&subscription.endpoint)
+			.map_err(|e| DeliveryError::Permanent(format!("invalid endpoint: {}", e)))?;
+		let p256dh = decode_key(&subscription.p256dh)?;
+		let auth = decode_key(&subscription.auth)?;
+
+		let mut salt = [0u8; 16];
+		OsRng.fill_bytes(&mut salt);
+		let body = encrypt(
+			payload,
+			&p256dh,
+			&auth,
+			&SecretKey::random(&mut OsRng),
+			&salt,
+		)?;
+
+		let response = self
+			.client
+			.post(endpoint.clone())
+			.header("Authorization", self.vapid_authorization(&endpoint)?)
+			.header("Content-Encoding", "aes128gcm")
+			.header("Content-Type", "application/octet-stream")
+			.header("TTL", self.ttl.as_secs().to_string())
+			.header("Urgency", if urgent { "high" } else { "normal" })
+			.body(body)
+			.send()
+			.await
+			.map_err(request_error)?;
+
+		let status = response.status();
+		let error = || {
+			format!(
+				"{} from {}",
+				status,
+				endpoint.origin().ascii_serialization()
+			)
+		};
+		Ok(match status {
+			status if status.is_success() => Outcome::Delivered,
+			StatusCode::NOT_FOUND | StatusCode::GONE => Outcome::Gone,
+			StatusCode::TOO_MANY_REQUESTS => {
+				Outcome::Failed(match retry_after(response.headers()) {
+					Some(delay) => DeliveryError::RetryAfter(delay, error()),
+					None => DeliveryError::Transient(error()),
+				})
+			}
+			// Payload too large, or a subscription made with another VAPID
+			// key.
+			status if status.is_client_error() => {
+				Outcome::Failed(DeliveryError::Permanent(error()))
+			}
+			_ => Outcome::Failed(DeliveryError::Transient(error())),
+		})
+	}
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>, DeliveryError> {
+	URL_SAFE_NO_PAD
+		.decode(key.trim_end_matches('='))
+		.map_err(|e| DeliveryError::Permanent(format!("invalid subscription key: {}", e)))
+}
+
+/// Encrypts `payload` as a single aes128gcm record (RFC 8188) with the keys
+/// derived as in RFC 8291 from the subscription's `ua_public` key and
+/// `auth_secret`, the ephemeral `as_secret` and `salt`.
+fn encrypt(
+	payload: &[u8],
+	ua_public: &[u8],
+	auth_secret: &[u8],
+	as_secret: &SecretKey,
+	salt: &[u8; 16],
+) -> Result<Vec<u8>, DeliveryError> {
+	if payload.len() > MAX_PAYLOAD {
+		return Err(DeliveryError::Permanent(format!(
+			"payload of {} bytes exceeds the {} bytes push services accept",
+			payload.len(),
+			MAX_PAYLOAD
+		)));
+	}
+	let ua_key = PublicKey::from_sec1_bytes(ua_public)
+		.map_err(|_| DeliveryError::Permanent("invalid p256dh key".to_string()))?;
+	let ua_public = ua_key.to_sec1_bytes();
+	let as_public = as_secret.public_key().to_sec1_bytes();
+	let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_key.as_affine());
+	let invalid = |_| DeliveryError::Permanent("failed to derive keys".to_string());
+
+	let mut key_info = b"WebPush: info\0".to_vec();
+	key_info.extend_from_slice(&ua_public);
+	key_info.extend_from_slice(&as_public);
+	let mut ikm = [0u8; 32];
+	Hkdf::<Sha256>::new(Some(auth_secret), shared.raw_secret_bytes())
+		.expand(&key_info, &mut ikm)
+		.map_err(invalid)?;
+
+	let prk = Hkdf::<Sha256>::new(Some(salt), &ikm);
+	let mut cek = [0u8; 16];
+	prk.expand(b"Content-Encoding: aes128gcm\0", &mut cek)
+		.map_err(invalid)?;
+	let mut nonce = [0u8; 12];
+	prk.expand(b"Content-Encoding: nonce\0", &mut nonce)
+		.map_err(invalid)?;
+
+	// 0x02 marks the last and only record, no further padding.
+	let mut plaintext = payload.to_vec();
+	plaintext.push(2);
+	let ciphertext = Aes128Gcm::new(&cek.into())
+		.encrypt(&nonce.into(), plaintext.as_slice())
+		.map_err(|_| DeliveryError::Permanent("failed to encrypt payload".to_string()))?;
+
+	let mut body = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+	body.extend_from_slice(salt);
+	body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+	body.push(as_public.len() as u8);
+	body.extend_from_slice(&as_public);
+	body.extend_from_slice(&ciphertext);
+	Ok(body)
+}
+
+#[async_trait]
+impl MessageHandler for WebPushHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_push(message)?;
+		let recipient_id = &notification.recipient.id;
+		let payload = json!({
+			"title": self.title,
+			"body": notification.content,
+			"notification_id": notification.id.clone().unwrap_or_default(),
+		})
+		.to_string();
+
+		let subscriptions = self.subscriptions(recipient_id).await?;
+		let mut delivered = 0;
+		let mut transient = None;
+		let mut permanent = None;
+		for subscription in &subscriptions {
+			let outcome = self
+				.send(subscription, payload.as_bytes(), is_urgent(&notification))
+				.await
+				.unwrap_or_else(Outcome::Failed);
+			match outcome {
+				Outcome::Delivered => delivered += 1,
+				Outcome::Gone => self.remove(recipient_id, &subscription.endpoint).await,
+				Outcome::Failed(e) => {
+					warn!(
+						"Failed to push notification {:?} to a browser of {}: {}",
+						notification.id, recipient_id, e
+					);
+					match e {
+						DeliveryError::Transient(_) | DeliveryError::RetryAfter(..) => {
+							transient = transient.or(Some(e))
+						}
+						DeliveryError::Permanent(_) => permanent = permanent.or(Some(e)),
+					}
+				}
+			}
+		}
+
+		// One browser showing the notification is enough, retrying would
+		// show it again in the others.
+		if delivered == 0 {
+			return Err(transient.or(permanent).unwrap_or_else(|| {
+				DeliveryError::Permanent(format!("{} has no web push subscriptions", recipient_id))
+			}));
+		}
+
+		debug!(
+			"Sent notification {:?} to {} of {} browsers",
+			notification.id,
+			delivered,
+			subscriptions.len()
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::http::Method;
+	use common::monitoring::{metrics::Metrics, server::default_pair};
+	use jsonwebtoken::{DecodingKey, Validation};
+	use serde_json::Value;
+
+	use super::*;
+	use crate::handler::test_server::TestServer;
+
+	// Keys and result of the example in RFC 8291, appendix A.
+	const AS_PRIVATE: &str = "yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw";
+	const UA_PUBLIC: &str =
+		"BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4";
+	const AUTH_SECRET: &str = "BTBZMqHH6r4Tts7J_aSIgg";
+	const SALT: &str = "DGv6ra1nlYgDCS1FRnbzlw";
+	const ENCRYPTED: &str = "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN";
+
+	const SUBSCRIPTIONS_PATH: &str = "/api/v1/recipients/user-1/webpush-subscriptions";
+
+	#[derive(Deserialize)]
+	struct Claims {
+		aud: String,
+		sub: String,
+	}
+
+	fn decode(value: &str) -> Vec<u8> {
+		URL_SAFE_NO_PAD.decode(value).unwrap()
+	}
+
+	fn handler(api: &TestServer) -> WebPushHandlerImpl {
+		let (_, recorder) = default_pair();
+		let metrics = Metrics::new(recorder);
+		metrics
+			.register_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
+			.unwrap();
+		WebPushHandlerImpl::new(
+			WebPushOptions {
+				api_url:           api.url.clone(),
+				vapid_private_key: AS_PRIVATE.to_string(),
+				vapid_subject:     "mailto:ops@example.com".to_string(),
+				title:             "New notification".to_string(),
+				ttl:               Duration::from_secs(3600),
+				timeout:           Duration::from_secs(5),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(metrics),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn subscription(endpoint: String) -> Value {
+		json!({
+			"_id": "s-1",
+			"recipientId": "user-1",
+			"endpoint": endpoint,
+			"p256dh": UA_PUBLIC,
+			"auth": AUTH_SECRET,
+			"createdAt": "2024-01-01T00:00:00Z",
+		})
+	}
+
+	async fn api(subscriptions: Vec<Value>) -> TestServer {
+		TestServer::start(vec![(
+			SUBSCRIPTIONS_PATH,
+			vec![(StatusCode::OK, Value::Array(subscriptions))],
+		)])
+		.await
+	}
+
+	fn payload() -> String {
+		json!({
+			"_id": "n-1",
+			"content": "Your order has shipped",
+			"channel": "webpush",
+			"recipient": { "id": "user-1", "timezone_offset": "+00:00" },
+			"scheduledTime": "2024-01-01T00:00:00Z",
+			"priority": "normal",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[test]
+	fn encrypts_the_rfc_8291_example() {
+		let as_secret = SecretKey::from_slice(&decode(AS_PRIVATE)).unwrap();
+		let salt: [u8; 16] = decode(SALT).try_into().unwrap();
+
+		let body = encrypt(
+			b"When I grow up, I want to be a watermelon",
+			&decode(UA_PUBLIC),
+			&decode(AUTH_SECRET),
+			&as_secret,
+			&salt,
+		)
+		.unwrap();
+		assert_eq!(URL_SAFE_NO_PAD.encode(body), ENCRYPTED);
+	}
+
+	#[tokio::test]
+	async fn pushes_to_every_subscription_with_vapid() {
+		let push = TestServer::start(vec![
+			("/send/a", vec![(StatusCode::CREATED, json!({}))]),
+			("/send/b", vec![(StatusCode::CREATED, json!({}))]),
+		])
+		.await;
+		let api = api(vec![
+			subscription(format!("{}/send/a", push.url)),
+			subscription(format!("{}/send/b", push.url)),
+		])
+		.await;
+
+		handler(&api).handle_message(&payload()).await.unwrap();
+
+		assert_eq!(push.requests("/send/b").len(), 1);
+		let request = &push.requests("/send/a")[0];
+		assert_eq!(request.header("content-encoding"), Some("aes128gcm"));
+		assert_eq!(request.header("ttl"), Some("3600"));
+		assert_eq!(request.header("urgency"), Some("normal"));
+		assert_eq!(request.body[16..20], RECORD_SIZE.to_be_bytes());
+		assert_eq!(request.body[20], 65);
+
+		let authorization = request.header("authorization").unwrap();
+		let (token, key) = authorization
+			.strip_prefix("vapid t=")
+			.and_then(|rest| rest.split_once(", k="))
+			.unwrap();
+		let key = decode(key);
+		let verifying_key = DecodingKey::from_ec_components(
+			&URL_SAFE_NO_PAD.encode(&key[1..33]),
+			&URL_SAFE_NO_PAD.encode(&key[33..65]),
+		)
+		.unwrap();
+		let mut validation = Validation::new(Algorithm::ES256);
+		validation.set_audience(&[push.url.as_str()]);
+		let claims = jsonwebtoken::decode::<Claims>(token, &verifying_key, &validation)
+			.unwrap()
+			.claims;
+		assert_eq!(claims.aud, push.url);
+		assert_eq!(claims.sub, "mailto:ops@example.com");
+	}
+
+	#[tokio::test]
+	async fn expired_subscriptions_are_removed() {
+		let push = TestServer::start(vec![
+			("/send/gone", vec![(StatusCode::GONE, json!({}))]),
+			("/send/ok", vec![(StatusCode::CREATED, json!({}))]),
+		])
+		.await;
+		let api = api(vec![
+			subscription(format!("{}/send/gone", push.url)),
+			subscription(format!("{}/send/ok", push.url)),
+		])
+		.await;
+
+		handler(&api).handle_message(&payload()).await.unwrap();
+
+		let removed: Vec<_> = api
+			.requests(SUBSCRIPTIONS_PATH)
+			.into_iter()
+			.filter(|r| r.method == Method::DELETE)
+			.collect();
+		assert_eq!(removed.len(), 1);
+		assert_eq!(
+			removed[0].query.as_deref(),
+			Some(
+				format!(
+					"endpoint={}",
+					url_encode(&format!("{}/send/gone", push.url))
+				)
+				.as_str()
+			)
+		);
+	}
+
+	fn url_encode(value: &str) -> String {
+		Url::parse_with_params("http://localhost", [("v", value)])
+			.unwrap()
+			.query()
+			.unwrap()
+			.trim_start_matches("v=")
+			.to_string()
+	}
+
+	#[tokio::test]
+	async fn failures_without_any_delivery_decide_the_outcome() {
+		let push = TestServer::start(vec![
+			("/send/gone", vec![(StatusCode::NOT_FOUND, json!({}))]),
+			(
+				"/send/busy",
+				vec![(StatusCode::SERVICE_UNAVAILABLE, json!({}))],
+			),
+			(
+				"/send/large",
+				vec![(StatusCode::PAYLOAD_TOO_LARGE, json!({}))],
+			),
+		])
+		.await;
+
+		let cases = [
+			(vec![], false),
+			(vec!["/send/gone"], false),
+			(vec!["/send/large"], false),
+			(vec!["/send/large", "/send/busy"], true),
+		];
+		for (paths, transient) in cases {
+			let subscriptions = paths
+				.iter()
+				.map(|path| subscription(format!("{}{}", push.url, path)))
+				.collect();
+			let api = api(subscriptions).await;
+
+			let result = handler(&api).handle_message(&payload()).await;
+			if transient {
+				assert!(
+					matches!(result, Err(DeliveryError::Transient(_))),
+					"{:?}",
+					paths
+				);
+			} else {
+				assert!(
+					matches!(result, Err(DeliveryError::Permanent(_))),
+					"{:?}",
+					paths
+				);
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn api_failures_are_transient() {
+		let api = TestServer::start(vec![(
+			SUBSCRIPTIONS_PATH,
+			vec![(StatusCode::INTERNAL_SERVER_ERROR, json!({}))],
+		)])
+		.await;
+
+		let result = handler(&api).handle_message(&payload()).await;
+		assert!(matches!(result, Err(DeliveryError::Transient(_))));
+	}
+
+	#[test]
+	fn oversized_payloads_are_permanent() {
+		let as_secret = SecretKey::from_slice(&decode(AS_PRIVATE)).unwrap();
+		let result = encrypt(
+			&vec![b'a'; MAX_PAYLOAD + 1],
+			&decode(UA_PUBLIC),
+			&decode(AUTH_SECRET),
+			&as_secret,
+			&[0; 16],
+		);
+		assert!(matches!(result, Err(DeliveryError::Permanent(_))));
+	}
+}
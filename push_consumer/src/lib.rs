@@ -0,0 +1,59 @@
+//! Push notifications through FCM, APNs and Web Push. The `push_consumer`
+//! binary hosts these handlers on their own, [`register`] adds them to any
+//! other host.
+
+use std::{fmt::Debug, sync::Arc};
+
+use common::axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder;
+use consumer::{registry::Handlers, std_out::StdOutHandlerImpl};
+
+use crate::{
+	config::{HandlerKind, Sections},
+	handler::{apns::ApnsHandlerImpl, fcm::FcmHandlerImpl, webpush::WebPushHandlerImpl},
+	metrics::metrics::PUSH_CONSUMER_CONSUMED_MESSAGES,
+};
+
+pub mod app_state;
+pub mod config;
+pub mod handler;
+pub mod metrics;
+
+/// Registers the push handlers under the names `name` gives them in the
+/// host's handler list. Only the configured ones are built, from `sections`.
+pub fn register<'a, H: Copy + Debug + PartialEq + 'a>(
+	handlers: Handlers<'a, H>,
+	sections: Sections<'a>,
+	recorder: Arc<PrometheusRecorder>,
+	name: fn(HandlerKind) -> H,
+) -> Handlers<'a, H> {
+	let metrics = Arc::new(metrics::metrics::setup_metrics(recorder));
+	let app_state = Arc::new(app_state::AppState {
+		metrics: metrics.clone(),
+	});
+
+	// Web Push runs on its own stream, so it can run next to one of the
+	// others.
+	let fcm_state = app_state.clone();
+	let apns_state = app_state.clone();
+	handlers
+		.register(name(HandlerKind::StdOut), "push", move || {
+			Ok(Arc::new(StdOutHandlerImpl::new(
+				metrics,
+				PUSH_CONSUMER_CONSUMED_MESSAGES,
+			)))
+		})
+		.register(name(HandlerKind::Fcm), "push", move || {
+			let opts = sections.fcm.options()?;
+			Ok(Arc::new(FcmHandlerImpl::new(opts, fcm_state)?))
+		})
+		.register(name(HandlerKind::Apns), "push", move || {
+			let opts = sections.apns.options()?;
+			Ok(Arc::new(ApnsHandlerImpl::new(opts, apns_state)?))
+		})
+		.register(name(HandlerKind::WebPush), "webpush", move || {
+			Ok(Arc::new(WebPushHandlerImpl::new(
+				sections.webpush.options(),
+				app_state,
+			)?))
+		})
+}
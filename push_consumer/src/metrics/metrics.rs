@@ -10,9 +10,7 @@ pub const PUSH_CONSUMER_CONSUMED_MESSAGES: &str = "push_consumer_consumed_messag
 pub fn setup_metrics(prometheus_recorder: Arc<PrometheusRecorder>) -> Metrics {
 	let metrics = Metrics::new(prometheus_recorder);
 	metrics
-		.register_counter(
-			PUSH_CONSUMER_CONSUMED_MESSAGES,
-		)
+		.register_counter(PUSH_CONSUMER_CONSUMED_MESSAGES)
 		.unwrap();
 
 	metrics
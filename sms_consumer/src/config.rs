@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::Deserialize;
+
+use crate::handler::{http::HttpGatewayOptions, smpp::SmppOptions};
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub smpp:     SmppConfig,
+	pub http:     HttpGatewayConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("sms_consumer"),
+			smpp:     SmppConfig::default(),
+			http:     HttpGatewayConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Logs each message.
+	#[default]
+	#[serde(rename = "stdout")]
+	StdOut,
+	/// Submits each message to an SMSC, see [`SmppConfig`].
+	#[serde(rename = "smpp")]
+	Smpp,
+	/// Posts each message to an HTTP SMS gateway, see [`HttpGatewayConfig`].
+	#[serde(rename = "http")]
+	Http,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmppConfig {
+	pub host:              String,
+	pub port:              u16,
+	pub system_id:         String,
+	pub password:          String,
+	pub system_type:       String,
+	/// Phone number, short code or alphanumeric sender id.
+	pub source_addr:       String,
+	pub delivery_receipts: bool,
+	/// Longer texts are rejected instead of sent in more segments.
+	pub max_segments:      usize,
+	pub timeout_secs:      u64,
+	pub enquire_link_secs: u64,
+}
+
+impl Default for SmppConfig {
+	fn default() -> Self {
+		SmppConfig {
+			host:              "localhost".to_string(),
+			port:              2775,
+			system_id:         String::new(),
+			password:          String::new(),
+			system_type:       String::new(),
+			source_addr:       String::new(),
+			delivery_receipts: true,
+			max_segments:      6,
+			timeout_secs:      10,
+			enquire_link_secs: 30,
+		}
+	}
+}
+
+impl SmppConfig {
+	pub fn options(&self) -> SmppOptions {
+		SmppOptions {
+			address:               format!("{}:{}", self.host, self.port),
+			system_id:             self.system_id.clone(),
+			password:              self.password.clone(),
+			system_type:           self.system_type.clone(),
+			source_addr:           self.source_addr.clone(),
+			delivery_receipts:     self.delivery_receipts,
+			max_segments:          self.max_segments,
+			timeout:               Duration::from_secs(self.timeout_secs),
+			enquire_link_interval: Duration::from_secs(self.enquire_link_secs),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpGatewayConfig {
+	pub url:          String,
+	pub token:        Option<String>,
+	pub from:         String,
+	/// Longer texts are rejected instead of sent in more segments.
+	pub max_segments: usize,
+	pub timeout_secs: u64,
+}
+
+impl Default for HttpGatewayConfig {
+	fn default() -> Self {
+		HttpGatewayConfig {
+			url:          String::new(),
+			token:        None,
+			from:         String::new(),
+			max_segments: 6,
+			timeout_secs: 10,
+		}
+	}
+}
+
+impl HttpGatewayConfig {
+	pub fn options(&self) -> HttpGatewayOptions {
+		HttpGatewayOptions {
+			url:          self.url.clone(),
+			token:        self.token.clone(),
+			from:         self.from.clone(),
+			max_segments: self.max_segments,
+			timeout:      Duration::from_secs(self.timeout_secs),
+		}
+	}
+}
+
+fn require_set(name: &str, value: &str) -> Result<(), ConfigError> {
+	if value.is_empty() {
+		return Err(ConfigError(format!("{} must not be empty", name)));
+	}
+	Ok(())
+}
+
+/// The UDH numbers segments in a single octet.
+fn require_segments(name: &str, value: usize) -> Result<(), ConfigError> {
+	if !(1..=255).contains(&value) {
+		return Err(ConfigError(format!("{} must be between 1 and 255", name)));
+	}
+	Ok(())
+}
+
+/// The sections the SMS handlers are built from, also part of hosts that
+/// run other channels next to SMS.
+pub struct Sections<'a> {
+	pub smpp: &'a SmppConfig,
+	pub http: &'a HttpGatewayConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		if consumer.uses(name(HandlerKind::Smpp)) {
+			require_set("smpp.host", &self.smpp.host)?;
+			require_positive("smpp.port", self.smpp.port)?;
+			require_set("smpp.system_id", &self.smpp.system_id)?;
+			require_set("smpp.source_addr", &self.smpp.source_addr)?;
+			require_segments("smpp.max_segments", self.smpp.max_segments)?;
+			require_positive("smpp.timeout_secs", self.smpp.timeout_secs)?;
+			require_positive("smpp.enquire_link_secs", self.smpp.enquire_link_secs)?;
+		}
+		if consumer.uses(name(HandlerKind::Http)) {
+			require_set("http.url", &self.http.url)?;
+			require_set("http.from", &self.http.from)?;
+			require_segments("http.max_segments", self.http.max_segments)?;
+			require_positive("http.timeout_secs", self.http.timeout_secs)?;
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections {
+			smpp: &self.smpp,
+			http: &self.http,
+		}
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn consumes_every_phone_number_by_default() {
+		let shipped: Config = common::config::load_from(Some("config.toml"), Vec::new()).unwrap();
+		// Like compose, which only names the consumer.
+		let env_only: Config = common::config::load_from(
+			None,
+			vec![(
+				"APP__CONSUMER__RECIPIENT_ID".to_string(),
+				"consumer1".to_string(),
+			)],
+		)
+		.unwrap();
+		for config in [Config::default(), shipped, env_only] {
+			config.validate().unwrap();
+			assert_eq!(config.consumer.filter_subject("sms"), "notifications_sms.*");
+		}
+	}
+}
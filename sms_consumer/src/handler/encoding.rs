@@ -0,0 +1,170 @@
+/// GSM 03.38 default alphabet, indexed by septet.
+const GSM7_BASIC: [char; 128] = [
+	'@', '£', '$', '¥', 'è', 'é', 'ù', 'ì', 'ò', 'Ç', '\n', 'Ø', 'ø', '\r', 'Å', 'å', //
+	'Δ', '_', 'Φ', 'Γ', 'Λ', 'Ω', 'Π', 'Ψ', 'Σ', 'Θ', 'Ξ', '\u{1b}', 'Æ', 'æ', 'ß', 'É', //
+	' ', '!', '"', '#', '¤', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', //
+	'0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?', //
+	'¡', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', //
+	'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'Ä', 'Ö', 'Ñ', 'Ü', '§', //
+	'¿', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', //
+	'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'ä', 'ö', 'ñ', 'ü', 'à', //
+];
+
+/// Characters of the extension table, sent as escape plus septet.
+const GSM7_EXTENSION: [(char, u8); 10] = [
+	('\u{c}', 0x0a),
+	('^', 0x14),
+	('{', 0x28),
+	('}', 0x29),
+	('\\', 0x2f),
+	('[', 0x3c),
+	('~', 0x3d),
+	(']', 0x3e),
+	('|', 0x40),
+	('€', 0x65),
+];
+
+const GSM7_ESCAPE: u8 = 0x1b;
+
+/// Septets or UTF-16 code units fitting a single message, and a segment of
+/// a concatenated one, which loses room to the user data header.
+const GSM7_SINGLE: usize = 160;
+const GSM7_SEGMENT: usize = 153;
+const UCS2_SINGLE: usize = 70;
+const UCS2_SEGMENT: usize = 67;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataCoding {
+	/// The GSM default alphabet, one unpacked septet per octet.
+	Gsm7,
+	/// UTF-16BE, for anything the default alphabet cannot express.
+	Ucs2,
+}
+
+impl DataCoding {
+	/// The SMPP `data_coding` value.
+	pub fn smpp_value(&self) -> u8 {
+		match self {
+			DataCoding::Gsm7 => 0x00,
+			DataCoding::Ucs2 => 0x08,
+		}
+	}
+}
+
+/// A text split into the segments it is sent as, without user data headers.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Encoded {
+	pub coding:   DataCoding,
+	pub segments: Vec<Vec<u8>>,
+}
+
+fn gsm7_char(c: char) -> Option<Vec<u8>> {
+	if c == '\u{1b}' {
+		return None;
+	}
+	if let Some(septet) = GSM7_BASIC.iter().position(|&b| b == c) {
+		return Some(vec![septet as u8]);
+	}
+	GSM7_EXTENSION
+		.iter()
+		.find(|(e, _)| *e == c)
+		.map(|(_, septet)| vec![GSM7_ESCAPE, *septet])
+}
+
+/// Uses GSM-7 when every character has a septet, UCS-2 otherwise. Escape
+/// sequences and surrogate pairs are never split across segments.
+pub fn encode(text: &str) -> Encoded {
+	let (coding, units, single, segment): (_, Vec<Vec<u8>>, _, _) =
+		match text.chars().map(gsm7_char).collect::<Option<Vec<_>>>() {
+			Some(septets) => (DataCoding::Gsm7, septets, GSM7_SINGLE, GSM7_SEGMENT),
+			None => (
+				DataCoding::Ucs2,
+				text.chars()
+					.map(|c| {
+						let mut buf = [0u16; 2];
+						c.encode_utf16(&mut buf)
+							.iter()
+							.flat_map(|unit| unit.to_be_bytes())
+							.collect()
+					})
+					.collect(),
+				UCS2_SINGLE,
+				UCS2_SEGMENT,
+			),
+		};
+	// Both limits count septets or code units, a UCS-2 unit is two bytes.
+	let unit_len = match coding {
+		DataCoding::Gsm7 => 1,
+		DataCoding::Ucs2 => 2,
+	};
+
+	let total: usize = units.iter().map(Vec::len).sum::<usize>() / unit_len;
+	if total <= single {
+		return Encoded {
+			coding,
+			segments: vec![units.concat()],
+		};
+	}
+
+	let mut segments = vec![];
+	let mut current: Vec<u8> = vec![];
+	for unit in units {
+		if (current.len() + unit.len()) / unit_len > segment {
+			segments.push(std::mem::take(&mut current));
+		}
+		current.extend(unit);
+	}
+	segments.push(current);
+	Encoded { coding, segments }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn short_gsm_texts_fit_one_message() {
+		let encoded = encode(&"a".repeat(160));
+		assert_eq!(encoded.coding, DataCoding::Gsm7);
+		assert_eq!(encoded.segments.len(), 1);
+
+		// 'ó' has no septet in the default alphabet.
+		assert_eq!(encode("Código").coding, DataCoding::Ucs2);
+		let encoded = encode("Price: 5€ [x]");
+		assert_eq!(encoded.segments[0].len(), 16);
+		assert_eq!(&encoded.segments[0][8..10], &[GSM7_ESCAPE, 0x65]);
+	}
+
+	#[test]
+	fn long_gsm_texts_are_split_without_breaking_escapes() {
+		let encoded = encode(&"a".repeat(161));
+		let lens: Vec<_> = encoded.segments.iter().map(Vec::len).collect();
+		assert_eq!(lens, vec![153, 8]);
+
+		// The escape would be the 153rd septet, its character moves on.
+		let text = format!("{}€{}", "a".repeat(152), "b".repeat(10));
+		let encoded = encode(&text);
+		assert_eq!(encoded.segments[0].len(), 152);
+		assert_eq!(&encoded.segments[1][..2], &[GSM7_ESCAPE, 0x65]);
+	}
+
+	#[test]
+	fn ucs2_counts_code_units_and_keeps_surrogate_pairs() {
+		let encoded = encode(&"ж".repeat(70));
+		assert_eq!(encoded.coding, DataCoding::Ucs2);
+		assert_eq!(encoded.segments, vec![[0x04, 0x36].repeat(70)]);
+
+		let text = format!("{}🔔{}", "ж".repeat(66), "ж".repeat(10));
+		let encoded = encode(&text);
+		let lens: Vec<_> = encoded.segments.iter().map(Vec::len).collect();
+		assert_eq!(lens, vec![132, 24]);
+		assert_eq!(
+			&encoded.segments[1][..4],
+			"🔔"
+				.encode_utf16()
+				.flat_map(u16::to_be_bytes)
+				.collect::<Vec<_>>()
+				.as_slice()
+		);
+	}
+}
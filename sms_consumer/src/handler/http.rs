@@ -0,0 +1,231 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use log::debug;
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler, encoding::encode, parse_sms, retry_after},
+	metrics::metrics::SMS_CONSUMER_CONSUMED_MESSAGES,
+};
+
+pub struct HttpGatewayOptions {
+	pub url:          String,
+	/// Sent as a bearer token when set.
+	pub token:        Option<String>,
+	pub from:         String,
+	pub max_segments: usize,
+	pub timeout:      Duration,
+}
+
+#[derive(Serialize)]
+struct GatewayRequest<'a> {
+	#[serde(rename = "to")]
+	to:   &'a str,
+	#[serde(rename = "from")]
+	from: &'a str,
+	#[serde(rename = "text")]
+	text: &'a str,
+}
+
+/// Posts each notification as `{"to", "from", "text"}` to an HTTP SMS
+/// gateway, which splits and encodes the text itself. The length is still
+/// checked here so oversized texts fail the same way as over SMPP. Delivery
+/// receipts are left to the gateway's own callbacks.
+pub struct HttpGatewayHandlerImpl {
+	client:       reqwest::Client,
+	url:          Url,
+	token:        Option<String>,
+	from:         String,
+	max_segments: usize,
+	app_state:    Arc<AppState>,
+}
+
+impl HttpGatewayHandlerImpl {
+	pub fn new(opts: HttpGatewayOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let url = Url::parse(&opts.url).map_err(|e| format!("invalid URL: {}", e))?;
+		let client = reqwest::Client::builder()
+			.timeout(opts.timeout)
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(HttpGatewayHandlerImpl {
+			client,
+			url,
+			token: opts.token,
+			from: opts.from,
+			max_segments: opts.max_segments,
+			app_state,
+		})
+	}
+}
+
+/// Gateway outages and rate limiting may pass, any other rejection would be
+/// repeated. A `Retry-After` on a retried reply sets the delay.
+fn classify(status: StatusCode, retry_after: Option<Duration>) -> DeliveryError {
+	let error = format!("gateway returned {}", status);
+	if !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS {
+		return DeliveryError::Permanent(error);
+	}
+	match retry_after {
+		Some(delay) => DeliveryError::RetryAfter(delay, error),
+		None => DeliveryError::Transient(error),
+	}
+}
+
+#[async_trait]
+impl MessageHandler for HttpGatewayHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_sms(message)?;
+		let segments = encode(&notification.content).segments.len();
+		if segments > self.max_segments {
+			return Err(DeliveryError::Permanent(format!(
+				"text needs {} segments, at most {} are sent",
+				segments, self.max_segments
+			)));
+		}
+
+		let mut request = self.client.post(self.url.clone()).json(&GatewayRequest {
+			to:   &notification.recipient.id,
+			from: &self.from,
+			text: &notification.content,
+		});
+		if let Some(token) = &self.token {
+			request = request.bearer_auth(token);
+		}
+		let response = request
+			.send()
+			.await
+			.map_err(|e| DeliveryError::Transient(format!("request failed: {}", e)))?;
+		if !response.status().is_success() {
+			return Err(classify(response.status(), retry_after(response.headers())));
+		}
+
+		debug!(
+			"Sent SMS {} through the gateway in {} segments",
+			notification.id.as_deref().unwrap_or("-"),
+			segments
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(SMS_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use axum::{Json, Router, extract::State, http::HeaderMap, routing::post};
+	use common::monitoring::server::default_pair;
+	use serde_json::{Value, json};
+	use tokio::net::TcpListener;
+
+	use super::*;
+	use crate::metrics::metrics::setup_metrics;
+
+	type Requests = Arc<Mutex<Vec<(HeaderMap, Value)>>>;
+
+	/// Answers every POST to `/sms` with `status`.
+	async fn start_gateway(status: StatusCode) -> (String, Requests) {
+		let requests: Requests = Arc::default();
+		let app = Router::new()
+			.route(
+				"/sms",
+				post(
+					move |State(requests): State<Requests>,
+					      headers: HeaderMap,
+					      Json(body): Json<Value>| async move {
+						requests.lock().unwrap().push((headers, body));
+						status
+					},
+				),
+			)
+			.with_state(requests.clone());
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/sms", listener.local_addr().unwrap());
+		tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+		(url, requests)
+	}
+
+	fn handler(url: &str) -> HttpGatewayHandlerImpl {
+		let (_, recorder) = default_pair();
+		HttpGatewayHandlerImpl::new(
+			HttpGatewayOptions {
+				url:          url.to_string(),
+				token:        Some("t0ken".to_string()),
+				from:         "Acme".to_string(),
+				max_segments: 2,
+				timeout:      Duration::from_secs(1),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(setup_metrics(recorder)),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn notification(content: &str) -> String {
+		json!({
+			"_id": "n-1",
+			"content": content,
+			"channel": "sms",
+			"recipient": {"id": "+15551234567", "timezone_offset": "+00:00"},
+			"scheduledTime": "2026-10-19T08:00:00Z",
+			"priority": "high",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn posts_the_text_with_the_token() {
+		let (url, requests) = start_gateway(StatusCode::ACCEPTED).await;
+
+		assert_eq!(
+			handler(&url)
+				.handle_message(&notification("Your code is 1234"))
+				.await,
+			Ok(())
+		);
+
+		let requests = requests.lock().unwrap();
+		let (headers, body) = &requests[0];
+		assert_eq!(headers["authorization"], "Bearer t0ken");
+		assert_eq!(
+			body,
+			&json!({"to": "+15551234567", "from": "Acme", "text": "Your code is 1234"})
+		);
+	}
+
+	#[tokio::test]
+	async fn gateway_errors_and_long_texts() {
+		let (url, _) = start_gateway(StatusCode::SERVICE_UNAVAILABLE).await;
+		assert!(matches!(
+			handler(&url).handle_message(&notification("hi")).await,
+			Err(DeliveryError::Transient(_))
+		));
+
+		let (url, requests) = start_gateway(StatusCode::BAD_REQUEST).await;
+		assert!(matches!(
+			handler(&url).handle_message(&notification("hi")).await,
+			Err(DeliveryError::Permanent(_))
+		));
+
+		// Three UCS-2 segments, never sent.
+		assert!(matches!(
+			handler(&url)
+				.handle_message(&notification(&"ж".repeat(140)))
+				.await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert_eq!(requests.lock().unwrap().len(), 1);
+	}
+}
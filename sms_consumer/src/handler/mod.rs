@@ -0,0 +1,19 @@
+use common::notification::Notification;
+use consumer::handler::parse_notification;
+pub use consumer::handler::{DeliveryError, MessageHandler, retry_after};
+
+pub mod encoding;
+pub mod http;
+pub mod smpp;
+
+/// Decodes a notification, whose recipient id is the phone number in
+/// international format with a leading `+`.
+pub(crate) fn parse_sms(message: &str) -> Result<Notification, DeliveryError> {
+	let notification = parse_notification(message)?;
+	if notification.recipient.id.is_empty() {
+		return Err(DeliveryError::Permanent(
+			"notification has no phone number".to_string(),
+		));
+	}
+	Ok(notification)
+}
@@ -0,0 +1,371 @@
+use std::{
+	sync::{
+		Arc,
+		atomic::{AtomicU8, Ordering},
+	},
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{debug, info, warn};
+use tokio::sync::Mutex;
+
+use crate::{
+	app_state::AppState,
+	handler::{
+		DeliveryError,
+		MessageHandler,
+		encoding::encode,
+		parse_sms,
+		smpp::{
+			pdu::{
+				Address,
+				ESM_CLASS_UDHI,
+				ESME_RMSGQFUL,
+				ESME_ROK,
+				ESME_RSYSERR,
+				ESME_RTHROTTLED,
+				Receipt,
+				SUBMIT_SM,
+				ShortMessage,
+				message_id,
+			},
+			session::{Session, SessionOptions},
+		},
+	},
+	metrics::metrics::{
+		SMS_CONSUMER_CONSUMED_MESSAGES,
+		SMS_CONSUMER_RECEIPTS_DELIVERED,
+		SMS_CONSUMER_RECEIPTS_FAILED,
+	},
+};
+
+pub mod pdu;
+pub mod session;
+#[cfg(test)]
+mod simulator;
+
+pub struct SmppOptions {
+	/// `host:port` of the SMSC.
+	pub address:               String,
+	pub system_id:             String,
+	pub password:              String,
+	pub system_type:           String,
+	/// Phone number, short code or alphanumeric sender id.
+	pub source_addr:           String,
+	pub delivery_receipts:     bool,
+	pub max_segments:          usize,
+	pub timeout:               Duration,
+	pub enquire_link_interval: Duration,
+}
+
+/// Sends each notification over SMPP 3.4 to the number in `recipient.id`.
+///
+/// One transceiver session is bound on the first message and reused, it is
+/// rebound once the SMSC drops it. Texts longer than one message are sent
+/// as concatenated segments. Receipts arrive on the same session whenever
+/// the SMSC has them and are only logged and counted, the notification was
+/// acked once the SMSC accepted it.
+pub struct SmppHandlerImpl {
+	session_options:   SessionOptions,
+	source:            Address,
+	delivery_receipts: bool,
+	max_segments:      usize,
+	session:           Mutex<Option<Arc<Session>>>,
+	/// Reference number tying the segments of one text together.
+	reference:         AtomicU8,
+	app_state:         Arc<AppState>,
+}
+
+impl SmppHandlerImpl {
+	pub fn new(opts: SmppOptions, app_state: Arc<AppState>) -> Self {
+		SmppHandlerImpl {
+			session_options: SessionOptions {
+				address:               opts.address,
+				system_id:             opts.system_id,
+				password:              opts.password,
+				system_type:           opts.system_type,
+				timeout:               opts.timeout,
+				enquire_link_interval: opts.enquire_link_interval,
+			},
+			source: Address::parse(&opts.source_addr),
+			delivery_receipts: opts.delivery_receipts,
+			max_segments: opts.max_segments,
+			session: Mutex::new(None),
+			reference: AtomicU8::new(0),
+			app_state,
+		}
+	}
+
+	async fn session(&self) -> Result<Arc<Session>, DeliveryError> {
+		let mut session = self.session.lock().await;
+		if let Some(session) = session.as_ref().filter(|s| !s.is_closed()) {
+			return Ok(session.clone());
+		}
+		let app_state = self.app_state.clone();
+		let bound = Session::connect(
+			&self.session_options,
+			Arc::new(move |receipt| record_receipt(&app_state, receipt)),
+		)
+		.await?;
+		info!("Bound to SMSC at {}", self.session_options.address);
+		*session = Some(bound.clone());
+		Ok(bound)
+	}
+}
+
+fn record_receipt(app_state: &AppState, receipt: Receipt) {
+	let counter = if receipt.is_delivered() {
+		debug!("SMS {} delivered", receipt.message_id);
+		SMS_CONSUMER_RECEIPTS_DELIVERED
+	} else {
+		warn!(
+			"SMS {} not delivered: {}",
+			receipt.message_id, receipt.state
+		);
+		SMS_CONSUMER_RECEIPTS_FAILED
+	};
+	if let Some(metric) = app_state.metrics.get_counter(counter) {
+		metric.increment(1);
+	}
+}
+
+/// Congestion and SMSC side failures may pass, anything else, e.g. an
+/// invalid destination, is about the message itself.
+fn classify(status: u32) -> DeliveryError {
+	let error = format!("SMSC rejected the message: status {:#x}", status);
+	match status {
+		ESME_RTHROTTLED | ESME_RMSGQFUL | ESME_RSYSERR => DeliveryError::Transient(error),
+		_ => DeliveryError::Permanent(error),
+	}
+}
+
+#[async_trait]
+impl MessageHandler for SmppHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		let notification = parse_sms(message)?;
+		let encoded = encode(&notification.content);
+		if encoded.segments.len() > self.max_segments {
+			return Err(DeliveryError::Permanent(format!(
+				"text needs {} segments, at most {} are sent",
+				encoded.segments.len(),
+				self.max_segments
+			)));
+		}
+
+		let session = self.session().await?;
+		let total = encoded.segments.len() as u8;
+		let reference = self.reference.fetch_add(1, Ordering::Relaxed);
+		let mut message_ids = vec![];
+		// A retry after a failed segment resends all of them, handsets show
+		// the text once every segment of one reference arrived.
+		for (i, segment) in encoded.segments.into_iter().enumerate() {
+			let (esm_class, short_message) = if total > 1 {
+				let mut udh = vec![0x05, 0x00, 0x03, reference, total, i as u8 + 1];
+				udh.extend(segment);
+				(ESM_CLASS_UDHI, udh)
+			} else {
+				(0, segment)
+			};
+			let submit = ShortMessage {
+				source: self.source.clone(),
+				destination: Address::parse(&notification.recipient.id),
+				esm_class,
+				registered_delivery: self.delivery_receipts as u8,
+				data_coding: encoded.coding.smpp_value(),
+				short_message,
+			};
+			let response = session.request(SUBMIT_SM, submit.encode()).await?;
+			if response.status != ESME_ROK {
+				return Err(classify(response.status));
+			}
+			message_ids.extend(message_id(&response.body));
+		}
+
+		debug!(
+			"Submitted SMS {} as {:?}",
+			notification.id.as_deref().unwrap_or("-"),
+			message_ids
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(SMS_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use common::{
+		axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder,
+		monitoring::server::default_pair,
+	};
+	use serde_json::json;
+
+	use super::{simulator::Simulator, *};
+	use crate::{handler::encoding::DataCoding, metrics::metrics::setup_metrics};
+
+	fn handler(address: &str) -> (SmppHandlerImpl, Arc<PrometheusRecorder>) {
+		let (_, recorder) = default_pair();
+		let metrics = Arc::new(setup_metrics(recorder.clone()));
+		let handler = SmppHandlerImpl::new(
+			SmppOptions {
+				address:               address.to_string(),
+				system_id:             "notifications".to_string(),
+				password:              "secret".to_string(),
+				system_type:           String::new(),
+				source_addr:           "Acme".to_string(),
+				delivery_receipts:     true,
+				max_segments:          3,
+				timeout:               Duration::from_secs(1),
+				enquire_link_interval: Duration::from_secs(30),
+			},
+			Arc::new(AppState { metrics }),
+		);
+		(handler, recorder)
+	}
+
+	fn notification(content: &str) -> String {
+		json!({
+			"_id": "n-1",
+			"content": content,
+			"channel": "sms",
+			"recipient": {"id": "+15551234567", "timezone_offset": "+00:00"},
+			"scheduledTime": "2026-10-19T08:00:00Z",
+			"priority": "high",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	async fn wait_for(mut done: impl FnMut() -> bool) {
+		for _ in 0..50 {
+			if done() {
+				return;
+			}
+			tokio::time::sleep(Duration::from_millis(20)).await;
+		}
+		panic!("timed out");
+	}
+
+	#[tokio::test]
+	async fn submits_a_short_text_and_counts_its_receipt() {
+		let simulator = Simulator::start(vec![ESME_ROK]).await;
+		let (handler, recorder) = handler(&simulator.address);
+
+		assert_eq!(
+			handler
+				.handle_message(&notification("Your code is 1234"))
+				.await,
+			Ok(())
+		);
+
+		assert_eq!(
+			simulator.binds(),
+			vec![("notifications".to_string(), "secret".to_string())]
+		);
+		let submits = simulator.submits();
+		assert_eq!(submits.len(), 1);
+		assert_eq!(submits[0].destination, Address::parse("+15551234567"));
+		assert_eq!(submits[0].source.addr, "Acme");
+		assert_eq!(submits[0].esm_class, 0);
+		assert_eq!(submits[0].registered_delivery, 1);
+		assert_eq!(submits[0].data_coding, DataCoding::Gsm7.smpp_value());
+		assert_eq!(submits[0].short_message, b"Your code is 1234");
+
+		// The simulator sends a receipt right after accepting the message.
+		wait_for(|| simulator.receipts_acked() == 1).await;
+		let rendered = recorder.handle().render();
+		assert!(rendered.contains("sms_consumer_receipts_delivered 1"));
+		assert!(rendered.contains("sms_consumer_consumed_messages 1"));
+	}
+
+	#[tokio::test]
+	async fn long_texts_are_sent_as_concatenated_segments() {
+		let simulator = Simulator::start(vec![ESME_ROK]).await;
+		let (handler, _) = handler(&simulator.address);
+
+		let text = "ж".repeat(100);
+		assert_eq!(handler.handle_message(&notification(&text)).await, Ok(()));
+		assert_eq!(handler.handle_message(&notification(&text)).await, Ok(()));
+
+		let submits = simulator.submits();
+		assert_eq!(submits.len(), 4);
+		// One session serves both texts.
+		assert_eq!(simulator.binds().len(), 1);
+		for submit in &submits {
+			assert_eq!(submit.esm_class, ESM_CLASS_UDHI);
+			assert_eq!(submit.data_coding, DataCoding::Ucs2.smpp_value());
+		}
+		let headers: Vec<_> = submits
+			.iter()
+			.map(|s| s.short_message[..6].to_vec())
+			.collect();
+		assert_eq!(headers[0][..5], [0x05, 0x00, 0x03, headers[0][3], 2]);
+		assert_eq!(headers[0][5], 1);
+		assert_eq!(headers[1][3..], [headers[0][3], 2, 2]);
+		assert_ne!(headers[2][3], headers[0][3]);
+		assert_eq!(submits[0].short_message.len(), 6 + 67 * 2);
+		assert_eq!(submits[1].short_message.len(), 6 + 33 * 2);
+
+		assert!(matches!(
+			handler
+				.handle_message(&notification(&"a".repeat(153 * 3 + 1)))
+				.await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert_eq!(simulator.submits().len(), 4);
+	}
+
+	#[tokio::test]
+	async fn submit_statuses_decide_whether_to_retry() {
+		let simulator = Simulator::start(vec![ESME_RTHROTTLED, 0x0b]).await;
+		let (handler, _) = handler(&simulator.address);
+
+		assert!(matches!(
+			handler.handle_message(&notification("hi")).await,
+			Err(DeliveryError::Transient(_))
+		));
+		assert!(matches!(
+			handler.handle_message(&notification("hi")).await,
+			Err(DeliveryError::Permanent(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn rebinds_after_the_smsc_drops_the_session() {
+		let simulator = Simulator::start(vec![ESME_ROK]).await;
+		let (handler, _) = handler(&simulator.address);
+
+		assert_eq!(handler.handle_message(&notification("one")).await, Ok(()));
+		simulator.disconnect();
+		wait_for(|| {
+			handler
+				.session
+				.try_lock()
+				.is_ok_and(|s| s.as_ref().is_some_and(|s| s.is_closed()))
+		})
+		.await;
+		assert_eq!(handler.handle_message(&notification("two")).await, Ok(()));
+
+		assert_eq!(simulator.binds().len(), 2);
+		assert_eq!(simulator.submits().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn unreachable_smscs_are_transient() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap().to_string();
+		drop(listener);
+		let (handler, _) = handler(&address);
+
+		assert!(matches!(
+			handler.handle_message(&notification("hi")).await,
+			Err(DeliveryError::Transient(_))
+		));
+	}
+}
@@ -0,0 +1,341 @@
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const GENERIC_NACK: u32 = 0x8000_0000;
+pub const BIND_TRANSCEIVER: u32 = 0x0000_0009;
+pub const SUBMIT_SM: u32 = 0x0000_0004;
+#[cfg(test)]
+pub const SUBMIT_SM_RESP: u32 = 0x8000_0004;
+pub const DELIVER_SM: u32 = 0x0000_0005;
+#[cfg(test)]
+pub const DELIVER_SM_RESP: u32 = 0x8000_0005;
+pub const UNBIND: u32 = 0x0000_0006;
+pub const ENQUIRE_LINK: u32 = 0x0000_0015;
+
+pub const ESME_ROK: u32 = 0x0000_0000;
+pub const ESME_RINVCMDID: u32 = 0x0000_0003;
+pub const ESME_RSYSERR: u32 = 0x0000_0008;
+pub const ESME_RMSGQFUL: u32 = 0x0000_0014;
+pub const ESME_RTHROTTLED: u32 = 0x0000_0058;
+
+/// `esm_class` bit telling the short message starts with a user data header.
+pub const ESM_CLASS_UDHI: u8 = 0x40;
+/// `esm_class` message type of an SMSC delivery receipt.
+pub const ESM_CLASS_RECEIPT: u8 = 0x04;
+const ESM_CLASS_TYPE_MASK: u8 = 0x3c;
+
+const HEADER_LEN: usize = 16;
+/// Far above anything an SMSC sends, guards against reading garbage.
+const MAX_PDU_LEN: usize = 64 * 1024;
+const INTERFACE_VERSION: u8 = 0x34;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pdu {
+	pub command_id: u32,
+	pub status:     u32,
+	pub sequence:   u32,
+	pub body:       Vec<u8>,
+}
+
+impl Pdu {
+	pub fn new(command_id: u32, sequence: u32, body: Vec<u8>) -> Self {
+		Pdu {
+			command_id,
+			status: ESME_ROK,
+			sequence,
+			body,
+		}
+	}
+
+	pub fn response(&self, status: u32, body: Vec<u8>) -> Self {
+		Pdu {
+			command_id: self.command_id | GENERIC_NACK,
+			status,
+			sequence: self.sequence,
+			body,
+		}
+	}
+
+	pub fn is_response(&self) -> bool {
+		self.command_id & GENERIC_NACK != 0
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut buf = Vec::with_capacity(HEADER_LEN + self.body.len());
+		buf.extend(((HEADER_LEN + self.body.len()) as u32).to_be_bytes());
+		buf.extend(self.command_id.to_be_bytes());
+		buf.extend(self.status.to_be_bytes());
+		buf.extend(self.sequence.to_be_bytes());
+		buf.extend(&self.body);
+		buf
+	}
+
+	pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Pdu> {
+		let len = reader.read_u32().await? as usize;
+		if !(HEADER_LEN..=MAX_PDU_LEN).contains(&len) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("invalid PDU length {}", len),
+			));
+		}
+		let command_id = reader.read_u32().await?;
+		let status = reader.read_u32().await?;
+		let sequence = reader.read_u32().await?;
+		let mut body = vec![0; len - HEADER_LEN];
+		reader.read_exact(&mut body).await?;
+		Ok(Pdu {
+			command_id,
+			status,
+			sequence,
+			body,
+		})
+	}
+}
+
+/// Type of number and numbering plan indicator with the address itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Address {
+	pub ton:  u8,
+	pub npi:  u8,
+	pub addr: String,
+}
+
+impl Address {
+	/// `+` prefixed numbers are international, anything with letters is an
+	/// alphanumeric sender id, other digits are left for the SMSC to
+	/// interpret.
+	pub fn parse(addr: &str) -> Self {
+		if let Some(number) = addr.strip_prefix('+') {
+			Address {
+				ton:  0x01,
+				npi:  0x01,
+				addr: number.to_string(),
+			}
+		} else if addr.chars().any(|c| !c.is_ascii_digit()) {
+			Address {
+				ton:  0x05,
+				npi:  0x00,
+				addr: addr.to_string(),
+			}
+		} else {
+			Address {
+				ton:  0x00,
+				npi:  0x01,
+				addr: addr.to_string(),
+			}
+		}
+	}
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+	fn cstring(mut self, value: &str) -> Self {
+		self.0.extend(value.as_bytes());
+		self.0.push(0);
+		self
+	}
+
+	fn u8(mut self, value: u8) -> Self {
+		self.0.push(value);
+		self
+	}
+
+	fn address(self, address: &Address) -> Self {
+		self.u8(address.ton).u8(address.npi).cstring(&address.addr)
+	}
+}
+
+struct Reader<'a> {
+	buf: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn new(buf: &'a [u8]) -> Self {
+		Reader { buf, pos: 0 }
+	}
+
+	fn cstring(&mut self) -> Option<String> {
+		let len = self.buf[self.pos..].iter().position(|&b| b == 0)?;
+		let value = String::from_utf8_lossy(&self.buf[self.pos..self.pos + len]).into_owned();
+		self.pos += len + 1;
+		Some(value)
+	}
+
+	fn u8(&mut self) -> Option<u8> {
+		let value = *self.buf.get(self.pos)?;
+		self.pos += 1;
+		Some(value)
+	}
+
+	fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+		let value = self.buf.get(self.pos..self.pos + len)?;
+		self.pos += len;
+		Some(value)
+	}
+
+	fn address(&mut self) -> Option<Address> {
+		Some(Address {
+			ton:  self.u8()?,
+			npi:  self.u8()?,
+			addr: self.cstring()?,
+		})
+	}
+}
+
+pub fn bind_transceiver(system_id: &str, password: &str, system_type: &str) -> Vec<u8> {
+	Writer::default()
+		.cstring(system_id)
+		.cstring(password)
+		.cstring(system_type)
+		.u8(INTERFACE_VERSION)
+		.u8(0)
+		.u8(0)
+		.cstring("")
+		.0
+}
+
+/// The fields shared by `submit_sm` and `deliver_sm` this client uses, the
+/// others are sent empty and ignored when read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShortMessage {
+	pub source:              Address,
+	pub destination:         Address,
+	pub esm_class:           u8,
+	pub registered_delivery: u8,
+	pub data_coding:         u8,
+	pub short_message:       Vec<u8>,
+}
+
+impl ShortMessage {
+	pub fn encode(&self) -> Vec<u8> {
+		let mut writer = Writer::default()
+			.cstring("")
+			.address(&self.source)
+			.address(&self.destination)
+			.u8(self.esm_class)
+			.u8(0)
+			.u8(0)
+			.cstring("")
+			.cstring("")
+			.u8(self.registered_delivery)
+			.u8(0)
+			.u8(self.data_coding)
+			.u8(0)
+			.u8(self.short_message.len() as u8);
+		writer.0.extend(&self.short_message);
+		writer.0
+	}
+
+	/// Optional parameters after the short message are skipped.
+	pub fn parse(body: &[u8]) -> Option<Self> {
+		let mut reader = Reader::new(body);
+		reader.cstring()?;
+		let source = reader.address()?;
+		let destination = reader.address()?;
+		let esm_class = reader.u8()?;
+		reader.u8()?;
+		reader.u8()?;
+		reader.cstring()?;
+		reader.cstring()?;
+		let registered_delivery = reader.u8()?;
+		reader.u8()?;
+		let data_coding = reader.u8()?;
+		reader.u8()?;
+		let len = reader.u8()? as usize;
+		Some(ShortMessage {
+			source,
+			destination,
+			esm_class,
+			registered_delivery,
+			data_coding,
+			short_message: reader.bytes(len)?.to_vec(),
+		})
+	}
+
+	pub fn is_receipt(&self) -> bool {
+		self.esm_class & ESM_CLASS_TYPE_MASK == ESM_CLASS_RECEIPT
+	}
+}
+
+/// The `message_id` of a `submit_sm_resp`.
+pub fn message_id(body: &[u8]) -> Option<String> {
+	Reader::new(body).cstring()
+}
+
+/// What an SMSC reports about a submitted message, from the
+/// `id:... stat:...` text of a receipt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+	pub message_id: String,
+	pub state:      String,
+}
+
+impl Receipt {
+	pub fn parse(text: &[u8]) -> Option<Self> {
+		let text = String::from_utf8_lossy(text);
+		let field = |name: &str| {
+			text.split_whitespace()
+				.find_map(|part| part.strip_prefix(name))
+				.map(str::to_string)
+		};
+		Some(Receipt {
+			message_id: field("id:")?,
+			state:      field("stat:")?,
+		})
+	}
+
+	pub fn is_delivered(&self) -> bool {
+		self.state == "DELIVRD"
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn short_messages_round_trip() {
+		let message = ShortMessage {
+			source:              Address::parse("Acme"),
+			destination:         Address::parse("+15551234567"),
+			esm_class:           ESM_CLASS_UDHI,
+			registered_delivery: 1,
+			data_coding:         0x08,
+			short_message:       vec![0x05, 0x00, 0x03, 0x2a, 0x02, 0x01, 0x04, 0x36],
+		};
+		assert_eq!(message.destination.addr, "15551234567");
+		assert_eq!((message.source.ton, message.source.npi), (0x05, 0x00));
+		assert_eq!(ShortMessage::parse(&message.encode()), Some(message));
+	}
+
+	#[tokio::test]
+	async fn pdus_round_trip() {
+		let pdu = Pdu::new(SUBMIT_SM, 7, vec![1, 2, 3]);
+		let encoded = pdu.encode();
+		assert_eq!(&encoded[..4], &19u32.to_be_bytes());
+		assert_eq!(Pdu::read(&mut encoded.as_slice()).await.unwrap(), pdu);
+
+		let response = pdu.response(ESME_RTHROTTLED, vec![]);
+		assert_eq!(response.command_id, SUBMIT_SM_RESP);
+		assert!(response.is_response());
+		assert!(
+			Pdu::read(&mut [0, 0, 0, 4].as_slice()).await.is_err(),
+			"shorter than the header"
+		);
+	}
+
+	#[test]
+	fn receipts_are_parsed_from_the_text() {
+		let receipt = Receipt::parse(
+			b"id:0123456789 sub:001 dlvrd:001 submit date:2410191200 done date:2410191201 stat:DELIVRD err:000 text:",
+		)
+		.unwrap();
+		assert_eq!(receipt.message_id, "0123456789");
+		assert!(receipt.is_delivered());
+		assert_eq!(Receipt::parse(b"hello"), None);
+	}
+}
@@ -0,0 +1,234 @@
+use std::{
+	collections::HashMap,
+	sync::{
+		Arc,
+		Mutex,
+		atomic::{AtomicBool, AtomicU32, Ordering},
+	},
+	time::Duration,
+};
+
+use log::{debug, warn};
+use tokio::{
+	io::AsyncWriteExt,
+	net::TcpStream,
+	sync::{mpsc, oneshot},
+	task::JoinHandle,
+	time::timeout,
+};
+
+use crate::handler::{
+	DeliveryError,
+	smpp::pdu::{
+		BIND_TRANSCEIVER,
+		DELIVER_SM,
+		ENQUIRE_LINK,
+		ESME_RINVCMDID,
+		ESME_ROK,
+		GENERIC_NACK,
+		Pdu,
+		Receipt,
+		ShortMessage,
+		UNBIND,
+		bind_transceiver,
+	},
+};
+
+pub struct SessionOptions {
+	pub address:               String,
+	pub system_id:             String,
+	pub password:              String,
+	pub system_type:           String,
+	/// Bounds connecting, binding and every response after that.
+	pub timeout:               Duration,
+	pub enquire_link_interval: Duration,
+}
+
+type Pending = Arc<Mutex<HashMap<u32, oneshot::Sender<Pdu>>>>;
+
+/// A bound transceiver session. Requests are matched to responses by
+/// sequence number, so they may overlap; receipts and link checks from the
+/// SMSC are answered in the background.
+pub struct Session {
+	writer:   mpsc::UnboundedSender<Pdu>,
+	pending:  Pending,
+	sequence: AtomicU32,
+	closed:   Arc<AtomicBool>,
+	timeout:  Duration,
+	tasks:    Vec<JoinHandle<()>>,
+}
+
+impl Session {
+	pub async fn connect(
+		opts: &SessionOptions,
+		on_receipt: Arc<dyn Fn(Receipt) + Send + Sync>,
+	) -> Result<Arc<Session>, DeliveryError> {
+		let stream = timeout(opts.timeout, TcpStream::connect(&opts.address))
+			.await
+			.map_err(|_| DeliveryError::Transient("timed out connecting to the SMSC".to_string()))?
+			.map_err(|e| {
+				DeliveryError::Transient(format!("failed to connect to the SMSC: {}", e))
+			})?;
+		let (mut read_half, mut write_half) = stream.into_split();
+
+		let (writer, mut outgoing) = mpsc::unbounded_channel::<Pdu>();
+		let pending: Pending = Arc::default();
+		let closed = Arc::new(AtomicBool::new(false));
+
+		let write_closed = closed.clone();
+		let write_task = tokio::spawn(async move {
+			while let Some(pdu) = outgoing.recv().await {
+				if let Err(e) = write_half.write_all(&pdu.encode()).await {
+					warn!("Lost SMPP connection: {}", e);
+					break;
+				}
+			}
+			write_closed.store(true, Ordering::SeqCst);
+		});
+
+		let read_pending = pending.clone();
+		let read_closed = closed.clone();
+		let replies = writer.clone();
+		let read_task = tokio::spawn(async move {
+			loop {
+				let pdu = match Pdu::read(&mut read_half).await {
+					Ok(pdu) => pdu,
+					Err(e) => {
+						warn!("Lost SMPP connection: {}", e);
+						break;
+					}
+				};
+				if pdu.is_response() {
+					// A generic_nack may answer a PDU the SMSC could not parse.
+					if let Some(sender) = read_pending.lock().unwrap().remove(&pdu.sequence) {
+						let _ = sender.send(pdu);
+					}
+					continue;
+				}
+				let reply = match pdu.command_id {
+					DELIVER_SM => {
+						match ShortMessage::parse(&pdu.body) {
+							Some(message) if message.is_receipt() => {
+								match Receipt::parse(&message.short_message) {
+									Some(receipt) => on_receipt(receipt),
+									None => warn!("Ignoring unreadable delivery receipt"),
+								}
+							}
+							_ => debug!("Ignoring mobile originated message"),
+						}
+						pdu.response(ESME_ROK, vec![0])
+					}
+					ENQUIRE_LINK => pdu.response(ESME_ROK, vec![]),
+					UNBIND => {
+						let _ = replies.send(pdu.response(ESME_ROK, vec![]));
+						break;
+					}
+					_ => Pdu {
+						command_id: GENERIC_NACK,
+						status:     ESME_RINVCMDID,
+						sequence:   pdu.sequence,
+						body:       vec![],
+					},
+				};
+				let _ = replies.send(reply);
+			}
+			read_closed.store(true, Ordering::SeqCst);
+			// Waiting requests fail right away instead of timing out.
+			read_pending.lock().unwrap().clear();
+		});
+
+		let session = Session {
+			writer,
+			pending,
+			sequence: AtomicU32::new(1),
+			closed,
+			timeout: opts.timeout,
+			tasks: vec![write_task, read_task],
+		};
+
+		let bind = session
+			.request(
+				BIND_TRANSCEIVER,
+				bind_transceiver(&opts.system_id, &opts.password, &opts.system_type),
+			)
+			.await?;
+		if bind.status != ESME_ROK {
+			return Err(DeliveryError::Transient(format!(
+				"SMSC refused to bind: status {:#x}",
+				bind.status
+			)));
+		}
+
+		let session = Arc::new(session);
+		let weak = Arc::downgrade(&session);
+		let interval = opts.enquire_link_interval;
+		// Holds no strong reference, it ends once the session is dropped.
+		tokio::spawn(async move {
+			loop {
+				tokio::time::sleep(interval).await;
+				let Some(session) = weak.upgrade() else { break };
+				if session.is_closed() {
+					break;
+				}
+				if let Err(e) = session.request(ENQUIRE_LINK, vec![]).await {
+					warn!("SMSC did not answer enquire_link: {}", e);
+					session.close();
+					break;
+				}
+			}
+		});
+		Ok(session)
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	fn close(&self) {
+		self.closed.store(true, Ordering::SeqCst);
+		for task in &self.tasks {
+			task.abort();
+		}
+		self.pending.lock().unwrap().clear();
+	}
+
+	/// Sends `command_id` and waits for its response. Losing the connection
+	/// or the SMSC not answering in time is transient.
+	pub async fn request(&self, command_id: u32, body: Vec<u8>) -> Result<Pdu, DeliveryError> {
+		let sequence = self.sequence.fetch_add(1, Ordering::SeqCst);
+		let (sender, receiver) = oneshot::channel();
+		self.pending.lock().unwrap().insert(sequence, sender);
+		if self.is_closed()
+			|| self
+				.writer
+				.send(Pdu::new(command_id, sequence, body))
+				.is_err()
+		{
+			self.pending.lock().unwrap().remove(&sequence);
+			return Err(DeliveryError::Transient(
+				"SMPP connection is closed".to_string(),
+			));
+		}
+
+		match timeout(self.timeout, receiver).await {
+			Ok(Ok(pdu)) => Ok(pdu),
+			Ok(Err(_)) => Err(DeliveryError::Transient(
+				"SMPP connection closed before the response".to_string(),
+			)),
+			Err(_) => {
+				self.pending.lock().unwrap().remove(&sequence);
+				Err(DeliveryError::Transient(
+					"timed out waiting for the SMSC".to_string(),
+				))
+			}
+		}
+	}
+}
+
+impl Drop for Session {
+	fn drop(&mut self) {
+		for task in &self.tasks {
+			task.abort();
+		}
+	}
+}
@@ -0,0 +1,160 @@
+use std::{
+	collections::VecDeque,
+	sync::{
+		Arc,
+		Mutex,
+		atomic::{AtomicUsize, Ordering},
+	},
+};
+
+use tokio::{
+	io::AsyncWriteExt,
+	net::{TcpListener, TcpStream},
+	sync::Notify,
+};
+
+use crate::handler::smpp::pdu::{
+	Address,
+	BIND_TRANSCEIVER,
+	DELIVER_SM,
+	DELIVER_SM_RESP,
+	ENQUIRE_LINK,
+	ESM_CLASS_RECEIPT,
+	ESME_ROK,
+	Pdu,
+	SUBMIT_SM,
+	ShortMessage,
+};
+
+/// Local stand-in for an SMSC. Accepts any bind, answers submits with the
+/// given statuses in order, repeating the last one, and follows every
+/// accepted submit asking for one with a `DELIVRD` receipt.
+#[derive(Default)]
+pub struct Simulator {
+	pub address:    String,
+	binds:          Arc<Mutex<Vec<(String, String)>>>,
+	submits:        Arc<Mutex<Vec<ShortMessage>>>,
+	receipts_acked: Arc<AtomicUsize>,
+	disconnect:     Arc<Notify>,
+}
+
+#[derive(Clone)]
+struct Connection {
+	statuses:       Arc<Mutex<VecDeque<u32>>>,
+	binds:          Arc<Mutex<Vec<(String, String)>>>,
+	submits:        Arc<Mutex<Vec<ShortMessage>>>,
+	receipts_acked: Arc<AtomicUsize>,
+	disconnect:     Arc<Notify>,
+}
+
+impl Simulator {
+	pub async fn start(statuses: Vec<u32>) -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let simulator = Simulator {
+			address: listener.local_addr().unwrap().to_string(),
+			..Simulator::default()
+		};
+		let connection = Connection {
+			statuses:       Arc::new(Mutex::new(statuses.into())),
+			binds:          simulator.binds.clone(),
+			submits:        simulator.submits.clone(),
+			receipts_acked: simulator.receipts_acked.clone(),
+			disconnect:     simulator.disconnect.clone(),
+		};
+		tokio::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				tokio::spawn(connection.clone().serve(stream));
+			}
+		});
+		simulator
+	}
+
+	pub fn binds(&self) -> Vec<(String, String)> {
+		self.binds.lock().unwrap().clone()
+	}
+
+	pub fn submits(&self) -> Vec<ShortMessage> {
+		self.submits.lock().unwrap().clone()
+	}
+
+	pub fn receipts_acked(&self) -> usize {
+		self.receipts_acked.load(Ordering::SeqCst)
+	}
+
+	/// Drops every open connection without unbinding.
+	pub fn disconnect(&self) {
+		self.disconnect.notify_waiters();
+	}
+}
+
+impl Connection {
+	fn next_status(&self) -> u32 {
+		let mut statuses = self.statuses.lock().unwrap();
+		if statuses.len() > 1 {
+			statuses.pop_front().unwrap()
+		} else {
+			statuses.front().copied().unwrap_or(ESME_ROK)
+		}
+	}
+
+	async fn serve(self, mut stream: TcpStream) {
+		let mut sequence = 1_000;
+		loop {
+			let pdu = tokio::select! {
+				pdu = Pdu::read(&mut stream) => match pdu {
+					Ok(pdu) => pdu,
+					Err(_) => return,
+				},
+				_ = self.disconnect.notified() => return,
+			};
+			let mut replies = vec![];
+			match pdu.command_id {
+				BIND_TRANSCEIVER => {
+					let mut fields = pdu.body.split(|&b| b == 0);
+					let mut field = || String::from_utf8(fields.next().unwrap().to_vec()).unwrap();
+					self.binds.lock().unwrap().push((field(), field()));
+					replies.push(pdu.response(ESME_ROK, b"simulator\0".to_vec()));
+				}
+				SUBMIT_SM => {
+					let submit = ShortMessage::parse(&pdu.body).unwrap();
+					let status = self.next_status();
+					let message_id = format!("msg-{}", self.submits.lock().unwrap().len());
+					self.submits.lock().unwrap().push(submit.clone());
+					if status != ESME_ROK {
+						replies.push(pdu.response(status, vec![]));
+					} else {
+						replies
+							.push(pdu.response(ESME_ROK, format!("{}\0", message_id).into_bytes()));
+						if submit.registered_delivery & 0x01 != 0 {
+							sequence += 1;
+							let receipt = ShortMessage {
+								source:              submit.destination.clone(),
+								destination:         Address::parse(&submit.source.addr),
+								esm_class:           ESM_CLASS_RECEIPT,
+								registered_delivery: 0,
+								data_coding:         0,
+								short_message:       format!(
+									"id:{} sub:001 dlvrd:001 submit date:2410191200 done \
+									 date:2410191200 stat:DELIVRD err:000 text:",
+									message_id
+								)
+								.into_bytes(),
+							};
+							replies.push(Pdu::new(DELIVER_SM, sequence, receipt.encode()));
+						}
+					}
+				}
+				DELIVER_SM_RESP => {
+					self.receipts_acked.fetch_add(1, Ordering::SeqCst);
+				}
+				ENQUIRE_LINK => replies.push(pdu.response(ESME_ROK, vec![])),
+				_ => {}
+			}
+			for reply in replies {
+				if stream.write_all(&reply.encode()).await.is_err() {
+					return;
+				}
+			}
+		}
+	}
+}
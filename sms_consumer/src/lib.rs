@@ -0,0 +1,56 @@
+//! Text messages through an SMPP server or an HTTP gateway. The
+//! `sms_consumer` binary hosts these handlers on their own, [`register`] adds
+//! them to any other host.
+
+use std::{fmt::Debug, sync::Arc};
+
+use common::axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder;
+use consumer::{registry::Handlers, std_out::StdOutHandlerImpl};
+
+use crate::{
+	config::{HandlerKind, Sections},
+	handler::{http::HttpGatewayHandlerImpl, smpp::SmppHandlerImpl},
+	metrics::metrics::SMS_CONSUMER_CONSUMED_MESSAGES,
+};
+
+pub mod app_state;
+pub mod config;
+pub mod handler;
+pub mod metrics;
+
+pub const CHANNEL: &str = "sms";
+
+/// Registers the SMS handlers under the names `name` gives them in the
+/// host's handler list. Only the configured ones are built, from `sections`.
+pub fn register<'a, H: Copy + Debug + PartialEq + 'a>(
+	handlers: Handlers<'a, H>,
+	sections: Sections<'a>,
+	recorder: Arc<PrometheusRecorder>,
+	name: fn(HandlerKind) -> H,
+) -> Handlers<'a, H> {
+	let metrics = Arc::new(metrics::metrics::setup_metrics(recorder));
+	let app_state = Arc::new(app_state::AppState {
+		metrics: metrics.clone(),
+	});
+
+	let smpp_state = app_state.clone();
+	handlers
+		.register(name(HandlerKind::StdOut), CHANNEL, move || {
+			Ok(Arc::new(StdOutHandlerImpl::new(
+				metrics,
+				SMS_CONSUMER_CONSUMED_MESSAGES,
+			)))
+		})
+		.register(name(HandlerKind::Smpp), CHANNEL, move || {
+			Ok(Arc::new(SmppHandlerImpl::new(
+				sections.smpp.options(),
+				smpp_state,
+			)))
+		})
+		.register(name(HandlerKind::Http), CHANNEL, move || {
+			Ok(Arc::new(HttpGatewayHandlerImpl::new(
+				sections.http.options(),
+				app_state,
+			)?))
+		})
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use common::{
+	axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder,
+	monitoring::metrics::Metrics,
+};
+
+pub const SMS_CONSUMER_CONSUMED_MESSAGES: &str = "sms_consumer_consumed_messages";
+/// Delivery receipts reporting the message reached the handset.
+pub const SMS_CONSUMER_RECEIPTS_DELIVERED: &str = "sms_consumer_receipts_delivered";
+/// Delivery receipts in any other final state, e.g. expired or rejected.
+pub const SMS_CONSUMER_RECEIPTS_FAILED: &str = "sms_consumer_receipts_failed";
+
+pub fn setup_metrics(prometheus_recorder: Arc<PrometheusRecorder>) -> Metrics {
+	let metrics = Metrics::new(prometheus_recorder);
+	for counter in [
+		SMS_CONSUMER_CONSUMED_MESSAGES,
+		SMS_CONSUMER_RECEIPTS_DELIVERED,
+		SMS_CONSUMER_RECEIPTS_FAILED,
+	] {
+		metrics.register_counter(counter).unwrap();
+	}
+
+	metrics
+}
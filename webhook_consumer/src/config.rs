@@ -0,0 +1,143 @@
+use std::{collections::HashMap, time::Duration};
+
+use common::{
+	config::{
+		ConfigError,
+		LogConfig,
+		MetricsConfig,
+		NatsConfig,
+		ShutdownConfig,
+		Validate,
+		require_positive,
+	},
+	faults::FaultConfig,
+};
+use consumer::config::ConsumerConfig;
+use serde::Deserialize;
+
+use crate::handler::http::WebhookOptions;
+
+/// Loaded from the TOML file named by `CONFIG_FILE`, overridden by
+/// `APP__SECTION__KEY` env vars.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub metrics:  MetricsConfig,
+	pub log:      LogConfig,
+	pub nats:     NatsConfig,
+	pub consumer: ConsumerConfig<HandlerKind>,
+	pub webhook:  WebhookConfig,
+	pub shutdown: ShutdownConfig,
+	pub faults:   FaultConfig,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			metrics:  MetricsConfig::default(),
+			log:      LogConfig::default(),
+			nats:     NatsConfig::default(),
+			consumer: ConsumerConfig::new("webhook_consumer"),
+			webhook:  WebhookConfig::default(),
+			shutdown: ShutdownConfig::default(),
+			faults:   FaultConfig::default(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum HandlerKind {
+	/// Posts each message to the webhook, see [`WebhookConfig`].
+	#[default]
+	#[serde(rename = "http")]
+	Http,
+}
+
+/// Where the notifications of each recipient are posted to.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+	/// URL per recipient id, e.g. `orders = "https://..."`. Env overrides
+	/// lowercase the recipient id.
+	pub urls:                 HashMap<String, String>,
+	/// Key of the HMAC signature, shared with the receiver.
+	pub secret:               String,
+	pub connect_timeout_secs: u64,
+	/// Covers the whole request, it should stay below
+	/// `consumer.ack_wait_secs`.
+	pub timeout_secs:         u64,
+}
+
+impl Default for WebhookConfig {
+	fn default() -> Self {
+		WebhookConfig {
+			urls:                 HashMap::new(),
+			secret:               String::new(),
+			connect_timeout_secs: 5,
+			timeout_secs:         10,
+		}
+	}
+}
+
+impl WebhookConfig {
+	pub fn options(&self) -> WebhookOptions {
+		WebhookOptions {
+			urls:            self.urls.clone(),
+			secret:          self.secret.clone(),
+			connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+			timeout:         Duration::from_secs(self.timeout_secs),
+		}
+	}
+}
+
+/// The sections the webhook handler is built from, also part of hosts that
+/// run other channels next to webhooks.
+pub struct Sections<'a> {
+	pub webhook: &'a WebhookConfig,
+}
+
+impl Sections<'_> {
+	/// Checks the sections of the handlers `consumer` runs, named by `name`
+	/// as in [`crate::register`].
+	pub fn validate<H: PartialEq>(
+		&self,
+		consumer: &ConsumerConfig<H>,
+		name: fn(HandlerKind) -> H,
+	) -> Result<(), ConfigError> {
+		if consumer.uses(name(HandlerKind::Http)) {
+			if self.webhook.urls.is_empty() {
+				return Err(ConfigError("webhook.urls must not be empty".to_string()));
+			}
+			if self.webhook.secret.is_empty() {
+				return Err(ConfigError("webhook.secret must not be empty".to_string()));
+			}
+			require_positive(
+				"webhook.connect_timeout_secs",
+				self.webhook.connect_timeout_secs,
+			)?;
+			require_positive("webhook.timeout_secs", self.webhook.timeout_secs)?;
+		}
+		Ok(())
+	}
+}
+
+impl Config {
+	pub fn sections(&self) -> Sections<'_> {
+		Sections {
+			webhook: &self.webhook,
+		}
+	}
+}
+
+impl Validate for Config {
+	fn validate(&self) -> Result<(), ConfigError> {
+		require_positive("metrics.port", self.metrics.port)?;
+		require_positive("shutdown.timeout_secs", self.shutdown.timeout_secs)?;
+		self.consumer.validate()?;
+		self.sections()
+			.validate(&self.consumer, |handler| handler)?;
+		self.faults
+			.validate()
+			.map_err(|e| ConfigError(format!("faults: {}", e)))
+	}
+}
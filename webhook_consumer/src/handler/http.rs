@@ -0,0 +1,291 @@
+use std::{
+	collections::HashMap,
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use consumer::handler::parse_notification;
+use hmac::{Hmac, Mac};
+use log::debug;
+use reqwest::{StatusCode, Url, redirect::Policy};
+use sha2::Sha256;
+
+use crate::{
+	app_state::AppState,
+	handler::{DeliveryError, MessageHandler, retry_after},
+	metrics::metrics::WEBHOOK_CONSUMER_CONSUMED_MESSAGES,
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const ID_HEADER: &str = "X-Webhook-Id";
+
+pub struct WebhookOptions {
+	/// URL per recipient id.
+	pub urls:            HashMap<String, String>,
+	pub secret:          String,
+	pub connect_timeout: Duration,
+	pub timeout:         Duration,
+}
+
+/// Posts each notification as JSON to the recipient's URL.
+///
+/// The body is signed with HMAC-SHA256 over `<timestamp>.<body>`, sent as
+/// `X-Webhook-Signature: sha256=<hex>` next to the `X-Webhook-Timestamp` it
+/// was computed with, so receivers can reject stale or replayed requests.
+/// `X-Webhook-Id` stays the same across redeliveries of a notification.
+pub struct WebhookHandlerImpl {
+	client:    reqwest::Client,
+	urls:      HashMap<String, Url>,
+	secret:    Vec<u8>,
+	app_state: Arc<AppState>,
+}
+
+impl WebhookHandlerImpl {
+	pub fn new(opts: WebhookOptions, app_state: Arc<AppState>) -> Result<Self, String> {
+		let mut urls = HashMap::new();
+		for (recipient_id, url) in opts.urls {
+			let parsed =
+				Url::parse(&url).map_err(|e| format!("invalid URL for {}: {}", recipient_id, e))?;
+			if !matches!(parsed.scheme(), "http" | "https") {
+				return Err(format!("invalid URL for {}: {}", recipient_id, url));
+			}
+			urls.insert(recipient_id, parsed);
+		}
+		// Following a redirect would send the signed body somewhere else.
+		let client = reqwest::Client::builder()
+			.connect_timeout(opts.connect_timeout)
+			.timeout(opts.timeout)
+			.redirect(Policy::none())
+			.build()
+			.map_err(|e| format!("failed to build HTTP client: {}", e))?;
+
+		Ok(WebhookHandlerImpl {
+			client,
+			urls,
+			secret: opts.secret.into_bytes(),
+			app_state,
+		})
+	}
+
+	fn sign(&self, timestamp: u64, body: &str) -> String {
+		let mut mac =
+			Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+		mac.update(timestamp.to_string().as_bytes());
+		mac.update(b".");
+		mac.update(body.as_bytes());
+		format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+	}
+}
+
+/// Server errors, timeouts and rate limiting may pass, any other rejection
+/// would be repeated. A `Retry-After` on a retried reply sets the delay.
+fn classify(status: StatusCode, retry_after: Option<Duration>) -> DeliveryError {
+	let error = format!("webhook returned {}", status);
+	if !status.is_server_error()
+		&& status != StatusCode::REQUEST_TIMEOUT
+		&& status != StatusCode::TOO_MANY_REQUESTS
+	{
+		return DeliveryError::Permanent(error);
+	}
+	match retry_after {
+		Some(delay) => DeliveryError::RetryAfter(delay, error),
+		None => DeliveryError::Transient(error),
+	}
+}
+
+#[async_trait]
+impl MessageHandler for WebhookHandlerImpl {
+	async fn handle_message(&self, message: &str) -> Result<(), DeliveryError> {
+		// Only decoded to reject messages that are not notifications, the
+		// body is posted exactly as it was published.
+		let notification = parse_notification(message)?;
+		let url = self.urls.get(&notification.recipient.id).ok_or_else(|| {
+			DeliveryError::Permanent(format!(
+				"no webhook URL for recipient {}",
+				notification.recipient.id
+			))
+		})?;
+
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		let mut request = self
+			.client
+			.post(url.clone())
+			.header("Content-Type", "application/json")
+			.header(TIMESTAMP_HEADER, timestamp.to_string())
+			.header(SIGNATURE_HEADER, self.sign(timestamp, message));
+		if let Some(id) = &notification.id {
+			request = request.header(ID_HEADER, id);
+		}
+
+		let response = request
+			.body(message.to_string())
+			.send()
+			.await
+			.map_err(|e| DeliveryError::Transient(format!("request failed: {}", e)))?;
+		if !response.status().is_success() {
+			return Err(classify(response.status(), retry_after(response.headers())));
+		}
+
+		debug!(
+			"Delivered webhook {} to {}",
+			notification.id.as_deref().unwrap_or("-"),
+			url
+		);
+		if let Some(metric) = self
+			.app_state
+			.metrics
+			.get_counter(WEBHOOK_CONSUMER_CONSUMED_MESSAGES)
+		{
+			metric.increment(1);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use axum::{Router, extract::State, http::HeaderMap, routing::post};
+	use common::monitoring::{metrics::Metrics, server::default_pair};
+	use serde_json::json;
+	use tokio::net::TcpListener;
+
+	use super::*;
+
+	type Requests = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+	/// Answers every POST to `/hook` with `status` after `delay`.
+	async fn start_receiver(status: StatusCode, delay: Duration) -> (String, Requests) {
+		let requests: Requests = Arc::default();
+		let app = Router::new()
+			.route(
+				"/hook",
+				post(
+					move |State(requests): State<Requests>, headers: HeaderMap, body: String| async move {
+						requests.lock().unwrap().push((headers, body));
+						tokio::time::sleep(delay).await;
+						status
+					},
+				),
+			)
+			.with_state(requests.clone());
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/hook", listener.local_addr().unwrap());
+		tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+		(url, requests)
+	}
+
+	fn handler(url: &str) -> WebhookHandlerImpl {
+		let (_, recorder) = default_pair();
+		let metrics = Metrics::new(recorder);
+		metrics
+			.register_counter(WEBHOOK_CONSUMER_CONSUMED_MESSAGES)
+			.unwrap();
+		WebhookHandlerImpl::new(
+			WebhookOptions {
+				urls:            HashMap::from([("orders".to_string(), url.to_string())]),
+				secret:          "s3cret".to_string(),
+				connect_timeout: Duration::from_secs(1),
+				timeout:         Duration::from_millis(200),
+			},
+			Arc::new(AppState {
+				metrics: Arc::new(metrics),
+			}),
+		)
+		.unwrap()
+	}
+
+	fn notification() -> String {
+		json!({
+			"_id": "n-1",
+			"content": "Your order shipped",
+			"channel": "webhook",
+			"recipient": {"id": "orders", "timezone_offset": "+00:00"},
+			"scheduledTime": "2026-10-19T08:00:00Z",
+			"priority": "high",
+			"status": "processing",
+		})
+		.to_string()
+	}
+
+	#[tokio::test]
+	async fn posts_the_notification_with_a_signature() {
+		let (url, requests) = start_receiver(StatusCode::NO_CONTENT, Duration::ZERO).await;
+		let message = notification();
+
+		assert_eq!(handler(&url).handle_message(&message).await, Ok(()));
+
+		let requests = requests.lock().unwrap();
+		let (headers, body) = &requests[0];
+		assert_eq!(body, &message);
+		assert_eq!(headers[ID_HEADER], "n-1");
+		assert_eq!(headers["content-type"], "application/json");
+		let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap();
+		let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+		mac.update(format!("{}.{}", timestamp, message).as_bytes());
+		let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+		assert_eq!(headers[SIGNATURE_HEADER], expected.as_str());
+	}
+
+	#[tokio::test]
+	async fn server_errors_and_timeouts_are_retried() {
+		let (url, _) = start_receiver(StatusCode::BAD_GATEWAY, Duration::ZERO).await;
+		assert!(matches!(
+			handler(&url).handle_message(&notification()).await,
+			Err(DeliveryError::Transient(_))
+		));
+
+		let (url, _) = start_receiver(StatusCode::OK, Duration::from_secs(2)).await;
+		assert!(matches!(
+			handler(&url).handle_message(&notification()).await,
+			Err(DeliveryError::Transient(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn client_errors_and_invalid_messages_are_permanent() {
+		let (url, requests) = start_receiver(StatusCode::BAD_REQUEST, Duration::ZERO).await;
+		let handler = handler(&url);
+		assert!(matches!(
+			handler.handle_message(&notification()).await,
+			Err(DeliveryError::Permanent(_))
+		));
+		assert!(matches!(
+			handler.handle_message("not json").await,
+			Err(DeliveryError::Permanent(_))
+		));
+		let unknown = notification().replace("\"orders\"", "\"billing\"");
+		assert_eq!(
+			handler.handle_message(&unknown).await,
+			Err(DeliveryError::Permanent(
+				"no webhook URL for recipient billing".to_string()
+			))
+		);
+		assert_eq!(requests.lock().unwrap().len(), 1);
+	}
+
+	#[test]
+	fn rate_limiting_is_retried() {
+		assert!(matches!(
+			classify(StatusCode::TOO_MANY_REQUESTS, None),
+			DeliveryError::Transient(_)
+		));
+		assert_eq!(
+			classify(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(30))),
+			DeliveryError::RetryAfter(
+				Duration::from_secs(30),
+				"webhook returned 429 Too Many Requests".to_string()
+			)
+		);
+		assert!(matches!(
+			classify(StatusCode::MOVED_PERMANENTLY, Some(Duration::from_secs(30))),
+			DeliveryError::Permanent(_)
+		));
+	}
+}
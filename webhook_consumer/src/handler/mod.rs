@@ -0,0 +1,3 @@
+pub use consumer::handler::{DeliveryError, MessageHandler, retry_after};
+
+pub mod http;
@@ -0,0 +1,39 @@
+//! Notifications posted to the recipients' own HTTP endpoints. The
+//! `webhook_consumer` binary hosts this handler on its own, [`register`] adds
+//! it to any other host.
+
+use std::{fmt::Debug, sync::Arc};
+
+use common::axum_prometheus::metrics_exporter_prometheus::PrometheusRecorder;
+use consumer::registry::Handlers;
+
+use crate::{
+	config::{HandlerKind, Sections},
+	handler::http::WebhookHandlerImpl,
+};
+
+pub mod app_state;
+pub mod config;
+pub mod handler;
+pub mod metrics;
+
+pub const CHANNEL: &str = "webhook";
+
+/// Registers the webhook handler under the name `name` gives it in the host's
+/// handler list. It is only built when configured, from `sections`.
+pub fn register<'a, H: Copy + Debug + PartialEq + 'a>(
+	handlers: Handlers<'a, H>,
+	sections: Sections<'a>,
+	recorder: Arc<PrometheusRecorder>,
+	name: fn(HandlerKind) -> H,
+) -> Handlers<'a, H> {
+	let metrics = Arc::new(metrics::metrics::setup_metrics(recorder));
+	let app_state = Arc::new(app_state::AppState { metrics });
+
+	handlers.register(name(HandlerKind::Http), CHANNEL, move || {
+		Ok(Arc::new(WebhookHandlerImpl::new(
+			sections.webhook.options(),
+			app_state,
+		)?))
+	})
+}
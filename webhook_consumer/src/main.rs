@@ -0,0 +1,33 @@
+use consumer::{
+	registry::Handlers,
+	runtime::{Runtime, Settings},
+};
+use webhook_consumer::config::Config;
+
+#[tokio::main]
+async fn main() {
+	let config: Config = common::config::load().unwrap_or_else(|e| {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	});
+
+	let runtime = Runtime::init(&config.log, &config.faults);
+	let handlers = webhook_consumer::register(
+		Handlers::new(),
+		config.sections(),
+		runtime.recorder(),
+		|handler| handler,
+	);
+
+	runtime
+		.run(
+			Settings {
+				metrics:  &config.metrics,
+				nats:     &config.nats,
+				consumer: &config.consumer,
+				shutdown: &config.shutdown,
+			},
+			handlers,
+		)
+		.await;
+}